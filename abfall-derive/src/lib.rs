@@ -0,0 +1,154 @@
+//! Derive macro for the `Trace` trait of [abfall](https://docs.rs/abfall)
+//!
+//! Use it through the `derive` feature of `abfall`, which re-exports the
+//! macro next to the trait:
+//!
+//! ```ignore
+//! use abfall::{GcPtr, Trace};
+//!
+//! #[derive(Trace)]
+//! struct Node {
+//!     value: i32,
+//!     next: Option<GcPtr<Node>>,
+//! }
+//! ```
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Field, Fields, GenericParam, Result, Type, parse_macro_input,
+    parse_quote,
+};
+
+/// Derive `Trace` by tracing every field
+///
+/// `NO_TRACE` is true if it is true for all traced fields. Fields marked
+/// with `#[trace(skip)]` are not traced; their type must still implement
+/// `Trace` with `NO_TRACE = true`, which is checked at compile time.
+///
+/// Every type parameter gets a `Trace` bound.
+#[proc_macro_derive(Trace, attributes(trace))]
+pub fn derive_trace(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(mut input: DeriveInput) -> Result<TokenStream> {
+    for param in &mut input.generics.params {
+        if let GenericParam::Type(ty) = param {
+            ty.bounds.push(parse_quote!(::abfall::Trace));
+        }
+    }
+
+    let mut traced_types = Vec::new();
+    let mut skipped_types = Vec::new();
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, calls) =
+                destructure(&data.fields, &mut traced_types, &mut skipped_types)?;
+            quote! {
+                let Self #pattern = self;
+                #(#calls)*
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let (pattern, calls) =
+                    destructure(&variant.fields, &mut traced_types, &mut skipped_types)?;
+                arms.push(quote! {
+                    Self::#ident #pattern => { #(#calls)* }
+                });
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(Error::new_spanned(
+                data.union_token,
+                "`Trace` cannot be derived for unions",
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let asserts = skipped_types.iter().map(|ty| {
+        quote! {
+            const {
+                assert!(
+                    <#ty as ::abfall::Trace>::NO_TRACE,
+                    "`#[trace(skip)]` is only allowed on fields with `Trace::NO_TRACE`",
+                )
+            };
+        }
+    });
+
+    Ok(quote! {
+        unsafe impl #impl_generics ::abfall::Trace for #name #ty_generics #where_clause {
+            const NO_TRACE: bool = true #(&& <#traced_types as ::abfall::Trace>::NO_TRACE)*;
+
+            #[allow(unused_variables)]
+            fn trace(&self, __tracer: &::abfall::Tracer) {
+                #(#asserts)*
+                #body
+            }
+        }
+    })
+}
+
+/// Build a pattern binding all fields and the trace calls for them
+fn destructure(
+    fields: &Fields,
+    traced_types: &mut Vec<Type>,
+    skipped_types: &mut Vec<Type>,
+) -> Result<(TokenStream, Vec<TokenStream>)> {
+    let mut bindings = Vec::new();
+    let mut calls = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let binding = format_ident!("__field{}", index);
+        if is_skipped(field)? {
+            skipped_types.push(field.ty.clone());
+        } else {
+            traced_types.push(field.ty.clone());
+            calls.push(quote! {
+                ::abfall::Trace::trace(#binding, __tracer);
+            });
+        }
+        bindings.push(binding);
+    }
+
+    let pattern = match fields {
+        Fields::Named(_) => {
+            let names = fields.iter().map(|f| f.ident.as_ref().unwrap());
+            quote!({ #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(( #(#bindings),* )),
+        Fields::Unit => TokenStream::new(),
+    };
+    Ok((pattern, calls))
+}
+
+fn is_skipped(field: &Field) -> Result<bool> {
+    let mut skip = false;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("trace") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown `trace` attribute, expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
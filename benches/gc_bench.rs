@@ -1,8 +1,10 @@
-use abfall::{GcContext, GcPtr, Heap, Trace, Tracer};
+use abfall::{GcContext, GcOptions, GcPtr, GcRoot, Heap, Trace, Tracer};
 use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
+#[allow(dead_code)] // `value` is payload only
 struct Node {
     value: usize,
     next: Option<GcPtr<Node>>,
@@ -45,33 +47,275 @@ fn bench_chain(c: &mut Criterion) {
 }
 
 fn bench_concurrent_alloc(c: &mut Criterion) {
-    c.bench_function("concurrent_alloc", |b| {
-        b.iter_batched(
-            || Heap::new(),
-            |heap| {
-                let threads: Vec<_> = (0..4)
-                    .map(|t| {
-                        let heap_cl = Arc::clone(&heap);
-                        thread::spawn(move || {
-                            let worker_ctx = GcContext::with_heap(heap_cl);
-                            for i in 0..25_000 {
-                                let _ = worker_ctx.allocate(((t as u64) << 32 | i as u64) as u64);
-                                if i % 500 == 0 {
-                                    worker_ctx.heap().collect();
+    // Every allocation linked into the heap's list, or in batches per thread
+    let mut group = c.benchmark_group("concurrent_alloc");
+    for batch in [0, GcOptions::DEFAULT.thread_alloc_batch] {
+        let name = if batch == 0 {
+            "global_head"
+        } else {
+            "thread_lists"
+        };
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    Heap::with_options(GcOptions {
+                        thread_alloc_batch: batch,
+                        ..GcOptions::DEFAULT
+                    })
+                },
+                |heap| {
+                    let threads: Vec<_> = (0..4)
+                        .map(|t| {
+                            let heap_cl = Arc::clone(&heap);
+                            thread::spawn(move || {
+                                let worker_ctx = GcContext::with_heap(heap_cl);
+                                for i in 0..25_000 {
+                                    let _ = worker_ctx.allocate((t as u64) << 32 | i as u64);
+                                    if i % 500 == 0 {
+                                        worker_ctx.heap().collect();
+                                    }
                                 }
-                            }
+                            })
                         })
-                    })
-                    .collect();
-                for h in threads {
-                    h.join().unwrap();
+                        .collect();
+                    for h in threads {
+                        h.join().unwrap();
+                    }
+                    heap.force_collect();
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Marking a heap whose list order is scattered, with and without `reorder_lists`
+fn bench_reorder_lists(c: &mut Criterion) {
+    fn scattered_heap(reorder: bool) -> (GcContext, Vec<abfall::GcRoot<[u64; 4]>>) {
+        let ctx = GcContext::off();
+        let mut keep = Vec::new();
+        for i in 0..200_000u64 {
+            let root = ctx.allocate([i; 4]);
+            if i % 2 == 0 {
+                keep.push(root);
+            }
+        }
+        ctx.heap().force_collect();
+        keep.extend((0..100_000u64).map(|i| ctx.allocate([i; 4])));
+        if reorder {
+            ctx.heap().reorder_lists();
+        }
+        (ctx, keep)
+    }
+
+    let mut group = c.benchmark_group("mark_scattered_200k");
+    for reorder in [false, true] {
+        let name = if reorder { "reordered" } else { "scattered" };
+        group.bench_function(name, |b| {
+            let (ctx, _keep) = scattered_heap(reorder);
+            b.iter(|| ctx.heap().force_collect());
+        });
+    }
+    group.finish();
+}
+
+fn bench_free_list(c: &mut Criterion) {
+    // Allocate and sweep the same shapes over and over
+    let mut group = c.benchmark_group("churn_10x10k");
+    for cap in [0, 1024 * 1024] {
+        let name = if cap == 0 {
+            "no_free_list"
+        } else {
+            "free_list"
+        };
+        group.bench_function(name, |b| {
+            let ctx = GcContext::with_options(GcOptions {
+                free_list_cap_bytes: cap,
+                ..GcOptions::OFF
+            });
+            b.iter(|| {
+                for _ in 0..10 {
+                    for i in 0..10_000 {
+                        let _ = ctx.allocate(i);
+                    }
+                    ctx.heap().force_collect();
                 }
-                heap.force_collect();
-            },
-            BatchSize::SmallInput,
-        );
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_alloc_while_marking(c: &mut Criterion) {
+    // Allocations during marking: traced objects assist, `NO_TRACE` ones don't
+    let mut group = c.benchmark_group("alloc_10k_while_marking");
+    let ctx = GcContext::with_options(GcOptions {
+        assist_work_budget: 5,
+        ..GcOptions::OFF
+    });
+    let mut chain: Option<GcRoot<Node>> = None;
+    for i in 0..100_000 {
+        chain = Some(ctx.allocate(Node {
+            value: i,
+            next: chain.map(|n| n.as_ptr()),
+        }));
+    }
+    fn timed(ctx: &GcContext, iters: u64, alloc: impl Fn(usize)) -> Duration {
+        let mut total = Duration::ZERO;
+        for _ in 0..iters {
+            ctx.begin_mark();
+            let start = Instant::now();
+            for i in 0..10_000 {
+                alloc(i);
+            }
+            total += start.elapsed();
+            ctx.sweep();
+        }
+        total
+    }
+    group.bench_function("ints", |b| {
+        b.iter_custom(|iters| {
+            timed(&ctx, iters, |i| {
+                let _ = ctx.allocate(i);
+            })
+        })
+    });
+    group.bench_function("traced", |b| {
+        b.iter_custom(|iters| {
+            timed(&ctx, iters, |_| {
+                let _ = ctx.allocate(None::<GcPtr<Node>>);
+            })
+        })
+    });
+    group.finish();
+    drop(chain);
+}
+
+fn bench_arena(c: &mut Criterion) {
+    // Allocate and sweep 10k objects, from an arena that fits them all or
+    // from the global allocator
+    let mut group = c.benchmark_group("arena_churn_10x10k");
+    for arena in [false, true] {
+        let name = if arena { "arena" } else { "global" };
+        group.bench_function(name, |b| {
+            let options = GcOptions {
+                free_list_cap_bytes: 0,
+                ..GcOptions::OFF
+            };
+            let heap = if arena {
+                Heap::with_arena(options, 10_000 * 128)
+            } else {
+                Heap::with_options(options)
+            };
+            b.iter(|| {
+                for _ in 0..10 {
+                    for i in 0..10_000 {
+                        let _ = heap.allocate(i);
+                    }
+                    heap.force_collect();
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_byte_payloads(c: &mut Criterion) {
+    // Tracing a chain whose nodes carry `NO_TRACE` byte arrays costs the same
+    // as a chain without them
+    struct Payload {
+        bytes: [[u8; 1024]; 4],
+        next: Option<GcPtr<Payload>>,
+    }
+    unsafe impl Trace for Payload {
+        fn trace(&self, t: &Tracer) {
+            self.bytes.trace(t);
+            self.next.trace(t);
+        }
+    }
+
+    let mut group = c.benchmark_group("trace_chain_1k");
+    group.bench_function("plain", |b| {
+        let ctx = GcContext::off();
+        let mut chain: Option<GcRoot<Node>> = None;
+        for i in 0..1_000 {
+            chain = Some(ctx.allocate(Node {
+                value: i,
+                next: chain.map(|n| n.as_ptr()),
+            }));
+        }
+        b.iter(|| ctx.force_collect());
+    });
+    group.bench_function("byte_payload", |b| {
+        let ctx = GcContext::off();
+        let mut chain: Option<GcRoot<Payload>> = None;
+        for _ in 0..1_000 {
+            chain = Some(ctx.allocate(Payload {
+                bytes: [[0; 1024]; 4],
+                next: chain.map(|n| n.as_ptr()),
+            }));
+        }
+        b.iter(|| ctx.force_collect());
+    });
+    group.finish();
+}
+
+fn bench_visited_set(c: &mut Criterion) {
+    // Visit every node of a 100k chain once, remembering the visited nodes
+    // in a SipHash set or one hashed by address
+    use abfall::collections::GcPtrHashSet;
+    use std::collections::HashSet;
+
+    fn visit(head: GcPtr<Node>, mut insert: impl FnMut(GcPtr<Node>) -> bool) {
+        let mut current = Some(head);
+        while let Some(node) = current {
+            if !insert(node) {
+                break;
+            }
+            current = unsafe { (*node.as_ptr()).next };
+        }
+    }
+
+    let ctx = GcContext::off();
+    let mut chain: Option<GcRoot<Node>> = None;
+    for i in 0..100_000 {
+        chain = Some(ctx.allocate(Node {
+            value: i,
+            next: chain.map(|n| n.as_ptr()),
+        }));
+    }
+    let chain = chain.unwrap();
+    let head = chain.as_ptr();
+
+    let mut group = c.benchmark_group("visited_set_100k");
+    group.bench_function("sip_hash", |b| {
+        b.iter(|| {
+            let mut visited = HashSet::new();
+            visit(head, |node| visited.insert(node));
+            visited.len()
+        });
+    });
+    group.bench_function("identity_hash", |b| {
+        b.iter(|| {
+            let mut visited = GcPtrHashSet::default();
+            visit(head, |node| visited.insert(node));
+            visited.len()
+        });
     });
+    group.finish();
 }
 
-criterion_group!(gc, bench_allocation, bench_chain, bench_concurrent_alloc);
+criterion_group!(
+    gc,
+    bench_allocation,
+    bench_chain,
+    bench_concurrent_alloc,
+    bench_reorder_lists,
+    bench_free_list,
+    bench_alloc_while_marking,
+    bench_arena,
+    bench_byte_payloads,
+    bench_visited_set
+);
 criterion_main!(gc);
@@ -6,7 +6,7 @@
 //! - How GC roots keep objects alive
 //! - Memory pressure and collection behavior
 
-use abfall::GcContext;
+use abfall::{GcContext, GcOptions};
 
 fn main() {
     println!("=== Abfall Garbage Collector Demo ===\n");
@@ -41,7 +41,7 @@ fn basic_allocation() {
 }
 
 fn manual_collection() {
-    let ctx = GcContext::new();
+    let ctx = GcContext::with_options(GcOptions::manual());
 
     println!("  Allocating 5 objects...");
     let ptr1 = ctx.allocate(1);
@@ -68,7 +68,7 @@ fn manual_collection() {
     );
 
     // Manually trigger collection
-    ctx.collect();
+    ctx.force_collect();
 
     println!(
         "  After collection: {} allocations, {} bytes",
@@ -99,7 +99,7 @@ fn memory_pressure() {
     );
 
     // Force collection
-    ctx.collect();
+    ctx.force_collect();
 
     println!(
         "  After collection: {} allocations, {} bytes",
@@ -187,16 +187,15 @@ fn main() {
     // Traverse from root to count reachable nodes
     let mut reachable = 0;
     let mut current = root.next.get();
-    let mut visited = std::collections::HashSet::new();
+    let mut visited = abfall::collections::GcPtrHashSet::default();
 
     while let Some(node_ptr) = current {
-        // Root the pointer to access fields
-        let node = unsafe { node_ptr.root() };
-        let node_id = node.id;
-        if visited.contains(&node_id) {
+        // GcPtrs compare and hash by identity, the set hashes just the address
+        if !visited.insert(node_ptr) {
             break; // Cycle detected
         }
-        visited.insert(node_id);
+        // Root the pointer to access fields
+        let node = unsafe { node_ptr.root() };
         reachable += 1;
         current = node.next.get();
     }
@@ -0,0 +1,114 @@
+//! Pre-reserved memory for objects, see [`Heap::with_arena`](crate::Heap::with_arena)
+//!
+//! The arena is one contiguous block that is handed out by a bump pointer.
+//! Swept arena objects go to the arena's own free list and are reused by
+//! allocations of the same layout, they are never returned to the global
+//! allocator. Once the block is used up, allocations fall back to the global
+//! allocator; objects record where they came from in `GcHeader::from_arena`.
+
+use crate::heap::ClassMap;
+use alloc::alloc::Layout;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Alignment of the block, enough for any `GcBox` without over-aligned data
+const ARENA_ALIGN: usize = 64;
+
+pub(crate) struct Arena {
+    base: NonNull<u8>,
+    layout: Layout,
+    /// Offset of the first byte that was never handed out
+    bump: AtomicUsize,
+    /// Swept arena allocations, by layout
+    free: crate::sync::Mutex<ClassMap>,
+}
+
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    /// Reserve a block of `bytes` (at least one byte)
+    pub fn new(bytes: usize) -> Self {
+        let layout = Layout::from_size_align(bytes.max(1), ARENA_ALIGN).expect("arena too large");
+        let base = unsafe { alloc::alloc::alloc(layout) };
+        let base = NonNull::new(base).unwrap_or_else(|| alloc::alloc::handle_alloc_error(layout));
+        Self {
+            base,
+            layout,
+            bump: AtomicUsize::new(0),
+            free: crate::sync::Mutex::new(ClassMap::new()),
+        }
+    }
+
+    /// Size of the block in bytes
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Bytes handed out by the bump pointer so far (including padding)
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.bump.load(Ordering::Relaxed)
+    }
+
+    /// Take a swept allocation of `layout`, or bump a new one
+    ///
+    /// Returns None if neither is available, the caller then uses the
+    /// global allocator.
+    pub fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if let Some(slot) = self
+            .free
+            .lock()
+            .get_mut(&layout.into())
+            .and_then(|slots| slots.pop())
+        {
+            return Some(slot);
+        }
+        let base = self.base.as_ptr() as usize;
+        let mut bump = self.bump.load(Ordering::Relaxed);
+        loop {
+            let start = (base + bump).checked_next_multiple_of(layout.align())? - base;
+            let end = start.checked_add(layout.size())?;
+            if end > self.capacity() {
+                return None;
+            }
+            match self
+                .bump
+                .compare_exchange_weak(bump, end, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                // SAFETY: `start` is inside the block
+                Ok(_) => return Some(unsafe { self.base.add(start) }),
+                Err(current) => bump = current,
+            }
+        }
+    }
+
+    /// Keep a swept arena allocation of `layout` for reuse
+    ///
+    /// # Safety
+    ///
+    /// `slot` must be an allocation of `layout` from this arena that is no
+    /// longer used.
+    pub unsafe fn release(&self, slot: NonNull<u8>, layout: Layout) {
+        unsafe { self.release_all([(slot, layout)]) };
+    }
+
+    /// `release` for many allocations, taking the lock once (sweeping)
+    ///
+    /// # Safety
+    ///
+    /// Like `release`, for every allocation.
+    pub unsafe fn release_all(&self, slots: impl IntoIterator<Item = (NonNull<u8>, Layout)>) {
+        let mut free = self.free.lock();
+        for (slot, layout) in slots {
+            free.entry(layout.into()).or_default().push(slot);
+        }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { alloc::alloc::dealloc(self.base.as_ptr(), self.layout) };
+    }
+}
@@ -2,15 +2,42 @@
 //!
 //! This module provides cells with write barriers for the tri-color marking algorithm:
 //! - `GcCell<T>`: Stores traceable value with write barrier
+//! - `GcRefCell<T>`: Borrow-checked like `RefCell`, runs the barrier when a
+//!   mutable borrow ends
+//! - `AtomicGcCell<T>`: Lock-free `Option<GcPtr<T>>` with compare-and-swap
 //!
-//! For non-traced types (primitives, etc.), use `std::cell::Cell<T>` directly since
-//! they cannot contain GC pointers and don't need write barriers.
+//! The std cells (`Cell`, `RefCell`) are only traced with the
+//! `single-threaded` feature, since the collector may trace objects from
+//! other threads.
 
+#[cfg(feature = "std")]
+use crate::gc::with_current_context;
 use crate::{
-    gc::with_current_context,
+    gc_box::{GcBox, GcHeader},
+    heap::{BarrierKind, Heap},
+    ptr::GcPtr,
+    sync::atomic::AtomicPtr,
     trace::{Trace, Tracer},
 };
-use std::cell::UnsafeCell;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{NonNull, null_mut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Without `std` no thread has a `GcContext`, so the barriers always shade
+/// through the stored pointers (`shade_without_context`)
+#[cfg(not(feature = "std"))]
+fn with_current_context(_f: impl FnOnce(&NoContext)) -> bool {
+    false
+}
+
+#[cfg(not(feature = "std"))]
+#[allow(dead_code)] // never constructed
+struct NoContext {
+    heap: alloc::sync::Arc<crate::heap::Heap>,
+    local_gray: core::cell::RefCell<Tracer>,
+    barrier_buffer: crate::heap::BarrierBuffer,
+}
 
 /// Cell for storing GC-traceable values with write barrier
 ///
@@ -21,65 +48,643 @@ use std::cell::UnsafeCell;
 ///
 /// When a value is stored during marking, the cell traces the new
 /// value to ensure any GC pointers it contains are marked gray.
+///
+/// With [`BarrierKind::IncrementalUpdate`], the object containing the cell
+/// is instead recorded once per cycle and traced again at the end of
+/// marking. Until marking traced that object, stores shade like above.
+///
+/// On a thread without a `GcContext`, the stored pointers are always shaded
+/// (on the heaps they belong to), regardless of the barrier kind.
+///
+/// On a generational heap, the young objects stored into a cell of an old
+/// object are also added to the remembered set, see
+/// `GcOptions::generational`.
+///
+/// `get` requires `T: Copy`; other values (like a `Vec<GcPtr<_>>`) are
+/// swapped in and out with [`replace`](Self::replace) and [`take`](Self::take).
+///
+/// The collector may trace the cell concurrently. A store waits for such a
+/// trace to finish, and the old value is only dropped afterwards.
 pub struct GcCell<T> {
     value: UnsafeCell<T>,
+    /// Held while the value is traced or swapped, see `CellLock`
+    locked: AtomicBool,
+    owner: CellOwner,
 }
 
-impl<T: Trace + Copy> GcCell<T> {
+impl<T: Trace> GcCell<T> {
     #[inline]
     pub fn new(value: T) -> Self {
         Self {
             value: UnsafeCell::new(value),
+            locked: AtomicBool::new(false),
+            owner: CellOwner::new(),
         }
     }
 
-    pub fn get(&self) -> T {
-        unsafe { *self.value.get() }
-    }
-
     /// Set the contained value with write barrier
     ///
     /// If marking is in progress, traces the new value to shade
     /// any GC pointers gray, preventing premature collection.
     pub fn set(&self, new_value: T) {
-        // Dijkstra write barrier: shade new pointer gray
-        // (To avoid race-conditions, we don't check is_marking here; overhead should be minimal)
-        unsafe {
-            let new_ref = &new_value;
-            with_current_context(|ctx| {
-                if ctx.heap.check_is_marking_and_increment_busy() {
-                    // Trace new value to shade it gray
-                    new_ref.trace(&ctx.local_gray);
-                    ctx.heap.merge_work(&ctx.local_gray);
-                    ctx.heap.decrement_busy_marking();
+        drop(self.replace(new_value));
+    }
+
+    /// Replace the contained value with write barrier, returning the old one
+    ///
+    /// The barrier runs on the new value exactly like in [`set`](Self::set).
+    pub fn replace(&self, new_value: T) -> T {
+        write_barrier(new_value, self, &self.owner, |new_value| {
+            let _lock = CellLock::lock(&self.locked);
+            unsafe { core::mem::replace(&mut *self.value.get(), new_value) }
+        })
+    }
+
+    /// Take the contained value with write barrier, leaving `T::default()`
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+}
+
+/// Spin lock between the collector tracing a `GcCell` and its thread
+/// swapping the value
+///
+/// Only held for the swap itself or the trace, never while waiting for
+/// another lock, so it can't deadlock.
+struct CellLock<'a>(&'a AtomicBool);
+
+impl<'a> CellLock<'a> {
+    #[inline]
+    fn lock(locked: &'a AtomicBool) -> Self {
+        while locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        Self(locked)
+    }
+}
+
+impl Drop for CellLock<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// The object a cell (or an `EphemeronMap`) was traced as a field of, for
+/// the incremental-update barrier
+///
+/// Set while marking traces the object, see `Tracer::owner`. The object is
+/// only trusted in the cycle that set it: marking keeps it alive until the
+/// end of that cycle, even if the cell was moved out of it meanwhile.
+pub(crate) struct CellOwner {
+    object: core::sync::atomic::AtomicPtr<GcHeader>,
+    /// Mark cycle in which `object` was set
+    traced: AtomicUsize,
+    /// Mark cycle in which `object` was recorded as dirty
+    recorded: AtomicUsize,
+    /// The cell was traced as part of an old object, see `traced_in_old`
+    old: AtomicBool,
+}
+
+impl CellOwner {
+    pub(crate) const fn new() -> Self {
+        Self {
+            object: core::sync::atomic::AtomicPtr::new(null_mut()),
+            traced: AtomicUsize::new(0),
+            recorded: AtomicUsize::new(0),
+            old: AtomicBool::new(false),
+        }
+    }
+
+    /// Record the object `tracer` is tracing as the owner
+    #[inline]
+    pub(crate) fn traced_by(&self, tracer: &Tracer) {
+        traced_in_old(&self.old, tracer);
+        let (object, cycle) = tracer.owner();
+        if cycle != 0 && self.traced.load(Ordering::Relaxed) != cycle {
+            self.object
+                .store(object as *mut GcHeader, Ordering::Relaxed);
+            self.traced.store(cycle, Ordering::Release);
+        }
+    }
+
+    /// Record the owner in the dirty set of `heap`, once per cycle
+    ///
+    /// Returns false if the owner didn't trace the cell in the current
+    /// cycle of `heap` (yet), then the new value has to be shaded instead.
+    fn record_dirty(&self, heap: &Heap) -> bool {
+        let cycle = heap.mark_cycle();
+        if self.traced.load(Ordering::Acquire) != cycle {
+            return false;
+        }
+        if self.recorded.swap(cycle, Ordering::AcqRel) != cycle {
+            heap.record_dirty(unsafe { &*self.object.load(Ordering::Relaxed) });
+        }
+        true
+    }
+}
+
+/// Set `old` once a cell is traced as part of an object that is old after
+/// the current cycle, see `Tracer::owner_is_old`
+///
+/// Never reset: the object only gets older. A cell moved out of it stays
+/// flagged, which just remembers more than needed. Sequentially consistent
+/// with the stores of `AtomicGcCell`, see `write_barrier`.
+#[inline]
+fn traced_in_old(old: &AtomicBool, tracer: &Tracer) {
+    if tracer.owner_is_old() && !old.load(Ordering::Relaxed) {
+        old.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Run the write barrier of `cell` for `new_value`, and `store` it
+///
+/// `owner` records the cell's object for the incremental-update barrier.
+///
+/// Once the cell belongs to an old object, the young objects it points to
+/// after the store are remembered. Checked after the store: marking sets the
+/// flag before reading the value, so either it reads the new value (and
+/// remembers what it points to, see `Heap::trace_fields`), or the flag is
+/// seen here. The lock of `GcCell` orders the two, the sequentially
+/// consistent operations of `AtomicGcCell` do the same.
+pub(crate) fn write_barrier<T: Trace, C: Trace, R>(
+    new_value: T,
+    cell: &C,
+    owner: &CellOwner,
+    store: impl FnOnce(T) -> R,
+) -> R {
+    let result = barrier_and_store(new_value, owner, store);
+    if !T::NO_TRACE && owner.old.load(Ordering::SeqCst) {
+        remember_young(cell);
+    }
+    result
+}
+
+/// Shade `new_value` if needed, and `store` it, see `write_barrier`
+fn barrier_and_store<T: Trace, R>(
+    new_value: T,
+    owner: &CellOwner,
+    store: impl FnOnce(T) -> R,
+) -> R {
+    // (To avoid race-conditions, we don't check is_marking here; overhead should be minimal)
+    let mut pending = Some((new_value, store));
+    let mut result = None;
+    let has_context = with_current_context(|ctx| {
+        if ctx.heap.check_is_marking_and_increment_busy() {
+            let (new_value, store) = pending.take().unwrap();
+            // Steele write barrier: remember the owner (once per cycle) for
+            // rescanning at the end of marking
+            if ctx.heap.barrier_kind() == BarrierKind::IncrementalUpdate
+                && owner.record_dirty(&ctx.heap)
+            {
+                result = Some(store(new_value));
+            } else {
+                // Dijkstra write barrier: shade new pointer gray
+                {
+                    let mut local_gray = ctx.local_gray.borrow_mut();
+                    new_value.trace(&local_gray);
+                    ctx.heap
+                        .merge_barrier_work(&mut local_gray, &ctx.barrier_buffer);
+                    debug_assert!(!local_gray.has_work());
                 }
-            });
-            *self.value.get() = new_value;
+                ctx.heap.count_barrier_op();
+                result = Some(store(new_value));
+            }
+            ctx.heap.decrement_busy_marking();
         }
+    });
+    if let Some(result) = result {
+        return result;
+    }
+    let (new_value, store) = pending.unwrap();
+    if !has_context && !T::NO_TRACE {
+        shade_without_context(&new_value);
     }
+    store(new_value)
 }
 
-impl<T> std::fmt::Debug for GcCell<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Write barrier for threads without a `GcContext`
+///
+/// Shades the stored pointers on the heaps of their objects.
+#[cold]
+fn shade_without_context<T: Trace>(new_value: &T) {
+    let mut tracer = Tracer::new_shadow();
+    new_value.trace(&tracer);
+    for header in tracer.take_shadow_visited() {
+        let header = unsafe { &*header };
+        header.heap().shade(header);
+    }
+}
+
+/// Add the young objects a cell of an old object points to to the
+/// remembered set
+///
+/// Traced as part of an old object, so cells nested in the value learn that
+/// they were moved into one.
+fn remember_young<T: Trace + ?Sized>(value: &T) {
+    let mut tracer = Tracer::new_shadow();
+    tracer.set_owner(core::ptr::null(), 0, true);
+    value.trace(&tracer);
+    for header in tracer.take_shadow_visited() {
+        let header = unsafe { &*header };
+        header.heap().remember(header);
+    }
+}
+
+/// Remember the young objects an object points to, once it is old
+///
+/// Called for objects that are promoted with those pointers already in
+/// place: no barrier saw them being stored into an old object.
+///
+/// # Safety
+///
+/// `header` must be a live object.
+pub(crate) unsafe fn remember_children(header: *const GcHeader) {
+    let mut tracer = Tracer::new_shadow();
+    tracer.set_owner(core::ptr::null(), 0, true);
+    unsafe { ((*header).vtable.trace)(header, &tracer) };
+    for header in tracer.take_shadow_visited() {
+        let header = unsafe { &*header };
+        header.heap().remember(header);
+    }
+}
+
+impl<T: Trace + Copy> GcCell<T> {
+    pub fn get(&self) -> T {
+        unsafe { *self.value.get() }
+    }
+
+    /// Replace the value with `f` applied to it, with write barrier
+    ///
+    /// The barrier runs on the result of `f`, like in [`set`](Self::set).
+    /// A `GcCell` is used by one thread at a time, so nothing is stored in
+    /// between; an edge shared between threads is an [`AtomicGcCell`], see
+    /// [`AtomicGcCell::update`].
+    pub fn update(&self, f: impl FnOnce(T) -> T) {
+        self.set(f(self.get()));
+    }
+}
+
+impl<T> core::fmt::Debug for GcCell<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("GcCell").finish_non_exhaustive()
     }
 }
 
 unsafe impl<T: Trace> Trace for GcCell<T> {
     fn trace(&self, tracer: &Tracer) {
-        unsafe {
-            (*self.value.get()).trace(tracer);
-        }
+        self.owner.traced_by(tracer);
+        let _lock = CellLock::lock(&self.locked);
+        tracer.drain_after(|| unsafe { (*self.value.get()).trace(tracer) });
     }
 }
 
 unsafe impl<T: Send> Send for GcCell<T> {}
 //unsafe impl<T: Sync> Sync for GcCell<T> {}
 
-#[cfg(test)]
+/// Lock-free cell holding an optional pointer, with write barrier
+///
+/// A [`GcCell`] can't be shared between threads: two threads storing into
+/// it race on the value. `AtomicGcCell` stores a single `GcPtr` atomically,
+/// so it can be used for mutable edges shared between threads. Each store
+/// runs the same write barrier as [`GcCell::set`] on the new pointer.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{AtomicGcCell, GcContext};
+///
+/// let ctx = GcContext::off();
+/// let first = ctx.allocate(1);
+/// let second = ctx.allocate(2);
+/// let cell = ctx.allocate(AtomicGcCell::new(Some(first.as_ptr())));
+///
+/// let current = cell.load();
+/// assert_eq!(cell.compare_exchange(current, Some(second.as_ptr())), Ok(current));
+/// assert!(cell.compare_exchange(current, None).is_err());
+/// assert_eq!(cell.load(), Some(second.as_ptr()));
+/// # }
+/// ```
+pub struct AtomicGcCell<T> {
+    ptr: AtomicPtr<GcBox<T>>,
+    owner: CellOwner,
+}
+
+impl<T: Trace> AtomicGcCell<T> {
+    #[inline]
+    pub fn new(value: Option<GcPtr<T>>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Self::into_raw(value)),
+            owner: CellOwner::new(),
+        }
+    }
+
+    /// Load the current pointer
+    #[inline]
+    pub fn load(&self) -> Option<GcPtr<T>> {
+        Self::from_raw(self.ptr.load(Ordering::Acquire))
+    }
+
+    /// Store a pointer with write barrier
+    pub fn store(&self, value: Option<GcPtr<T>>) {
+        self.swap(value);
+    }
+
+    /// Store a pointer with write barrier, returning the previous one
+    pub fn swap(&self, value: Option<GcPtr<T>>) -> Option<GcPtr<T>> {
+        write_barrier(value, self, &self.owner, |value| {
+            Self::from_raw(self.ptr.swap(Self::into_raw(value), Ordering::SeqCst))
+        })
+    }
+
+    /// Store `new` if the cell holds `current`, with write barrier
+    ///
+    /// Returns the previous pointer, in `Ok` if it was replaced. Pointers are
+    /// compared by identity. While marking, the barrier runs even if the
+    /// exchange fails.
+    pub fn compare_exchange(
+        &self,
+        current: Option<GcPtr<T>>,
+        new: Option<GcPtr<T>>,
+    ) -> Result<Option<GcPtr<T>>, Option<GcPtr<T>>> {
+        write_barrier(new, self, &self.owner, |new| {
+            self.ptr
+                .compare_exchange(
+                    Self::into_raw(current),
+                    Self::into_raw(new),
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .map(Self::from_raw)
+                .map_err(Self::from_raw)
+        })
+    }
+
+    /// Replace the pointer with `f` applied to it, with write barrier
+    ///
+    /// Retries with the current pointer while other threads store meanwhile,
+    /// so `f` may be called more than once. The barrier runs on the results
+    /// of `f`. Returns the previous pointer.
+    pub fn update(
+        &self,
+        mut f: impl FnMut(Option<GcPtr<T>>) -> Option<GcPtr<T>>,
+    ) -> Option<GcPtr<T>> {
+        let mut current = self.load();
+        loop {
+            match self.compare_exchange(current, f(current)) {
+                Ok(previous) => return previous,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    #[inline]
+    fn into_raw(value: Option<GcPtr<T>>) -> *mut GcBox<T> {
+        value.map_or(null_mut(), |ptr| ptr.as_non_null().as_ptr())
+    }
+
+    #[inline]
+    fn from_raw(raw: *mut GcBox<T>) -> Option<GcPtr<T>> {
+        NonNull::new(raw).map(GcPtr::new)
+    }
+}
+
+impl<T: Trace> core::fmt::Debug for AtomicGcCell<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("AtomicGcCell").field(&self.load()).finish()
+    }
+}
+
+unsafe impl<T: Trace> Trace for AtomicGcCell<T> {
+    fn trace(&self, tracer: &Tracer) {
+        self.owner.traced_by(tracer);
+        // Ordered after the flag of `traced_by`, see `write_barrier`
+        Self::from_raw(self.ptr.load(Ordering::SeqCst)).trace(tracer);
+    }
+}
+
+unsafe impl<T: Send + Sync> Send for AtomicGcCell<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicGcCell<T> {}
+
+/// Borrow state of a `GcRefCell`: the number of shared borrows, plus flags
+const WRITER: usize = 1 << (usize::BITS - 1);
+const TRACING: usize = 1 << (usize::BITS - 2);
+
+/// `RefCell`-like cell with a write barrier
+///
+/// [`borrow_mut`](Self::borrow_mut) gives access to the whole value, so any
+/// pointer in it might have changed when the borrow ends. Dropping the
+/// [`GcRefMut`] guard therefore runs the Dijkstra barrier on the whole value
+/// while marking is in progress (or shades the pointers on a thread without
+/// a `GcContext`).
+///
+/// The collector may trace the cell concurrently. A mutable borrow waits for
+/// such a trace to finish. If the collector finds the cell mutably borrowed,
+/// it can't read the value, and the cycle conservatively sweeps nothing.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcContext, GcRefCell};
+///
+/// let ctx = GcContext::new();
+/// let item = ctx.allocate(1);
+/// let list = ctx.allocate(GcRefCell::new(Vec::new()));
+/// list.borrow_mut().push(item.as_ptr());
+/// assert_eq!(list.borrow().len(), 1);
+/// # }
+/// ```
+pub struct GcRefCell<T> {
+    value: UnsafeCell<T>,
+    state: AtomicUsize,
+    /// Traced as part of an old object, see `traced_in_old`
+    old: AtomicBool,
+}
+
+impl<T: Trace> GcRefCell<T> {
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+            state: AtomicUsize::new(0),
+            old: AtomicBool::new(false),
+        }
+    }
+
+    /// Immutably borrow the value
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is mutably borrowed.
+    #[track_caller]
+    pub fn borrow(&self) -> GcRef<'_, T> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & WRITER != 0 {
+                panic!("GcRefCell is already mutably borrowed");
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return GcRef { cell: self },
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Mutably borrow the value
+    ///
+    /// The write barrier runs when the returned guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is borrowed.
+    #[track_caller]
+    pub fn borrow_mut(&self) -> GcRefMut<'_, T> {
+        loop {
+            match self
+                .state
+                .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return GcRefMut { cell: self },
+                // Wait for the collector, it only reads the value
+                Err(TRACING) => core::hint::spin_loop(),
+                Err(0) => {}
+                Err(_) => panic!("GcRefCell is already borrowed"),
+            }
+        }
+    }
+
+    /// Write barrier for the end of a mutable borrow
+    ///
+    /// Runs before the borrow is released: marking sets `old` before it
+    /// waits for the borrow, see `write_barrier`.
+    fn barrier(&self) {
+        let value = unsafe { &*self.value.get() };
+        if !T::NO_TRACE && self.old.load(Ordering::SeqCst) {
+            remember_young(value);
+        }
+        let has_context = with_current_context(|ctx| {
+            if ctx.heap.check_is_marking_and_increment_busy() {
+                {
+                    let mut local_gray = ctx.local_gray.borrow_mut();
+                    value.trace(&local_gray);
+                    ctx.heap
+                        .merge_barrier_work(&mut local_gray, &ctx.barrier_buffer);
+                    debug_assert!(!local_gray.has_work());
+                }
+                ctx.heap.count_barrier_op();
+                ctx.heap.decrement_busy_marking();
+            }
+        });
+        if !has_context && !T::NO_TRACE {
+            shade_without_context(value);
+        }
+    }
+}
+
+/// Shared borrow of a [`GcRefCell`]
+pub struct GcRef<'a, T> {
+    cell: &'a GcRefCell<T>,
+}
+
+impl<T> Deref for GcRef<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<T> Drop for GcRef<'_, T> {
+    fn drop(&mut self) {
+        self.cell.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Mutable borrow of a [`GcRefCell`], runs the write barrier when dropped
+pub struct GcRefMut<'a, T: Trace> {
+    cell: &'a GcRefCell<T>,
+}
+
+impl<T: Trace> Deref for GcRefMut<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<T: Trace> DerefMut for GcRefMut<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+
+impl<T: Trace> Drop for GcRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.cell.barrier();
+        self.cell.state.store(0, Ordering::Release);
+    }
+}
+
+impl<T> core::fmt::Debug for GcRefCell<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GcRefCell").finish_non_exhaustive()
+    }
+}
+
+unsafe impl<T: Trace> Trace for GcRefCell<T> {
+    const NO_TRACE: bool = T::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        traced_in_old(&self.old, tracer);
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & WRITER != 0 {
+                tracer.mark_incomplete();
+                return;
+            }
+            if state & TRACING != 0 {
+                // Traced by another marker right now
+                core::hint::spin_loop();
+                state = self.state.load(Ordering::Relaxed);
+                continue;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | TRACING,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => state = actual,
+            }
+        }
+        tracer.drain_after(|| unsafe { (*self.value.get()).trace(tracer) });
+        self.state.fetch_and(!TRACING, Ordering::Release);
+    }
+}
+
+unsafe impl<T: Send> Send for GcRefCell<T> {}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
-    use crate::GcContext;
+    use crate::{GcContext, GcOptions};
 
     #[test]
     fn test_gcptrcell_basic() {
@@ -96,6 +701,105 @@ mod tests {
         assert_eq!(*retrieved, 20);
     }
 
+    #[test]
+    fn test_replace_vec_during_marking() {
+        let ctx = GcContext::off();
+        let old: Vec<_> = (0..3).map(|i| ctx.allocate(i).as_ptr()).collect();
+        let new: Vec<_> = (10..13).map(|i| ctx.allocate(i).as_ptr()).collect();
+        let cell = ctx.allocate(GcCell::new(old));
+
+        ctx.heap().try_mark_full();
+        assert!(unsafe { &*new[0].header_ptr() }.is_white());
+
+        // The new elements are only reachable through the cell now
+        let replaced = cell.replace(new.clone());
+        assert_eq!(replaced.len(), 3);
+        ctx.heap().sweep_and_finish();
+        assert_eq!(ctx.heap().allocation_count(), 7);
+
+        let current = cell.take();
+        assert_eq!(current, new);
+        for (ptr, expected) in current.iter().zip(10..) {
+            assert_eq!(unsafe { *ptr.as_ptr() }, expected);
+        }
+    }
+
+    #[test]
+    fn test_update_during_marking() {
+        let ctx = GcContext::off();
+        let old = ctx.allocate(1).as_ptr();
+        let target = ctx.allocate(2).as_ptr();
+        let shared = ctx.allocate(3).as_ptr();
+        let cell = ctx.allocate(GcCell::new(old));
+        let atomic = ctx.allocate(AtomicGcCell::new(None));
+
+        ctx.heap().try_mark_full();
+        assert!(unsafe { &*target.header_ptr() }.is_white());
+
+        // The targets are only reachable through the cells now
+        cell.update(|current| {
+            assert_eq!(current, old);
+            target
+        });
+        assert_eq!(atomic.update(|_| Some(shared)), None);
+        ctx.heap().sweep_and_finish();
+        assert_eq!(ctx.heap().allocation_count(), 5);
+
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 4);
+        assert_eq!(unsafe { *cell.get().as_ptr() }, 2);
+        assert_eq!(unsafe { *atomic.load().unwrap().as_ptr() }, 3);
+    }
+
+    #[test]
+    fn test_refcell_borrow_mut_during_marking() {
+        let ctx = GcContext::off();
+        let old = ctx.allocate(0).as_ptr();
+        let new: Vec<_> = (1..4).map(|i| ctx.allocate(i).as_ptr()).collect();
+        let cell = ctx.allocate(GcRefCell::new(vec![old]));
+
+        ctx.heap().try_mark_full();
+        {
+            let mut items = cell.borrow_mut();
+            items.clear();
+            items.extend(&new);
+        }
+        assert!(
+            !unsafe { &*new[0].header_ptr() }.is_white(),
+            "barrier ran when the borrow ended"
+        );
+        ctx.heap().sweep_and_finish();
+        assert_eq!(ctx.heap().allocation_count(), 5);
+
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 4);
+        let sum: i32 = cell.borrow().iter().map(|p| unsafe { *p.as_ptr() }).sum();
+        assert_eq!(sum, 6);
+    }
+
+    #[test]
+    fn test_refcell_mutably_borrowed_cycle_keeps_everything() {
+        let ctx = GcContext::off();
+        let cell = ctx.allocate(GcRefCell::new(vec![ctx.allocate(1).as_ptr()]));
+        drop(ctx.allocate(2));
+
+        let borrowed = cell.borrow_mut();
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 3);
+        drop(borrowed);
+
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_refcell_double_borrow_panics() {
+        let cell = GcRefCell::new(0);
+        let _shared = cell.borrow();
+        let _mutable = cell.borrow_mut();
+    }
+
     #[test]
     fn test_gcptrcell_write_barrier() {
         let ctx = GcContext::off();
@@ -133,4 +837,41 @@ mod tests {
 
         assert_eq!(unsafe { *value2_unrooted.as_ptr() }, 20);
     }
+
+    /// Mutate a cell many times during marking and return the number of
+    /// barrier operations and the number of surviving objects
+    fn repeated_mutation_during_marking(barrier: BarrierKind) -> (usize, usize) {
+        let ctx = GcContext::with_options(GcOptions {
+            barrier,
+            ..GcOptions::OFF
+        });
+        let values: Vec<_> = (0..100).map(|i| ctx.allocate(i).as_ptr()).collect();
+        let cell = ctx.allocate(GcCell::new(values[0]));
+
+        ctx.heap().try_mark_full();
+        let ops_before = ctx.heap().barrier_ops.load(Ordering::Relaxed);
+        for v in &values {
+            cell.set(*v);
+        }
+        let ops = ctx.heap().barrier_ops.load(Ordering::Relaxed) - ops_before;
+        ctx.heap().sweep_and_finish();
+
+        // The final pointee must have been rescanned
+        assert_eq!(unsafe { *cell.get().as_ptr() }, 99);
+        (ops, ctx.heap().allocation_count())
+    }
+
+    #[test]
+    fn test_incremental_update_barrier_rescans_cell() {
+        let (iu_ops, iu_live) = repeated_mutation_during_marking(BarrierKind::IncrementalUpdate);
+        let (dijkstra_ops, dijkstra_live) = repeated_mutation_during_marking(BarrierKind::Dijkstra);
+
+        assert_eq!(iu_ops, 1, "cell is recorded only once per cycle");
+        assert_eq!(dijkstra_ops, 100, "Dijkstra shades on every store");
+        // Incremental update only keeps the cell, the pointee it had when it
+        // was scanned, and the final pointee
+        assert_eq!(iu_live, 3);
+        // Dijkstra keeps every value that was stored during marking
+        assert_eq!(dijkstra_live, 101);
+    }
 }
@@ -0,0 +1,901 @@
+//! GC-aware collection types
+//!
+//! - `DropList<T>`: Singly linked list that is dropped iteratively
+//! - `GcPtrMap<K, V>`: Hash map keyed by the identity of GC objects (`std`)
+//! - `GcPtrHashMap<K, V>`, `GcPtrHashSet<T>`: Std collections of `GcPtr`s with
+//!   the cheap [`IdentityHasher`] (`std`)
+//! - `EphemeronMap<K, V>`: Map whose values live only as long as their keys (`std`)
+//! - `Interner<T>`: Table of unique objects, held weakly (`std`)
+
+use crate::trace::{Trace, Tracer};
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use {
+    crate::cell::{CellOwner, write_barrier},
+    crate::heap::Heap,
+    crate::ptr::{GcPtr, GcRoot, GcWeak},
+    alloc::sync::Arc,
+    alloc::vec::Vec,
+    core::borrow::Borrow,
+    core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
+    std::collections::{HashMap, HashSet, hash_map::RandomState},
+};
+
+/// Singly linked list that drops its nodes iteratively
+///
+/// A GC object that owns a long chain of boxes (e.g.
+/// `struct Node { next: Option<Box<Node>> }`) is dropped recursively by the
+/// compiler-generated `Drop`, one stack frame per node. When the sweeper
+/// frees such an object, a long enough chain overflows the stack.
+/// (Chains of `GcPtr`s are unaffected: the sweeper frees every object
+/// separately.)
+///
+/// `DropList` owns its nodes like a `Box` chain, but unlinks them in a loop
+/// when dropped, so its length is not limited by the stack size.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcContext, collections::DropList};
+///
+/// let ctx = GcContext::new();
+/// let mut list = DropList::new();
+/// for i in 0..100_000 {
+///     list.push_front(i);
+/// }
+/// let list = ctx.allocate(list);
+/// assert_eq!(list.front(), Some(&99_999));
+/// # }
+/// ```
+pub struct DropList<T> {
+    head: Option<Box<DropNode<T>>>,
+    len: usize,
+}
+
+struct DropNode<T> {
+    value: T,
+    next: Option<Box<DropNode<T>>>,
+}
+
+impl<T> DropList<T> {
+    /// Create an empty list
+    #[inline]
+    pub const fn new() -> Self {
+        Self { head: None, len: 0 }
+    }
+
+    /// Number of elements in the list
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Add an element to the front of the list
+    pub fn push_front(&mut self, value: T) {
+        let next = self.head.take();
+        self.head = Some(Box::new(DropNode { value, next }));
+        self.len += 1;
+    }
+
+    /// Remove the first element of the list
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.head.take()?;
+        self.head = node.next;
+        self.len -= 1;
+        Some(node.value)
+    }
+
+    /// Get the first element of the list
+    #[inline]
+    pub fn front(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    /// Iterate over the elements, front to back
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut current = self.head.as_deref();
+        core::iter::from_fn(move || {
+            let node = current?;
+            current = node.next.as_deref();
+            Some(&node.value)
+        })
+    }
+}
+
+impl<T> Default for DropList<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for DropList<T> {
+    fn drop(&mut self) {
+        // Unlink nodes one by one, so each node is dropped without recursion
+        let mut current = self.head.take();
+        while let Some(mut node) = current {
+            current = node.next.take();
+        }
+    }
+}
+
+impl<T> FromIterator<T> for DropList<T> {
+    /// Collect into a list; the last element of the iterator is the front
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        for value in iter {
+            list.push_front(value);
+        }
+        list
+    }
+}
+
+unsafe impl<T: Trace> Trace for DropList<T> {
+    const NO_TRACE: bool = T::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        for value in self.iter() {
+            value.trace(tracer);
+        }
+    }
+}
+
+/// Hash map keyed by the identity (address) of GC objects
+///
+/// Two keys are equal if they point to the same object; the key objects'
+/// contents are never compared or hashed, so `K` needs neither `Eq` nor `Hash`.
+///
+/// # Keys are strong
+///
+/// The map keeps its keys alive: tracing the map marks every key object (and
+/// traces every value). As long as the map itself is reachable, no key is
+/// collected, so entries never dangle and are never pruned behind your back.
+/// Remove entries explicitly to release their keys.
+///
+/// # Example
+///
+/// ```
+/// use abfall::{GcContext, collections::GcPtrMap};
+///
+/// let ctx = GcContext::new();
+/// let a = ctx.allocate(1);
+/// let b = ctx.allocate(1); // same value, different identity
+///
+/// let mut map = GcPtrMap::new();
+/// map.insert(a.as_ptr(), "a");
+/// map.insert(b.as_ptr(), "b");
+/// assert_eq!(map.get(a.as_ptr()), Some(&"a"));
+/// assert_eq!(map.get(b.as_ptr()), Some(&"b"));
+/// ```
+#[cfg(feature = "std")]
+pub struct GcPtrMap<K: ?Sized, V> {
+    map: HashMap<IdKey<K>, V, BuildIdentityHasher>,
+}
+
+/// `GcPtr` compared and hashed by address
+#[cfg(feature = "std")]
+struct IdKey<K: ?Sized>(GcPtr<K>);
+
+#[cfg(feature = "std")]
+impl<K: ?Sized> IdKey<K> {
+    #[inline]
+    fn addr(&self) -> *const () {
+        self.0.header_ptr() as *const ()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized> PartialEq for IdKey<K> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized> Eq for IdKey<K> {}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized> Hash for IdKey<K> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized, V> GcPtrMap<K, V> {
+    /// Create an empty map
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::default(),
+        }
+    }
+
+    /// Number of entries in the map
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Insert a value for the object `key`, returning the previous value
+    #[inline]
+    pub fn insert(&mut self, key: GcPtr<K>, value: V) -> Option<V> {
+        self.map.insert(IdKey(key), value)
+    }
+
+    /// Get the value stored for the object `key`
+    #[inline]
+    pub fn get(&self, key: GcPtr<K>) -> Option<&V> {
+        self.map.get(&IdKey(key))
+    }
+
+    /// Get the value stored for the object `key` mutably
+    #[inline]
+    pub fn get_mut(&mut self, key: GcPtr<K>) -> Option<&mut V> {
+        self.map.get_mut(&IdKey(key))
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: GcPtr<K>) -> bool {
+        self.map.contains_key(&IdKey(key))
+    }
+
+    /// Remove the entry for the object `key`, returning its value
+    #[inline]
+    pub fn remove(&mut self, key: GcPtr<K>) -> Option<V> {
+        self.map.remove(&IdKey(key))
+    }
+
+    /// Remove all entries
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Iterate over all entries in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (GcPtr<K>, &V)> {
+        self.map.iter().map(|(key, value)| (key.0, value))
+    }
+
+    /// Iterate over all keys in arbitrary order
+    pub fn keys(&self) -> impl Iterator<Item = GcPtr<K>> + '_ {
+        self.map.keys().map(|key| key.0)
+    }
+
+    /// Iterate over all values in arbitrary order
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.map.values()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized, V> Default for GcPtrMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe impl<K: ?Sized, V: Trace> Trace for GcPtrMap<K, V> {
+    fn trace(&self, tracer: &Tracer) {
+        for (key, value) in &self.map {
+            // SAFETY: keys are kept alive by this map, see type docs
+            tracer.mark_header(unsafe { &*key.0.header_ptr() });
+            value.trace(tracer);
+        }
+    }
+}
+
+/// Hasher for object addresses, without the cost of SipHash
+///
+/// Addresses are unique and not chosen by an attacker, so they need no
+/// keyed hash: the address is only multiplied by a constant and folded, to
+/// spread the bits that alignment leaves zero. Meant for keys that hash a
+/// single pointer or `usize`, like [`GcPtr`] and [`GcRoot`]; other keys are
+/// hashed too, but poorly.
+///
+/// # Example
+///
+/// ```
+/// use abfall::{GcContext, collections::GcPtrHashSet};
+///
+/// let ctx = GcContext::new();
+/// let a = ctx.allocate(1);
+/// let b = ctx.allocate(1);
+///
+/// let mut visited = GcPtrHashSet::default();
+/// assert!(visited.insert(a.as_ptr()));
+/// assert!(visited.insert(b.as_ptr()));
+/// assert!(!visited.insert(a.as_ptr()));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityHasher(u64);
+
+#[cfg(feature = "std")]
+impl Hasher for IdentityHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        let hash = self.0.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        hash ^ (hash >> 32)
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+
+    #[inline]
+    fn write_usize(&mut self, value: usize) {
+        self.0 ^= value as u64;
+    }
+}
+
+/// Builds an [`IdentityHasher`] for a `HashMap` or `HashSet`
+#[cfg(feature = "std")]
+pub type BuildIdentityHasher = BuildHasherDefault<IdentityHasher>;
+
+/// `HashMap` keyed by `GcPtr`s, hashed by address with [`IdentityHasher`]
+///
+/// Unlike [`GcPtrMap`] this is a plain std map: it is not [`Trace`] and
+/// doesn't keep its keys alive, so it is meant for temporary maps over
+/// objects that are rooted elsewhere, for example while walking a graph.
+/// Create it with `GcPtrHashMap::default()`.
+#[cfg(feature = "std")]
+pub type GcPtrHashMap<K, V> = HashMap<GcPtr<K>, V, BuildIdentityHasher>;
+
+/// `HashSet` of `GcPtr`s, hashed by address, see [`GcPtrHashMap`]
+#[cfg(feature = "std")]
+pub type GcPtrHashSet<T> = HashSet<GcPtr<T>, BuildIdentityHasher>;
+
+/// Hash map whose entries live only as long as their keys (ephemerons)
+///
+/// Keys are compared by identity, like in [`GcPtrMap`], but held weakly:
+/// the map doesn't keep them alive. The value of an entry is only traced
+/// once its key was marked through some other path, so a value that points
+/// back to its key (directly or not) doesn't keep the entry alive either.
+/// When the key is collected, so is everything only its value reached.
+/// This is what a cache of data attached to objects needs: a
+/// `GcPtrMap<K, GcPtr<V>>` would keep all keys alive, and a map of weak
+/// values would drop values that are still in use.
+///
+/// Marking traces the objects holding maps with unmarked keys again until
+/// no more keys become reachable. A map traced outside of an object, e.g.
+/// by a write barrier, keeps all its values alive for that cycle. Entries
+/// whose key was collected are never returned; their values are dropped by
+/// [`purge`](Self::purge).
+///
+/// Keys must be live objects of the heap the map is allocated on when they
+/// are inserted. The values may be dropped on the thread calling `purge` or
+/// dropping the map.
+///
+/// # Example
+///
+/// ```
+/// use abfall::{GcContext, GcPtr, collections::EphemeronMap};
+///
+/// let ctx = GcContext::off();
+/// let cache = ctx.allocate(EphemeronMap::new());
+/// let key = ctx.allocate("key");
+/// cache.insert(key.as_ptr(), ctx.allocate(42).as_ptr());
+///
+/// ctx.force_collect();
+/// let value: GcPtr<i32> = cache.get(key.as_ptr()).unwrap();
+/// assert_eq!(unsafe { *value.as_ptr() }, 42);
+///
+/// // The value goes with its key
+/// drop(key);
+/// ctx.force_collect();
+/// assert_eq!(cache.purge(), 1);
+/// assert_eq!(ctx.allocation_count(), 1);
+/// ```
+#[cfg(feature = "std")]
+pub struct EphemeronMap<K: ?Sized, V> {
+    /// Entries by the address of their key
+    entries: crate::sync::Mutex<HashMap<usize, (GcWeak<K>, V), BuildIdentityHasher>>,
+    /// The object the map was traced as a field of, for the write barrier
+    owner: CellOwner,
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized, V: Trace> EphemeronMap<K, V> {
+    /// Create an empty map
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            entries: crate::sync::Mutex::new(HashMap::default()),
+            owner: CellOwner::new(),
+        }
+    }
+
+    /// Insert a value for the object `key`, returning the previous value
+    ///
+    /// Runs the write barrier of a cell: while marking, the object the map
+    /// belongs to is rescanned, so the value is traced if the key turns out
+    /// to be reachable. If the map wasn't traced as part of an object in the
+    /// current cycle, the value is kept alive until the next one.
+    pub fn insert(&self, key: GcPtr<K>, value: V) -> Option<V> {
+        let address = key.header_ptr() as usize;
+        write_barrier(value, self, &self.owner, |value| {
+            let mut entries = self.entries.lock();
+            match entries.get_mut(&address) {
+                Some(entry) => Some(core::mem::replace(&mut entry.1, value)),
+                None => {
+                    entries.insert(address, (GcWeak::from_ptr(key), value));
+                    None
+                }
+            }
+        })
+    }
+
+    /// Get a copy of the value stored for the object `key`
+    pub fn get(&self, key: GcPtr<K>) -> Option<V>
+    where
+        V: Clone,
+    {
+        let entries = self.entries.lock();
+        let (weak, value) = entries.get(&(key.header_ptr() as usize))?;
+        (!weak.header().is_dead()).then(|| value.clone())
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: GcPtr<K>) -> bool {
+        let entries = self.entries.lock();
+        entries
+            .get(&(key.header_ptr() as usize))
+            .is_some_and(|(weak, _)| !weak.header().is_dead())
+    }
+
+    /// Remove the entry for the object `key`, returning its value
+    pub fn remove(&self, key: GcPtr<K>) -> Option<V> {
+        let (_, value) = self.entries.lock().remove(&(key.header_ptr() as usize))?;
+        Some(value)
+    }
+
+    /// Number of entries whose key is alive
+    pub fn len(&self) -> usize {
+        let entries = self.entries.lock();
+        entries
+            .values()
+            .filter(|(weak, _)| !weak.header().is_dead())
+            .count()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove the entries whose key was collected, returning their number
+    ///
+    /// Their values are unreachable, but only dropped here.
+    pub fn purge(&self) -> usize {
+        let mut entries = self.entries.lock();
+        let before = entries.len();
+        entries.retain(|_, (weak, _)| !weak.header().is_dead());
+        before - entries.len()
+    }
+
+    /// Trace the values of the entries whose keys are marked
+    ///
+    /// If some key isn't marked yet, the object being traced is traced again
+    /// at the end of marking. Outside of an object, all values are traced.
+    fn trace_marked(&self, tracer: &Tracer) {
+        let entries = self.entries.lock();
+        // Values are only borrowed under the lock
+        tracer.drain_after(|| {
+            let mut deferred = None;
+            for (weak, value) in entries.values() {
+                let header = weak.header();
+                if header.is_dead() {
+                    continue;
+                }
+                if tracer.is_marked(header)
+                    || !*deferred.get_or_insert_with(|| tracer.defer_ephemeron())
+                {
+                    value.trace(tracer);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized, V: Trace> Default for EphemeronMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized, V> core::fmt::Debug for EphemeronMap<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EphemeronMap").finish_non_exhaustive()
+    }
+}
+
+// Keys are weak: only the values of marked keys are traced
+#[cfg(feature = "std")]
+unsafe impl<K: ?Sized, V: Trace> Trace for EphemeronMap<K, V> {
+    const NO_TRACE: bool = V::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        self.owner.traced_by(tracer);
+        self.trace_marked(tracer);
+    }
+}
+
+/// Table of unique objects, for interning strings or symbols
+///
+/// [`intern`](Self::intern) returns the live object equal to a value if
+/// there is one, and allocates the value otherwise, so equal values share
+/// one object and can be compared by identity. The table holds its objects
+/// weakly: an interned object is collected once nothing else references it,
+/// and interning an equal value afterwards allocates a new object. Entries
+/// of collected objects are removed lazily, when their bucket is looked up
+/// and whenever the table doubled in size since the last cleanup, or by
+/// [`purge`](Self::purge).
+///
+/// Interned objects must not be changed in a way that affects their `Hash`
+/// or `Eq` (through a `GcCell`, say).
+///
+/// # Example
+///
+/// ```
+/// use abfall::{GcContext, collections::Interner};
+/// use std::sync::Arc;
+///
+/// let ctx = GcContext::off();
+/// let symbols = Interner::new(Arc::clone(ctx.heap()));
+/// let first = symbols.intern(String::from("lambda"));
+/// let second = symbols.intern(String::from("lambda"));
+/// assert_eq!(first.as_ptr(), second.as_ptr());
+/// assert_eq!(symbols.get("lambda").map(|s| s.as_ptr()), Some(first.as_ptr()));
+/// ```
+#[cfg(feature = "std")]
+pub struct Interner<T> {
+    /// Dropped before the heap, which may be dropped with the interner
+    table: crate::sync::Mutex<InternTable<T>>,
+    hasher: RandomState,
+    heap: Arc<Heap>,
+}
+
+#[cfg(feature = "std")]
+struct InternTable<T> {
+    /// Objects by the hash of their value
+    buckets: HashMap<u64, Vec<GcWeak<T>>>,
+    /// Number of objects in the buckets, dead or alive
+    len: usize,
+    /// `len` that triggers the next cleanup of all buckets
+    purge_at: usize,
+}
+
+#[cfg(feature = "std")]
+impl<T> InternTable<T> {
+    /// Remove the entries of collected objects, returning their number
+    fn purge(&mut self) -> usize {
+        let before = self.len;
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|weak| !weak.header().is_dead());
+            !bucket.is_empty()
+        });
+        self.len = self.buckets.values().map(Vec::len).sum();
+        self.purge_at = (2 * self.len).max(Self::MIN_PURGE_AT);
+        before - self.len
+    }
+
+    const MIN_PURGE_AT: usize = 64;
+}
+
+#[cfg(feature = "std")]
+impl<T: Trace + Eq + Hash> Interner<T> {
+    /// Create an empty table for objects on `heap`
+    pub fn new(heap: Arc<Heap>) -> Self {
+        Self {
+            heap,
+            hasher: RandomState::new(),
+            table: crate::sync::Mutex::new(InternTable {
+                buckets: HashMap::new(),
+                len: 0,
+                purge_at: InternTable::<T>::MIN_PURGE_AT,
+            }),
+        }
+    }
+
+    /// The live object equal to `value`, or `value` allocated as a new one
+    pub fn intern(&self, value: T) -> GcRoot<T> {
+        let hash = self.hasher.hash_one(&value);
+        let mut table = self.table.lock();
+        if let Some(found) = Self::find(&mut table, hash, &value) {
+            return found;
+        }
+        let root = self.heap.allocate(value);
+        table
+            .buckets
+            .entry(hash)
+            .or_default()
+            .push(root.downgrade());
+        table.len += 1;
+        if table.len >= table.purge_at {
+            table.purge();
+        }
+        root
+    }
+
+    /// The live object equal to `key`, if there is one
+    pub fn get<Q>(&self, key: &Q) -> Option<GcRoot<T>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        Self::find(&mut self.table.lock(), hash, key)
+    }
+
+    /// Look up `key` in its bucket, dropping the entries of collected objects
+    fn find<Q>(table: &mut InternTable<T>, hash: u64, key: &Q) -> Option<GcRoot<T>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket = table.buckets.get_mut(&hash)?;
+        let mut found = None;
+        let before = bucket.len();
+        bucket.retain(|weak| match weak.upgrade() {
+            Some(root) => {
+                if found.is_none() && (*root).borrow() == key {
+                    found = Some(root);
+                }
+                true
+            }
+            None => false,
+        });
+        table.len -= before - bucket.len();
+        if bucket.is_empty() {
+            table.buckets.remove(&hash);
+        }
+        found
+    }
+
+    /// Number of entries, including those of objects collected meanwhile
+    pub fn len(&self) -> usize {
+        self.table.lock().len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove the entries of collected objects, returning their number
+    pub fn purge(&self) -> usize {
+        self.table.lock().purge()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> core::fmt::Debug for Interner<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Interner")
+            .field("len", &self.table.lock().len)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::GcContext;
+
+    #[test]
+    fn drop_long_list_without_stack_overflow() {
+        let ctx = GcContext::off();
+        let list: DropList<usize> = (0..100_000).collect();
+        assert_eq!(list.len(), 100_000);
+        let list = ctx.allocate(list);
+        assert_eq!(list.front(), Some(&99_999));
+
+        drop(list);
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 0);
+    }
+
+    #[test]
+    fn list_traces_elements() {
+        let ctx = GcContext::off();
+        let mut list = DropList::new();
+        for i in 0..10 {
+            list.push_front(ctx.allocate(i).as_ptr());
+        }
+        let list = ctx.allocate(list);
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 11);
+
+        let values: Vec<_> = list.iter().map(|p| unsafe { *p.as_ptr() }).collect();
+        assert_eq!(values, (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn identity_hasher_spreads_addresses() {
+        let ctx = GcContext::off();
+        let roots: Vec<_> = (0..1000).map(|i| ctx.allocate(i)).collect();
+        let mut map = GcPtrHashMap::default();
+        for (i, root) in roots.iter().enumerate() {
+            assert_eq!(map.insert(root.as_ptr(), i), None);
+        }
+        for (i, root) in roots.iter().enumerate() {
+            assert_eq!(map.get(&root.as_ptr()), Some(&i));
+        }
+
+        // Aligned addresses still differ in the low bits and the top 7,
+        // which `HashMap` uses for the bucket and the tag
+        let hashes: Vec<_> = roots
+            .iter()
+            .map(|root| BuildIdentityHasher::default().hash_one(root.as_ptr()))
+            .collect();
+        let buckets: HashSet<_> = hashes.iter().map(|hash| hash & 0xff).collect();
+        let tags: HashSet<_> = hashes.iter().map(|hash| hash >> 57).collect();
+        assert!(buckets.len() > 128, "{}", buckets.len());
+        assert!(tags.len() > 64, "{}", tags.len());
+    }
+
+    #[test]
+    fn ptr_map_keeps_keys_alive() {
+        let ctx = GcContext::off();
+        let mut map = GcPtrMap::new();
+        let keys: Vec<_> = (0..10).map(|i| ctx.allocate(i % 2).as_ptr()).collect();
+        for (i, &key) in keys.iter().enumerate() {
+            map.insert(key, i);
+        }
+        let map = ctx.allocate(map);
+
+        // The keys are only reachable through the map
+        ctx.heap().force_collect();
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 11);
+
+        // Lookups are by identity, not by (equal) contents
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(map.get(key), Some(&i));
+            assert_eq!(unsafe { *key.as_ptr() }, i % 2);
+        }
+        let other = ctx.allocate(0);
+        assert!(!map.contains_key(other.as_ptr()));
+
+        drop(map);
+        drop(other);
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 0);
+    }
+
+    #[test]
+    fn ephemeron_values_live_as_long_as_their_keys() {
+        let ctx = GcContext::off();
+        let map = ctx.allocate(EphemeronMap::new());
+        let kept_key = ctx.allocate(1u32);
+        let lost_key = ctx.allocate(2u32);
+        // Reachable only through the value of `kept_key`: found by the rescan
+        let chained_key = ctx.allocate(3u32).as_ptr();
+        map.insert(kept_key.as_ptr(), chained_key);
+        map.insert(chained_key, ctx.allocate(30u32).as_ptr());
+        map.insert(lost_key.as_ptr(), ctx.allocate(20u32).as_ptr());
+
+        ctx.force_collect();
+        assert_eq!(ctx.allocation_count(), 6);
+        assert_eq!(map.len(), 3);
+
+        drop(lost_key);
+        ctx.force_collect();
+        assert_eq!(ctx.allocation_count(), 4);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.purge(), 1);
+        let value = map.get(chained_key).unwrap();
+        assert_eq!(unsafe { *value.as_ptr() }, 30);
+
+        drop(kept_key);
+        ctx.force_collect();
+        assert_eq!(ctx.allocation_count(), 1);
+        assert_eq!(map.purge(), 2);
+    }
+
+    #[test]
+    fn ephemeron_value_pointing_to_its_key_is_collected() {
+        let ctx = GcContext::off();
+        let map = ctx.allocate(EphemeronMap::new());
+        let key = ctx.allocate(0u32);
+        // The value refers back to the key
+        map.insert(key.as_ptr(), ctx.allocate(Some(key.as_ptr())).as_ptr());
+        drop(key);
+        ctx.force_collect();
+        assert_eq!(ctx.allocation_count(), 1);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn ephemeron_inserted_while_marking_is_traced() {
+        let ctx = GcContext::off();
+        let map = ctx.allocate(EphemeronMap::new());
+        let key = ctx.allocate(0u32);
+        assert!(ctx.begin_mark());
+        while !ctx.do_mark_work(usize::MAX) {}
+        // The map is black already
+        map.insert(key.as_ptr(), ctx.allocate(1u32).as_ptr());
+        assert!(ctx.sweep().is_some());
+        assert_eq!(ctx.allocation_count(), 3);
+    }
+
+    #[test]
+    fn ephemeron_map_replaced_while_deferred() {
+        let ctx = GcContext::off();
+        let holder = ctx.allocate(crate::GcRefCell::new(EphemeronMap::new()));
+        let cell: &crate::GcRefCell<_> = &holder;
+        let key = ctx.allocate(0u32).as_ptr();
+        cell.borrow().insert(key, ctx.allocate(1u32).as_ptr());
+        assert!(ctx.begin_mark());
+        // Traces the holder, whose map has an unmarked key
+        ctx.do_mark_work(1);
+        // Neither the entries nor the map are needed by the rescan
+        assert!(cell.borrow().remove(key).is_some());
+        *cell.borrow_mut() = EphemeronMap::new();
+        assert!(ctx.sweep().is_some());
+        assert_eq!(ctx.allocation_count(), 1);
+    }
+
+    #[test]
+    fn interned_values_share_an_object_while_alive() {
+        let ctx = GcContext::off();
+        let interner = Interner::new(Arc::clone(ctx.heap()));
+        let first = interner.intern(String::from("symbol"));
+        let second = interner.intern(String::from("symbol"));
+        let other = interner.intern(String::from("other"));
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        assert_ne!(first.as_ptr(), other.as_ptr());
+        assert_eq!(ctx.allocation_count(), 2);
+
+        // The table doesn't keep its objects alive
+        drop(first);
+        drop(second);
+        ctx.force_collect();
+        assert_eq!(ctx.allocation_count(), 1);
+        assert!(interner.get("symbol").is_none());
+        assert_eq!(interner.len(), 1);
+
+        let again = interner.intern(String::from("symbol"));
+        assert_eq!(*again, "symbol");
+        assert_eq!(interner.get("symbol").unwrap().as_ptr(), again.as_ptr());
+        assert_eq!(ctx.allocation_count(), 2);
+
+        drop(other);
+        ctx.force_collect();
+        assert_eq!(interner.purge(), 1);
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn interner_can_drop_the_last_reference_to_its_heap() {
+        let ctx = GcContext::off();
+        let interner = Interner::new(Arc::clone(ctx.heap()));
+        drop(interner.intern(String::from("collected")));
+        ctx.force_collect();
+        drop(interner.intern(String::from("unreachable")));
+        assert_eq!(ctx.allocation_count(), 1);
+        drop(ctx);
+        // Drops the heap, with an object and a tombstone in the table
+        drop(interner);
+    }
+}
@@ -5,7 +5,7 @@
 //! - Gray: Objects that are reachable but not yet scanned
 //! - Black: Objects that are reachable and fully scanned
 
-use std::sync::atomic::{AtomicU8, Ordering};
+use crate::sync::atomic::{AtomicUsize, Ordering};
 
 /// The color of an object in the tri-color marking algorithm
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,63 +30,190 @@ impl From<u8> for Color {
     }
 }
 
-/// Thread-safe atomic color storage
-pub struct AtomicColor {
-    inner: AtomicU8,
+/// Bits of the packed word holding the color
+const COLOR_MASK: usize = 0b11;
+/// One root in the packed word
+const ONE_ROOT: usize = COLOR_MASK + 1;
+
+/// Root count of an object that was swept
+///
+/// Only `GcWeak`s can still observe such a header; the data is already dropped.
+pub(crate) const DEAD_ROOTS: usize = usize::MAX >> 2;
+
+/// Thread-safe storage of an object's color and root count
+///
+/// Both are packed into one word: the color in the low 2 bits, the root
+/// count in the remaining bits. A single load sees a consistent pair, so
+/// "white and not rooted" can't race with a root being added.
+pub struct AtomicState {
+    inner: AtomicUsize,
 }
 
-impl AtomicColor {
-    pub fn new(color: Color) -> Self {
+impl AtomicState {
+    pub fn new(color: Color, root_count: usize) -> Self {
         Self {
-            inner: AtomicU8::new(color as u8),
+            inner: AtomicUsize::new(root_count << 2 | color as usize),
         }
     }
 
-    fn load(&self, ordering: Ordering) -> Color {
-        Color::from(self.inner.load(ordering))
-    }
-
-    fn store(&self, color: Color, ordering: Ordering) {
-        self.inner.store(color as u8, ordering);
+    #[inline]
+    fn color_of(word: usize) -> Color {
+        Color::from((word & COLOR_MASK) as u8)
     }
 
+    /// Replace the color, keeping the root count
     #[inline]
-    fn compare_exchange(
-        &self,
-        current: Color,
-        new: Color,
-        success: Ordering,
-        failure: Ordering,
-    ) -> Result<Color, Color> {
-        self.inner
-            .compare_exchange(current as u8, new as u8, success, failure)
-            .map(Color::from)
-            .map_err(Color::from)
+    fn set_color(&self, color: Color, success: Ordering) {
+        let mut word = self.inner.load(Ordering::Relaxed);
+        while let Err(actual) = self.inner.compare_exchange_weak(
+            word,
+            word & !COLOR_MASK | color as usize,
+            success,
+            Ordering::Relaxed,
+        ) {
+            word = actual;
+        }
     }
 
     #[inline]
     pub fn mark_white_to_gray(&self) -> bool {
-        self.compare_exchange(
-            Color::White,
-            Color::Gray,
-            Ordering::SeqCst,
-            Ordering::SeqCst,
-        )
-        .is_ok()
+        let mut word = self.inner.load(Ordering::SeqCst);
+        loop {
+            if Self::color_of(word) != Color::White {
+                return false;
+            }
+            match self.inner.compare_exchange_weak(
+                word,
+                word | Color::Gray as usize,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => word = actual,
+            }
+        }
     }
 
     #[inline]
     pub fn mark_black(&self) {
-        self.store(Color::Black, Ordering::Release);
+        self.set_color(Color::Black, Ordering::Release);
     }
 
     #[inline]
     pub fn reset_white(&self) {
-        self.store(Color::White, Ordering::Release);
+        self.inner.fetch_and(!COLOR_MASK, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn get(&self) -> Color {
+        Self::color_of(self.inner.load(Ordering::Acquire))
     }
 
     #[inline]
     pub fn is_white(&self) -> bool {
-        self.load(Ordering::Acquire) == Color::White
+        self.get() == Color::White
+    }
+
+    /// White and not rooted, i.e. garbage once marking is complete
+    #[inline]
+    pub fn is_white_unrooted(&self) -> bool {
+        self.inner.load(Ordering::Acquire) == Color::White as usize
+    }
+
+    #[inline]
+    pub fn root_count(&self) -> usize {
+        self.inner.load(Ordering::Relaxed) >> 2
+    }
+
+    /// Add a root, returns the previous root count
+    #[inline]
+    pub fn inc_root(&self) -> usize {
+        self.inner.fetch_add(ONE_ROOT, Ordering::Relaxed) >> 2
+    }
+
+    /// Remove a root, returns the previous root count
+    #[inline]
+    pub fn dec_root(&self) -> usize {
+        self.inner.fetch_sub(ONE_ROOT, Ordering::Relaxed) >> 2
+    }
+
+    /// Add a root unless the object is dead, returns the previous root count
+    pub fn try_inc_root(&self) -> Option<usize> {
+        let mut word = self.inner.load(Ordering::Relaxed);
+        loop {
+            if word >> 2 == DEAD_ROOTS {
+                return None;
+            }
+            match self.inner.compare_exchange_weak(
+                word,
+                word + ONE_ROOT,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(word >> 2),
+                Err(actual) => word = actual,
+            }
+        }
+    }
+
+    /// Mark a swept object, see `DEAD_ROOTS`
+    #[inline]
+    pub fn mark_dead(&self) {
+        self.inner.store(DEAD_ROOTS << 2, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn roots_and_colors_in_one_word() {
+        let state = AtomicState::new(Color::White, 1);
+        assert!(!state.is_white_unrooted());
+        assert!(state.mark_white_to_gray());
+        assert!(!state.mark_white_to_gray());
+        assert_eq!(state.root_count(), 1);
+        state.mark_black();
+        assert_eq!(state.dec_root(), 1);
+        assert_eq!(state.get(), Color::Black);
+        state.reset_white();
+        assert!(state.is_white_unrooted());
+
+        state.mark_dead();
+        assert_eq!(state.try_inc_root(), None);
+        assert!(state.is_white());
+    }
+
+    #[test]
+    fn concurrent_roots_and_colors_lose_no_updates() {
+        const ROUNDS: usize = 20_000;
+        let state = Arc::new(AtomicState::new(Color::White, 3));
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let state = Arc::clone(&state);
+                thread::spawn(move || {
+                    for _ in 0..ROUNDS {
+                        if i % 2 == 0 {
+                            state.inc_root();
+                            state.try_inc_root().unwrap();
+                            state.dec_root();
+                            state.dec_root();
+                        } else {
+                            state.mark_white_to_gray();
+                            state.mark_black();
+                            state.reset_white();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(state.root_count(), 3);
+        assert_eq!(state.get(), Color::White);
     }
 }
@@ -0,0 +1,51 @@
+//! Error type for recoverable failures
+
+use crate::heap::AllocError;
+use core::fmt;
+
+/// Errors returned by the fallible (`try_*`) variants of the API
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A `GcContext` is already active on this thread
+    ContextAlreadySet,
+    /// The background collection thread terminated with a panic
+    BackgroundThreadPanicked,
+    /// An allocation would exceed `GcOptions::limit_bytes`
+    LimitExceeded(AllocError),
+    /// The heap was shut down with [`Heap::shutdown`](crate::Heap::shutdown)
+    HeapShuttingDown,
+    /// `GcOptionsBuilder::build` rejected the options, with the reason
+    InvalidOptions(&'static str),
+    /// A `Trace` impl panicked during marking, see [`Heap::last_error`](crate::Heap::last_error)
+    TracePanicked,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ContextAlreadySet => f.write_str("A GcContext is already set for this thread"),
+            Self::BackgroundThreadPanicked => f.write_str("the background collector panicked"),
+            Self::LimitExceeded(err) => err.fmt(f),
+            Self::HeapShuttingDown => f.write_str("the heap is shutting down"),
+            Self::InvalidOptions(reason) => write!(f, "invalid GcOptions: {reason}"),
+            Self::TracePanicked => f.write_str("a `Trace` impl panicked during marking"),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::LimitExceeded(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<AllocError> for Error {
+    #[inline]
+    fn from(err: AllocError) -> Self {
+        Self::LimitExceeded(err)
+    }
+}
@@ -0,0 +1,194 @@
+//! FFI-safe handles for passing GC objects through C code
+//!
+//! A `GcHandle` is a type-erased root. It is `#[repr(transparent)]` over a
+//! pointer to the object's [`GcHeader`], so it can be handed to C as an opaque
+//! pointer. C code keeps objects alive with the `extern "C"` functions
+//! [`abfall_root`], [`abfall_unroot`] and [`abfall_deref`].
+
+use crate::gc_box::GcHeader;
+use crate::ptr::GcRoot;
+use core::ffi::c_void;
+use core::mem::ManuallyDrop;
+use core::ptr::{self, NonNull};
+
+/// Type-erased rooted handle to a GC object
+///
+/// A handle owns one root count of its object, exactly like a `GcRoot`.
+/// Cloning a handle adds a root, dropping it removes one.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcContext, GcHandle, abfall_deref, abfall_unroot};
+///
+/// let ctx = GcContext::new();
+/// let handle = GcHandle::from(ctx.allocate(42u32));
+///
+/// // Hand the root over to C
+/// let raw = handle.into_raw();
+///
+/// // ... later, in C: read the value and release the root
+/// let value = unsafe { *(abfall_deref(raw) as *const u32) };
+/// assert_eq!(value, 42);
+/// unsafe { abfall_unroot(raw) };
+/// # }
+/// ```
+#[repr(transparent)]
+pub struct GcHandle(NonNull<GcHeader>);
+
+impl GcHandle {
+    /// Get the raw header pointer without giving up the root
+    #[inline]
+    pub fn as_raw(&self) -> *const GcHeader {
+        self.0.as_ptr()
+    }
+
+    /// Consume the handle, transferring its root to the returned pointer
+    ///
+    /// The root must be released again with [`abfall_unroot`] or by
+    /// [`GcHandle::from_raw`].
+    #[inline]
+    pub fn into_raw(self) -> *const GcHeader {
+        ManuallyDrop::new(self).as_raw()
+    }
+
+    /// Reclaim a handle from a pointer returned by [`GcHandle::into_raw`]
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must carry a root count that is owned by the caller (from
+    /// `into_raw` or `abfall_root`). This root is transferred to the handle.
+    #[inline]
+    pub unsafe fn from_raw(ptr: *const GcHeader) -> Self {
+        Self(NonNull::new(ptr as *mut GcHeader).expect("null GcHandle"))
+    }
+
+    /// Pointer to the object data
+    #[inline]
+    pub fn data_ptr(&self) -> *const c_void {
+        unsafe { self.0.as_ref().data_ptr() as *const c_void }
+    }
+}
+
+impl<T: ?Sized> From<GcRoot<T>> for GcHandle {
+    #[inline]
+    fn from(root: GcRoot<T>) -> Self {
+        let root = ManuallyDrop::new(root);
+        Self(NonNull::new(root.as_ptr().header_ptr() as *mut GcHeader).unwrap())
+    }
+}
+
+impl Clone for GcHandle {
+    #[inline]
+    fn clone(&self) -> Self {
+        unsafe { self.0.as_ref().inc_root() };
+        Self(self.0)
+    }
+}
+
+impl Drop for GcHandle {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.0.as_ref().dec_root() };
+    }
+}
+
+unsafe impl Send for GcHandle {}
+unsafe impl Sync for GcHandle {}
+
+/// Add a root to the object behind `header`
+///
+/// Null pointers are ignored.
+///
+/// # Safety
+///
+/// `header` must point to a live GC object (for example obtained from
+/// [`GcHandle::into_raw`] and not yet released).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn abfall_root(header: *const GcHeader) {
+    if let Some(header) = unsafe { header.as_ref() } {
+        header.inc_root();
+    }
+}
+
+/// Release a root of the object behind `header`
+///
+/// After the last root is released, the object may be collected.
+/// Null pointers are ignored.
+///
+/// # Safety
+///
+/// The caller must own a root of the object (see [`abfall_root`]).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn abfall_unroot(header: *const GcHeader) {
+    if let Some(header) = unsafe { header.as_ref() } {
+        header.dec_root();
+    }
+}
+
+/// Get a pointer to the data of the object behind `header`
+///
+/// Returns null for null pointers.
+///
+/// # Safety
+///
+/// `header` must point to a live GC object. The returned pointer is only
+/// valid as long as the object is rooted.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn abfall_deref(header: *const GcHeader) -> *const c_void {
+    match unsafe { header.as_ref() } {
+        Some(header) => header.data_ptr() as *const c_void,
+        None => ptr::null(),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::GcContext;
+
+    /// Simulates C code holding an opaque pointer across a collection
+    unsafe fn c_side_round_trip(ctx: &GcContext, raw: *const GcHeader) -> u64 {
+        unsafe {
+            abfall_root(raw);
+            // The original root was given up, C holds the only remaining one
+            abfall_unroot(raw);
+            ctx.heap().force_collect();
+            let value = *(abfall_deref(raw) as *const u64);
+            abfall_unroot(raw);
+            value
+        }
+    }
+
+    #[test]
+    fn handle_survives_collection_while_held_by_c() {
+        let ctx = GcContext::off();
+        let handle = GcHandle::from(ctx.allocate(0xdead_beef_u64));
+        let raw = handle.into_raw();
+
+        let value = unsafe { c_side_round_trip(&ctx, raw) };
+        assert_eq!(value, 0xdead_beef);
+
+        // All roots are released now
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 0);
+    }
+
+    #[test]
+    fn handle_clone_and_drop_adjust_roots() {
+        let ctx = GcContext::off();
+        let handle = GcHandle::from(ctx.allocate(String::from("hello")));
+        let clone = handle.clone();
+        drop(handle);
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 1);
+        assert_eq!(unsafe { &*(clone.data_ptr() as *const String) }, "hello");
+
+        let clone = unsafe { GcHandle::from_raw(clone.into_raw()) };
+        drop(clone);
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 0);
+        assert!(unsafe { abfall_deref(ptr::null()) }.is_null());
+    }
+}
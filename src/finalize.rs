@@ -0,0 +1,51 @@
+//! Finalization of collected objects
+
+/// A callback that runs when the collector reclaims an object
+///
+/// Only objects allocated with [`Heap::allocate_finalized`](crate::Heap::allocate_finalized)
+/// are finalized. The sweeper first unlinks all unreachable objects, then
+/// runs their finalizers, and only then drops them, so `finalize` can still
+/// read `GcPtr`s to other garbage.
+///
+/// Finalizers run on the sweeping thread while the sweep lock is held. They
+/// must not resurrect objects: any `GcRoot` created inside `finalize` has to
+/// be dropped before it returns, otherwise the process is aborted. For the
+/// same reason, they must not upgrade `GcWeak`s (which panics). Allocating
+/// and collecting behave like in the `Drop` of garbage, see
+/// [`Heap`](crate::Heap#reentrancy).
+///
+/// Objects that are still allocated when the heap is dropped are not
+/// finalized (their `Drop` still runs).
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{Finalize, GcContext};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static CLOSED: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct Handle(u32);
+///
+/// unsafe impl abfall::Trace for Handle {
+///     const NO_TRACE: bool = true;
+///     fn trace(&self, _tracer: &abfall::Tracer) {}
+/// }
+///
+/// impl Finalize for Handle {
+///     fn finalize(&self) {
+///         CLOSED.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let ctx = GcContext::off();
+/// drop(ctx.allocate_finalized(Handle(3)));
+/// ctx.force_collect();
+/// assert_eq!(CLOSED.load(Ordering::Relaxed), 1);
+/// # }
+/// ```
+pub trait Finalize {
+    /// Called once before the object is dropped by the sweeper
+    fn finalize(&self);
+}
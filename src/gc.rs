@@ -4,9 +4,12 @@
 //! Each thread has its own heap, accessed through a RAII guard.
 
 use crate::Tracer;
-use crate::heap::{GcOptions, Heap};
+use crate::error::Error;
+use crate::finalize::Finalize;
+use crate::heap::{AllocError, BarrierBuffer, CollectionResult, GcOptions, Heap, ThreadList};
 use crate::trace::Trace;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
 use std::ops::Deref;
 use std::pin::Pin;
 use std::ptr;
@@ -14,17 +17,20 @@ use std::sync::Arc;
 
 thread_local! {
     static CURRENT_CTX: Cell<*const GcContextInner> = const { Cell::new(ptr::null()) };
+    /// Contexts shadowed by `GcContext::scope`, innermost last
+    static SAVED_CTX: RefCell<Vec<*const GcContextInner>> = const { RefCell::new(Vec::new()) };
 }
 
 /// Set the current thread-local heap
-fn set_current_context(ctx: &Pin<Box<GcContextInner>>) {
+fn set_current_context(ctx: &Pin<Box<GcContextInner>>) -> Result<(), Error> {
     let target_ptr: *const GcContextInner = ctx.as_ref().get_ref();
     CURRENT_CTX.with(|tls| {
         if !tls.get().is_null() {
-            panic!("A GcContext is already set for this thread");
+            return Err(Error::ContextAlreadySet);
         }
         tls.set(target_ptr);
-    });
+        Ok(())
+    })
 }
 
 fn reset_current_context(ctx: &Pin<Box<GcContextInner>>) {
@@ -34,6 +40,32 @@ fn reset_current_context(ctx: &Pin<Box<GcContextInner>>) {
             tls.set(ptr::null());
         }
     });
+    // A shadowed context may be dropped inside the scope, don't restore it
+    SAVED_CTX.with(|saved| {
+        for saved in saved.borrow_mut().iter_mut() {
+            if saved.addr() == target_ptr.addr() {
+                *saved = ptr::null();
+            }
+        }
+    });
+}
+
+/// Restores the context shadowed by `GcContext::scope`, also on panic
+struct RestoreContext;
+
+impl RestoreContext {
+    fn shadow() -> Self {
+        let previous = CURRENT_CTX.with(|tls| tls.replace(ptr::null()));
+        SAVED_CTX.with(|saved| saved.borrow_mut().push(previous));
+        RestoreContext
+    }
+}
+
+impl Drop for RestoreContext {
+    fn drop(&mut self) {
+        let previous = SAVED_CTX.with(|saved| saved.borrow_mut().pop());
+        CURRENT_CTX.with(|tls| tls.set(previous.unwrap_or(ptr::null())));
+    }
 }
 
 pub(crate) fn with_current_context(f: impl FnOnce(&GcContextInner)) -> bool {
@@ -52,8 +84,25 @@ pub(crate) fn with_current_context(f: impl FnOnce(&GcContextInner)) -> bool {
 
 pub(crate) struct GcContextInner {
     pub heap: Arc<Heap>,
-    pub local_gray: Tracer,
-    _marker: std::marker::PhantomData<*const ()>, // Makes GcContext !Send + !Sync
+    /// Objects shaded by the write barrier of this thread that is running
+    ///
+    /// A barrier moves them to `barrier_buffer` before it leaves the busy
+    /// section that the sweep waits for, so this is empty whenever a
+    /// collection on another thread could look for them. Borrowed only
+    /// while the barrier traces the new value and merges the work.
+    pub local_gray: RefCell<Tracer>,
+    /// Objects shaded by the write barriers of this thread, not yet queued
+    pub barrier_buffer: BarrierBuffer,
+    /// Allocations not yet linked into the heap's list
+    pub alloc_list: ThreadList,
+    _marker: PhantomData<*const ()>, // Makes GcContext !Send + !Sync
+}
+
+/// The heap of the thread's active context, if any
+fn current_heap() -> Option<Arc<Heap>> {
+    let mut heap = None;
+    with_current_context(|ctx| heap = Some(Arc::clone(&ctx.heap)));
+    heap
 }
 
 /// RAII guard for GC context
@@ -74,7 +123,41 @@ pub(crate) struct GcContextInner {
 /// let value = ctx.allocate(42);
 /// // ctx is dropped here, clearing thread-local context
 /// ```
-pub struct GcContext(Pin<Box<GcContextInner>>);
+pub struct GcContext(Registration);
+
+enum Registration {
+    /// Registered as the thread's context, cleared on drop
+    Active(Pin<Box<GcContextInner>>),
+    /// Created by `with_heap` for the heap of the active context, which it
+    /// leaves in place
+    Reentered(Arc<Heap>, PhantomData<*const ()>),
+}
+
+/// The active context of the current thread, see [`GcContext::current`]
+///
+/// Holds a reference to the context's heap, so it stays usable after the
+/// context is dropped; allocations then work like [`Heap::allocate`]
+/// without a context.
+pub struct GcContextHandle {
+    heap: Arc<Heap>,
+    _marker: PhantomData<*const ()>,
+}
+
+impl GcContextHandle {
+    /// The heap of the context
+    pub fn heap(&self) -> &Arc<Heap> {
+        &self.heap
+    }
+}
+
+impl Deref for GcContextHandle {
+    type Target = Arc<Heap>;
+
+    #[inline]
+    fn deref(&self) -> &Arc<Heap> {
+        &self.heap
+    }
+}
 
 impl Default for GcContext {
     fn default() -> Self {
@@ -96,19 +179,31 @@ impl GcContext {
     /// let value = ctx.allocate(42);
     /// ```
     pub fn new() -> Self {
-        let heap = Heap::new();
-        Self::with_heap(heap)
+        Self::try_new().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible version of [`new`](Self::new)
+    pub fn try_new() -> Result<Self, Error> {
+        Self::try_with_heap(Heap::new())
     }
 
     pub fn off() -> Self {
-        let heap = Heap::off();
-        Self::with_heap(heap)
+        Self::try_off().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible version of [`off`](Self::off)
+    pub fn try_off() -> Result<Self, Error> {
+        Self::try_with_heap(Heap::off())
     }
 
     /// Create a new GC context and a new Heap with custom options
     pub fn with_options(options: GcOptions) -> Self {
-        let heap = Heap::with_options(options);
-        Self::with_heap(heap)
+        Self::try_with_options(options).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible version of [`with_options`](Self::with_options)
+    pub fn try_with_options(options: GcOptions) -> Result<Self, Error> {
+        Self::try_with_heap(Heap::with_options(options))
     }
 
     /// Create a new GC context for the current thread using a shared heap
@@ -116,6 +211,16 @@ impl GcContext {
     /// This allows multiple threads to share the same underlying heap,
     /// each with its own thread-local context.
     ///
+    /// If the thread's context already uses `heap`, for example in recursive
+    /// library code, the returned context shares it instead of registering
+    /// again: dropping it leaves the outer context active.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread has a context for another heap or the heap was
+    /// shut down; use [`try_with_heap`](Self::try_with_heap) to handle these
+    /// cases, or [`scope`](Self::scope) to switch heaps temporarily.
+    ///
     /// # Example
     ///
     /// ```
@@ -135,13 +240,129 @@ impl GcContext {
     /// let result = handle.join().unwrap();
     /// ```
     pub fn with_heap(heap: Arc<Heap>) -> Self {
+        Self::try_with_heap(heap).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Create a new GC context for the current thread using a shared heap
+    ///
+    /// Returns [`Error::ContextAlreadySet`] if this thread already has an
+    /// active context for another heap, and [`Error::HeapShuttingDown`] if
+    /// the heap was shut down with [`Heap::shutdown`]. A context for the same
+    /// heap is reentered, like in [`with_heap`](Self::with_heap).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{Error, GcContext, Heap};
+    /// use std::sync::Arc;
+    ///
+    /// let ctx = GcContext::new();
+    /// let other = GcContext::try_with_heap(Heap::new());
+    /// assert!(matches!(other, Err(Error::ContextAlreadySet)));
+    /// let same = GcContext::try_with_heap(Arc::clone(ctx.heap()));
+    /// assert!(same.is_ok());
+    /// ```
+    pub fn try_with_heap(heap: Arc<Heap>) -> Result<Self, Error> {
+        if heap.is_shut_down() {
+            return Err(Error::HeapShuttingDown);
+        }
+        if let Some(current) = current_heap() {
+            if !Arc::ptr_eq(&current, &heap) {
+                return Err(Error::ContextAlreadySet);
+            }
+            return Ok(GcContext(Registration::Reentered(heap, PhantomData)));
+        }
         let inner = Box::pin(GcContextInner {
             heap,
-            local_gray: Tracer::new(),
-            _marker: std::marker::PhantomData,
+            local_gray: RefCell::new(Tracer::new()),
+            alloc_list: ThreadList::new(),
+            barrier_buffer: BarrierBuffer::new(),
+            _marker: PhantomData,
         });
-        set_current_context(&inner);
-        GcContext(inner)
+        set_current_context(&inner)?;
+        // SAFETY: pinned, and unregistered when the context is dropped
+        unsafe {
+            inner.heap.register_thread_list(&inner.alloc_list);
+            inner.heap.register_barrier_buffer(&inner.barrier_buffer);
+        }
+        Ok(GcContext(Registration::Active(inner)))
+    }
+
+    /// The active context of the current thread, if any
+    ///
+    /// Library code can use this to allocate on the caller's heap without
+    /// creating a context of its own.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::GcContext;
+    /// use std::sync::Arc;
+    ///
+    /// assert!(GcContext::current().is_none());
+    /// let ctx = GcContext::off();
+    /// let current = GcContext::current().unwrap();
+    /// assert!(Arc::ptr_eq(current.heap(), ctx.heap()));
+    /// ```
+    pub fn current() -> Option<GcContextHandle> {
+        current_heap().map(|heap| GcContextHandle {
+            heap,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Create a new GC context for the current thread using the process-global heap
+    ///
+    /// The global heap is initialized with default options on first use,
+    /// unless [`global::init_global`](crate::global::init_global) was called before.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::global();
+    /// let value = ctx.allocate(42);
+    /// ```
+    pub fn global() -> Self {
+        Self::try_global().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible version of [`global`](Self::global)
+    pub fn try_global() -> Result<Self, Error> {
+        Self::try_with_heap(Arc::clone(crate::global::global_heap()))
+    }
+
+    /// Run `f` with a temporary context for `heap`, even if the thread already has one
+    ///
+    /// The current context (if any) is set aside while `f` runs: allocations
+    /// and write barriers inside the scope go to the scoped heap. The previous
+    /// context is restored afterwards, also if `f` panics. Scopes can be nested.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the heap was shut down.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcContext, Heap};
+    ///
+    /// let ctx = GcContext::off();
+    /// let outer = ctx.allocate(1);
+    /// let len = GcContext::scope(Heap::off(), |scoped| {
+    ///     let temp = scoped.allocate(vec![1, 2, 3]);
+    ///     temp.len()
+    /// });
+    /// assert_eq!(len, 3);
+    /// assert_eq!(ctx.allocation_count(), 1);
+    /// # drop(outer);
+    /// ```
+    pub fn scope<R>(heap: Arc<Heap>, f: impl FnOnce(&GcContext) -> R) -> R {
+        // Declared first, so it runs after the scoped context is dropped
+        let _restore = RestoreContext::shadow();
+        let ctx = Self::with_heap(heap);
+        f(&ctx)
     }
 
     /// Allocate an object on the GC heap
@@ -161,19 +382,83 @@ impl GcContext {
     /// assert_eq!(*number, 42);
     /// ```
     pub fn allocate<T: Trace>(&self, data: T) -> crate::GcRoot<T> {
-        self.0.heap.allocate(data)
+        self.heap().allocate(data)
+    }
+
+    /// Allocate an object on the GC heap, failing if the heap limit is exceeded
+    ///
+    /// See [`Heap::try_allocate`].
+    pub fn try_allocate<T: Trace>(&self, data: T) -> Result<crate::GcRoot<T>, AllocError> {
+        self.heap().try_allocate(data)
+    }
+
+    /// Allocate an object that is never collected while it is pinned
+    ///
+    /// See [`Heap::pin`].
+    pub fn pin<T: Trace>(&self, data: T) -> crate::PinnedGc<T> {
+        self.heap().pin(data)
+    }
+
+    /// Allocate an object whose finalizer runs before it is swept
+    ///
+    /// See [`Heap::allocate_finalized`].
+    pub fn allocate_finalized<T: Trace + Finalize>(&self, data: T) -> crate::GcRoot<T> {
+        self.heap().allocate_finalized(data)
+    }
+
+    /// Allocate an object that is constructed with a pointer to itself
+    ///
+    /// See [`Heap::allocate_with`].
+    pub fn allocate_with<T: Trace>(
+        &self,
+        f: impl FnOnce(crate::GcPtr<T>) -> T,
+    ) -> crate::GcRoot<T> {
+        self.heap().allocate_with(f)
+    }
+
+    /// Allocate a copy of a slice on the GC heap
+    ///
+    /// See [`Heap::allocate_slice`].
+    pub fn allocate_slice<T: Trace + Copy>(&self, data: &[T]) -> crate::GcRoot<[T]> {
+        self.heap().allocate_slice(data)
+    }
+
+    /// Run a full collection and return once its garbage is freed
+    ///
+    /// See [`Heap::collect_and_wait`].
+    pub fn collect_and_wait(&self) -> CollectionResult {
+        self.heap().collect_and_wait()
     }
 
     /// Get reference to the underlying heap (for advanced use)
     pub fn heap(&self) -> &Arc<Heap> {
-        &self.0.heap
+        match &self.0 {
+            Registration::Active(inner) => &inner.heap,
+            Registration::Reentered(heap, _) => heap,
+        }
     }
 }
 
 impl Drop for GcContext {
     fn drop(&mut self) {
+        let Registration::Active(inner) = &self.0 else {
+            return;
+        };
         // Clear thread-local heap when context is dropped
-        reset_current_context(&self.0);
+        reset_current_context(inner);
+        inner.heap.unregister_thread_list(&inner.alloc_list);
+        inner.heap.unregister_barrier_buffer(&inner.barrier_buffer);
+
+        // Don't strand objects shaded by this thread
+        {
+            let mut local_gray = inner.local_gray.borrow_mut();
+            if local_gray.has_work() {
+                inner.heap.merge_work_unbounded(&mut local_gray);
+            }
+        }
+        if inner.heap.options().collect_on_context_drop {
+            inner.heap.collect_after_context_drop();
+        }
     }
 }
 
@@ -182,6 +467,6 @@ impl Deref for GcContext {
 
     #[inline]
     fn deref(&self) -> &Arc<Heap> {
-        &self.0.heap
+        self.heap()
     }
 }
@@ -3,11 +3,16 @@
 //! This module defines the internal structure of garbage-collected objects,
 //! including the header, vtable, and container.
 
-use crate::color::{AtomicColor, Color};
+use crate::color::{AtomicState, Color};
+use crate::finalize::Finalize;
+use crate::heap::Heap;
+use crate::sync::atomic::AtomicPtr;
 use crate::trace::{Trace, Tracer};
-use std::alloc::Layout;
-use std::ptr::{NonNull, null_mut};
-use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use alloc::alloc::Layout;
+use alloc::boxed::Box;
+use core::any::TypeId;
+use core::ptr::{NonNull, null_mut};
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
 
 /// Type-erased virtual table for GC operations
 ///
@@ -20,15 +25,62 @@ pub struct GcVTable {
     /// Drop function - properly drops the object using Box::from_raw
     pub drop: unsafe fn(*mut GcHeader),
 
+    /// Drops only the data in place, the allocation stays valid
+    pub drop_data: unsafe fn(*mut GcHeader),
+
+    /// Runs `Finalize::finalize` before the object is swept, if allocated finalized
+    pub finalize: Option<unsafe fn(*mut GcHeader)>,
+
     /// Layout of the complete GcBox<T>
     pub layout: Layout,
+
+    /// Offset of the `data` field inside the GcBox<T>
+    pub data_offset: usize,
+
+    /// Number of elements of a `GcBox<[T]>` (0 for sized types)
+    pub slice_len: usize,
+
+    /// Type of the data, see `erased_type_id`
+    pub type_id: fn() -> TypeId,
+
+    /// Name of the type of the data, for `GcOptions::verify_marking`
+    #[cfg_attr(not(all(debug_assertions, feature = "std")), allow(dead_code))]
+    pub type_name: fn() -> &'static str,
+}
+
+/// `TypeId` of `T`, with the lifetimes of `T` erased
+///
+/// `TypeId::of` requires `T: 'static`, but objects may borrow, so types that
+/// only differ in lifetimes share an id. Only reported once `GcHeader::typed`
+/// is set, by `record_type` for a `T: 'static`.
+fn erased_type_id<T: ?Sized>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T: ?Sized> NonStaticAny for core::marker::PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let phantom = core::marker::PhantomData::<T>;
+    let erased: &dyn NonStaticAny = &phantom;
+    // SAFETY: only the lifetime bound changes, `type_id` reads no data
+    let erased: &(dyn NonStaticAny + 'static) = unsafe { core::mem::transmute(erased) };
+    erased.type_id()
 }
 
 impl GcVTable {
     /// Create a new vtable for type T
     const fn new<T: Trace>() -> Self {
         // Compile-time assertion: header must be at offset 0 due to repr(C)
-        const _: () = assert!(std::mem::offset_of!(GcBox<()>, header) == 0);
+        const _: () = assert!(core::mem::offset_of!(GcBox<()>, header) == 0);
 
         unsafe fn trace_noop(_ptr: *const GcHeader, _tracer: &Tracer) {
             // No-op trace for types that have NO_TRACE=true
@@ -38,7 +90,7 @@ impl GcVTable {
             unsafe {
                 // Calculate GcBox pointer from header pointer using offset
                 // SAFETY: GcBox is repr(C) so header is at offset 0
-                let gc_box_ptr = (ptr as *const u8).sub(std::mem::offset_of!(GcBox<T>, header))
+                let gc_box_ptr = (ptr as *const u8).sub(core::mem::offset_of!(GcBox<T>, header))
                     as *const GcBox<T>;
 
                 let data = &(*gc_box_ptr).data;
@@ -51,13 +103,21 @@ impl GcVTable {
                 // Calculate GcBox pointer from header pointer using offset
                 // SAFETY: GcBox is repr(C) so header is at offset 0
                 let gc_box_ptr =
-                    (ptr as *mut u8).sub(std::mem::offset_of!(GcBox<T>, header)) as *mut GcBox<T>;
+                    (ptr as *mut u8).sub(core::mem::offset_of!(GcBox<T>, header)) as *mut GcBox<T>;
 
                 let _box = Box::from_raw(gc_box_ptr);
                 // Box drops T here
             }
         }
 
+        unsafe fn drop_data_impl<T>(ptr: *mut GcHeader) {
+            unsafe {
+                let gc_box_ptr =
+                    (ptr as *mut u8).sub(core::mem::offset_of!(GcBox<T>, header)) as *mut GcBox<T>;
+                core::ptr::drop_in_place(&raw mut (*gc_box_ptr).data);
+            }
+        }
+
         Self {
             trace: if T::NO_TRACE {
                 trace_noop
@@ -65,7 +125,91 @@ impl GcVTable {
                 trace_impl::<T>
             },
             drop: drop_impl::<T>,
+            drop_data: drop_data_impl::<T>,
+            finalize: None,
             layout: Layout::new::<GcBox<T>>(),
+            data_offset: core::mem::offset_of!(GcBox<T>, data),
+            slice_len: 0,
+            type_id: erased_type_id::<T>,
+            type_name: core::any::type_name::<T>,
+        }
+    }
+}
+
+impl GcVTable {
+    /// Layout of a `GcBox<[T]>` with `len` elements, followed by its vtable
+    ///
+    /// Returns the layout and the offsets of the data and of the vtable.
+    fn slice_layout<T>(len: usize) -> (Layout, usize, usize) {
+        let array = Layout::array::<T>(len).expect("slice too large");
+        let (layout, data_offset) = Layout::new::<GcHeader>()
+            .extend(array)
+            .expect("slice too large");
+        let (layout, vtable_offset) = layout
+            .extend(Layout::new::<GcVTable>())
+            .expect("slice too large");
+        (layout.pad_to_align(), data_offset, vtable_offset)
+    }
+
+    /// Create the vtable of a slice allocation
+    ///
+    /// It is stored in the allocation itself, since the layout depends on
+    /// the length. `T: Copy`, so the elements need no drop.
+    fn for_slice<T: Trace + Copy>(len: usize, layout: Layout, data_offset: usize) -> Self {
+        unsafe fn trace_slice<T: Trace>(ptr: *const GcHeader, tracer: &Tracer) {
+            unsafe {
+                let header = &*ptr;
+                let data = header.data_ptr() as *const T;
+                core::slice::from_raw_parts(data, header.vtable.slice_len).trace(tracer);
+            }
+        }
+
+        unsafe fn drop_slice(ptr: *mut GcHeader) {
+            unsafe {
+                // Copy the layout out of the vtable before it is deallocated
+                let layout = (*ptr).vtable.layout;
+                alloc::alloc::dealloc(ptr as *mut u8, layout);
+            }
+        }
+
+        unsafe fn drop_slice_data(_ptr: *mut GcHeader) {
+            // Copy types have no drop glue
+        }
+
+        unsafe fn trace_noop(_ptr: *const GcHeader, _tracer: &Tracer) {}
+
+        Self {
+            trace: if T::NO_TRACE {
+                trace_noop
+            } else {
+                trace_slice::<T>
+            },
+            drop: drop_slice,
+            drop_data: drop_slice_data,
+            finalize: None,
+            layout,
+            data_offset,
+            slice_len: len,
+            type_id: erased_type_id::<[T]>,
+            type_name: core::any::type_name::<[T]>,
+        }
+    }
+}
+
+impl GcVTable {
+    /// Create a vtable for type T that runs its finalizer
+    const fn new_finalized<T: Trace + Finalize>() -> Self {
+        unsafe fn finalize_impl<T: Finalize>(ptr: *mut GcHeader) {
+            unsafe {
+                let gc_box_ptr =
+                    (ptr as *mut u8).sub(core::mem::offset_of!(GcBox<T>, header)) as *mut GcBox<T>;
+                (*gc_box_ptr).data.finalize();
+            }
+        }
+
+        Self {
+            finalize: Some(finalize_impl::<T>),
+            ..Self::new::<T>()
         }
     }
 }
@@ -74,45 +218,297 @@ impl GcVTable {
 ///
 /// This header is shared by all `GcBox<T>` instances and allows
 /// uniform handling of objects in the allocation list.
+///
+/// Outside of the crate this type is opaque; it only appears behind raw
+/// pointers (see [`GcHandle`](crate::GcHandle)).
 pub struct GcHeader {
-    /// Current color in the tri-color marking algorithm
-    pub color: AtomicColor,
-    /// Reference count for root pointers (0 = not a root)
-    pub root_count: AtomicUsize,
+    /// Current color in the tri-color marking algorithm, and the number of
+    /// root pointers (0 = not a root)
+    pub(crate) state: AtomicState,
+    /// Number of collections survived, `OLD_AGE` once promoted (generational mode)
+    pub(crate) age: AtomicU8,
+    /// In the heap's remembered set (generational mode)
+    pub(crate) remembered: AtomicBool,
+    /// `Heap::sweep_epoch` when allocated or last passed by an incremental sweep
+    ///
+    /// Tells the objects of an incremental sweep's snapshot from those
+    /// allocated meanwhile, see `Heap::is_condemned`.
+    pub(crate) sweep_epoch: AtomicU8,
     /// Next pointer in the intrusive linked list
-    pub next: AtomicPtr<GcHeader>,
+    pub(crate) next: AtomicPtr<GcHeader>,
     /// Static vtable reference for type-erased operations
-    pub vtable: &'static GcVTable,
+    pub(crate) vtable: &'static GcVTable,
+    /// Number of `GcWeak`s, plus one while the object is alive or its
+    /// tombstone is kept by the heap (see `Heap::reap_tombstones`)
+    ///
+    /// Whoever releases the last count frees the allocation.
+    pub(crate) weak_count: AtomicUsize,
+    /// The heap this object belongs to
+    ///
+    /// The heap it was allocated on, until moved by `Heap::merge_from`.
+    pub(crate) heap: AtomicPtr<Heap>,
+    /// Position in the heap's root set, `NO_ROOT_INDEX` if not in it
+    ///
+    /// Only modified while the root set is locked.
+    pub(crate) root_index: AtomicUsize,
+    /// Allocated from the heap's arena rather than the global allocator
+    pub(crate) from_arena: bool,
+    /// `vtable.type_id` is the real type of the data, see `record_type`
+    pub(crate) typed: AtomicBool,
+}
+
+pub(crate) const NO_ROOT_INDEX: usize = usize::MAX;
+
+/// Memory for a new object, instead of a new allocation
+#[derive(Clone, Copy)]
+pub(crate) struct Slot {
+    pub ptr: NonNull<u8>,
+    /// From the heap's arena, see `GcHeader::from_arena`
+    pub from_arena: bool,
 }
 
+/// `age` of an object in the old generation
+pub(crate) const OLD_AGE: u8 = u8::MAX;
+
+// Headers are shared between threads, all mutable state is atomic
+unsafe impl Send for GcHeader {}
+unsafe impl Sync for GcHeader {}
+
 impl GcHeader {
-    // TODO: Combine `color` and `root_count` by using bit-patterns or avoid having a seperate `root_count` at all.
     #[inline]
-    fn new(vtable: &'static GcVTable) -> Self {
+    fn new(vtable: &'static GcVTable, heap: *const Heap, from_arena: bool) -> Self {
         Self {
-            color: AtomicColor::new(Color::White),
-            root_count: AtomicUsize::new(1), // Start at 1 - already rooted! (allocation safety)
+            // Start with one root - already rooted! (allocation safety)
+            state: AtomicState::new(Color::White, 1),
+            age: AtomicU8::new(0),
+            remembered: AtomicBool::new(false),
+            // Set by `Heap::link_allocation`
+            sweep_epoch: AtomicU8::new(0),
             next: AtomicPtr::new(null_mut()),
             vtable,
+            weak_count: AtomicUsize::new(1),
+            heap: AtomicPtr::new(heap as *mut Heap),
+            // Added to the root set by `Heap::allocate`
+            root_index: AtomicUsize::new(NO_ROOT_INDEX),
+            from_arena,
+            typed: AtomicBool::new(false),
+        }
+    }
+
+    /// Record that the data is a `T`, known to outlive `'static`
+    ///
+    /// From then on `type_id` reports the vtable's type id, which erases
+    /// lifetimes and so can't be trusted for objects that may borrow.
+    #[inline]
+    pub(crate) fn record_type<T: ?Sized + 'static>(&self) {
+        if (self.vtable.type_id)() == TypeId::of::<T>() {
+            self.typed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Type of the data, if recorded by `record_type`
+    #[inline]
+    pub(crate) fn type_id(&self) -> Option<TypeId> {
+        if self.typed.load(Ordering::Relaxed) {
+            Some((self.vtable.type_id)())
+        } else {
+            None
+        }
+    }
+
+    /// The heap this object belongs to
+    #[inline]
+    pub(crate) fn heap(&self) -> &Heap {
+        // SAFETY: objects (and handles to them) never outlive their heap,
+        // except the tombstones `detach`ed from it
+        unsafe { &*self.heap.load(Ordering::Relaxed) }
+    }
+
+    /// The heap this object belongs to, None once the heap was dropped
+    ///
+    /// Only tombstones, reached through a `GcWeak`, outlive their heap.
+    #[inline]
+    pub(crate) fn attached_heap(&self) -> Option<&Heap> {
+        unsafe { self.heap.load(Ordering::Acquire).as_ref() }
+    }
+
+    pub(crate) fn inc_root(&self) {
+        if self.state.inc_root() == 0 {
+            self.heap().sync_root(self);
+        }
+    }
+
+    pub(crate) fn dec_root(&self) {
+        if self.state.dec_root() == 1 {
+            self.heap().sync_root(self);
         }
     }
 
-    pub fn inc_root(&self) {
-        self.root_count.fetch_add(1, Ordering::Relaxed);
+    /// Promoted to the old generation, skipped by minor collections
+    #[inline]
+    pub(crate) fn is_old(&self) -> bool {
+        self.age.load(Ordering::Relaxed) == OLD_AGE
     }
 
-    pub fn dec_root(&self) {
-        self.root_count.fetch_sub(1, Ordering::Relaxed);
+    /// Count a survived collection, promoting the object after `promote_after`
+    pub(crate) fn survive(&self, promote_after: u8) {
+        let age = self.age.load(Ordering::Relaxed);
+        if age != OLD_AGE {
+            let age = age + 1;
+            let age = if age >= promote_after { OLD_AGE } else { age };
+            self.age.store(age, Ordering::Relaxed);
+        }
     }
 
-    pub fn is_root(&self) -> bool {
-        self.root_count.load(Ordering::Relaxed) > 0
+    /// The object is old once it survived the next minor collection
+    #[inline]
+    pub(crate) fn promoted_by_survive(&self, promote_after: u8) -> bool {
+        let age = self.age.load(Ordering::Relaxed);
+        age == OLD_AGE || age + 1 >= promote_after
+    }
+
+    pub(crate) fn is_root(&self) -> bool {
+        self.state.root_count() > 0
+    }
+
+    /// Number of `GcRoot`s, 0 once swept
+    #[inline]
+    pub(crate) fn root_count(&self) -> usize {
+        match self.state.root_count() {
+            crate::color::DEAD_ROOTS => 0,
+            count => count,
+        }
+    }
+
+    /// Swept while `GcWeak`s pointed to it, only the header is left
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn is_dead(&self) -> bool {
+        self.state.root_count() == crate::color::DEAD_ROOTS
+    }
+
+    /// Add a root unless the object was swept
+    pub(crate) fn try_inc_root(&self) -> bool {
+        match self.state.try_inc_root() {
+            Some(0) => {
+                self.heap().sync_root(self);
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn inc_weak(&self) {
+        self.weak_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of `GcWeak`s pointing to this (live) object
+    #[inline]
+    pub(crate) fn weak_count(&self) -> usize {
+        self.weak_count.load(Ordering::Relaxed) - 1
+    }
+
+    /// Release one weak count, freeing the allocation if it was the last
+    ///
+    /// The heap holds a count of the objects attached to it, so only the
+    /// last `GcWeak` of a detached tombstone frees it here.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own a weak count of `ptr`.
+    pub(crate) unsafe fn release_weak(ptr: *mut GcHeader) {
+        unsafe {
+            if (*ptr).weak_count.fetch_sub(1, Ordering::Release) == 1 {
+                core::sync::atomic::fence(Ordering::Acquire);
+                Self::deallocate(ptr);
+            }
+        }
+    }
+
+    /// Return the memory of an object whose data was dropped
+    ///
+    /// Detached arena objects are left to the arena block, which their
+    /// heap didn't free (see `Heap::drop`).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be unreachable, and its data dropped or never initialized.
+    pub(crate) unsafe fn deallocate(ptr: *mut GcHeader) {
+        unsafe {
+            let layout = (*ptr).vtable.layout;
+            if (*ptr).from_arena {
+                if let Some(heap) = (*ptr).attached_heap() {
+                    let arena = heap.arena().expect("arena object without an arena");
+                    arena.release(NonNull::new_unchecked(ptr as *mut u8), layout);
+                }
+            } else {
+                alloc::alloc::dealloc(ptr as *mut u8, layout);
+            }
+        }
+    }
+
+    /// Free a swept object
+    ///
+    /// Drops the data. The allocation is kept as a tombstone while `GcWeak`s
+    /// point to it, until the heap reaps it (`Heap::reap_tombstones`) or is
+    /// dropped (`detach`). Otherwise, with `recycle`, the allocation is returned
+    /// instead of deallocated, for reuse by an object of the same layout.
+    /// Arena allocations are always returned, for the arena's free list.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be unreachable and already unlinked from the heap.
+    pub(crate) unsafe fn free(ptr: *mut GcHeader, recycle: bool) -> Option<Slot> {
+        unsafe {
+            if (*ptr).weak_count.load(Ordering::Acquire) == 1 {
+                // No weak refs: no one can observe the header anymore
+                let from_arena = (*ptr).from_arena;
+                if recycle || from_arena {
+                    // Not `vtable.drop`, which frees like a `Box`
+                    ((*ptr).vtable.drop_data)(ptr);
+                    return Some(Slot {
+                        ptr: NonNull::new_unchecked(ptr as *mut u8),
+                        from_arena,
+                    });
+                }
+                ((*ptr).vtable.drop)(ptr);
+            } else {
+                (*ptr).state.mark_dead();
+                ((*ptr).vtable.drop_data)(ptr);
+                (*ptr).heap().add_tombstone(ptr);
+            }
+            None
+        }
+    }
+
+    /// Hand a dropped object of a dropped heap over to its `GcWeak`s
+    ///
+    /// Frees it right away if there are none, otherwise the last one frees
+    /// it, without accessing the heap.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be dead and its data dropped, and its heap about to be
+    /// freed.
+    pub(crate) unsafe fn detach(ptr: *mut GcHeader) {
+        unsafe {
+            (*ptr).heap.store(null_mut(), Ordering::Release);
+            Self::release_weak(ptr);
+        }
     }
 
     /// Check if the object is collectable after all reachable objects have been transitioned from white & gray to black:
     /// (White and not a root)
-    pub fn is_white(&self) -> bool {
-        self.color.is_white() && !self.is_root()
+    pub(crate) fn is_white(&self) -> bool {
+        self.state.is_white_unrooted()
+    }
+
+    /// Pointer to the object data following this header
+    #[inline]
+    pub(crate) fn data_ptr(&self) -> *const u8 {
+        unsafe { (self as *const Self as *const u8).add(self.vtable.data_offset) }
     }
 }
 
@@ -120,25 +516,118 @@ impl GcHeader {
 ///
 /// `GcBox` wraps a value with GC metadata including color and root status.
 ///
+/// Outside of the crate this type is opaque; it only appears behind raw
+/// pointers (see [`GcRoot::into_raw`](crate::GcRoot::into_raw)).
+///
 /// SAFETY: repr(C) ensures that `header` is always at offset 0, making it
 /// safe to cast between `*GcHeader` and `*GcBox<T>`.
 #[repr(C)]
 pub struct GcBox<T: ?Sized> {
-    pub header: GcHeader,
-    pub data: T,
+    pub(crate) header: GcHeader,
+    pub(crate) data: T,
 }
 
 impl<T: Trace> GcBox<T> {
-    const VTABLE: GcVTable = GcVTable::new::<T>();
+    pub(crate) const VTABLE: GcVTable = GcVTable::new::<T>();
 
     /// Allocate a new GcBox using Box (idiomatic Rust!)
-    pub(crate) fn new(data: T) -> NonNull<GcBox<T>> {
-        let gc_box = Box::new(GcBox {
-            header: GcHeader::new(&Self::VTABLE),
+    ///
+    /// `vtable` is `VTABLE` or, for finalized objects, `FINALIZED_VTABLE`.
+    /// If given, `slot` is used instead of a new allocation; it must be a
+    /// recycled (or arena) allocation of `Layout::new::<GcBox<T>>()`.
+    pub(crate) fn new(
+        data: T,
+        vtable: &'static GcVTable,
+        heap: *const Heap,
+        slot: Option<Slot>,
+    ) -> NonNull<GcBox<T>> {
+        let gc_box = GcBox {
+            header: GcHeader::new(vtable, heap, slot.is_some_and(|s| s.from_arena)),
             data,
-        });
+        };
+        if let Some(slot) = slot {
+            let ptr = slot.ptr.cast::<GcBox<T>>();
+            // SAFETY: the slot has the size and alignment of `GcBox<T>`, and
+            // it is deallocated with the same layout, like a `Box`
+            unsafe { ptr.as_ptr().write(gc_box) };
+            return ptr;
+        }
 
         // Leak the box to get a raw pointer
-        NonNull::from(Box::leak(gc_box))
+        NonNull::from(Box::leak(Box::new(gc_box)))
     }
 }
+
+impl<T: Trace> GcBox<T> {
+    /// Allocate a GcBox with an initialized header, but uninitialized data
+    ///
+    /// The header is black, so a tracer reaching the object through an early
+    /// `GcPtr` doesn't scan the data. `slot` is like in [`GcBox::new`].
+    pub(crate) fn reserve(
+        vtable: &'static GcVTable,
+        heap: *const Heap,
+        slot: Option<Slot>,
+    ) -> NonNull<GcBox<T>> {
+        let layout = Layout::new::<GcBox<T>>();
+        let from_arena = slot.is_some_and(|s| s.from_arena);
+        let ptr = match slot {
+            Some(slot) => slot.ptr.cast::<GcBox<T>>(),
+            None => {
+                let ptr = unsafe { alloc::alloc::alloc(layout) } as *mut GcBox<T>;
+                NonNull::new(ptr).unwrap_or_else(|| alloc::alloc::handle_alloc_error(layout))
+            }
+        };
+        unsafe {
+            let header = &raw mut (*ptr.as_ptr()).header;
+            header.write(GcHeader::new(vtable, heap, from_arena));
+            (*header).state.mark_black();
+        }
+        ptr
+    }
+}
+
+impl<T: Trace + Copy> GcBox<[T]> {
+    /// Layout of the allocation for a slice of `len` elements
+    pub(crate) fn slice_layout(len: usize) -> Layout {
+        GcVTable::slice_layout::<T>(len).0
+    }
+
+    /// Allocate a GcBox holding a copy of `data`, with its vtable inline
+    ///
+    /// If given, `slot` is used instead of a new allocation; it must be a
+    /// recycled (or arena) allocation of `slice_layout(data.len())`.
+    pub(crate) fn new_slice(
+        data: &[T],
+        heap: *const Heap,
+        slot: Option<Slot>,
+    ) -> NonNull<GcBox<[T]>> {
+        let (layout, data_offset, vtable_offset) = GcVTable::slice_layout::<T>(data.len());
+        let from_arena = slot.is_some_and(|s| s.from_arena);
+        let base = match slot {
+            Some(slot) => slot.ptr.as_ptr(),
+            None => {
+                let ptr = unsafe { alloc::alloc::alloc(layout) };
+                if ptr.is_null() {
+                    alloc::alloc::handle_alloc_error(layout);
+                }
+                ptr
+            }
+        };
+        unsafe {
+            let vtable_ptr = base.add(vtable_offset) as *mut GcVTable;
+            vtable_ptr.write(GcVTable::for_slice::<T>(data.len(), layout, data_offset));
+            // SAFETY: the vtable lives as long as the allocation, and is only
+            // read through the header of this allocation
+            let vtable: &'static GcVTable = &*vtable_ptr;
+            (base as *mut GcHeader).write(GcHeader::new(vtable, heap, from_arena));
+            let elements = base.add(data_offset) as *mut T;
+            core::ptr::copy_nonoverlapping(data.as_ptr(), elements, data.len());
+            let slice = core::ptr::slice_from_raw_parts_mut(base, data.len());
+            NonNull::new_unchecked(slice as *mut GcBox<[T]>)
+        }
+    }
+}
+
+impl<T: Trace + Finalize> GcBox<T> {
+    pub(crate) const FINALIZED_VTABLE: GcVTable = GcVTable::new_finalized::<T>();
+}
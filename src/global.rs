@@ -0,0 +1,51 @@
+//! Process-global heap
+//!
+//! For applications that use a single GC heap for the whole process, this
+//! module provides a lazily initialized global `Heap`, so the `Arc<Heap>`
+//! doesn't have to be threaded through the application. Each thread attaches
+//! to it with [`GcContext::global`](crate::GcContext::global).
+//!
+//! # Example
+//!
+//! ```
+//! use abfall::{GcContext, GcOptions, global};
+//!
+//! // Optional: configure the global heap before first use
+//! global::init_global(GcOptions::DEFAULT);
+//!
+//! let ctx = GcContext::global();
+//! let value = ctx.allocate(42);
+//! assert!(std::sync::Arc::ptr_eq(ctx.heap(), global::global_heap()));
+//! ```
+
+use crate::heap::{GcOptions, Heap};
+use std::sync::{Arc, OnceLock};
+
+static GLOBAL_HEAP: OnceLock<Arc<Heap>> = OnceLock::new();
+
+/// Initialize the global heap with the given options
+///
+/// Returns `false` (and leaves the existing heap untouched) if the global
+/// heap was already initialized, either by an earlier call to this function
+/// or implicitly by [`global_heap`].
+pub fn init_global(options: GcOptions) -> bool {
+    let mut initialized = false;
+    GLOBAL_HEAP.get_or_init(|| {
+        initialized = true;
+        Heap::with_options(options)
+    });
+    initialized
+}
+
+/// Get the global heap
+///
+/// Initializes the global heap with [`GcOptions::DEFAULT`] if
+/// [`init_global`] wasn't called before.
+pub fn global_heap() -> &'static Arc<Heap> {
+    GLOBAL_HEAP.get_or_init(Heap::new)
+}
+
+/// Check whether the global heap was initialized
+pub fn is_global_initialized() -> bool {
+    GLOBAL_HEAP.get().is_some()
+}
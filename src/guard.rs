@@ -0,0 +1,188 @@
+//! Read guards for root-free traversal of the object graph
+//!
+//! Rooting every visited node (`GcPtr::root`) costs two atomic operations per
+//! node. A `ReadGuard` pins the collector once for a whole traversal instead:
+//! while it is alive the sweeper cannot free any object.
+
+use crate::heap::{Heap, thread_token};
+use crate::ptr::GcPtr;
+
+/// Pins a heap against sweeping for the lifetime of the guard
+///
+/// Created by [`Heap::read_guard`]. Multiple guards (also on different threads)
+/// can be held at the same time. A collection that finishes marking while a
+/// guard is alive waits for all guards to be released before it sweeps.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcContext, GcPtr, Trace, Tracer};
+///
+/// struct Node {
+///     value: usize,
+///     next: Option<GcPtr<Node>>,
+/// }
+///
+/// unsafe impl Trace for Node {
+///     fn trace(&self, tracer: &Tracer) {
+///         self.next.trace(tracer);
+///     }
+/// }
+///
+/// let ctx = GcContext::new();
+/// let tail = ctx.allocate(Node { value: 1, next: None });
+/// let head = ctx.allocate(Node { value: 0, next: Some(tail.as_ptr()) });
+///
+/// let guard = ctx.read_guard();
+/// let next = guard.get(head.next.unwrap());
+/// assert_eq!(next.value, 1);
+/// # }
+/// ```
+pub struct ReadGuard<'h> {
+    heap: &'h Heap,
+    _guard: crate::sync::RwLockReadGuard<'h, ()>,
+}
+
+impl<'h> ReadGuard<'h> {
+    pub(crate) fn new(heap: &'h Heap) -> Self {
+        // The sweeper holds the lock exclusively
+        if heap.in_sweep() {
+            panic!(
+                "abfall: a `ReadGuard` can't be created from a `Drop` or finalizer run by the sweep"
+            );
+        }
+        let guard = heap.sweep_lock.read();
+        heap.guard_holders.lock().push(thread_token());
+        Self {
+            heap,
+            _guard: guard,
+        }
+    }
+
+    /// Dereference a `GcPtr` without rooting it
+    ///
+    /// The returned reference is valid for as long as the guard is borrowed.
+    ///
+    /// The pointer must be reachable from a root (directly or through other
+    /// objects), or must have been reachable at some point while this guard
+    /// was held. Objects swept before the guard was acquired are gone.
+    #[inline]
+    pub fn get<T: ?Sized>(&self, ptr: GcPtr<T>) -> &T {
+        // SAFETY: sweeping is blocked while `self` exists, so the object
+        // stays allocated for as long as `self` is borrowed.
+        unsafe { &*ptr.as_ptr() }
+    }
+}
+
+impl Drop for ReadGuard<'_> {
+    fn drop(&mut self) {
+        let token = thread_token();
+        let mut holders = self.heap.guard_holders.lock();
+        if let Some(index) = holders.iter().position(|&holder| holder == token) {
+            holders.swap_remove(index);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{GcContext, Trace, Tracer};
+
+    struct Node {
+        value: usize,
+        next: Option<GcPtr<Node>>,
+    }
+
+    unsafe impl Trace for Node {
+        fn trace(&self, tracer: &Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+
+    #[test]
+    fn traverse_chain_without_rooting() {
+        let ctx = GcContext::off();
+        let mut head = ctx.allocate(Node {
+            value: 0,
+            next: None,
+        });
+        for i in 1..10_000 {
+            head = ctx.allocate(Node {
+                value: i,
+                next: Some(head.as_ptr()),
+            });
+        }
+
+        let guard = ctx.read_guard();
+        let mut count = 1;
+        let mut cur: &Node = &head;
+        while let Some(next) = cur.next {
+            assert!(!unsafe { &*next.header_ptr() }.is_root());
+            cur = guard.get(next);
+            count += 1;
+        }
+        assert_eq!(count, 10_000);
+        assert_eq!(cur.value, 0);
+    }
+
+    #[test]
+    fn allocating_under_a_guard_never_collects() {
+        use crate::{GcOptions, LimitAction};
+
+        let ctx = GcContext::with_options(GcOptions {
+            limit_bytes: 4096,
+            soft_limit_bytes: 1024,
+            on_limit_exceeded: LimitAction::Block,
+            ..GcOptions::OFF
+        });
+        let guard = ctx.read_guard();
+        // Over both limits: collecting here would wait for `guard`
+        let garbage: Vec<_> = (0..100).map(|i| ctx.allocate([i; 16]).as_ptr()).collect();
+        assert_eq!(guard.get(garbage[99])[0], 99);
+        assert!(ctx.bytes_allocated() > 4096);
+
+        let collect = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            ctx.force_collect();
+        }));
+        assert!(collect.is_err());
+
+        drop(guard);
+        ctx.force_collect();
+        assert_eq!(ctx.allocation_count(), 0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "single-threaded"))]
+    fn sweep_waits_for_guard() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+        use std::time::Duration;
+
+        let ctx = GcContext::off();
+        let heap = Arc::clone(ctx.heap());
+        let _garbage = ctx.allocate(1).as_ptr();
+
+        let guard = heap.read_guard();
+        let done = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let heap = Arc::clone(&heap);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                heap.force_collect();
+                done.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!done.load(Ordering::SeqCst), "sweep must wait for guard");
+        assert_eq!(heap.allocation_count(), 1);
+
+        drop(guard);
+        handle.join().unwrap();
+        assert!(done.load(Ordering::SeqCst));
+        assert_eq!(heap.allocation_count(), 0);
+    }
+}
@@ -3,14 +3,31 @@
 //! This module provides the heap structure that stores GC-managed objects
 //! and implements the mark and sweep phases of garbage collection.
 
-use crate::gc_box::{GcBox, GcHeader};
-use crate::ptr::GcRoot;
-use crate::trace::{Trace, Tracer};
-use std::ptr::null_mut;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, Ordering};
-use std::thread::JoinHandle;
-use std::time::Duration;
+use crate::arena::Arena;
+use crate::cell::remember_children;
+use crate::color::Color;
+use crate::error::Error;
+use crate::finalize::Finalize;
+use crate::gc_box::{GcBox, GcHeader, GcVTable, NO_ROOT_INDEX, OLD_AGE, Slot};
+use crate::guard::ReadGuard;
+use crate::hooks::{CollectionHooks, GcEvent};
+use crate::phase::PhaseState;
+use crate::ptr::{GcErased, GcPtr, GcRoot, PinnedGc};
+use crate::sync::atomic::AtomicPtr;
+use crate::trace::{ErasedRoot, Trace, Tracer};
+use alloc::alloc::Layout;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ptr::{NonNull, null_mut};
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::{collections::HashMap, panic::AssertUnwindSafe, thread::JoinHandle, time::Instant};
+
+/// Source of the mark cycle ids, unique across all heaps (0 is no cycle)
+static MARK_CYCLES: AtomicUsize = AtomicUsize::new(0);
 
 /// Send-safe wrapper for raw pointer queue
 struct GrayQueue(Vec<*const GcHeader>);
@@ -24,78 +41,343 @@ impl GrayQueue {
     }
 }
 
-struct StartStopJoinHandle {
-    mutex: parking_lot::Mutex<(usize, Option<JoinHandle<()>>)>,
-    condvar: parking_lot::Condvar,
+/// Send-safe wrapper for the objects with cells mutated during marking
+struct DirtySet(Vec<*const GcHeader>);
+
+unsafe impl Send for DirtySet {}
+unsafe impl Sync for DirtySet {}
+
+/// Send-safe wrapper for the swept objects that `GcWeak`s still point to
+struct Tombstones(Vec<*mut GcHeader>);
+
+unsafe impl Send for Tombstones {}
+unsafe impl Sync for Tombstones {}
+
+/// Send-safe wrapper for the objects whose ephemeron tables await a rescan
+struct EphemeronSet(Vec<*const GcHeader>);
+
+unsafe impl Send for EphemeronSet {}
+unsafe impl Sync for EphemeronSet {}
+
+/// Allocations of one `GcContext`, not yet linked into `Heap::head`
+///
+/// Only the owning thread pushes; anyone holding `Heap::thread_lists` may
+/// take the whole list. Pushing to a list of its own avoids contending on
+/// the global head for every allocation.
+pub(crate) struct ThreadList {
+    head: core::sync::atomic::AtomicPtr<GcHeader>,
+    /// Pushes since the owner last spliced the list (owner only)
+    #[cfg(feature = "std")]
+    len: core::cell::Cell<usize>,
 }
 
-impl StartStopJoinHandle {
-    fn new() -> Self {
+impl ThreadList {
+    #[cfg(feature = "std")]
+    pub(crate) const fn new() -> Self {
+        Self {
+            head: core::sync::atomic::AtomicPtr::new(null_mut()),
+            len: core::cell::Cell::new(0),
+        }
+    }
+
+    /// Push a new object, returning the length of the list
+    ///
+    /// Must only be called by the owner.
+    #[cfg(feature = "std")]
+    fn push(&self, header: *mut GcHeader) -> usize {
+        loop {
+            let current_head = self.head.load(Ordering::Acquire);
+            unsafe { (*header).next.store(current_head, Ordering::Relaxed) };
+            // Fails only if the list was taken meanwhile
+            if self
+                .head
+                .compare_exchange(current_head, header, Ordering::Release, Ordering::Acquire)
+                .is_ok()
+            {
+                self.len.set(self.len.get() + 1);
+                return self.len.get();
+            }
+        }
+    }
+
+    /// Take all objects, returning the first and the last one
+    fn take(&self) -> Option<(*mut GcHeader, *mut GcHeader)> {
+        let first = self.head.swap(null_mut(), Ordering::AcqRel);
+        if first.is_null() {
+            return None;
+        }
+        let mut last = first;
+        loop {
+            let next = unsafe { (*last).next.load(Ordering::Acquire) };
+            if next.is_null() {
+                return Some((first, last));
+            }
+            last = next;
+        }
+    }
+}
+
+/// Send-safe wrapper for the `ThreadList`s of the contexts of a heap
+struct ThreadLists(Vec<*const ThreadList>);
+
+unsafe impl Send for ThreadLists {}
+unsafe impl Sync for ThreadLists {}
+
+/// Objects shaded by the write barriers of one `GcContext`
+///
+/// Only the owning thread pushes; anyone holding `Heap::barrier_buffers` may
+/// take the whole buffer. Moving them to the shared gray queue in batches
+/// saves the barriers from contending on its lock for every store.
+pub(crate) struct BarrierBuffer(crate::sync::Mutex<GrayQueue>);
+
+impl BarrierBuffer {
+    #[cfg(feature = "std")]
+    pub(crate) fn new() -> Self {
+        Self(crate::sync::Mutex::new(GrayQueue::new()))
+    }
+}
+
+/// Send-safe wrapper for the `BarrierBuffer`s of the contexts of a heap
+struct BarrierBuffers(Vec<*const BarrierBuffer>);
+
+unsafe impl Send for BarrierBuffers {}
+unsafe impl Sync for BarrierBuffers {}
+
+/// Dense array of all rooted objects
+///
+/// Each member stores its position in `GcHeader::root_index`, so it can be
+/// removed in O(1) when its last root is dropped.
+struct RootSet(Vec<*const GcHeader>);
+
+unsafe impl Send for RootSet {}
+unsafe impl Sync for RootSet {}
+
+/// Objects of the `PinnedGc`s of a heap, scanned along with the root set
+struct PinSet(Vec<*const GcHeader>);
+
+unsafe impl Send for PinSet {}
+unsafe impl Sync for PinSet {}
+
+/// Size and alignment of a free list class (`Layout` is not `Ord`)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct LayoutKey(usize, usize);
+
+impl From<Layout> for LayoutKey {
+    fn from(layout: Layout) -> Self {
+        Self(layout.size(), layout.align())
+    }
+}
+
+impl From<LayoutKey> for Layout {
+    fn from(key: LayoutKey) -> Self {
+        // Only created from valid layouts
+        unsafe { Layout::from_size_align_unchecked(key.0, key.1) }
+    }
+}
+
+/// Allocations by layout (free lists)
+#[cfg(feature = "std")]
+pub(crate) type ClassMap = HashMap<LayoutKey, Vec<NonNull<u8>>>;
+#[cfg(not(feature = "std"))]
+pub(crate) type ClassMap = alloc::collections::BTreeMap<LayoutKey, Vec<NonNull<u8>>>;
+
+/// Swept allocations kept for reuse, by layout
+///
+/// Retains at most `cap_bytes`; the rest is deallocated right away.
+struct FreeList {
+    classes: ClassMap,
+    retained_bytes: usize,
+    cap_bytes: usize,
+}
+
+unsafe impl Send for FreeList {}
+unsafe impl Sync for FreeList {}
+
+impl FreeList {
+    fn new(cap_bytes: usize) -> Self {
         Self {
-            mutex: parking_lot::Mutex::new((0, None)),
-            condvar: parking_lot::Condvar::new(),
+            classes: ClassMap::new(),
+            retained_bytes: 0,
+            cap_bytes,
+        }
+    }
+
+    /// Take a recycled allocation of `layout`
+    fn pop(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let slot = self.classes.get_mut(&layout.into())?.pop()?;
+        self.retained_bytes -= layout.size();
+        Some(slot)
+    }
+
+    /// Keep an allocation of `layout` for reuse, or deallocate it if full
+    fn push(&mut self, slot: NonNull<u8>, layout: Layout) {
+        if self.retained_bytes + layout.size() > self.cap_bytes {
+            unsafe { alloc::alloc::dealloc(slot.as_ptr(), layout) };
+            return;
+        }
+        self.retained_bytes += layout.size();
+        self.classes.entry(layout.into()).or_default().push(slot);
+    }
+
+    /// Deallocate all retained allocations
+    fn clear(&mut self) {
+        for (layout, slots) in core::mem::take(&mut self.classes) {
+            for slot in slots {
+                unsafe { alloc::alloc::dealloc(slot.as_ptr(), layout.into()) };
+            }
         }
+        self.retained_bytes = 0;
+    }
+}
+
+impl Drop for FreeList {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Young objects pointed to by old objects (generational mode)
+///
+/// Added by the cells of old objects when they store young ones, and when a
+/// minor collection promotes an object pointing to young ones. Scanned as
+/// roots by minor collections. Entries are dropped once promoted, and all of
+/// them after a full collection.
+struct RememberedSet(Vec<*const GcHeader>);
+
+unsafe impl Send for RememberedSet {}
+unsafe impl Sync for RememberedSet {}
+
+/// State of the background thread, guarded by `BackgroundShared::mutex`
+#[cfg(feature = "std")]
+struct BackgroundState {
+    /// Incremented on every start, identifies the current thread
+    counter: usize,
+    handle: Option<JoinHandle<()>>,
+    /// Set by `kick` to wake the thread up before its interval elapsed
+    kicked: bool,
+}
+
+/// Shared with the background thread, which only holds a `Weak` to the heap
+#[cfg(feature = "std")]
+struct BackgroundShared {
+    mutex: crate::sync::Mutex<BackgroundState>,
+    condvar: crate::sync::Condvar,
+}
+
+#[cfg(feature = "std")]
+struct StartStopJoinHandle(Arc<BackgroundShared>);
+
+#[cfg(feature = "std")]
+impl StartStopJoinHandle {
+    fn new() -> Self {
+        Self(Arc::new(BackgroundShared {
+            mutex: crate::sync::Mutex::new(BackgroundState {
+                counter: 0,
+                handle: None,
+                kicked: false,
+            }),
+            condvar: crate::sync::Condvar::new(),
+        }))
     }
 
     fn start(&self, f: impl FnOnce(StopCondition) + Send + 'static) -> bool {
-        let mut guard = self.mutex.lock();
-        if guard.1.is_some() {
+        let mut guard = self.0.mutex.lock();
+        if guard.handle.is_some() {
             return false; // already started
         }
-        let counter = guard.0 + 1;
-        guard.0 = counter;
-        let c = StopCondition(counter);
-        guard.1 = Some(std::thread::spawn(move || f(c)));
+        let counter = guard.counter + 1;
+        guard.counter = counter;
+        guard.kicked = false;
+        let c = StopCondition {
+            counter,
+            shared: Arc::clone(&self.0),
+        };
+        guard.handle = Some(std::thread::spawn(move || f(c)));
         true
     }
 
     fn stop(&self) -> bool {
+        // A panic of the background thread was already reported by the panic hook
+        self.stop_and_join().is_some()
+    }
+
+    /// Stop the thread and return its join result, or None if it was not running
+    ///
+    /// Called on the background thread itself (the heap is dropped there, or
+    /// a finalizer stops it), the thread is detached instead; it exits once
+    /// it returns to its loop.
+    fn stop_and_join(&self) -> Option<std::thread::Result<()>> {
         let handle = {
-            let mut stopped = self.mutex.lock();
-            if let Some(handle) = stopped.1.take() {
-                self.condvar.notify_all();
-                handle
-            } else {
-                return false; // already stopped
-            }
+            let mut stopped = self.0.mutex.lock();
+            let handle = stopped.handle.take()?;
+            self.0.condvar.notify_all();
+            handle
         };
-        handle.join().unwrap();
-        true
+        if handle.thread().id() == std::thread::current().id() {
+            return Some(Ok(()));
+        }
+        Some(handle.join())
     }
 
-    fn wait_stopped(&self, c: StopCondition, timeout: Duration) -> bool {
-        let mut stopped = self.mutex.lock();
-        if stopped.1.is_none() || stopped.0 != c.0 {
-            return true; // already stopped
+    /// Wake up the background thread before its interval elapsed
+    fn kick(&self) -> bool {
+        let mut state = self.0.mutex.lock();
+        if state.handle.is_none() {
+            return false;
         }
-        let result = self.condvar.wait_for(&mut stopped, timeout);
-        !result.timed_out()
+        state.kicked = true;
+        self.0.condvar.notify_all();
+        true
     }
 
-    fn is_stopped(&self, c: StopCondition) -> bool {
-        let stopped = self.mutex.lock();
-        stopped.1.is_none() || stopped.0 != c.0
+    fn is_started(&self) -> bool {
+        let stopped = self.0.mutex.lock();
+        stopped.handle.is_some()
     }
 
-    fn is_started(&self) -> bool {
-        let stopped = self.mutex.lock();
-        stopped.1.is_some()
+    /// Check that the thread was started and has not terminated
+    fn is_alive(&self) -> bool {
+        let state = self.0.mutex.lock();
+        state.handle.as_ref().is_some_and(|h| !h.is_finished())
     }
 }
 
-impl Drop for StartStopJoinHandle {
-    fn drop(&mut self) {
-        self.stop();
-    }
+/// Handed to the background thread, tells it when it was stopped
+#[cfg(feature = "std")]
+struct StopCondition {
+    counter: usize,
+    shared: Arc<BackgroundShared>,
 }
 
-#[derive(Copy, Clone)]
-struct StopCondition(usize);
+#[cfg(feature = "std")]
+impl StopCondition {
+    /// Wait until the timeout elapsed, the thread was kicked or stopped
+    ///
+    /// Returns true if the thread was stopped
+    fn wait_stopped(&self, timeout: Duration) -> bool {
+        let mut state = self.shared.mutex.lock();
+        if !state.kicked && !self.is_stopped_state(&state) {
+            self.shared.condvar.wait_for(&mut state, timeout);
+        }
+        state.kicked = false;
+        self.is_stopped_state(&state)
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.is_stopped_state(&self.shared.mutex.lock())
+    }
+
+    fn is_stopped_state(&self, state: &BackgroundState) -> bool {
+        state.handle.is_none() || state.counter != self.counter
+    }
+}
 
 /// GC phase states
+///
+/// A cycle moves `Idle → Marking → Sweeping → Idle`, see [`Heap::phase`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
-enum GcPhase {
+pub enum GcPhase {
     /// GC is idle, no collection in progress
     Idle = 0,
     /// GC is marking reachable objects
@@ -104,6 +386,7 @@ enum GcPhase {
     Sweeping = 2,
 }
 
+/// Decodes `phase as u8`; unknown values are `Idle`
 impl From<u8> for GcPhase {
     fn from(value: u8) -> Self {
         match value {
@@ -119,6 +402,19 @@ impl From<u8> for GcPhase {
 /// Manages allocation and deallocation of GC objects using an intrusive
 /// linked list, and implements the mark and sweep collection algorithm
 /// with incremental marking support.
+///
+/// # Reentrancy
+///
+/// The `Drop` impls (and finalizers) of garbage run on the sweeping thread,
+/// in the middle of the sweep. From there:
+/// - allocations succeed, and the new objects survive the current cycle;
+///   `limit_bytes` and `soft_limit_bytes` are only enforced again after it,
+/// - `collect`, `force_collect`, `collect_incremental` and `sweep` return
+///   without collecting, since a cycle is in progress,
+/// - calls that would wait for the sweep to finish panic instead of
+///   deadlocking: `request_collection(true)`, upgrading a `GcWeak` (or
+///   `try_root`), and creating a [`ReadGuard`] (which `for_each_object` and
+///   `preview_garbage` do).
 pub struct Heap {
     /// Head of the intrusive linked list of allocations
     head: AtomicPtr<GcHeader>,
@@ -128,26 +424,377 @@ pub struct Heap {
     bytes_allocated: AtomicUsize,
     /// Current collection threshold in bytes
     current_threshold: AtomicUsize,
+    /// Floor of `current_threshold`, see `set_min_threshold`
+    min_threshold: AtomicUsize,
     /// Gray queue for incremental marking
-    gray_queue: parking_lot::Mutex<GrayQueue>,
+    gray_queue: crate::sync::Mutex<GrayQueue>,
     /// Current GC phase
-    phase: AtomicU8,
+    phase: PhaseState,
     /// Background GC thread handle
+    #[cfg(feature = "std")]
     bg_thread: StartStopJoinHandle,
-    /// Number of Assist mutators or write-barriers active
-    n_busy_marking: std::sync::atomic::AtomicUsize,
+    /// Collection lifecycle hooks (metrics, ...)
+    hooks: CollectionHooks,
+    /// Held shared by `ReadGuard`s and exclusively by the sweeper
+    pub(crate) sweep_lock: crate::sync::RwLock<()>,
+    /// Threads holding a `ReadGuard`, once per guard, see `holds_read_guard`
+    pub(crate) guard_holders: crate::sync::Mutex<Vec<usize>>,
+    /// Objects with cells mutated during marking (incremental-update barrier)
+    dirty_objects: crate::sync::Mutex<DirtySet>,
+    /// Id of the current (or last) mark cycle, see `MARK_CYCLES`
+    mark_cycle: AtomicUsize,
+    /// Ephemeron tables with entries whose keys were not marked yet
+    ephemerons: crate::sync::Mutex<EphemeronSet>,
+    /// Swept objects with `GcWeak`s, see `reap_tombstones`
+    tombstones: crate::sync::Mutex<Tombstones>,
+    /// Allocation lists of the `GcContext`s, see `GcOptions::thread_alloc_batch`
+    thread_lists: crate::sync::Mutex<ThreadLists>,
+    /// Barrier buffers of the `GcContext`s, see `GcOptions::barrier_batch`
+    barrier_buffers: crate::sync::Mutex<BarrierBuffers>,
+    /// Batches of barrier work moved to the gray queue, see `barrier_flushes`
+    barrier_flushes: AtomicUsize,
+    /// Number of write-barrier slow-path operations (for instrumentation)
+    pub(crate) barrier_ops: AtomicUsize,
+    /// Signalled whenever a collection cycle finishes
+    collection_done: crate::sync::Condvar,
+    collection_done_lock: crate::sync::Mutex<()>,
+    /// Heap size when the current mark phase started (assist pacing)
+    mark_start_bytes: AtomicUsize,
+    /// Thread running the sweep (and the `Drop` of garbage), see `in_sweep`
+    sweeping_thread: AtomicUsize,
+    /// A soft-limit collection left the heap above `soft_limit_bytes`
+    over_soft_limit: AtomicBool,
+    /// Called for allocations over `limit_bytes`, see `set_oom_handler`
+    oom_handler: crate::sync::Mutex<Option<OomHandler>>,
+    /// Marks of already shaded objects, merged from tracers
+    redundant_marks: AtomicUsize,
+    /// Longest the shared gray queue has been, see `peak_gray_queue_len`
+    peak_gray_queue_len: AtomicUsize,
+    /// An object could not be traced this cycle, so nothing may be swept
+    mark_incomplete: AtomicBool,
+    /// See `last_error`
+    last_error: crate::sync::Mutex<Option<Error>>,
+    /// All objects with a root count, scanned by `do_mark_roots`
+    roots: crate::sync::Mutex<RootSet>,
+    /// Objects pinned by `Heap::pin`, scanned like the roots
+    pinned: crate::sync::Mutex<PinSet>,
+    /// Extra roots of minor collections, see `RememberedSet`
+    remembered: crate::sync::Mutex<RememberedSet>,
+    /// Swept allocations for reuse, see `GcOptions::free_list_cap_bytes`
+    free_list: crate::sync::Mutex<FreeList>,
+    /// Pre-reserved memory for objects, see `Heap::with_arena`
+    arena: Option<Arena>,
+    /// Average marking time per object in nanoseconds (0 = not measured yet)
+    mark_unit_cost_ns: AtomicU64,
+    /// Weak reference counters, see `WeakStats`
+    weak_refs: AtomicUsize,
+    weak_upgrades: AtomicUsize,
+    weak_upgrade_failures: AtomicUsize,
+    /// Set by `shutdown`, rejects new contexts and background threads
+    shut_down: AtomicBool,
+    /// Set once an object with a finalizer was allocated
+    has_finalizers: AtomicBool,
+    /// The current cycle was started by `collect_incremental` and awaits `sweep`
+    incremental_cycle: AtomicBool,
+    /// Counters for `GcStats`
+    live_objects: AtomicUsize,
+    total_collections: AtomicUsize,
+    total_bytes_freed: AtomicU64,
+    last_pause_ns: AtomicU64,
+    /// Survivors of the last sweep, see `live_bytes`
+    last_live_bytes: AtomicUsize,
+    last_live_objects: AtomicUsize,
+    /// Phase durations and pauses, see `GcOptions::collect_metrics`
+    metrics: crate::sync::Mutex<PhaseMetrics>,
+    /// Incremented when an incremental sweep starts, see `GcHeader::sweep_epoch`
+    sweep_epoch: AtomicU8,
+    /// Progress of the incremental sweep in progress, see `begin_lazy_sweep`
+    lazy_sweep: crate::sync::Mutex<Option<SweepCursor>>,
+    /// `lazy_sweep` is set
+    lazy_sweep_pending: AtomicBool,
+}
+
+/// Position and totals of a sweep, see `Heap::sweep_objects`
+struct SweepCursor {
+    /// The link in front of `current`
+    prev_next: *const AtomicPtr<GcHeader>,
+    /// Next object to sweep, null once done
+    current: *mut GcHeader,
+    minor: bool,
+    /// Whiteness is meaningless if some object could not be traced
+    keep_all: bool,
+    /// With finalizers, garbage is unlinked first and freed after all
+    /// finalizers ran, since they may still read other garbage
+    defer_free: bool,
+    unlinked: *mut GcHeader,
+    /// Stamped on the survivors, see `GcHeader::sweep_epoch`
+    epoch: u8,
+    freed: usize,
+    freed_objects: usize,
+    live: usize,
+    live_objects: usize,
+    /// Longest hold of the sweep lock
+    longest_pause: Duration,
+}
+
+// Only used by the thread holding the sweep lock exclusively
+unsafe impl Send for SweepCursor {}
+
+/// Durations of the phases of the last collection, see [`Heap::phase_timings`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    /// From the start of marking until the last gray object was traced
+    pub mark: Duration,
+    /// Scanning the root set, which blocks rooting and unrooting objects
+    pub root_scan: Duration,
+    /// From the end of marking until the sweep finished
+    pub sweep: Duration,
+}
+
+/// Upper bounds of the buckets of `Heap::pause_histogram` in microseconds
+const PAUSE_BUCKET_BOUNDS_MICROS: [u64; Heap::PAUSE_BUCKETS - 1] =
+    [10, 100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+
+/// Number of recent pauses counted by `Heap::pause_histogram`
+const PAUSE_WINDOW: usize = 256;
+
+/// State of `GcOptions::collect_metrics`
+struct PhaseMetrics {
+    mark_started: Option<Instant>,
+    sweep_started: Option<Instant>,
+    timings: PhaseTimings,
+    /// Buckets of the last `PAUSE_WINDOW` pauses, as a ring
+    recent: [u8; PAUSE_WINDOW],
+    recorded: usize,
+    histogram: [u64; Heap::PAUSE_BUCKETS],
+    last_pause_micros: u64,
+}
+
+impl PhaseMetrics {
+    const fn new() -> Self {
+        Self {
+            mark_started: None,
+            sweep_started: None,
+            timings: PhaseTimings {
+                mark: Duration::ZERO,
+                root_scan: Duration::ZERO,
+                sweep: Duration::ZERO,
+            },
+            recent: [0; PAUSE_WINDOW],
+            recorded: 0,
+            histogram: [0; Heap::PAUSE_BUCKETS],
+            last_pause_micros: 0,
+        }
+    }
+
+    fn record_pause(&mut self, pause: Duration) {
+        let micros = pause.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = PAUSE_BUCKET_BOUNDS_MICROS
+            .iter()
+            .position(|&bound| micros < bound)
+            .unwrap_or(Heap::PAUSE_BUCKETS - 1);
+        let slot = self.recorded % PAUSE_WINDOW;
+        if self.recorded >= PAUSE_WINDOW {
+            self.histogram[self.recent[slot] as usize] -= 1;
+        }
+        self.recent[slot] = bucket as u8;
+        self.histogram[bucket] += 1;
+        self.recorded += 1;
+        self.last_pause_micros = micros;
+    }
+}
+
+/// What a collection freed, see [`Heap::force_collect`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CollectionResult {
+    /// Bytes freed by the sweep
+    pub freed_bytes: usize,
+    /// Number of objects freed by the sweep
+    pub freed_objects: usize,
+    /// Bytes allocated after the collection, including objects allocated
+    /// while it ran, unlike [`Heap::live_bytes`]
+    pub bytes_after: usize,
+    /// Number of objects allocated after the collection
+    pub live_objects: usize,
+    /// Wall time of the whole cycle, zero without a clock
+    pub duration: Duration,
 }
 
+/// Snapshot of the heap's counters, see [`Heap::stats`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GcStats {
+    /// Total bytes currently allocated
+    pub bytes_allocated: usize,
+    /// Allocation volume that triggers the next collection
+    pub current_threshold: usize,
+    /// Number of objects currently allocated
+    pub live_objects: usize,
+    /// Number of completed sweeps
+    pub total_collections: usize,
+    /// Bytes freed by all sweeps
+    pub total_bytes_freed: u64,
+    /// How long the last sweep held the sweep lock, blocking `ReadGuard`s
+    ///
+    /// For an incremental sweep (`GcOptions::incremental_sweep`), the longest
+    /// of its steps.
+    ///
+    /// Always zero on `wasm32-unknown-unknown`, which has no clock.
+    pub last_pause: Duration,
+    /// Phase at the time of the snapshot
+    pub phase: GcPhase,
+}
+
+/// Counters of weak references on a heap, see [`Heap::weak_stats`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WeakStats {
+    /// Number of `GcWeak`s currently existing
+    pub weak_refs: usize,
+    /// Number of successful `GcWeak::upgrade` calls
+    pub upgrades: usize,
+    /// Number of `GcWeak::upgrade` calls that found the object swept
+    pub failed_upgrades: usize,
+}
+
+/// Diagnostic information about a single allocation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjectInfo {
+    /// Address of the object data (as returned by `GcPtr::as_ptr`)
+    pub address: usize,
+    /// Size of the allocation in bytes (including the GC header)
+    pub size: usize,
+    /// Current color of the object
+    pub color: Color,
+    /// Whether the object is a root (`GcRoot`s exist)
+    pub is_root: bool,
+}
+
+impl ObjectInfo {
+    fn from_header(header: &GcHeader) -> Self {
+        Self {
+            address: header.data_ptr() as usize,
+            size: header.vtable.layout.size(),
+            color: header.state.get(),
+            is_root: header.is_root(),
+        }
+    }
+}
+
+/// Write barrier flavour used by `GcCell` during marking
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BarrierKind {
+    /// Shade the newly stored value gray on every store (Dijkstra)
+    #[default]
+    Dijkstra,
+    /// Remember mutated cells and rescan them at the end of marking (Steele)
+    ///
+    /// Each cell is recorded only once per cycle, which is cheaper than
+    /// shading on every store for workloads that write few cells many times.
+    IncrementalUpdate,
+}
+
+/// What an allocation does when it would exceed `GcOptions::limit_bytes`
+///
+/// Every action starts with a synchronous full collection; they differ in what
+/// happens when the heap is still over the limit afterwards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LimitAction {
+    /// Allocate anyway (the limit is a soft limit)
+    #[default]
+    CollectThenContinue,
+    /// Fail with an [`AllocError`]
+    ///
+    /// `try_allocate` returns the error, `allocate` panics with it.
+    Error,
+    /// Abort the process
+    Abort,
+    /// Wait until other threads released enough objects
+    ///
+    /// Collects periodically while waiting. Blocks forever if the live
+    /// objects never drop below the limit. Behaves like `Error` in
+    /// `single-threaded` builds.
+    Block,
+}
+
+/// What an allocation over the hard limit does, returned by the handler of
+/// [`Heap::set_oom_handler`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OomAction {
+    /// Collect again and retry the allocation
+    ///
+    /// The handler is called again if the heap is still over the limit.
+    Retry,
+    /// Fail with an [`AllocError`], like [`LimitAction::Error`]
+    Fail,
+    /// Abort the process
+    Abort,
+}
+
+/// Handler of allocations over the hard limit, see [`Heap::set_oom_handler`]
+type OomHandler = Arc<dyn Fn(usize) -> OomAction + Send + Sync>;
+
+/// Error returned by `try_allocate` if the heap limit would be exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError {
+    /// Size of the failed allocation in bytes
+    pub requested_bytes: usize,
+    /// Bytes allocated on the heap at the time of the failure
+    pub allocated_bytes: usize,
+    /// The configured `limit_bytes`
+    pub limit_bytes: usize,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "allocation of {} bytes exceeds the heap limit ({} of {} bytes in use)",
+            self.requested_bytes, self.allocated_bytes, self.limit_bytes
+        )
+    }
+}
+
+impl core::error::Error for AllocError {}
+
 #[derive(Clone, Copy, Debug)]
 pub struct GcOptions {
     /// Interval between background collection attempts.
     ///
     /// If set to 0, background collection is disabled
     pub collection_interval: Duration,
+    /// Random deviation of each background wake-up from `collection_interval`
+    ///
+    /// Each wait of the background thread lasts `collection_interval` plus or
+    /// minus up to this much, so heaps created together (one per actor, say)
+    /// don't all wake up and collect at the same moments. Must be less than
+    /// `collection_interval`; 0 wakes up exactly every interval.
+    pub interval_jitter: Duration,
     /// Work budget for incremental marking steps in background collection
     pub incremental_work_budget: usize,
     /// Work budget for mutator assist (0 = disabled)
+    ///
+    /// Allocations of `Trace::NO_TRACE` types don't assist.
     pub assist_work_budget: usize,
+    /// Pace mutator assist to the allocation rate (0 = fixed `assist_work_budget`)
+    ///
+    /// Marking is planned to finish by the time the mutator allocated
+    /// `assist_ratio` percent of the bytes live at the start of the mark
+    /// phase. An assisting allocation marks as many objects as marking is
+    /// behind that plan, so fast allocators can't outrun the collector;
+    /// lower values finish marking sooner with larger assists. At least
+    /// `assist_work_budget` objects are marked per assist.
+    pub assist_ratio: usize,
+    /// Derive the work budgets from the measured marking cost
+    ///
+    /// The collector keeps an average of the time per marked object and sizes
+    /// incremental steps to take `auto_tune_step` each. Assist steps keep the
+    /// configured ratio `assist_work_budget / incremental_work_budget`.
+    /// The configured budgets are used until the first measurement.
+    ///
+    /// Needs `std::time::Instant`, which is not available on
+    /// `wasm32-unknown-unknown`.
+    pub auto_tune: bool,
+    /// Target duration of an incremental marking step with `auto_tune`
+    pub auto_tune_step: Duration,
     /// Percentage threshold for triggering collection
     ///
     /// This is the percentage of additional memory usage since the last collection
@@ -164,28 +811,224 @@ pub struct GcOptions {
     pub threshold_shrink_percent: usize,
     /// Initial & minimum threshold in bytes to trigger collection
     pub min_threshold_bytes: usize,
-    /// Maximum allowed heap size in bytes
+    /// Maximum allowed heap size in bytes (the hard limit)
     pub limit_bytes: usize,
+    /// Heap size in bytes above which allocations collect right away
+    ///
+    /// An allocation that would exceed the soft limit runs a full collection
+    /// on the allocating thread, instead of leaving it to the threshold. If
+    /// the live objects alone exceed the soft limit, it is not enforced again
+    /// until a collection brings the heap below it. Must not exceed a
+    /// configured `limit_bytes`; `usize::MAX` disables it.
+    pub soft_limit_bytes: usize,
+    /// Behavior of allocations that would exceed `limit_bytes`
+    pub on_limit_exceeded: LimitAction,
+    /// Write barrier used by `GcCell` while marking
+    pub barrier: BarrierKind,
+    /// Collect when a `GcContext` is dropped
+    ///
+    /// Objects only rooted by a finished worker thread become garbage when
+    /// its context is dropped. With this option, dropping a context wakes the
+    /// background thread (see `Heap::kick_background`), or collects
+    /// synchronously if background collection is not running.
+    pub collect_on_context_drop: bool,
+    /// Restart the background collection loop if it panicked
+    ///
+    /// If disabled, a panic stops background collection;
+    /// see `Heap::background_healthy`.
+    pub restart_background_on_panic: bool,
+    /// Track object ages for minor collections, see `Heap::collect_minor`
+    ///
+    /// Objects surviving `promote_after` collections (or any full collection)
+    /// are promoted to the old generation, which minor collections neither
+    /// trace nor sweep. Young objects stored into the cells (`GcCell`,
+    /// `GcRefCell`, `AtomicGcCell`) of old objects are remembered; stores
+    /// through std cells (`Cell`, `RefCell`, traced with the `single-threaded`
+    /// feature) are not, and must not create pointers from old to young
+    /// objects in this mode.
+    pub generational: bool,
+    /// Number of minor collections an object survives before it is promoted
+    pub promote_after: u8,
+    /// Maximum bytes of swept allocations kept for reuse
+    ///
+    /// Swept objects are dropped, but their memory is kept in a free list per
+    /// layout and reused by the next allocation of the same layout, instead
+    /// of going back to the global allocator. 0 (the default) disables the
+    /// free list.
+    ///
+    /// Retained memory is not counted in `bytes_allocated`, so it is not
+    /// checked against `limit_bytes` and `soft_limit_bytes` either: the
+    /// process may hold up to this much more than the limits.
+    pub free_list_cap_bytes: usize,
+    /// Number of threads marking a full collection in parallel
+    ///
+    /// Used by `force_collect` and the final mark before each sweep, not by
+    /// incremental steps. The collecting thread is one of the markers; the
+    /// others are spawned for the mark phase and share the work through the
+    /// gray queue, sleeping while it is empty. 0 and 1 mark on the
+    /// collecting thread only. Ignored in `single-threaded` builds.
+    pub mark_threads: usize,
+    /// Length of the shared gray queue above which marking threads mark inline
+    ///
+    /// A marking step that would grow the queue beyond this cap keeps the
+    /// overflow and traces it itself instead, which bounds the memory of the
+    /// queue for wide object graphs. Write barriers still push past the cap.
+    /// `usize::MAX` (the default) leaves the queue unbounded.
+    pub gray_queue_soft_cap: usize,
+    /// Number of allocations a `GcContext` links into the heap at once
+    ///
+    /// Each context collects its allocations in a list of its own, which is
+    /// spliced into the heap's list once it holds this many objects (and
+    /// before each sweep), so allocating threads don't contend on a single
+    /// list head. Allocations while marking, and without a context of the
+    /// heap, are linked right away. 0 links every allocation right away.
+    pub thread_alloc_batch: usize,
+    /// Number of objects a `GcContext`'s write barriers shade before they are
+    /// queued for the collector
+    ///
+    /// Each context collects the objects its barriers shade in a buffer of
+    /// its own, which is moved to the heap's gray queue once it holds this
+    /// many (and before marking finishes), so storing threads don't contend
+    /// on the queue's lock for every store. Barriers without a context of the
+    /// heap queue right away. 0 queues every barrier's objects right away.
+    pub barrier_batch: usize,
+    /// Allocate objects black while marking, instead of gray
+    ///
+    /// A gray allocation is queued and traced later by the collector. A black
+    /// one is traced right away by the allocating thread: the pointers it was
+    /// constructed with are shaded, and it never enters the gray queue. This
+    /// keeps the queue short when mutators allocate a lot during marking, at
+    /// the cost of tracing on the allocating thread.
+    ///
+    /// Either way, an object allocated while marking survives the cycle, even
+    /// if it is dropped right away. With black allocation, so does everything
+    /// it pointed to when it was allocated, even if the pointers are replaced
+    /// before the collector would have traced it: more floating garbage, kept
+    /// until the next cycle.
+    pub alloc_black_during_mark: bool,
+    /// Measure phase durations and pauses, see `Heap::phase_timings` and
+    /// `Heap::pause_histogram`
+    ///
+    /// Off, collections don't read the clock for these.
+    pub collect_metrics: bool,
+    /// Sweep the background thread's cycles incrementally
+    ///
+    /// Instead of freeing all garbage in one pause, the background thread
+    /// sweeps `incremental_work_budget` objects at a time, releasing the
+    /// sweep lock in between. Allocations sweep as many before they are
+    /// served, so freed memory is reused right away (sweep on demand).
+    /// Explicit collections (`force_collect`, `sweep`, ...) still sweep in
+    /// one go; [`Heap::begin_sweep`] sweeps a manual cycle incrementally.
+    pub incremental_sweep: bool,
+    /// Check the tri-color invariant after marking, in debug builds
+    ///
+    /// Before each full sweep, the black objects are traced again, and the
+    /// collector panics if one points to a white object, which would be
+    /// freed while still referenced. That happens if a pointer was stored
+    /// without a write barrier (through a `Cell` instead of a [`GcCell`],
+    /// for example), or if a `Trace` impl doesn't report the same pointers
+    /// every time; a pointer that `Trace` never reports can't be found this
+    /// way. Costs a second trace of the live objects. Ignored in release
+    /// builds and without `std`.
+    ///
+    /// [`GcCell`]: crate::GcCell
+    pub verify_marking: bool,
 }
 
 impl GcOptions {
     pub const DEFAULT: Self = Self {
         collection_interval: Duration::from_millis(100),
+        interval_jitter: Duration::ZERO,
         incremental_work_budget: 100,
         assist_work_budget: 5,
+        assist_ratio: 0,
+        auto_tune: false,
+        auto_tune_step: Duration::from_micros(500),
         threshold_percent: 30,
         threshold_shrink_percent: 30,
         min_threshold_bytes: 1024 * 1024,
         limit_bytes: usize::MAX,
+        soft_limit_bytes: usize::MAX,
+        on_limit_exceeded: LimitAction::CollectThenContinue,
+        barrier: BarrierKind::Dijkstra,
+        collect_on_context_drop: false,
+        restart_background_on_panic: false,
+        generational: false,
+        promote_after: 2,
+        free_list_cap_bytes: 0,
+        mark_threads: 1,
+        gray_queue_soft_cap: usize::MAX,
+        thread_alloc_batch: 64,
+        barrier_batch: 64,
+        alloc_black_during_mark: false,
+        collect_metrics: false,
+        incremental_sweep: false,
+        verify_marking: false,
     };
     pub const OFF: Self = Self {
         collection_interval: Duration::from_millis(0),
+        interval_jitter: Duration::ZERO,
         incremental_work_budget: usize::MAX,
         assist_work_budget: 0,
+        assist_ratio: 0,
+        auto_tune: false,
+        auto_tune_step: Duration::from_micros(500),
         threshold_percent: usize::MAX,
         threshold_shrink_percent: 0,
         min_threshold_bytes: usize::MAX,
         limit_bytes: usize::MAX,
+        soft_limit_bytes: usize::MAX,
+        on_limit_exceeded: LimitAction::CollectThenContinue,
+        barrier: BarrierKind::Dijkstra,
+        collect_on_context_drop: false,
+        restart_background_on_panic: false,
+        generational: false,
+        promote_after: 2,
+        free_list_cap_bytes: 0,
+        mark_threads: 1,
+        gray_queue_soft_cap: usize::MAX,
+        thread_alloc_batch: 64,
+        barrier_batch: 64,
+        alloc_black_during_mark: false,
+        collect_metrics: false,
+        incremental_sweep: false,
+        verify_marking: false,
+    };
+    /// Short pauses, at the cost of more collector work
+    ///
+    /// Starting point for interactive programs. The background thread checks
+    /// every 10 ms and collects once the heap grew by 10% (at least 256 KiB),
+    /// so each cycle has little garbage to free. Marking and sweeping proceed
+    /// in steps of 20 objects, the sweep incrementally; allocations assist
+    /// paced to finish marking within 50% of the live bytes, so a fast
+    /// allocator can't outrun the collector. Expect more, smaller collections
+    /// than with [`DEFAULT`](Self::DEFAULT).
+    pub const LOW_LATENCY: Self = Self {
+        collection_interval: Duration::from_millis(10),
+        incremental_work_budget: 20,
+        assist_work_budget: 10,
+        assist_ratio: 50,
+        threshold_percent: 10,
+        min_threshold_bytes: 256 * 1024,
+        incremental_sweep: true,
+        ..Self::DEFAULT
+    };
+    /// Few, large collections, at the cost of memory and longer pauses
+    ///
+    /// Starting point for batch jobs. The background thread checks every
+    /// 500 ms and collects once the heap doubled (at least 8 MiB); marking
+    /// runs in large steps and allocations don't assist, so the mutator is
+    /// interrupted rarely. Up to 8 MiB of swept memory is kept for reuse.
+    /// Expect fewer, larger collections than with [`DEFAULT`](Self::DEFAULT).
+    pub const HIGH_THROUGHPUT: Self = Self {
+        collection_interval: Duration::from_millis(500),
+        incremental_work_budget: 10_000,
+        assist_work_budget: 0,
+        assist_ratio: 0,
+        threshold_percent: 100,
+        min_threshold_bytes: 8 * 1024 * 1024,
+        free_list_cap_bytes: 8 * 1024 * 1024,
+        ..Self::DEFAULT
     };
 
     #[inline]
@@ -198,6 +1041,46 @@ impl GcOptions {
         Self::OFF
     }
 
+    /// Default options with background collection every `interval`
+    ///
+    /// A zero `interval` disables background collection, like [`manual`](Self::manual).
+    #[inline]
+    pub const fn automatic(interval: Duration) -> Self {
+        Self {
+            collection_interval: interval,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Default options without background collection
+    ///
+    /// No background thread is started; the heap is only collected by
+    /// `collect` (once the threshold is exceeded), `force_collect`, or the
+    /// incremental API.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcOptions};
+    ///
+    /// let ctx = GcContext::with_options(GcOptions::manual());
+    /// drop(ctx.allocate(100));
+    /// ctx.force_collect();
+    /// assert_eq!(ctx.allocation_count(), 0);
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn manual() -> Self {
+        Self::automatic(Duration::ZERO)
+    }
+
+    /// Builder starting from `GcOptions::DEFAULT`, see [`GcOptionsBuilder`]
+    #[inline]
+    pub const fn builder() -> GcOptionsBuilder {
+        GcOptionsBuilder::from_options(Self::DEFAULT)
+    }
+
     #[inline]
     fn is_threshold_off(&self) -> bool {
         self.threshold_percent == 0 || self.threshold_percent == !0
@@ -208,6 +1091,12 @@ impl GcOptions {
         self.limit_bytes == usize::MAX
     }
 
+    #[inline]
+    fn is_soft_limit_off(&self) -> bool {
+        self.soft_limit_bytes == usize::MAX
+    }
+
+    #[cfg(feature = "std")]
     #[inline]
     fn is_background_collection_off(&self) -> bool {
         self.is_threshold_off() || self.collection_interval.as_millis() == 0
@@ -219,7 +1108,12 @@ impl GcOptions {
     }
 
     /// pacing
-    fn calculate_threshold(&self, old_threshold: usize, live_usage: usize) -> usize {
+    fn calculate_threshold(
+        &self,
+        old_threshold: usize,
+        live_usage: usize,
+        min_threshold: usize,
+    ) -> usize {
         if self.is_threshold_off() {
             usize::MAX
         } else {
@@ -234,8 +1128,8 @@ impl GcOptions {
                     }
                 }
             }
-            if new_threshold < self.min_threshold_bytes {
-                self.min_threshold_bytes
+            if new_threshold < min_threshold {
+                min_threshold
             } else {
                 new_threshold
             }
@@ -250,6 +1144,142 @@ impl Default for GcOptions {
     }
 }
 
+/// Fluent builder for [`GcOptions`], see [`GcOptions::builder`]
+///
+/// Every setter sets the `GcOptions` field of the same name.
+/// [`build`](Self::build) checks the combination of the values.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcContext, GcOptions};
+/// use std::time::Duration;
+///
+/// let options = GcOptions::builder()
+///     .collection_interval(Duration::from_millis(10))
+///     .min_threshold_bytes(64 * 1024)
+///     .build()
+///     .unwrap();
+/// let ctx = GcContext::with_options(options);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+#[must_use]
+pub struct GcOptionsBuilder {
+    options: GcOptions,
+}
+
+macro_rules! builder_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[inline]
+            pub const fn $field(mut self, $field: $ty) -> Self {
+                self.options.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+impl GcOptionsBuilder {
+    /// Start from `options` instead of `GcOptions::DEFAULT`
+    #[inline]
+    pub const fn from_options(options: GcOptions) -> Self {
+        Self { options }
+    }
+
+    builder_setters! {
+        collection_interval: Duration,
+        interval_jitter: Duration,
+        incremental_work_budget: usize,
+        assist_work_budget: usize,
+        assist_ratio: usize,
+        auto_tune: bool,
+        auto_tune_step: Duration,
+        threshold_percent: usize,
+        threshold_shrink_percent: usize,
+        min_threshold_bytes: usize,
+        limit_bytes: usize,
+        soft_limit_bytes: usize,
+        on_limit_exceeded: LimitAction,
+        barrier: BarrierKind,
+        collect_on_context_drop: bool,
+        restart_background_on_panic: bool,
+        generational: bool,
+        promote_after: u8,
+        free_list_cap_bytes: usize,
+        mark_threads: usize,
+        gray_queue_soft_cap: usize,
+        thread_alloc_batch: usize,
+        barrier_batch: usize,
+        alloc_black_during_mark: bool,
+        collect_metrics: bool,
+        incremental_sweep: bool,
+        verify_marking: bool,
+    }
+
+    /// Validate and return the options
+    ///
+    /// Returns [`Error::InvalidOptions`] if
+    /// - `threshold_shrink_percent` is above 100,
+    /// - `incremental_work_budget` is 0 (marking would never progress),
+    /// - `auto_tune` is enabled with a zero `auto_tune_step`,
+    /// - `interval_jitter` isn't less than a nonzero `collection_interval`,
+    /// - `min_threshold_bytes` exceeds a configured `limit_bytes`,
+    /// - `soft_limit_bytes` exceeds a configured `limit_bytes`.
+    pub fn build(self) -> Result<GcOptions, Error> {
+        let options = self.options;
+        if options.threshold_shrink_percent > 100 {
+            return Err(Error::InvalidOptions(
+                "threshold_shrink_percent must be at most 100",
+            ));
+        }
+        if options.incremental_work_budget == 0 {
+            return Err(Error::InvalidOptions(
+                "incremental_work_budget must not be 0",
+            ));
+        }
+        if options.auto_tune && options.auto_tune_step.is_zero() {
+            return Err(Error::InvalidOptions(
+                "auto_tune_step must not be 0 with auto_tune",
+            ));
+        }
+        if !options.collection_interval.is_zero()
+            && options.interval_jitter >= options.collection_interval
+        {
+            return Err(Error::InvalidOptions(
+                "interval_jitter must be less than collection_interval",
+            ));
+        }
+        if !options.is_limit_off() && options.min_threshold_bytes > options.limit_bytes {
+            return Err(Error::InvalidOptions(
+                "min_threshold_bytes must not exceed limit_bytes",
+            ));
+        }
+        if !options.is_soft_limit_off() && options.soft_limit_bytes > options.limit_bytes {
+            return Err(Error::InvalidOptions(
+                "soft_limit_bytes must not exceed limit_bytes",
+            ));
+        }
+        Ok(options)
+    }
+}
+
+impl Default for GcOptionsBuilder {
+    #[inline]
+    fn default() -> Self {
+        GcOptions::builder()
+    }
+}
+
+impl From<GcOptions> for GcOptionsBuilder {
+    #[inline]
+    fn from(options: GcOptions) -> Self {
+        Self::from_options(options)
+    }
+}
+
 impl Heap {
     pub fn new() -> Arc<Self> {
         Self::with_options(GcOptions::new())
@@ -260,65 +1290,852 @@ impl Heap {
     }
 
     pub fn with_options(options: GcOptions) -> Arc<Self> {
+        Self::with_options_and_arena(options, None)
+    }
+
+    /// Create a heap that allocates objects from a pre-reserved block
+    ///
+    /// `arena_bytes` are reserved up front, and objects are bump-allocated
+    /// from them. Swept arena objects are kept for reuse by objects of the
+    /// same layout (regardless of `free_list_cap_bytes`). When the arena is
+    /// full, objects are allocated with the global allocator as usual. The
+    /// block is released when the heap is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcOptions, Heap};
+    ///
+    /// let heap = Heap::with_arena(GcOptions::manual(), 64 * 1024);
+    /// let value = heap.allocate(42);
+    /// assert_eq!(*value, 42);
+    /// assert!(heap.arena_bytes_used() > 0);
+    /// ```
+    pub fn with_arena(options: GcOptions, arena_bytes: usize) -> Arc<Self> {
+        Self::with_options_and_arena(options, Some(Arena::new(arena_bytes)))
+    }
+
+    fn with_options_and_arena(options: GcOptions, arena: Option<Arena>) -> Arc<Self> {
         let current_threshold = AtomicUsize::new(options.min_threshold_bytes);
         let heap = Arc::new(Self {
             head: AtomicPtr::new(null_mut()),
             options,
             bytes_allocated: AtomicUsize::new(0),
             current_threshold,
-            gray_queue: parking_lot::Mutex::new(GrayQueue::new()),
-            phase: AtomicU8::new(GcPhase::Idle as u8),
+            min_threshold: AtomicUsize::new(options.min_threshold_bytes),
+            gray_queue: crate::sync::Mutex::new(GrayQueue::new()),
+            phase: PhaseState::new(),
+            #[cfg(feature = "std")]
             bg_thread: StartStopJoinHandle::new(),
-            n_busy_marking: std::sync::atomic::AtomicUsize::new(0),
+            hooks: CollectionHooks::new(),
+            sweep_lock: crate::sync::RwLock::new(()),
+            guard_holders: crate::sync::Mutex::new(Vec::new()),
+            dirty_objects: crate::sync::Mutex::new(DirtySet(Vec::new())),
+            mark_cycle: AtomicUsize::new(0),
+            ephemerons: crate::sync::Mutex::new(EphemeronSet(Vec::new())),
+            tombstones: crate::sync::Mutex::new(Tombstones(Vec::new())),
+            thread_lists: crate::sync::Mutex::new(ThreadLists(Vec::new())),
+            barrier_buffers: crate::sync::Mutex::new(BarrierBuffers(Vec::new())),
+            barrier_flushes: AtomicUsize::new(0),
+            barrier_ops: AtomicUsize::new(0),
+            collection_done: crate::sync::Condvar::new(),
+            collection_done_lock: crate::sync::Mutex::new(()),
+            mark_start_bytes: AtomicUsize::new(0),
+            sweeping_thread: AtomicUsize::new(0),
+            over_soft_limit: AtomicBool::new(false),
+            oom_handler: crate::sync::Mutex::new(None),
+            redundant_marks: AtomicUsize::new(0),
+            peak_gray_queue_len: AtomicUsize::new(0),
+            mark_incomplete: AtomicBool::new(false),
+            last_error: crate::sync::Mutex::new(None),
+            roots: crate::sync::Mutex::new(RootSet(Vec::new())),
+            pinned: crate::sync::Mutex::new(PinSet(Vec::new())),
+            remembered: crate::sync::Mutex::new(RememberedSet(Vec::new())),
+            free_list: crate::sync::Mutex::new(FreeList::new(options.free_list_cap_bytes)),
+            arena,
+            mark_unit_cost_ns: AtomicU64::new(0),
+            weak_refs: AtomicUsize::new(0),
+            weak_upgrades: AtomicUsize::new(0),
+            weak_upgrade_failures: AtomicUsize::new(0),
+            shut_down: AtomicBool::new(false),
+            has_finalizers: AtomicBool::new(false),
+            incremental_cycle: AtomicBool::new(false),
+            live_objects: AtomicUsize::new(0),
+            total_collections: AtomicUsize::new(0),
+            total_bytes_freed: AtomicU64::new(0),
+            last_pause_ns: AtomicU64::new(0),
+            last_live_bytes: AtomicUsize::new(0),
+            last_live_objects: AtomicUsize::new(0),
+            metrics: crate::sync::Mutex::new(PhaseMetrics::new()),
+            sweep_epoch: AtomicU8::new(0),
+            lazy_sweep: crate::sync::Mutex::new(None),
+            lazy_sweep_pending: AtomicBool::new(false),
         });
 
+        #[cfg(feature = "std")]
         heap.start_background_collection();
 
         heap
     }
 
+    /// Allocate an object on the heap
+    ///
+    /// Works without a `GcContext` on the current thread, see
+    /// [`Heap::allocate_rooted`]. Zero-sized types are allocated like any
+    /// other: the object still has its header, so each one is distinct and
+    /// counts towards `bytes_allocated`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the heap limit is exceeded and `on_limit_exceeded` is
+    /// [`LimitAction::Error`]; use [`Heap::try_allocate`] to handle this case.
     pub fn allocate<T: Trace>(&self, data: T) -> GcRoot<T> {
-        // Mutator assist: help with marking if enabled
-        if self.options.assist_work_budget > 0 && self.check_is_marking_and_increment_busy() {
-            self.do_mark_incremental(self.options.assist_work_budget);
-            self.decrement_busy_marking();
+        match self.try_allocate(data) {
+            Ok(root) => root,
+            Err(err) => panic!("{err}"),
         }
+    }
+
+    /// Allocate an object that is never collected while it is pinned
+    ///
+    /// For objects that live as long as the program or interpreter using
+    /// them, like interned strings or a global environment. The object is
+    /// kept in a pin set of the heap that every cycle scans like the roots,
+    /// until the last clone of the returned handle is dropped. Cloning a
+    /// [`PinnedGc`] doesn't touch the object or the heap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// let globals = ctx.pin(vec![1, 2, 3]);
+    /// ctx.force_collect();
+    /// assert_eq!(globals.clone()[2], 3);
+    /// # }
+    /// ```
+    pub fn pin<T: Trace>(&self, data: T) -> PinnedGc<T> {
+        let root = self.allocate(data);
+        // Pinned before the root is dropped, so the object is never unprotected
+        self.pinned.lock().0.push(root.as_ptr().header_ptr());
+        PinnedGc::new(root.as_ptr())
+    }
+
+    /// Remove an object from the pin set, when its last `PinnedGc` is dropped
+    pub(crate) fn unpin(&self, header: *const GcHeader) {
+        let mut pinned = self.pinned.lock();
+        if let Some(index) = pinned.0.iter().position(|&p| core::ptr::eq(p, header)) {
+            pinned.0.swap_remove(index);
+        }
+    }
+
+    /// Allocate an object on this heap, without relying on a `GcContext`
+    ///
+    /// For library code that receives a heap but can't assume (or install)
+    /// a thread-local context. Objects allocated while marking is in progress
+    /// are shaded, so they survive the current cycle even if they are only
+    /// stored into already scanned objects. `GcCell::set` on a thread
+    /// without a context finds the heap through the stored pointers instead.
+    ///
+    /// Same as [`Heap::allocate`].
+    #[inline]
+    pub fn allocate_rooted<T: Trace>(&self, data: T) -> GcRoot<T> {
+        self.allocate(data)
+    }
+
+    /// Allocate an object on the heap, failing if the heap limit is exceeded
+    ///
+    /// Only fails if `on_limit_exceeded` is [`LimitAction::Error`]. A forced
+    /// collection runs first, so this only fails if the live objects don't
+    /// leave room for the new one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcOptions, LimitAction};
+    ///
+    /// let ctx = GcContext::with_options(GcOptions {
+    ///     limit_bytes: 4096,
+    ///     on_limit_exceeded: LimitAction::Error,
+    ///     ..GcOptions::OFF
+    /// });
+    /// let mut live = Vec::new();
+    /// let err = loop {
+    ///     match ctx.try_allocate([0u8; 256]) {
+    ///         Ok(root) => live.push(root),
+    ///         Err(err) => break err,
+    ///     }
+    /// };
+    /// assert_eq!(err.limit_bytes, 4096);
+    /// assert!(err.allocated_bytes + err.requested_bytes > 4096);
+    /// # }
+    /// ```
+    pub fn try_allocate<T: Trace>(&self, data: T) -> Result<GcRoot<T>, AllocError> {
+        self.try_allocate_with(data, &GcBox::<T>::VTABLE)
+    }
+
+    /// Allocate an object whose [`Finalize::finalize`] runs before it is swept
+    ///
+    /// # Panics
+    ///
+    /// Like [`Heap::allocate`], if the heap limit is exceeded.
+    pub fn allocate_finalized<T: Trace + Finalize>(&self, data: T) -> GcRoot<T> {
+        self.has_finalizers.store(true, Ordering::Relaxed);
+        match self.try_allocate_with(data, &GcBox::<T>::FINALIZED_VTABLE) {
+            Ok(root) => root,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    fn try_allocate_with<T: Trace>(
+        &self,
+        data: T,
+        vtable: &'static GcVTable,
+    ) -> Result<GcRoot<T>, AllocError> {
+        let slot = self.prepare_allocation(Layout::new::<GcBox<T>>(), T::NO_TRACE)?;
+        let ptr = GcBox::new(data, vtable, self, slot);
+        unsafe { self.link_allocation(&(*ptr.as_ptr()).header, T::NO_TRACE) };
+
+        // Return as GcRoot (already rooted with root_count = 1)
+        Ok(unsafe { GcRoot::new_from_nonnull(ptr) })
+    }
+
+    /// Allocate an object that is constructed with a pointer to itself
+    ///
+    /// `f` receives the `GcPtr` of the reserved object and returns its value,
+    /// which makes it possible to build cycles without `Option` or a cell.
+    /// The object is only added to the heap once `f` returned: until then
+    /// the pointer must not be rooted or dereferenced, only stored.
+    ///
+    /// Objects allocated in `f` that are only reachable through the new
+    /// object must stay rooted until `allocate_with` returns.
+    ///
+    /// # Panics
+    ///
+    /// Like [`Heap::allocate`], if the heap limit is exceeded. If `f` panics,
+    /// the reservation is released.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcPtr, Trace, Tracer};
+    ///
+    /// struct Node {
+    ///     next: GcPtr<Node>,
+    /// }
+    ///
+    /// unsafe impl Trace for Node {
+    ///     fn trace(&self, tracer: &Tracer) {
+    ///         tracer.mark(&self.next);
+    ///     }
+    /// }
+    ///
+    /// let ctx = GcContext::new();
+    /// let node = ctx.allocate_with(|this| Node { next: this });
+    /// assert!(node.next.ptr_eq(&node.as_ptr()));
+    /// # }
+    /// ```
+    pub fn allocate_with<T: Trace>(&self, f: impl FnOnce(GcPtr<T>) -> T) -> GcRoot<T> {
+        let ptr = match self.reserve::<T>() {
+            Ok(ptr) => ptr,
+            Err(err) => panic!("{err}"),
+        };
+
+        /// Releases the reservation if `f` panics
+        struct Reservation<T: Trace>(NonNull<GcBox<T>>);
+        impl<T: Trace> Drop for Reservation<T> {
+            fn drop(&mut self) {
+                unsafe { Heap::release(self.0) };
+            }
+        }
+        let reservation = Reservation(ptr);
+        let data = f(GcPtr::new(ptr));
+        core::mem::forget(reservation);
+
+        unsafe { self.commit(ptr, data) }
+    }
+
+    /// Reserve an object whose data is written later by [`commit`](Self::commit)
+    ///
+    /// The object is not on the heap yet; see [`GcBox::reserve`].
+    pub(crate) fn reserve<T: Trace>(&self) -> Result<NonNull<GcBox<T>>, AllocError> {
+        let slot = self.prepare_allocation(Layout::new::<GcBox<T>>(), T::NO_TRACE)?;
+        Ok(GcBox::<T>::reserve(&GcBox::<T>::VTABLE, self, slot))
+    }
+
+    /// Initialize a reserved object with `data` and add it to the heap
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be reserved on this heap and not committed or released yet.
+    pub(crate) unsafe fn commit<T: Trace>(&self, ptr: NonNull<GcBox<T>>, data: T) -> GcRoot<T> {
+        unsafe {
+            (&raw mut (*ptr.as_ptr()).data).write(data);
+            let header = &(*ptr.as_ptr()).header;
+            header.state.reset_white();
+            self.link_allocation(header, T::NO_TRACE);
+            GcRoot::new_from_nonnull(ptr)
+        }
+    }
+
+    /// Free a reserved object that was never committed
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be reserved and not committed or released yet.
+    pub(crate) unsafe fn release<T: Trace>(ptr: NonNull<GcBox<T>>) {
+        unsafe { GcHeader::deallocate(ptr.cast().as_ptr()) };
+    }
+
+    /// Allocate a copy of a slice, with the elements inline
+    ///
+    /// # Panics
+    ///
+    /// Like [`Heap::allocate`], if the heap limit is exceeded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::new();
+    /// let numbers = ctx.allocate_slice(&[1, 2, 3]);
+    /// assert_eq!(numbers.len(), 3);
+    /// assert_eq!(numbers[2], 3);
+    /// # }
+    /// ```
+    pub fn allocate_slice<T: Trace + Copy>(&self, data: &[T]) -> GcRoot<[T]> {
+        let layout = GcBox::<[T]>::slice_layout(data.len());
+        let slot = match self.prepare_allocation(layout, T::NO_TRACE) {
+            Ok(slot) => slot,
+            Err(err) => panic!("{err}"),
+        };
+        let ptr = GcBox::new_slice(data, self, slot);
+        unsafe { self.link_allocation(&(*ptr.as_ptr()).header, T::NO_TRACE) };
+        unsafe { GcRoot::new_from_nonnull(ptr) }
+    }
+
+    /// Enforce the heap limit and assist marking before an allocation
+    ///
+    /// Objects without pointers (`no_trace`) skip the assist: they are
+    /// allocated black while marking and never add work.
+    /// Returns memory from the arena or a recycled allocation of `layout`
+    /// from the free list, if any.
+    fn prepare_allocation(
+        &self,
+        layout: Layout,
+        no_trace: bool,
+    ) -> Result<Option<Slot>, AllocError> {
+        if !self.options.is_soft_limit_off() {
+            self.enforce_soft_limit(layout.size());
+        }
+        if !self.options.is_limit_off() {
+            self.enforce_limit(layout.size())?;
+        }
+
+        // Mutator assist: help with marking if enabled
+        if !no_trace
+            && (self.options.assist_work_budget > 0 || self.options.assist_ratio > 0)
+            && self.check_is_marking_and_increment_busy()
+        {
+            self.do_mark_incremental(self.assist_work_budget());
+            self.decrement_busy_marking();
+        }
+
+        // Sweep on demand: garbage freed here can serve this allocation
+        if self.lazy_sweep_pending.load(Ordering::Relaxed) {
+            self.try_sweep_work(self.options.incremental_work_budget);
+        }
+
+        if let Some(ptr) = self.arena.as_ref().and_then(|arena| arena.allocate(layout)) {
+            return Ok(Some(Slot {
+                ptr,
+                from_arena: true,
+            }));
+        }
+        if self.options.free_list_cap_bytes > 0 {
+            Ok(self.free_list.lock().pop(layout).map(|ptr| Slot {
+                ptr,
+                from_arena: false,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The arena of a heap created with [`Heap::with_arena`]
+    #[inline]
+    pub(crate) fn arena(&self) -> Option<&Arena> {
+        self.arena.as_ref()
+    }
+
+    /// Bytes of the arena handed out so far (see [`Heap::with_arena`])
+    ///
+    /// Swept arena objects are reused, so this only grows when no swept
+    /// object of the same layout is available. 0 without an arena.
+    pub fn arena_bytes_used(&self) -> usize {
+        self.arena.as_ref().map_or(0, Arena::used)
+    }
+
+    /// Release capacity retained from earlier collections
+    ///
+    /// Deallocates the swept allocations kept for reuse (see
+    /// [`GcOptions::free_list_cap_bytes`]) and shrinks the internal queues to
+    /// their current length, e.g. after a burst of allocations was collected.
+    /// Arena memory stays reserved. Does nothing unless the heap is
+    /// [`GcPhase::Idle`].
+    pub fn shrink_to_fit(&self) {
+        if self.phase() != GcPhase::Idle {
+            return;
+        }
+        // Shrinking keeps the contents, so a cycle starting meanwhile is fine
+        self.gray_queue.lock().0.shrink_to_fit();
+        self.dirty_objects.lock().0.shrink_to_fit();
+        self.remembered.lock().0.shrink_to_fit();
+        self.roots.lock().0.shrink_to_fit();
+        self.pinned.lock().0.shrink_to_fit();
+        self.free_list.lock().clear();
+    }
+
+    /// Move all objects of `other` to this heap
+    ///
+    /// The objects keep their addresses, and their roots, `PinnedGc`s and
+    /// `GcWeak`s stay valid: from now on they belong to this heap, are freed
+    /// by its collections and counted in its statistics. So do the `GcWeak`s
+    /// to objects `other` freed already. `other` is left empty and dropped;
+    /// its options, callbacks and retained free list are not carried over.
+    ///
+    /// Both heaps are idle while the objects move. A cycle in progress on
+    /// `other` is completed first. On this heap, a cycle in progress is waited
+    /// for (with the caveat of [`request_collection`](Self::request_collection)
+    /// about incremental cycles of the calling thread), and no cycle starts
+    /// until the merge is done. The background thread of `other` is stopped,
+    /// and `other` must be its last reference, which rules out contexts and
+    /// collections on it. Its objects must not be used by other threads
+    /// during the call.
+    ///
+    /// # Panics
+    ///
+    /// If `other` is shared (e.g. with a `GcContext`), if it was created with
+    /// [`Heap::with_arena`], whose objects can't outlive it, or if called from
+    /// a `Drop` or finalizer run by the sweep.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcOptions, Heap};
+    /// use std::sync::Arc;
+    ///
+    /// let heap = Heap::with_options(GcOptions::manual());
+    /// let other = Heap::with_options(GcOptions::manual());
+    /// let value = other.allocate(42);
+    ///
+    /// heap.merge_from(other);
+    /// assert_eq!(heap.allocation_count(), 1);
+    /// heap.force_collect();
+    /// assert_eq!(*value, 42);
+    /// ```
+    pub fn merge_from(&self, other: Arc<Heap>) {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+        assert!(
+            other.arena.is_none(),
+            "abfall: the objects of a heap with an arena can't be merged"
+        );
+        if self.in_sweep() {
+            panic!("abfall: merging heaps from a `Drop` or finalizer run by the sweep");
+        }
+        // Holds a reference during its collections
+        #[cfg(feature = "std")]
+        other.stop_background_collection();
+        // The objects point to `other` until they are moved: it is only
+        // dropped after that
+        assert_eq!(
+            Arc::strong_count(&other),
+            1,
+            "abfall: merging a heap that is still in use"
+        );
+
+        other.sweep();
+        other.do_sweep_work(usize::MAX);
+        debug_assert_eq!(other.phase(), GcPhase::Idle);
+        debug_assert!(other.dirty_objects.lock().0.is_empty());
+        debug_assert!(other.ephemerons.lock().0.is_empty());
+
+        // Claimed like a sweep: cycles can't start, barriers don't shade
+        while !self.phase.try_start(GcPhase::Sweeping) {
+            self.do_sweep_work(usize::MAX);
+            let mut guard = self.collection_done_lock.lock();
+            if self.phase.get() != GcPhase::Idle {
+                self.collection_done.wait_for(&mut guard, POLL_INTERVAL);
+            }
+        }
+
+        {
+            // Excludes walks of the list, like `roots`
+            let _sweep_guard = self.sweep_lock.write();
+            other.splice_thread_lists();
+            let first = other.head.swap(null_mut(), Ordering::AcqRel);
+            let epoch = self.sweep_epoch.load(Ordering::SeqCst);
+            let mut roots = self.roots.lock();
+            let mut last = first;
+            let mut current = first;
+            while let Some(header) = unsafe { current.as_ref() } {
+                header
+                    .heap
+                    .store(self as *const Self as *mut Self, Ordering::Relaxed);
+                header.sweep_epoch.store(epoch, Ordering::Relaxed);
+                header.root_index.store(NO_ROOT_INDEX, Ordering::Relaxed);
+                Self::sync_root_locked(&mut roots, header);
+                last = current;
+                current = header.next.load(Ordering::Acquire);
+            }
+            if !first.is_null() {
+                self.splice(first, last);
+            }
+            drop(roots);
+
+            let pinned = core::mem::take(&mut other.pinned.lock().0);
+            self.pinned.lock().0.extend(pinned);
+            let remembered = core::mem::take(&mut other.remembered.lock().0);
+            if self.options.generational {
+                self.remembered.lock().0.extend(remembered);
+            } else {
+                for header in remembered {
+                    unsafe { &*header }
+                        .remembered
+                        .store(false, Ordering::Relaxed);
+                }
+            }
+            let tombstones = core::mem::take(&mut other.tombstones.lock().0);
+            for &header in &tombstones {
+                unsafe { &*header }
+                    .heap
+                    .store(self as *const Self as *mut Self, Ordering::Relaxed);
+            }
+            self.tombstones.lock().0.extend(tombstones);
+
+            let bytes = other.bytes_allocated.swap(0, Ordering::Relaxed);
+            self.bytes_allocated.fetch_add(bytes, Ordering::Relaxed);
+            let objects = other.live_objects.swap(0, Ordering::Relaxed);
+            self.live_objects.fetch_add(objects, Ordering::Relaxed);
+            let weak_refs = other.weak_refs.swap(0, Ordering::Relaxed);
+            self.weak_refs.fetch_add(weak_refs, Ordering::Relaxed);
+            if other.has_finalizers.load(Ordering::Relaxed) {
+                self.has_finalizers.store(true, Ordering::Relaxed);
+            }
+        }
+        self.finish_gc();
+    }
 
-        let ptr = GcBox::new(data);
-        let size = unsafe { (*ptr.as_ptr()).header.vtable.layout.size() };
+    /// Add a new object to the allocation list and the root set
+    ///
+    /// # Safety
+    ///
+    /// `header` must belong to a new, initialized object of this heap.
+    unsafe fn link_allocation(&self, header: &GcHeader, no_trace: bool) {
+        let size = header.vtable.layout.size();
+        // Checked before linking: an object linked before the sweeper's list
+        // snapshot must be shaded, see `do_sweep`
+        let marking = self.check_is_marking_and_increment_busy();
 
         // Insert at head of linked list atomically
-        let header_ptr = unsafe { &(*ptr.as_ptr()).header as *const GcHeader as *mut GcHeader };
+        let header_ptr = header as *const GcHeader as *mut GcHeader;
+        // Linked before the root set is unlocked: an object shaded by a root
+        // scan must be in the sweep's snapshot, see `start_sweep`
+        let mut roots = self.roots.lock();
+        Self::sync_root_locked(&mut roots, header);
+        let epoch = if self.options.incremental_sweep {
+            let epoch = self.sweep_epoch.load(Ordering::SeqCst);
+            header.sweep_epoch.store(epoch, Ordering::Relaxed);
+            epoch
+        } else {
+            0
+        };
+
+        // Shaded objects are linked right away: the sweep only resets the
+        // colors of the objects in its snapshot and in front of it, see
+        // `whiten_unswept`
+        if marking || !self.link_thread_local(header_ptr) {
+            self.splice(header_ptr, header_ptr);
+        }
+        drop(roots);
+
+        if self.options.incremental_sweep {
+            // An incremental sweep started meanwhile: its snapshot, taken
+            // after the epoch changed, may not contain this object
+            core::sync::atomic::fence(Ordering::SeqCst);
+            let now = self.sweep_epoch.load(Ordering::SeqCst);
+            if now != epoch {
+                // Fails if the sweep stamped it already
+                let _ = header.sweep_epoch.compare_exchange(
+                    epoch,
+                    now,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+        }
+
+        self.bytes_allocated.fetch_add(size, Ordering::Relaxed);
+        self.live_objects.fetch_add(1, Ordering::Relaxed);
+
+        // The root scan might be over already
+        if marking {
+            if no_trace {
+                header.state.mark_black();
+            } else if self.options.alloc_black_during_mark {
+                // Gray first, so a pointer to itself doesn't queue it
+                header.state.mark_white_to_gray();
+                let mut tracer = Tracer::new();
+                unsafe { self.trace_fields(header_ptr, &tracer) };
+                header.state.mark_black();
+                self.merge_work_unbounded(&mut tracer);
+            } else if header.state.mark_white_to_gray() {
+                self.gray_queue.lock().0.push(header);
+            }
+            self.decrement_busy_marking();
+        }
+    }
+
+    /// Push a new object to the allocation list of the current context
+    ///
+    /// Returns false if the thread has no context of this heap, or batching
+    /// is disabled.
+    #[cfg(feature = "std")]
+    fn link_thread_local(&self, header: *mut GcHeader) -> bool {
+        let batch = self.options.thread_alloc_batch;
+        if batch == 0 {
+            return false;
+        }
+        let mut linked = false;
+        crate::gc::with_current_context(|ctx| {
+            if core::ptr::eq(Arc::as_ptr(&ctx.heap), self) {
+                if ctx.alloc_list.push(header) >= batch {
+                    ctx.alloc_list.len.set(0);
+                    self.splice_thread_list(&ctx.alloc_list);
+                }
+                linked = true;
+            }
+        });
+        linked
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn link_thread_local(&self, _header: *mut GcHeader) -> bool {
+        false
+    }
 
+    /// Link the chain from `first` to `last` in front of the allocation list
+    ///
+    /// Like a single push, this only touches `head`, so it is safe while
+    /// sweeping (see `unlink_head`).
+    fn splice(&self, first: *mut GcHeader, last: *mut GcHeader) {
         loop {
             let current_head = self.head.load(Ordering::Acquire);
-            unsafe {
-                (*header_ptr).next.store(current_head, Ordering::Relaxed);
-            }
+            unsafe { (*last).next.store(current_head, Ordering::Relaxed) };
 
             if self
                 .head
-                .compare_exchange(
-                    current_head,
-                    header_ptr,
-                    Ordering::Release,
-                    Ordering::Acquire,
-                )
+                .compare_exchange(current_head, first, Ordering::Release, Ordering::Acquire)
                 .is_ok()
             {
                 break;
             }
         }
+    }
 
-        self.bytes_allocated.fetch_add(size, Ordering::Relaxed);
+    /// Move the objects of a thread list to the allocation list
+    fn splice_thread_list(&self, list: &ThreadList) {
+        if let Some((first, last)) = list.take() {
+            self.splice(first, last);
+        }
+    }
 
-        // Return as GcRoot (already rooted with root_count = 1)
-        unsafe { GcRoot::new_from_nonnull(ptr) }
+    /// Move the objects of all thread lists to the allocation list
+    ///
+    /// Called before walking the allocation list: objects still in a thread
+    /// list are neither swept nor counted.
+    fn splice_thread_lists(&self) {
+        let lists = self.thread_lists.lock();
+        for &list in &lists.0 {
+            self.splice_thread_list(unsafe { &*list });
+        }
+    }
+
+    /// Register the allocation list of a new `GcContext`
+    ///
+    /// # Safety
+    ///
+    /// `list` must stay valid until passed to `unregister_thread_list`.
+    #[cfg(feature = "std")]
+    pub(crate) unsafe fn register_thread_list(&self, list: &ThreadList) {
+        self.thread_lists.lock().0.push(list);
+    }
+
+    /// Splice and forget the allocation list of a dropped `GcContext`
+    #[cfg(feature = "std")]
+    pub(crate) fn unregister_thread_list(&self, list: &ThreadList) {
+        let mut lists = self.thread_lists.lock();
+        lists
+            .0
+            .retain(|&registered| !core::ptr::eq(registered, list));
+        self.splice_thread_list(list);
+    }
+
+    /// Register the barrier buffer of a new `GcContext`
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must stay valid until passed to `unregister_barrier_buffer`.
+    #[cfg(feature = "std")]
+    pub(crate) unsafe fn register_barrier_buffer(&self, buffer: &BarrierBuffer) {
+        self.barrier_buffers.lock().0.push(buffer);
+    }
+
+    /// Flush and forget the barrier buffer of a dropped `GcContext`
+    #[cfg(feature = "std")]
+    pub(crate) fn unregister_barrier_buffer(&self, buffer: &BarrierBuffer) {
+        // Flushed under the lock: `flush_barrier_buffers` either finds the
+        // buffer, or the objects in the gray queue
+        let mut buffers = self.barrier_buffers.lock();
+        buffers
+            .0
+            .retain(|&registered| !core::ptr::eq(registered, buffer));
+        self.flush_barrier_queue(&mut buffer.0.lock().0);
+    }
+
+    /// Move what a write barrier shaded into `tracer` to the context's buffer
+    ///
+    /// The buffer goes to the shared gray queue once it holds
+    /// `barrier_batch` objects; `finish_marking` takes the rest.
+    pub(crate) fn merge_barrier_work(&self, tracer: &mut Tracer, buffer: &BarrierBuffer) {
+        {
+            let mut queue = buffer.0.lock();
+            tracer.append_to(&mut queue.0);
+            if queue.0.len() >= self.options.barrier_batch {
+                self.flush_barrier_queue(&mut queue.0);
+            }
+        }
+        self.merge_stats(tracer);
+    }
+
+    fn flush_barrier_queue(&self, queue: &mut Vec<*const GcHeader>) {
+        if queue.is_empty() {
+            return;
+        }
+        self.barrier_flushes.fetch_add(1, Ordering::Relaxed);
+        let mut gray_queue = self.gray_queue.lock();
+        gray_queue.0.append(queue);
+        self.peak_gray_queue_len
+            .fetch_max(gray_queue.0.len(), Ordering::Relaxed);
+    }
+
+    /// Move the objects of all barrier buffers to the gray queue
+    ///
+    /// Returns true if the gray queue has work afterwards.
+    fn flush_barrier_buffers(&self) -> bool {
+        let buffers = self.barrier_buffers.lock();
+        for &buffer in &buffers.0 {
+            self.flush_barrier_queue(&mut unsafe { &*buffer }.0.lock().0);
+        }
+        !self.gray_queue.lock().0.is_empty()
+    }
+
+    #[inline]
+    fn exceeds_limit(&self, size: usize) -> bool {
+        self.bytes_allocated().saturating_add(size) > self.options.limit_bytes
+    }
+
+    /// Collect if an allocation of `size` bytes would exceed the soft limit
+    fn enforce_soft_limit(&self, size: usize) {
+        if self.over_soft_limit.load(Ordering::Relaxed)
+            || self.bytes_allocated().saturating_add(size) <= self.options.soft_limit_bytes
+            || self.in_sweep()
+            || self.holds_read_guard()
+        {
+            return;
+        }
+        let allocated = self.force_collect().bytes_after;
+        if allocated.saturating_add(size) > self.options.soft_limit_bytes {
+            // Live objects alone exceed it: don't collect on every allocation
+            self.over_soft_limit.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Apply the OOM handler or `on_limit_exceeded` if an allocation of
+    /// `size` bytes would exceed the limit
+    fn enforce_limit(&self, size: usize) -> Result<(), AllocError> {
+        // Collecting can't help during the sweep, and waiting would deadlock,
+        // as would a sweep waiting for a guard of this thread
+        if !self.exceeds_limit(size) || self.in_sweep() || self.holds_read_guard() {
+            return Ok(());
+        }
+        self.force_collect();
+        if !self.exceeds_limit(size) {
+            return Ok(());
+        }
+        let error = || AllocError {
+            requested_bytes: size,
+            allocated_bytes: self.bytes_allocated(),
+            limit_bytes: self.options.limit_bytes,
+        };
+        let abort_over_limit = || -> ! {
+            self.abort(format_args!(
+                "abfall: allocation of {size} bytes exceeds the heap limit of {} bytes, aborting",
+                self.options.limit_bytes
+            ))
+        };
+        // Not called with the lock held, the handler may replace itself
+        let handler = self.oom_handler.lock().clone();
+        if let Some(handler) = handler {
+            loop {
+                match handler(size) {
+                    OomAction::Retry => {
+                        self.force_collect();
+                        if !self.exceeds_limit(size) {
+                            return Ok(());
+                        }
+                    }
+                    OomAction::Fail => return Err(error()),
+                    OomAction::Abort => abort_over_limit(),
+                }
+            }
+        }
+        match self.options.on_limit_exceeded {
+            LimitAction::CollectThenContinue => Ok(()),
+            LimitAction::Error => Err(error()),
+            // There is no other thread that could release objects
+            LimitAction::Block if !crate::sync::THREADS => Err(error()),
+            LimitAction::Abort => abort_over_limit(),
+            LimitAction::Block => {
+                const POLL_INTERVAL: Duration = Duration::from_millis(10);
+                while self.exceeds_limit(size) {
+                    self.wait_for_collection(POLL_INTERVAL);
+                    self.force_collect();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait until a collection cycle finishes or the timeout expires
+    fn wait_for_collection(&self, timeout: Duration) {
+        let mut guard = self.collection_done_lock.lock();
+        self.collection_done.wait_for(&mut guard, timeout);
     }
 
     fn update_threshold(&self, live_bytes: usize) {
         let old_threshold = self.current_threshold.load(Ordering::Relaxed);
-        let new_threshold = self.options.calculate_threshold(old_threshold, live_bytes);
+        let min_threshold = self.min_threshold.load(Ordering::Relaxed);
+        let new_threshold =
+            self.options
+                .calculate_threshold(old_threshold, live_bytes, min_threshold);
         self.current_threshold
             .store(new_threshold, Ordering::Relaxed);
     }
@@ -338,312 +2155,3114 @@ impl Heap {
         allocated > threshold
     }
 
-    pub fn force_collect(&self) -> usize {
-        if !self.try_mark_full() {
-            // Already marking or sweeping
-            // TODO: wait and start new cycle?
-            return self.bytes_allocated();
-        }
+    /// Run a full collection cycle on the calling thread
+    ///
+    /// Returns what the cycle freed and what is left. If a cycle is already
+    /// in progress, returns immediately without collecting, with nothing
+    /// freed; the running cycle may keep garbage created after it started.
+    /// Use [`request_collection`](Self::request_collection) to wait for it
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// If the calling thread holds a [`ReadGuard`] of this heap: the sweep
+    /// would wait for it forever.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// let _keep = ctx.allocate(1u64);
+    /// drop(ctx.allocate(2u64));
+    /// let result = ctx.force_collect();
+    /// assert_eq!(result.freed_objects, 1);
+    /// assert_eq!(result.live_objects, 1);
+    /// # }
+    /// ```
+    pub fn force_collect(&self) -> CollectionResult {
+        self.assert_no_read_guard();
+        let started = clock_now();
+        if !self.try_mark_full() {
+            // Already marking or sweeping
+            return CollectionResult {
+                bytes_after: self.bytes_allocated(),
+                live_objects: self.live_objects.load(Ordering::Relaxed),
+                ..CollectionResult::default()
+            };
+        }
+
+        let mut result = self.sweep_and_finish();
+        if let Some(started) = started {
+            result.duration = started.elapsed();
+        }
+        result
+    }
+
+    /// Run a full collection with explicit roots, in addition to the root set
+    ///
+    /// Objects reachable from `roots` survive like objects reachable from a
+    /// [`GcRoot`], so a VM can keep its roots (e.g. a stack of `GcPtr`s) in
+    /// memory it controls instead of rooting each of them. The roots only
+    /// protect objects during this call: on a heap with background collection
+    /// other cycles don't know them. Returns the bytes allocated afterwards;
+    /// like `force_collect`, returns right away if a cycle is in progress.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcOptions, Heap};
+    ///
+    /// let heap = Heap::with_options(GcOptions::manual());
+    /// let stack = vec![heap.allocate(1).as_ptr(), heap.allocate(2).as_ptr()];
+    /// drop(heap.allocate(3));
+    /// heap.collect_with_roots(&[&stack]);
+    /// assert_eq!(heap.allocation_count(), 2);
+    /// ```
+    pub fn collect_with_roots(&self, roots: &[&dyn ErasedRoot]) -> usize {
+        let marked = self.try_mark_full_with(|tracer| {
+            for root in roots {
+                root.trace_root(tracer);
+            }
+        });
+        if !marked {
+            return self.bytes_allocated();
+        }
+        self.sweep_and_finish().bytes_after
+    }
+
+    /// Run a full collection, treating the words of a memory range as roots
+    ///
+    /// For embedders whose pointers live where no [`GcRoot`] can be kept,
+    /// like the stack of foreign code. Every pointer-aligned word between
+    /// `stack_bottom` and `stack_top` (in either order) that holds the
+    /// address of a live object, or an address inside of it, keeps the
+    /// object alive for this collection, like [`collect_with_roots`].
+    ///
+    /// The scan is conservative and best-effort: an integer that happens to
+    /// look like such an address keeps an object alive as well, while
+    /// pointers that are not aligned, or only held in registers, are missed.
+    /// Returns the bytes allocated afterwards, or right away if a cycle is in
+    /// progress.
+    ///
+    /// # Safety
+    ///
+    /// The whole range must be readable for the duration of the call.
+    ///
+    /// [`collect_with_roots`]: Self::collect_with_roots
+    pub unsafe fn collect_conservative(
+        &self,
+        stack_bottom: *const u8,
+        stack_top: *const u8,
+    ) -> usize {
+        let (start, end) = if stack_bottom <= stack_top {
+            (stack_bottom, stack_top)
+        } else {
+            (stack_top, stack_bottom)
+        };
+        let marked = self.try_mark_full_with(|tracer| {
+            // Objects allocated while marking are not swept by this cycle, so
+            // the allocations at its start are all that can be found
+            let mut objects: Vec<(usize, usize, &GcHeader)> = self
+                .iter_headers()
+                .map(|header| {
+                    let addr = header as *const GcHeader as usize;
+                    (addr, addr + header.vtable.layout.size(), header)
+                })
+                .collect();
+            objects.sort_unstable_by_key(|&(addr, _, _)| addr);
+
+            let mut offset = start.align_offset(align_of::<usize>());
+            while offset + size_of::<usize>() <= end as usize - start as usize {
+                let word = unsafe { start.add(offset).cast::<usize>().read() };
+                let index = objects.partition_point(|&(addr, _, _)| addr <= word);
+                if let Some(&(_, object_end, header)) = index.checked_sub(1).map(|i| &objects[i])
+                    && word < object_end
+                {
+                    tracer.mark_header(header);
+                }
+                offset += size_of::<usize>();
+            }
+        });
+        if !marked {
+            return self.bytes_allocated();
+        }
+        self.sweep_and_finish().bytes_after
+    }
+
+    /// Collect now, coalescing with a cycle already in progress
+    ///
+    /// If the heap is idle, runs a full cycle like `force_collect` and returns
+    /// `Some` with what it freed. Otherwise, with `wait` false, returns None
+    /// at once. With `wait` true, blocks until the running cycle (for example
+    /// one of the background thread) is finished, then runs a full cycle of
+    /// its own, so every object unreachable at the time of the call is freed
+    /// when this returns `Some`.
+    ///
+    /// Waiting for an incremental cycle (`begin_mark`, `collect_incremental`)
+    /// that the calling thread itself has to sweep never returns.
+    ///
+    /// # Panics
+    ///
+    /// If called with `wait` from a `Drop` or finalizer run by the sweep, or
+    /// while the calling thread holds a [`ReadGuard`] of this heap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::new();
+    /// drop(ctx.allocate(1));
+    /// assert!(ctx.request_collection(true).is_some());
+    /// assert_eq!(ctx.allocation_count(), 0);
+    /// # }
+    /// ```
+    pub fn request_collection(&self, wait: bool) -> Option<CollectionResult> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+        if wait && self.in_sweep() {
+            panic!("abfall: waiting for a collection from a `Drop` or finalizer run by the sweep");
+        }
+        self.assert_no_read_guard();
+        loop {
+            if self.try_mark_full() {
+                return Some(self.sweep_and_finish());
+            }
+            if !wait {
+                return None;
+            }
+            // The phase is checked under the lock `finish_gc` notifies with,
+            // so the wake-up cannot be missed
+            let mut guard = self.collection_done_lock.lock();
+            if self.phase.get() != GcPhase::Idle {
+                self.collection_done.wait_for(&mut guard, POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Run a full collection and return once its garbage is freed
+    ///
+    /// Waits for a cycle in progress (for example one of the background
+    /// thread) and then collects on the calling thread, so every object
+    /// unreachable at the time of the call is freed, and no longer counted by
+    /// [`allocation_count`](Self::allocation_count), when this returns.
+    /// Returns what it freed. This is `request_collection(true)`, with the
+    /// same caveat about incremental cycles of the calling thread.
+    ///
+    /// # Panics
+    ///
+    /// If called from a `Drop` or finalizer run by the sweep.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::new();
+    /// let keep = ctx.allocate(1);
+    /// for i in 0..100 {
+    ///     drop(ctx.allocate(i));
+    /// }
+    /// ctx.collect_and_wait();
+    /// assert_eq!(ctx.allocation_count(), 1);
+    /// # drop(keep);
+    /// # }
+    /// ```
+    pub fn collect_and_wait(&self) -> CollectionResult {
+        self.request_collection(true)
+            .expect("a waiting request always collects")
+    }
+
+    /// Collect only the young generation (`GcOptions::generational`)
+    ///
+    /// Marks from the young roots and the remembered set, without tracing
+    /// old objects, and frees unreachable young objects. Old garbage is only
+    /// freed by full collections (`force_collect`, and the background thread).
+    /// Without `generational`, this is a full collection.
+    ///
+    /// Returns the bytes allocated after the collection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcOptions};
+    ///
+    /// let ctx = GcContext::with_options(GcOptions {
+    ///     generational: true,
+    ///     ..GcOptions::OFF
+    /// });
+    /// let long_lived = ctx.allocate(1);
+    /// ctx.force_collect(); // promotes `long_lived`
+    /// drop(ctx.allocate(2));
+    /// ctx.collect_minor();
+    /// assert_eq!(ctx.allocation_count(), 1);
+    /// # drop(long_lived);
+    /// # }
+    /// ```
+    pub fn collect_minor(&self) -> usize {
+        if !self.options.generational {
+            return self.force_collect().bytes_after;
+        }
+        if !self.try_start_marking() {
+            return self.bytes_allocated();
+        }
+
+        let abort = AbortOnUnwind(self);
+        {
+            let mut tracer = Tracer::new_minor();
+            self.do_mark_young_roots(&mut tracer);
+            self.do_mark_work_full(&mut tracer);
+        }
+        core::mem::forget(abort);
+        self.do_sweep(true).bytes_after
+    }
+
+    /// Run `force_collect` if the allocation threshold is exceeded
+    ///
+    /// Returns None if no collection ran.
+    pub fn collect(&self) -> Option<CollectionResult> {
+        if self.should_collect() {
+            Some(self.force_collect())
+        } else {
+            None
+        }
+    }
+
+    /// Run one bounded slice of marking work
+    ///
+    /// Starts a cycle by scanning the roots if the heap is idle, otherwise
+    /// continues the mark phase in progress, scanning at most `work_budget`
+    /// objects. Returns true once marking is complete; then call
+    /// [`Heap::sweep`] to finish the cycle.
+    ///
+    /// Returns false without doing any work while another thread sweeps.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// drop(ctx.allocate(1));
+    /// while !ctx.collect_incremental(2) {
+    ///     // do other work between the slices
+    /// }
+    /// ctx.sweep();
+    /// assert_eq!(ctx.allocation_count(), 0);
+    /// # }
+    /// ```
+    pub fn collect_incremental(&self, work_budget: usize) -> bool {
+        if !self.begin_mark() && !self.is_marking() {
+            return false;
+        }
+        self.do_mark_work(work_budget)
+    }
+
+    /// Start a manual collection cycle: switch to marking and scan the roots
+    ///
+    /// Drive the cycle with [`Heap::do_mark_work`] and finish it with
+    /// [`Heap::sweep`]. Returns false (and does nothing) if a cycle is
+    /// already in progress.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// drop(ctx.allocate(1));
+    /// assert!(ctx.begin_mark());
+    /// while !ctx.do_mark_work(10) {}
+    /// assert!(ctx.sweep().is_some());
+    /// assert!(!ctx.is_marking());
+    /// # }
+    /// ```
+    pub fn begin_mark(&self) -> bool {
+        if !self.try_start_marking() {
+            return false;
+        }
+        self.do_mark_roots(&mut Tracer::new());
+        self.incremental_cycle.store(true, Ordering::Release);
+        true
+    }
+
+    /// Scan at most `work_budget` objects of the mark phase in progress
+    ///
+    /// Returns true once marking is complete, or if no cycle is marking.
+    pub fn do_mark_work(&self, work_budget: usize) -> bool {
+        if !self.is_marking() {
+            return true;
+        }
+        self.do_mark_incremental(work_budget) && !self.phase.is_busy()
+    }
+
+    /// Sweep a cycle started by [`Heap::begin_mark`] or [`Heap::collect_incremental`]
+    ///
+    /// Finishes any remaining marking work first, so it is correct (but not
+    /// incremental) to call this before `collect_incremental` returned true.
+    /// Returns what it freed, or None (doing nothing) if no such cycle is in
+    /// progress, for example while idle or because the background thread
+    /// started the current one; it sweeps its cycles itself.
+    pub fn sweep(&self) -> Option<CollectionResult> {
+        if self.incremental_cycle.swap(false, Ordering::AcqRel) {
+            Some(self.sweep_and_finish())
+        } else {
+            None
+        }
+    }
+
+    /// Like [`sweep`](Self::sweep), but sweep incrementally
+    ///
+    /// With [`GcOptions::incremental_sweep`], finishes marking and returns:
+    /// the heap stays in [`GcPhase::Sweeping`] while the garbage is freed by
+    /// [`do_sweep_work`](Self::do_sweep_work) and by allocations. Otherwise
+    /// sweeps completely, like `sweep`. Returns false (doing nothing) in the
+    /// same cases `sweep` returns None.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcOptions, GcPhase};
+    ///
+    /// let ctx = GcContext::with_options(GcOptions {
+    ///     incremental_sweep: true,
+    ///     ..GcOptions::manual()
+    /// });
+    /// for i in 0..100 {
+    ///     drop(ctx.allocate(i));
+    /// }
+    /// assert!(ctx.begin_mark());
+    /// assert!(ctx.begin_sweep());
+    /// assert_eq!(ctx.phase(), GcPhase::Sweeping);
+    /// while !ctx.do_sweep_work(10) {
+    ///     // do other work between the slices
+    /// }
+    /// assert_eq!(ctx.allocation_count(), 0);
+    /// # }
+    /// ```
+    pub fn begin_sweep(&self) -> bool {
+        if !self.incremental_cycle.swap(false, Ordering::AcqRel) {
+            return false;
+        }
+        if self.options.incremental_sweep {
+            self.begin_lazy_sweep();
+        } else {
+            self.sweep_and_finish();
+        }
+        true
+    }
+
+    /// Sweep at most `work_budget` objects of the incremental sweep in progress
+    ///
+    /// Returns true once no incremental sweep is in progress (anymore), see
+    /// [`begin_sweep`](Self::begin_sweep). Waits for all read guards to be
+    /// released, like a sweep.
+    pub fn do_sweep_work(&self, work_budget: usize) -> bool {
+        if !self.lazy_sweep_pending.load(Ordering::Acquire) {
+            return true;
+        }
+        if self.in_sweep() {
+            // From a `Drop` run by the sweep
+            return false;
+        }
+        let finished = {
+            let _sweep_guard = self.sweep_lock.write();
+            self.sweep_step(work_budget)
+        };
+        match finished {
+            Some(cursor) => {
+                self.finish_sweep(cursor);
+                true
+            }
+            None => !self.lazy_sweep_pending.load(Ordering::Acquire),
+        }
+    }
+
+    /// `do_sweep_work`, unless a read guard or another sweeper is in the way
+    fn try_sweep_work(&self, work_budget: usize) {
+        if self.in_sweep() {
+            return;
+        }
+        let Some(sweep_guard) = self.sweep_lock.try_write() else {
+            return;
+        };
+        let finished = self.sweep_step(work_budget);
+        drop(sweep_guard);
+        if let Some(cursor) = finished {
+            self.finish_sweep(cursor);
+        }
+    }
+
+    /// The current phase of the collector
+    ///
+    /// Another thread (like the background thread) may change it at any
+    /// time, so this is a snapshot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcOptions, GcPhase, Heap};
+    ///
+    /// let heap = Heap::with_options(GcOptions::manual());
+    /// assert_eq!(heap.phase(), GcPhase::Idle);
+    /// assert!(heap.begin_mark());
+    /// assert_eq!(heap.phase(), GcPhase::Marking);
+    /// heap.sweep();
+    /// assert_eq!(heap.phase(), GcPhase::Idle);
+    /// ```
+    #[inline]
+    pub fn phase(&self) -> GcPhase {
+        self.phase.get()
+    }
+
+    /// Check if GC is currently in marking phase
+    pub fn is_marking(&self) -> bool {
+        self.phase() == GcPhase::Marking
+    }
+
+    /// Enter a write barrier (or allocation) that shades while marking
+    ///
+    /// Returns true if marking is in progress; then the caller must call
+    /// [`decrement_busy_marking`](Self::decrement_busy_marking) once done
+    /// shading. The sweeper waits for all such callers after leaving the
+    /// marking phase, and traces what they shaded before sweeping.
+    pub fn check_is_marking_and_increment_busy(&self) -> bool {
+        self.phase.enter_busy()
+    }
+
+    pub fn decrement_busy_marking(&self) {
+        self.phase.leave_busy();
+    }
+
+    /// Try to transition to marking phase
+    fn try_start_marking(&self) -> bool {
+        let started = self.phase.try_start(GcPhase::Marking);
+        if started {
+            self.mark_cycle.store(
+                MARK_CYCLES.fetch_add(1, Ordering::Relaxed) + 1,
+                Ordering::Release,
+            );
+            // Baseline of the assist pacing
+            self.mark_start_bytes
+                .store(self.bytes_allocated(), Ordering::Relaxed);
+            if self.options.collect_metrics {
+                self.metrics.lock().mark_started = clock_now();
+            }
+            self.hooks.mark_start(self.bytes_allocated());
+        }
+        started
+    }
+
+    /// Transition to sweeping phase
+    ///
+    /// Write barriers and allocations that saw the marking phase may still be
+    /// shading objects: waits for them, and traces what they shaded, so no
+    /// gray object is left when the sweep starts.
+    fn start_sweeping(&self, tracer: &mut Tracer) {
+        self.phase.start_sweeping();
+        self.finish_marking(tracer);
+        if self.options.collect_metrics {
+            let mut metrics = self.metrics.lock();
+            if let Some(started) = metrics.mark_started.take() {
+                metrics.timings.mark = started.elapsed();
+            }
+            metrics.sweep_started = clock_now();
+        }
+        self.hooks.mark_end();
+    }
+
+    /// Start timing a root scan, if metrics are collected
+    #[inline]
+    fn root_scan_started(&self) -> Option<Instant> {
+        if self.options.collect_metrics {
+            clock_now()
+        } else {
+            None
+        }
+    }
+
+    /// Record the duration of a root scan, a pause for root changes
+    fn record_root_scan(&self, started: Option<Instant>) {
+        if let Some(started) = started {
+            let elapsed = started.elapsed();
+            let mut metrics = self.metrics.lock();
+            metrics.timings.root_scan = elapsed;
+            metrics.record_pause(elapsed);
+        }
+    }
+
+    /// Transition back to idle phase
+    fn finish_gc(&self) {
+        self.phase.finish();
+        let _guard = self.collection_done_lock.lock();
+        self.collection_done.notify_all();
+    }
+
+    pub(crate) fn try_mark_full(&self) -> bool {
+        self.try_mark_full_with(|_| {})
+    }
+
+    /// `try_mark_full`, with `extra_roots` marking objects along with the root set
+    fn try_mark_full_with(&self, extra_roots: impl FnOnce(&Tracer)) -> bool {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("gc_mark").entered();
+        // An incremental sweep in progress is completed first
+        self.do_sweep_work(usize::MAX);
+        if !self.try_start_marking() {
+            return false;
+        }
+
+        let abort = AbortOnUnwind(self);
+        {
+            let mut tracer = Tracer::new();
+
+            // STW pause: scan roots
+            self.do_mark_roots(&mut tracer);
+            extra_roots(&tracer);
+            self.merge_work(&mut tracer);
+
+            // Concurrent marking
+            self.do_mark_work_full(&mut tracer);
+        }
+        core::mem::forget(abort);
+        true
+    }
+
+    pub(crate) fn sweep_and_finish(&self) -> CollectionResult {
+        // `do_sweep` returns to idle itself: finishing again here could end
+        // a cycle another thread started in the meantime
+        self.do_sweep(false)
+    }
+
+    /// Steal work from the shared gray queue into a tracer
+    ///
+    /// Returns true if work was stolen, false if queue is empty
+    fn steal_work(&self, tracer: &mut Tracer, max_items: usize) -> bool {
+        let mut gray_queue = self.gray_queue.lock();
+        tracer.steal_from(max_items, &mut gray_queue.0)
+    }
+
+    /// Merge tracer's local work back to the shared gray queue
+    ///
+    /// Applies back-pressure: work that doesn't fit below
+    /// `gray_queue_soft_cap` is marked inline by the calling thread. Returns
+    /// the number of objects marked inline. Only for marking threads, the
+    /// mutator uses `merge_work_unbounded`.
+    pub(crate) fn merge_work(&self, tracer: &mut Tracer) -> usize {
+        /// Objects marked inline before trying to merge again
+        const INLINE_BATCH: usize = 64;
+        let cap = self.options.gray_queue_soft_cap;
+        let mut marked = 0;
+        loop {
+            {
+                let mut gray_queue = self.gray_queue.lock();
+                let room = cap.saturating_sub(gray_queue.0.len());
+                tracer.move_to(room, &mut gray_queue.0);
+                self.peak_gray_queue_len
+                    .fetch_max(gray_queue.0.len(), Ordering::Relaxed);
+            }
+            if !tracer.has_work() {
+                break;
+            }
+            for _ in 0..INLINE_BATCH {
+                let Some(ptr) = tracer.pop_work() else { break };
+                unsafe { self.trace_gray(ptr, tracer) };
+                marked += 1;
+            }
+        }
+        self.merge_stats(tracer);
+        self.hooks.count_marked(marked);
+        marked
+    }
+
+    /// Merge tracer's local work back to the shared gray queue, ignoring the cap
+    ///
+    /// Used by write barriers and dropped contexts, which must not trace
+    /// other objects.
+    pub(crate) fn merge_work_unbounded(&self, tracer: &mut Tracer) {
+        {
+            let mut gray_queue = self.gray_queue.lock();
+            tracer.append_to(&mut gray_queue.0);
+            self.peak_gray_queue_len
+                .fetch_max(gray_queue.0.len(), Ordering::Relaxed);
+        }
+        self.merge_stats(tracer);
+    }
+
+    /// Merge tracer's statistics into the heap counters
+    fn merge_stats(&self, tracer: &mut Tracer) {
+        let redundant = tracer.take_redundant_marks();
+        if redundant > 0 {
+            self.redundant_marks.fetch_add(redundant, Ordering::Relaxed);
+        }
+        if tracer.take_incomplete() {
+            self.mark_incomplete.store(true, Ordering::Relaxed);
+        }
+        let ephemerons = tracer.take_ephemerons();
+        if !ephemerons.is_empty() {
+            self.ephemerons.lock().0.extend(ephemerons);
+        }
+    }
+
+    /// Trace a gray object and blacken it
+    ///
+    /// If its `Trace` impl panics, the object stays gray with its children
+    /// possibly unshaded, so the cycle must not free anything anymore: it is
+    /// marked incomplete (see `mark_incomplete`), and the error is recorded
+    /// for `last_error`. The thread that started the cycle abandons it while
+    /// unwinding, see `AbortOnUnwind`.
+    #[inline]
+    unsafe fn trace_gray(&self, ptr: *const GcHeader, tracer: &Tracer) {
+        unsafe {
+            self.trace_fields(ptr, tracer);
+            (*ptr).state.mark_black();
+        }
+    }
+
+    /// Trace the fields of an object, as their owner (see `Tracer::owner`)
+    ///
+    /// On a generational heap, the cells of an object that is old after
+    /// this cycle remember the young objects stored into them from now on.
+    /// The young objects it points to already are remembered here, if a
+    /// minor collection promotes it.
+    unsafe fn trace_fields(&self, ptr: *const GcHeader, tracer: &Tracer) {
+        let panicked = TracePanicked(self);
+        let header = unsafe { &*ptr };
+        let old = self.options.generational
+            && (!tracer.is_minor() || header.promoted_by_survive(self.options.promote_after));
+        tracer.set_owner(ptr, self.mark_cycle(), old);
+        unsafe { (header.vtable.trace)(ptr, tracer) };
+        tracer.set_owner(core::ptr::null(), 0, false);
+        if old && tracer.is_minor() {
+            unsafe { remember_children(ptr) };
+        }
+        core::mem::forget(panicked);
+    }
+
+    /// Process marking work using a tracer
+    ///
+    /// Steals work, processes it locally, then merges new work back
+    fn do_mark_with_tracer(&self, tracer: &mut Tracer, work_budget: usize) -> usize {
+        let started = self.options.auto_tune.then(clock_now).flatten();
+        let mut work_done = 0;
+
+        while work_done < work_budget {
+            // Try to get work from tracer's local queue first
+            let ptr = if let Some(p) = tracer.pop_work() {
+                p
+            } else {
+                // Local queue empty, try to steal from shared queue
+                const BATCH_SIZE: usize = 8;
+                if !self.steal_work(tracer, BATCH_SIZE) {
+                    // No work available anywhere
+                    break;
+                }
+                continue;
+            };
+
+            // Process one object
+            unsafe { self.trace_gray(ptr, tracer) };
+
+            work_done += 1;
+        }
+
+        // Merge any newly discovered work back to shared queue
+        let marked_inline = if tracer.has_work() {
+            self.merge_work(tracer)
+        } else {
+            self.merge_stats(tracer);
+            0
+        };
+
+        if let Some(started) = started {
+            self.record_mark_cost(started.elapsed(), work_done + marked_inline);
+        }
+        self.hooks.count_marked(work_done);
+
+        work_done + marked_inline
+    }
+
+    /// Update the average marking cost per object (`auto_tune`)
+    fn record_mark_cost(&self, elapsed: Duration, work_done: usize) {
+        if work_done == 0 {
+            return;
+        }
+        let cost = (elapsed.as_nanos() / work_done as u128).max(1) as u64;
+        let old = self.mark_unit_cost_ns.load(Ordering::Relaxed);
+        // EWMA with a weight of 1/8 for the new sample
+        let new = if old == 0 { cost } else { (old * 7 + cost) / 8 };
+        self.mark_unit_cost_ns.store(new.max(1), Ordering::Relaxed);
+    }
+
+    /// Work budget of a background (or forced) marking step
+    pub(crate) fn incremental_work_budget(&self) -> usize {
+        let configured = self.options.incremental_work_budget;
+        let cost = self.mark_unit_cost_ns.load(Ordering::Relaxed);
+        if !self.options.auto_tune || cost == 0 {
+            return configured;
+        }
+        let target = self.options.auto_tune_step.as_nanos() as u64;
+        (target / cost).max(1) as usize
+    }
+
+    /// Work budget of a mutator assist step without pacing
+    fn fixed_assist_work_budget(&self) -> usize {
+        let configured = self.options.assist_work_budget;
+        let cost = self.mark_unit_cost_ns.load(Ordering::Relaxed);
+        if !self.options.auto_tune || cost == 0 || self.options.incremental_work_budget == 0 {
+            return configured;
+        }
+        let budget = self.incremental_work_budget() as u128 * configured as u128
+            / self.options.incremental_work_budget as u128;
+        budget.clamp(1, usize::MAX as u128) as usize
+    }
+
+    /// Work budget of a mutator assist step, including the pacing debt
+    fn assist_work_budget(&self) -> usize {
+        let budget = self.fixed_assist_work_budget();
+        if self.options.assist_ratio == 0 {
+            return budget;
+        }
+        budget.max(self.mark_debt())
+    }
+
+    /// Objects marking is behind the plan of `assist_ratio`
+    ///
+    /// The plan scans all objects (the ones live at the start of the phase
+    /// and the ones allocated since) by the end of the runway, so once the
+    /// mutator reached it every assist drains the remaining work.
+    fn mark_debt(&self) -> usize {
+        let start_bytes = self.mark_start_bytes.load(Ordering::Relaxed) as u128;
+        let objects = self.live_objects.load(Ordering::Relaxed) as u128;
+        let allocated = (self.bytes_allocated() as u128).saturating_sub(start_bytes);
+        let runway = (start_bytes * self.options.assist_ratio as u128 / 100).max(1);
+        let planned = objects * allocated.min(runway) / runway;
+        (planned as usize).saturating_sub(self.hooks.marked())
+    }
+
+    /// Perform a bounded amount of incremental marking work
+    ///
+    /// Returns true if marking is complete, false if more work remains
+    fn do_mark_incremental(&self, work_budget: usize) -> bool {
+        let mut tracer = Tracer::new();
+        let work_done = self.do_mark_with_tracer(&mut tracer, work_budget);
+
+        // If we did no work, marking is complete
+        work_done == 0
+    }
+
+    fn yield_once_if_marking_busy(&self) -> bool {
+        if self.phase.is_busy() {
+            crate::sync::yield_now();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn do_mark_work_full(&self, tracer: &mut Tracer) {
+        #[cfg(feature = "std")]
+        if self.options.mark_threads > 1 && crate::sync::THREADS {
+            self.do_mark_parallel(tracer);
+        }
+        loop {
+            // Process until all work is complete
+            while self.do_mark_with_tracer(tracer, self.incremental_work_budget()) > 0
+                || self.yield_once_if_marking_busy()
+            {
+                // Keep going until no more work
+            }
+            // Ephemeron values whose keys were marked meanwhile
+            if !self.rescan_ephemerons(tracer) {
+                break;
+            }
+        }
+    }
+
+    /// Mark with `mark_threads` threads until the gray queue is drained
+    #[cfg(feature = "std")]
+    fn do_mark_parallel(&self, tracer: &mut Tracer) {
+        let markers = MarkWorkers::new(self.options.mark_threads);
+        std::thread::scope(|scope| {
+            for _ in 1..self.options.mark_threads {
+                scope.spawn(|| self.mark_worker(&mut Tracer::new(), &markers));
+            }
+            self.mark_worker(tracer, &markers);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    fn mark_worker(&self, tracer: &mut Tracer, markers: &MarkWorkers) {
+        loop {
+            while self.do_mark_with_tracer(tracer, self.incremental_work_budget()) > 0 {
+                markers.wake_idle();
+            }
+            let mut active = markers.active.lock();
+            *active -= 1;
+            markers.idle.fetch_add(1, Ordering::SeqCst);
+            loop {
+                if !self.gray_queue.lock().0.is_empty() {
+                    *active += 1;
+                    break;
+                }
+                if *active == 0 {
+                    markers.wake.notify_all();
+                    return;
+                }
+                markers.wake.wait(&mut active);
+            }
+            markers.idle.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Write barrier used by `GcCell`
+    #[inline]
+    pub(crate) fn barrier_kind(&self) -> BarrierKind {
+        self.options.barrier
+    }
+
+    #[inline]
+    pub(crate) fn count_barrier_op(&self) {
+        self.barrier_ops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Id of the current mark cycle, unique across heaps
+    ///
+    /// Cells compare it to the cycle in which their owner traced them.
+    #[inline]
+    pub(crate) fn mark_cycle(&self) -> usize {
+        self.mark_cycle.load(Ordering::Acquire)
+    }
+
+    /// Remember an object whose cell was mutated during marking
+    ///
+    /// The object was traced in the current cycle, so it stays alive until
+    /// the rescan at the end of marking.
+    pub(crate) fn record_dirty(&self, owner: &GcHeader) {
+        self.count_barrier_op();
+        self.dirty_objects.lock().0.push(owner);
+    }
+
+    /// Trace the objects with dirty cells again
+    ///
+    /// Returns true if any object was rescanned
+    fn rescan_dirty(&self, tracer: &mut Tracer) -> bool {
+        let dirty = core::mem::take(&mut self.dirty_objects.lock().0);
+        if dirty.is_empty() {
+            return false;
+        }
+        for object in dirty {
+            unsafe { self.trace_fields(object, tracer) };
+        }
+        self.merge_work(tracer);
+        true
+    }
+
+    /// Retrace the objects whose ephemeron tables had unmarked keys
+    ///
+    /// Traces the values of entries whose keys were marked since; tables
+    /// that still have unmarked keys defer their objects again. Returns true
+    /// if that shaded any object, then marking has to continue, since more
+    /// keys might become reachable.
+    ///
+    /// The objects were marked in this cycle, so they stay allocated at
+    /// least until the queue is cleared, whatever happens to their tables.
+    fn rescan_ephemerons(&self, tracer: &mut Tracer) -> bool {
+        let mut objects = core::mem::take(&mut self.ephemerons.lock().0);
+        if objects.is_empty() {
+            return false;
+        }
+        // An object is deferred once per table and trace
+        objects.sort_unstable();
+        objects.dedup();
+        let shaded = tracer.shaded();
+        for object in objects {
+            unsafe { self.trace_fields(object, tracer) };
+        }
+        self.merge_work(tracer);
+        tracer.shaded() != shaded
+    }
+
+    /// Drop the deferred ephemeron tables once marking is complete
+    ///
+    /// Their remaining entries have unreachable keys, which are swept.
+    fn clear_ephemerons(&self) {
+        self.ephemerons.lock().0.clear();
+    }
+
+    /// Complete marking, including the rescan of dirty cells
+    fn finish_marking(&self, tracer: &mut Tracer) {
+        loop {
+            self.do_mark_work_full(tracer);
+            if !self.flush_barrier_buffers() && !self.rescan_dirty(tracer) {
+                break;
+            }
+        }
+    }
+
+    /// Add the object to the root set or remove it, according to its root count
+    ///
+    /// Called after the root count changed from or to zero. Concurrent
+    /// transitions of the same object are resolved by re-checking the root
+    /// count under the lock.
+    pub(crate) fn sync_root(&self, header: &GcHeader) {
+        Self::sync_root_locked(&mut self.roots.lock(), header);
+    }
+
+    /// `sync_root` with the root set locked by the caller
+    fn sync_root_locked(roots: &mut RootSet, header: &GcHeader) {
+        let index = header.root_index.load(Ordering::Relaxed);
+        if header.is_root() {
+            if index == NO_ROOT_INDEX {
+                header.root_index.store(roots.0.len(), Ordering::Relaxed);
+                roots.0.push(header);
+            }
+        } else if index != NO_ROOT_INDEX {
+            roots.0.swap_remove(index);
+            if let Some(&moved) = roots.0.get(index) {
+                unsafe { (*moved).root_index.store(index, Ordering::Relaxed) };
+            }
+            header.root_index.store(NO_ROOT_INDEX, Ordering::Relaxed);
+        }
+    }
+
+    /// Remember a young object stored into a cell (generational mode)
+    pub(crate) fn remember(&self, header: &GcHeader) {
+        if !self.options.generational
+            || header.is_old()
+            || header.remembered.swap(true, Ordering::Relaxed)
+        {
+            return;
+        }
+        self.remembered.lock().0.push(header);
+    }
+
+    /// Shade the young roots and the remembered set for a minor collection
+    fn do_mark_young_roots(&self, tracer: &mut Tracer) {
+        let started = self.root_scan_started();
+        {
+            let roots = self.roots.lock();
+            for &header in &roots.0 {
+                tracer.mark_header(unsafe { &*header });
+            }
+        }
+        self.mark_pinned(tracer);
+        {
+            let remembered = self.remembered.lock();
+            for &header in &remembered.0 {
+                tracer.mark_header(unsafe { &*header });
+            }
+        }
+        self.record_root_scan(started);
+        self.merge_work(tracer);
+    }
+
+    /// Shade all roots, returns the number of roots
+    fn do_mark_roots(&self, tracer: &mut Tracer) -> usize {
+        let started = self.root_scan_started();
+        let count = {
+            let roots = self.roots.lock();
+            for &header in &roots.0 {
+                tracer.mark_header(unsafe { &*header });
+            }
+            roots.0.len()
+        } + self.mark_pinned(tracer);
+        self.record_root_scan(started);
+
+        // Merge roots into shared gray queue
+        self.merge_work(tracer);
+        count
+    }
+
+    /// Shade the pinned objects, returns their number
+    fn mark_pinned(&self, tracer: &Tracer) -> usize {
+        let pinned = self.pinned.lock();
+        for &header in &pinned.0 {
+            tracer.mark_header(unsafe { &*header });
+        }
+        pinned.0.len()
+    }
+
+    /// Sweep all white objects, or only the young ones if `minor`
+    fn do_sweep(&self, minor: bool) -> CollectionResult {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("gc_sweep", minor).entered();
+        // Released after the sweep lock when unwinding
+        let abort = AbortOnUnwind(self);
+        // Wait for all read guards to be released
+        let sweep_guard = self.sweep_lock.write();
+        let sweeping_here = SweepingThread::enter(self);
+        let pause_started = clock_now();
+        let mut cursor = self.start_sweep(minor, false);
+        core::mem::forget(abort);
+        unsafe {
+            self.sweep_objects(&mut cursor, usize::MAX);
+            self.complete_sweep(&mut cursor);
+        }
+        drop(sweeping_here);
+        drop(sweep_guard);
+        self.record_sweep_pause(&mut cursor, pause_started);
+        self.finish_sweep(cursor)
+    }
+
+    /// Start an incremental sweep, see `begin_sweep`
+    ///
+    /// Only takes the snapshot of the list; the objects are swept by
+    /// `sweep_step`. Until it is done, weak references can't be upgraded to
+    /// the unswept garbage, see `is_condemned`.
+    fn begin_lazy_sweep(&self) {
+        let abort = AbortOnUnwind(self);
+        let sweep_guard = self.sweep_lock.write();
+        let sweeping_here = SweepingThread::enter(self);
+        let pause_started = clock_now();
+        let mut cursor = self.start_sweep(false, true);
+        core::mem::forget(abort);
+        if cursor.keep_all {
+            // Nothing is freed, the survivors are only reset
+            unsafe { self.sweep_objects(&mut cursor, usize::MAX) };
+        }
+        if cursor.current.is_null() {
+            unsafe { self.complete_sweep(&mut cursor) };
+            drop(sweeping_here);
+            drop(sweep_guard);
+            self.record_sweep_pause(&mut cursor, pause_started);
+            self.finish_sweep(cursor);
+            return;
+        }
+        self.record_sweep_pause(&mut cursor, pause_started);
+        *self.lazy_sweep.lock() = Some(cursor);
+        self.lazy_sweep_pending.store(true, Ordering::Release);
+    }
+
+    /// Sweep at most `work_budget` objects of the incremental sweep
+    ///
+    /// Must be called with the sweep lock held exclusively. Returns the
+    /// cursor for `finish_sweep` if the sweep was completed.
+    fn sweep_step(&self, work_budget: usize) -> Option<SweepCursor> {
+        let sweeping_here = SweepingThread::enter(self);
+        let pause_started = clock_now();
+        let mut lazy_sweep = self.lazy_sweep.lock();
+        // Another thread might have completed it meanwhile
+        let cursor = lazy_sweep.as_mut()?;
+        let done = unsafe { self.sweep_objects(cursor, work_budget) };
+        if !done {
+            self.record_sweep_pause(cursor, pause_started);
+            return None;
+        }
+        let mut cursor = lazy_sweep.take()?;
+        drop(lazy_sweep);
+        self.lazy_sweep_pending.store(false, Ordering::Release);
+        unsafe { self.complete_sweep(&mut cursor) };
+        drop(sweeping_here);
+        self.record_sweep_pause(&mut cursor, pause_started);
+        Some(cursor)
+    }
+
+    /// Take the snapshot of the list to sweep, and leave the marking phase
+    ///
+    /// Must be called with the sweep lock held exclusively.
+    fn start_sweep(&self, minor: bool, lazy: bool) -> SweepCursor {
+        // Pick up work shaded by write barriers while we were waiting,
+        // and rescan cells recorded by the incremental-update barrier
+        let mut tracer = Tracer::new();
+        self.finish_marking(&mut tracer);
+        if self.options.generational && !minor {
+            // All survivors are promoted, so no young object is left to remember.
+            // Cleared before the list is read: objects stored into cells from
+            // now on were linked before, or are allocated after the sweep started
+            self.forget_remembered(|_| true);
+        }
+        let epoch = if lazy {
+            // Objects linked after the snapshot see the new epoch, see
+            // `link_allocation`
+            let epoch = self
+                .sweep_epoch
+                .fetch_add(1, Ordering::SeqCst)
+                .wrapping_add(1);
+            core::sync::atomic::fence(Ordering::SeqCst);
+            epoch
+        } else {
+            self.sweep_epoch.load(Ordering::Relaxed)
+        };
+        // Objects linked before this snapshot were allocated while marking
+        // (and shaded), or before the cycle. Later ones are not swept, since
+        // allocations after the phase change below are not shaded. Under the
+        // root set lock, so the objects the root scan shaded are all linked.
+        let head = {
+            let _roots = self.roots.lock();
+            self.splice_thread_lists();
+            self.head.load(Ordering::Acquire)
+        };
+        self.start_sweeping(&mut tracer);
+        self.whiten_unswept(head);
+        #[cfg(all(debug_assertions, feature = "std"))]
+        if self.options.verify_marking && !minor && !self.mark_incomplete.load(Ordering::Relaxed) {
+            self.verify_marking(head);
+        }
+        self.clear_ephemerons();
+        self.hooks.sweep_start();
+
+        SweepCursor {
+            prev_next: &self.head,
+            current: head,
+            minor,
+            keep_all: self.mark_incomplete.swap(false, Ordering::Relaxed),
+            defer_free: self.has_finalizers.load(Ordering::Relaxed),
+            unlinked: null_mut(),
+            epoch,
+            freed: 0,
+            freed_objects: 0,
+            live: 0,
+            live_objects: 0,
+            longest_pause: Duration::ZERO,
+        }
+    }
+
+    /// Panic if a black object of the sweep's snapshot points to a white one
+    ///
+    /// See `GcOptions::verify_marking`. Objects outside of the snapshot are
+    /// not swept, so pointers to them are fine.
+    #[cfg(all(debug_assertions, feature = "std"))]
+    fn verify_marking(&self, snapshot: *mut GcHeader) {
+        let mut objects = std::collections::HashSet::new();
+        let mut current = snapshot;
+        while let Some(header) = unsafe { current.as_ref() } {
+            objects.insert(current as *const GcHeader);
+            current = header.next.load(Ordering::Acquire);
+        }
+        let mut tracer = Tracer::new_check();
+        for &object in &objects {
+            let header = unsafe { &*object };
+            if header.state.get() != Color::Black {
+                continue;
+            }
+            // As the owner, so ephemeron tables don't trace unmarked keys' values
+            tracer.set_owner(object, 0, false);
+            unsafe { (header.vtable.trace)(object, &tracer) };
+            tracer.set_owner(core::ptr::null(), 0, false);
+            tracer.take_ephemerons();
+            while let Some(target) = tracer.pop_work() {
+                let target_header = unsafe { &*target };
+                if objects.contains(&target) && target_header.is_white() {
+                    panic!(
+                        "abfall: the object at {object:p} ({}) points to the unmarked object at \
+                         {target:p} ({}): a pointer was stored without a write barrier, or the \
+                         `Trace` impl doesn't report it consistently",
+                        (header.vtable.type_name)(),
+                        (target_header.vtable.type_name)(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reset the colors of the objects linked in front of the sweep's snapshot
+    ///
+    /// An allocation that saw the marking phase shades its object, but may
+    /// link it only after `snapshot` was taken. The sweep doesn't visit such
+    /// objects, and they would start the next cycle black, without their
+    /// children being traced. Called once `start_sweeping` waited for those
+    /// allocations; the objects in front of the snapshot are not swept, so
+    /// nobody frees them meanwhile.
+    fn whiten_unswept(&self, snapshot: *mut GcHeader) {
+        // Objects of the thread lists were linked after the snapshot as well
+        self.splice_thread_lists();
+        let mut current = self.head.load(Ordering::Acquire);
+        while current != snapshot {
+            let header = unsafe { &*current };
+            header.state.reset_white();
+            current = header.next.load(Ordering::Acquire);
+        }
+    }
+
+    /// Sweep at most `work_budget` objects from the cursor
+    ///
+    /// Returns true once the end of the list is reached.
+    ///
+    /// # Safety
+    ///
+    /// Must be called with the sweep lock held exclusively, with a cursor of
+    /// the current cycle.
+    unsafe fn sweep_objects(&self, cursor: &mut SweepCursor, work_budget: usize) -> bool {
+        let generational = self.options.generational;
+        let mut freed = 0;
+        let mut freed_objects = 0;
+        // Handed to the free list after the walk: dropping objects may allocate
+        let recycle = self.options.free_list_cap_bytes > 0;
+        let mut recycled = Vec::new();
+
+        unsafe {
+            let mut budget = work_budget;
+            while !cursor.current.is_null() && budget > 0 {
+                budget -= 1;
+                let current = cursor.current;
+                let header = &*current;
+                let next = header.next.load(Ordering::Acquire);
+                // Advanced before any `Drop` runs, which might panic
+                cursor.current = next;
+
+                // Check if object should be collected
+                if cursor.minor && header.is_old() {
+                    header.state.reset_white();
+                    cursor.live += header.vtable.layout.size();
+                    cursor.live_objects += 1;
+                    cursor.prev_next = &header.next;
+                } else if header.is_white() && !cursor.keep_all {
+                    // Remove from list by updating previous node's next pointer
+                    if core::ptr::eq(cursor.prev_next, &self.head) {
+                        cursor.prev_next = self.unlink_head(current, next);
+                    } else {
+                        (*cursor.prev_next).store(next, Ordering::Release);
+                    }
+
+                    // Get size from vtable and call drop function
+                    let layout = header.vtable.layout;
+                    let size = layout.size();
+                    freed += size;
+                    freed_objects += 1;
+                    if cursor.defer_free {
+                        // The `next` link is free now, reuse it for the unlinked list
+                        header.next.store(cursor.unlinked, Ordering::Relaxed);
+                        cursor.unlinked = current;
+                    } else {
+                        // Proper Drop via Box::from_raw, or in place if recycled
+                        if let Some(slot) = GcHeader::free(current, recycle) {
+                            recycled.push((slot, layout));
+                        }
+                    }
+                } else {
+                    let traced = header.state.get() == Color::Black;
+                    // Reset color for next cycle
+                    header.state.reset_white();
+                    header.sweep_epoch.store(cursor.epoch, Ordering::Relaxed);
+                    cursor.live += header.vtable.layout.size();
+                    cursor.live_objects += 1;
+                    if generational {
+                        let was_old = header.is_old();
+                        if cursor.minor {
+                            // Marking might have missed stores into old
+                            // cells, so nothing is promoted
+                            if !cursor.keep_all {
+                                header.survive(self.options.promote_after);
+                            }
+                        } else {
+                            header.age.store(OLD_AGE, Ordering::Relaxed);
+                        }
+                        // Kept by an incomplete cycle without being traced:
+                        // its cells don't know yet that it is old
+                        if !traced && !was_old && header.is_old() {
+                            remember_children(current);
+                        }
+                    }
+                    cursor.prev_next = &header.next;
+                }
+            }
+        }
+        if !recycled.is_empty() {
+            self.recycle(recycled);
+        }
+        cursor.freed += freed;
+        cursor.freed_objects += freed_objects;
+        self.bytes_allocated.fetch_sub(freed, Ordering::Relaxed);
+        self.live_objects
+            .fetch_sub(freed_objects, Ordering::Relaxed);
+        cursor.current.is_null()
+    }
+
+    /// Run the finalizers of the unlinked garbage and free it
+    ///
+    /// # Safety
+    ///
+    /// Must be called with the sweep lock held exclusively, once the cursor
+    /// reached the end of the list.
+    unsafe fn complete_sweep(&self, cursor: &mut SweepCursor) {
+        if !cursor.unlinked.is_null() {
+            let recycle = self.options.free_list_cap_bytes > 0;
+            let mut recycled = Vec::new();
+            let unlinked = core::mem::replace(&mut cursor.unlinked, null_mut());
+            unsafe { self.finalize_and_free(unlinked, recycle, &mut recycled) };
+            if !recycled.is_empty() {
+                self.recycle(recycled);
+            }
+        }
+        if cursor.minor {
+            self.forget_remembered(GcHeader::is_old);
+        }
+        self.reap_tombstones();
+    }
+
+    /// Keep a swept object with `GcWeak`s until they are dropped
+    pub(crate) fn add_tombstone(&self, header: *mut GcHeader) {
+        self.tombstones.lock().0.push(header);
+    }
+
+    /// Free the tombstones whose `GcWeak`s were all dropped
+    ///
+    /// The heap holds a weak count of each tombstone, so they stay attached
+    /// (and the `GcWeak`s can access the heap) until the heap is dropped.
+    fn reap_tombstones(&self) {
+        self.tombstones.lock().0.retain(|&header| unsafe {
+            if (*header).weak_count.load(Ordering::Acquire) == 1 {
+                GcHeader::deallocate(header);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Record a hold of the sweep lock started at `started`
+    fn record_sweep_pause(&self, cursor: &mut SweepCursor, started: Option<Instant>) {
+        if let Some(started) = started {
+            let pause = started.elapsed();
+            cursor.longest_pause = cursor.longest_pause.max(pause);
+            if self.options.collect_metrics {
+                self.metrics.lock().record_pause(pause);
+            }
+        }
+    }
+
+    /// Update the counters after a sweep, and return to idle
+    ///
+    /// The `duration` of the result is left to the caller.
+    fn finish_sweep(&self, cursor: SweepCursor) -> CollectionResult {
+        let allocated = self.bytes_allocated();
+        self.total_collections.fetch_add(1, Ordering::Relaxed);
+        self.total_bytes_freed
+            .fetch_add(cursor.freed as u64, Ordering::Relaxed);
+        self.last_live_bytes.store(cursor.live, Ordering::Relaxed);
+        self.last_live_objects
+            .store(cursor.live_objects, Ordering::Relaxed);
+        self.last_pause_ns.store(
+            cursor.longest_pause.as_nanos().min(u64::MAX as u128) as u64,
+            Ordering::Relaxed,
+        );
+        if self.options.collect_metrics {
+            let mut metrics = self.metrics.lock();
+            if let Some(sweep_started) = metrics.sweep_started.take() {
+                metrics.timings.sweep = sweep_started.elapsed();
+            }
+        }
+        self.hooks
+            .sweep_end(cursor.freed, cursor.freed_objects, allocated);
+        self.update_threshold(allocated);
+        if allocated <= self.options.soft_limit_bytes {
+            self.over_soft_limit.store(false, Ordering::Relaxed);
+        }
+        self.finish_gc();
+        CollectionResult {
+            freed_bytes: cursor.freed,
+            freed_objects: cursor.freed_objects,
+            bytes_after: allocated,
+            live_objects: self.live_objects.load(Ordering::Relaxed),
+            duration: Duration::ZERO,
+        }
+    }
+
+    /// Unreachable, but not swept yet by the incremental sweep in progress
+    ///
+    /// Must be called with the sweep lock held. Objects allocated after the
+    /// sweep started aren't in its snapshot: they carry the new epoch.
+    fn is_condemned(&self, header: &GcHeader) -> bool {
+        self.lazy_sweep_pending.load(Ordering::Acquire)
+            && header.is_white()
+            && header.sweep_epoch.load(Ordering::Relaxed) != self.sweep_epoch.load(Ordering::SeqCst)
+    }
+
+    /// Whether the calling thread holds a `ReadGuard` of this heap
+    ///
+    /// A sweep started on this thread would wait for the guard forever, so
+    /// allocations skip the collections of the heap limits meanwhile.
+    pub(crate) fn holds_read_guard(&self) -> bool {
+        self.guard_holders.lock().contains(&thread_token())
+    }
+
+    fn assert_no_read_guard(&self) {
+        if self.holds_read_guard() {
+            panic!("abfall: collecting while the calling thread holds a `ReadGuard` of the heap");
+        }
+    }
+
+    /// Whether the calling thread is sweeping this heap
+    ///
+    /// True in the `Drop` impls and finalizers of garbage, and in the
+    /// callbacks of events emitted under the sweep lock.
+    #[inline]
+    pub(crate) fn in_sweep(&self) -> bool {
+        self.sweeping_thread.load(Ordering::Relaxed) == thread_token()
+    }
+
+    /// Unlink `current`, the first object of the list that is swept
+    ///
+    /// Allocations push to the head concurrently, so the head cannot just be
+    /// overwritten. Objects pushed after the sweep's snapshot sit in front of
+    /// `current` and are never swept; the `next` links of linked objects are
+    /// only written by the sweeper (holding the sweep lock), so the last of
+    /// them is a stable predecessor. Returns the link now in front of `next`.
+    ///
+    /// # Safety
+    ///
+    /// Must be called by the sweeper, with `current` reachable from the head
+    /// through objects pushed after the snapshot only.
+    unsafe fn unlink_head(
+        &self,
+        current: *mut GcHeader,
+        next: *mut GcHeader,
+    ) -> *const AtomicPtr<GcHeader> {
+        match self
+            .head
+            .compare_exchange(current, next, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => &self.head,
+            Err(mut pred) => unsafe {
+                while (*pred).next.load(Ordering::Acquire) != current {
+                    pred = (*pred).next.load(Ordering::Acquire);
+                }
+                (*pred).next.store(next, Ordering::Release);
+                &(*pred).next
+            },
+        }
+    }
+
+    /// Remove entries from the remembered set
+    fn forget_remembered(&self, mut forget: impl FnMut(&GcHeader) -> bool) {
+        self.remembered.lock().0.retain(|&header| {
+            let header = unsafe { &*header };
+            let forgotten = forget(header);
+            if forgotten {
+                header.remembered.store(false, Ordering::Relaxed);
+            }
+            !forgotten
+        });
+    }
+
+    /// Return swept allocations to the arena or the free list
+    fn recycle(&self, slots: Vec<(Slot, Layout)>) {
+        let (arena_slots, slots): (Vec<_>, Vec<_>) =
+            slots.into_iter().partition(|(slot, _)| slot.from_arena);
+        if let (Some(arena), false) = (&self.arena, arena_slots.is_empty()) {
+            unsafe { arena.release_all(arena_slots.into_iter().map(|(s, l)| (s.ptr, l))) };
+        }
+        if !slots.is_empty() {
+            let mut free_list = self.free_list.lock();
+            for (slot, layout) in slots {
+                free_list.push(slot.ptr, layout);
+            }
+        }
+    }
+
+    /// Run the finalizers of a list of unlinked objects, then free them
+    ///
+    /// # Safety
+    ///
+    /// `unlinked` must be a list of unreachable objects linked through `next`.
+    unsafe fn finalize_and_free(
+        &self,
+        unlinked: *mut GcHeader,
+        recycle: bool,
+        recycled: &mut Vec<(Slot, Layout)>,
+    ) {
+        unsafe {
+            let mut current = unlinked;
+            while !current.is_null() {
+                if let Some(finalize) = (*current).vtable.finalize {
+                    finalize(current);
+                }
+                current = (*current).next.load(Ordering::Relaxed);
+            }
+
+            let mut current = unlinked;
+            while !current.is_null() {
+                let next = (*current).next.load(Ordering::Relaxed);
+                if (*current).is_root() {
+                    // Freeing it would leave a dangling root behind
+                    self.abort(format_args!(
+                        "abfall: a finalizer resurrected a collected object"
+                    ));
+                }
+                let layout = (*current).vtable.layout;
+                if let Some(slot) = GcHeader::free(current, recycle) {
+                    recycled.push((slot, layout));
+                }
+                current = next;
+            }
+        }
+    }
+
+    /// Relink the allocation list in ascending address order
+    ///
+    /// After many cycles the list order (newest first, interleaved with
+    /// survivors) no longer matches the memory layout, which hurts cache
+    /// locality of the list walks during marking and sweeping. This sorts the
+    /// list by address. Objects are not moved.
+    ///
+    /// Acts as a safepoint: waits for all read guards to be released and
+    /// returns false without doing anything if a collection is in progress.
+    /// Objects allocated concurrently stay in front of the sorted list.
+    pub fn reorder_lists(&self) -> bool {
+        let _sweep_guard = self.sweep_lock.write();
+        // Keep collections out while the list is relinked
+        if !self.phase.try_start(GcPhase::Sweeping) {
+            return false;
+        }
+
+        // Allocators only ever touch `head`, so everything reachable from
+        // this snapshot belongs to us
+        self.splice_thread_lists();
+        let first = self.head.load(Ordering::Acquire);
+        let mut headers: Vec<*mut GcHeader> = Vec::new();
+        let mut current = first;
+        while !current.is_null() {
+            headers.push(current);
+            current = unsafe { (*current).next.load(Ordering::Acquire) };
+        }
+        headers.sort_unstable();
+
+        if !headers.is_empty() {
+            for pair in headers.windows(2) {
+                unsafe { (*pair[0]).next.store(pair[1], Ordering::Relaxed) };
+            }
+            let last = headers[headers.len() - 1];
+            unsafe { (*last).next.store(null_mut(), Ordering::Relaxed) };
+
+            // Link the sorted list to the predecessor of the old first node:
+            // either `head` or the oldest object allocated meanwhile
+            let sorted = headers[0];
+            if let Err(mut prev) =
+                self.head
+                    .compare_exchange(first, sorted, Ordering::AcqRel, Ordering::Acquire)
+            {
+                unsafe {
+                    loop {
+                        let next = (*prev).next.load(Ordering::Acquire);
+                        if next == first {
+                            (*prev).next.store(sorted, Ordering::Release);
+                            break;
+                        }
+                        prev = next;
+                    }
+                }
+            }
+        }
+
+        self.phase.finish();
+        true
+    }
+
+    /// Pin the collector for read-only traversal of the object graph
+    ///
+    /// While the returned guard is alive, no object is swept, so `GcPtr`s
+    /// reachable from the graph can be dereferenced through
+    /// [`ReadGuard::get`] without rooting each visited object.
+    ///
+    /// A sweep (or a step of an incremental sweep) that is already in progress
+    /// is completed before this returns.
+    /// Collections started while the guard is held block before sweeping.
+    /// Allocations on the thread holding the guard don't collect, even over
+    /// `limit_bytes` or `soft_limit_bytes`, and `force_collect` or
+    /// `request_collection` on it panic instead of deadlocking.
+    pub fn read_guard(&self) -> ReadGuard<'_> {
+        ReadGuard::new(self)
+    }
+
+    /// Report the objects the next collection would free, without freeing them
+    ///
+    /// Marks all objects reachable from the roots into a side table, so the
+    /// real colors (and a collection that might be running concurrently) are
+    /// not affected. Objects are not swept while the preview runs.
+    ///
+    /// The result is a snapshot: objects might become unreachable right after.
+    pub fn preview_garbage(&self) -> Vec<ObjectInfo> {
+        let _guard = self.read_guard();
+        let mut tracer = Tracer::new_shadow();
+
+        for header in self.iter_headers() {
+            if header.is_root() {
+                tracer.mark_header(header);
+            }
+        }
+        self.mark_pinned(&tracer);
+        // As the owner (of no cycle), so ephemeron tables can be deferred
+        let trace = |tracer: &Tracer, ptr: *const GcHeader| {
+            tracer.set_owner(ptr, 0, false);
+            unsafe { ((*ptr).vtable.trace)(ptr, tracer) };
+            tracer.set_owner(core::ptr::null(), 0, false);
+        };
+        loop {
+            while let Some(ptr) = tracer.pop_work() {
+                trace(&tracer, ptr);
+            }
+            // Ephemeron values whose keys were visited meanwhile
+            let shaded = tracer.shaded();
+            let mut objects = tracer.take_ephemerons();
+            objects.sort_unstable();
+            objects.dedup();
+            for ptr in objects {
+                trace(&tracer, ptr);
+            }
+            if tracer.shaded() == shaded {
+                break;
+            }
+        }
+
+        self.iter_headers()
+            .filter(|header| !tracer.shadow_visited(*header))
+            .map(ObjectInfo::from_header)
+            .collect()
+    }
+
+    /// Call `f` with information about every allocation on the heap
+    ///
+    /// Holds a read guard, so no sweep runs while walking the list; `f`
+    /// must not start a collection. While idle the result is exact. During
+    /// concurrent marking it is a best-effort snapshot: colors change while
+    /// the list is walked, and objects allocated meanwhile may be missing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// let _root = ctx.allocate(1u32);
+    /// let mut bytes = 0;
+    /// ctx.for_each_object(|info| bytes += info.size);
+    /// assert_eq!(bytes, ctx.bytes_allocated());
+    /// # }
+    /// ```
+    pub fn for_each_object(&self, mut f: impl FnMut(ObjectInfo)) {
+        let _guard = self.read_guard();
+        for header in self.iter_headers() {
+            f(ObjectInfo::from_header(header));
+        }
+    }
+
+    /// Check whether the heap holds no allocations
+    ///
+    /// Like `allocation_count() == 0`: garbage counts until it is swept.
+    pub fn is_empty(&self) -> bool {
+        self.splice_thread_lists();
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    /// The objects held by a [`GcRoot`]
+    ///
+    /// For tools that look for what keeps a heap alive. The list is walked
+    /// once, under a read guard, when this is called, so the result is a
+    /// snapshot: roots created or dropped afterwards are not reflected, and
+    /// the returned pointers are not rooted. While idle it is exact.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// let number = ctx.allocate(1u32);
+    /// let _pointee = ctx.allocate(2u64).as_ptr();
+    /// let roots: Vec<_> = ctx.roots().collect();
+    /// assert_eq!(roots.len(), 1);
+    /// // Live while `number` is rooted, of a known type once erased
+    /// assert!(unsafe { roots[0].downcast::<u32>() }.is_none());
+    /// let number = number.into_any();
+    /// assert!(unsafe { roots[0].is::<u32>() } && number.is::<u32>());
+    /// # }
+    /// ```
+    pub fn roots(&self) -> impl Iterator<Item = GcErased> {
+        let _guard = self.read_guard();
+        let roots: Vec<_> = self
+            .iter_headers()
+            .filter(|header| header.is_root())
+            .map(GcErased::from_header)
+            .collect();
+        roots.into_iter()
+    }
+
+    /// Iterate over all headers in the allocation list
+    ///
+    /// Callers must make sure that no sweep runs concurrently.
+    fn iter_headers(&self) -> impl Iterator<Item = &GcHeader> {
+        self.splice_thread_lists();
+        let mut current = self.head.load(Ordering::Acquire);
+        core::iter::from_fn(move || {
+            let header = unsafe { current.as_ref()? };
+            current = header.next.load(Ordering::Acquire);
+            Some(header)
+        })
+    }
+
+    /// Number of times marking touched an object that was already shaded
+    ///
+    /// Counts marks by the collector and by write barriers that found the
+    /// object gray or black. This is wasted work; a high count relative to the
+    /// number of live objects indicates that the write barrier fires more
+    /// often than necessary.
+    pub fn redundant_marks(&self) -> usize {
+        self.redundant_marks.load(Ordering::Relaxed)
+    }
+
+    /// Longest the shared gray queue has been since the heap was created
+    ///
+    /// Marking threads keep it near [`GcOptions::gray_queue_soft_cap`]; write
+    /// barriers may push it a little beyond.
+    pub fn peak_gray_queue_len(&self) -> usize {
+        self.peak_gray_queue_len.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the objects shaded by write barriers were moved to
+    /// the shared gray queue
+    ///
+    /// Each move takes the queue's lock once for a whole batch, see
+    /// [`GcOptions::barrier_batch`].
+    pub fn barrier_flushes(&self) -> usize {
+        self.barrier_flushes.load(Ordering::Relaxed)
+    }
+
+    /// Register a callback for collection lifecycle events, replacing any previous one
+    ///
+    /// Callbacks run on the thread driving the collection (the background
+    /// thread or a caller of `collect`), without holding the gray-queue lock.
+    /// [`GcEvent::MarkEnd`] and [`GcEvent::SweepStart`] are emitted while the
+    /// sweep lock is held, so those callbacks must not create `ReadGuard`s or
+    /// start a collection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcEvent};
+    ///
+    /// let ctx = GcContext::off();
+    /// ctx.heap().set_gc_callback(Box::new(|event| {
+    ///     if let GcEvent::SweepEnd { freed_objects, .. } = event {
+    ///         println!("freed {freed_objects} objects");
+    ///     }
+    /// }));
+    /// ctx.heap().force_collect();
+    /// # }
+    /// ```
+    pub fn set_gc_callback(&self, cb: Box<dyn Fn(GcEvent) + Send + Sync>) {
+        self.hooks.set_callback(Some(Arc::from(cb)));
+    }
+
+    /// Remove the callback set with `set_gc_callback`
+    pub fn clear_gc_callback(&self) {
+        self.hooks.set_callback(None);
+    }
+
+    /// Register a handler for allocations over `limit_bytes`, replacing any previous one
+    ///
+    /// When an allocation would exceed the hard limit even after a full
+    /// collection, the handler is called with the size of the allocation
+    /// instead of applying [`GcOptions::on_limit_exceeded`]. It can release
+    /// roots and return [`OomAction::Retry`] to collect and try again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcOptions, OomAction};
+    ///
+    /// let ctx = GcContext::with_options(GcOptions {
+    ///     limit_bytes: 4096,
+    ///     ..GcOptions::OFF
+    /// });
+    /// ctx.heap().set_oom_handler(Box::new(|_size| OomAction::Fail));
+    /// let mut live = Vec::new();
+    /// while let Ok(root) = ctx.try_allocate([0u8; 64]) {
+    ///     live.push(root);
+    /// }
+    /// assert!(ctx.heap().bytes_allocated() <= 4096);
+    /// # }
+    /// ```
+    pub fn set_oom_handler(&self, handler: Box<dyn Fn(usize) -> OomAction + Send + Sync>) {
+        *self.oom_handler.lock() = Some(Arc::from(handler));
+    }
+
+    /// Remove the handler set with `set_oom_handler`
+    pub fn clear_oom_handler(&self) {
+        *self.oom_handler.lock() = None;
+    }
+
+    /// Snapshot of the heap's counters
+    ///
+    /// The counters are read one after another, so a snapshot taken during a
+    /// collection may mix values from before and after the sweep.
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            bytes_allocated: self.bytes_allocated(),
+            current_threshold: self.current_threshold(),
+            live_objects: self.live_objects.load(Ordering::Relaxed),
+            total_collections: self.total_collections.load(Ordering::Relaxed),
+            total_bytes_freed: self.total_bytes_freed.load(Ordering::Relaxed),
+            last_pause: Duration::from_nanos(self.last_pause_ns.load(Ordering::Relaxed)),
+            phase: self.phase.get(),
+        }
+    }
+
+    /// Number of buckets of [`pause_histogram`](Self::pause_histogram)
+    pub const PAUSE_BUCKETS: usize = 8;
+
+    /// Durations of the phases of the last collection
+    ///
+    /// Only measured with [`GcOptions::collect_metrics`], zero otherwise.
+    /// Each duration is from the last cycle that completed the phase.
+    pub fn phase_timings(&self) -> PhaseTimings {
+        self.metrics.lock().timings
+    }
+
+    /// Number of recent pauses per duration bucket
+    ///
+    /// Counts the root scans and the sweep pauses (see [`GcStats::last_pause`])
+    /// of the last 256 pauses, in buckets below 10µs, 100µs, 1ms, 10ms, 100ms,
+    /// 1s, 10s, and the rest. Only measured with
+    /// [`GcOptions::collect_metrics`], all zero otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::{GcContext, GcOptions};
+    ///
+    /// let ctx = GcContext::with_options(GcOptions {
+    ///     collect_metrics: true,
+    ///     ..GcOptions::manual()
+    /// });
+    /// ctx.force_collect();
+    /// // One root scan and one sweep pause
+    /// assert_eq!(ctx.pause_histogram().iter().sum::<u64>(), 2);
+    /// # }
+    /// ```
+    pub fn pause_histogram(&self) -> [u64; Self::PAUSE_BUCKETS] {
+        self.metrics.lock().histogram
+    }
+
+    /// Duration of the most recent pause in microseconds
+    ///
+    /// See [`pause_histogram`](Self::pause_histogram); 0 without
+    /// [`GcOptions::collect_metrics`].
+    pub fn last_pause_micros(&self) -> u64 {
+        self.metrics.lock().last_pause_micros
+    }
+
+    /// Snapshot of the weak reference counters
+    pub fn weak_stats(&self) -> WeakStats {
+        WeakStats {
+            weak_refs: self.weak_refs.load(Ordering::Relaxed),
+            upgrades: self.weak_upgrades.load(Ordering::Relaxed),
+            failed_upgrades: self.weak_upgrade_failures.load(Ordering::Relaxed),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn count_weak_created(&self) {
+        self.weak_refs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn count_weak_dropped(&self) {
+        self.weak_refs.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Shade an object gray if marking is in progress
+    pub(crate) fn shade(&self, header: &GcHeader) {
+        if self.check_is_marking_and_increment_busy() {
+            if header.state.mark_white_to_gray() {
+                self.gray_queue.lock().0.push(header);
+            }
+            self.decrement_busy_marking();
+        }
+    }
+
+    /// Root the object behind a `GcWeak` unless it was swept
+    pub(crate) fn upgrade_weak(&self, header: &GcHeader) -> bool {
+        if !self.try_root(header) {
+            self.weak_upgrade_failures.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.weak_upgrades.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Add a root to an object unless it was swept
+    pub(crate) fn try_root(&self, header: &GcHeader) -> bool {
+        // Keep the sweeper out: a white object must not be resurrected while
+        // it (or one of its children) is being swept. Recursive, because the
+        // caller might hold a `ReadGuard`.
+        if self.in_sweep() {
+            panic!(
+                "abfall: a `GcWeak` can't be upgraded from a `Drop` or finalizer run by the sweep"
+            );
+        }
+        let _sweep_guard = self.sweep_lock.read_recursive();
+        if self.is_condemned(header) || !header.try_inc_root() {
+            return false;
+        }
+        // The root scan might be over already: the object might be
+        // unreachable otherwise, so its children have to be marked
+        self.shade(header);
+        true
+    }
+
+    /// The options this heap was created with
+    #[inline]
+    pub fn options(&self) -> &GcOptions {
+        &self.options
+    }
+
+    pub fn bytes_allocated(&self) -> usize {
+        self.bytes_allocated.load(Ordering::Relaxed)
+    }
+
+    /// Allocated bytes above which the next allocation triggers a collection
+    ///
+    /// Recalculated after each collection from the live bytes, see
+    /// [`GcOptions::threshold_percent`]; `usize::MAX` if threshold-based
+    /// collection is disabled.
+    pub fn current_threshold(&self) -> usize {
+        self.current_threshold.load(Ordering::Relaxed)
+    }
+
+    /// Change the minimum threshold at runtime
+    ///
+    /// Replaces [`GcOptions::min_threshold_bytes`] (which `options` still
+    /// reports), for example to collect less often during a known burst of
+    /// allocations. The threshold is set to `bytes` right away; after the
+    /// next collection it follows the live bytes again, but never drops
+    /// below `bytes`. Does nothing if threshold-based collection is
+    /// disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcOptions, Heap};
+    ///
+    /// let heap = Heap::with_options(GcOptions::DEFAULT);
+    /// heap.set_min_threshold(64 * 1024 * 1024);
+    /// assert_eq!(heap.current_threshold(), 64 * 1024 * 1024);
+    /// ```
+    pub fn set_min_threshold(&self, bytes: usize) {
+        if self.options.is_threshold_off() {
+            return;
+        }
+        self.min_threshold.store(bytes, Ordering::Relaxed);
+        self.current_threshold.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Bytes of the objects that survived the last sweep
+    ///
+    /// Unlike [`bytes_allocated`](Self::bytes_allocated), this excludes
+    /// objects allocated since, reachable or not; it is the reachable heap as
+    /// of the last completed mark. Objects allocated while that cycle was
+    /// sweeping are not included either. 0 before the first collection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use abfall::GcContext;
+    ///
+    /// let ctx = GcContext::off();
+    /// let _live = ctx.allocate(1u64);
+    /// ctx.force_collect();
+    /// drop(ctx.allocate(2u64));
+    /// assert!(ctx.live_bytes() < ctx.bytes_allocated());
+    /// assert_eq!(ctx.live_object_count(), 1);
+    /// # }
+    /// ```
+    pub fn live_bytes(&self) -> usize {
+        self.last_live_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Number of objects that survived the last sweep, see [`live_bytes`](Self::live_bytes)
+    pub fn live_object_count(&self) -> usize {
+        self.last_live_objects.load(Ordering::Relaxed)
+    }
+
+    /// Number of objects allocated on this heap
+    ///
+    /// Read from a counter kept by allocation and the sweep, so this is
+    /// O(1). Garbage counts until it is swept.
+    pub fn allocation_count(&self) -> usize {
+        self.live_objects.load(Ordering::Relaxed)
+    }
+
+    /// [`allocation_count`](Self::allocation_count) by walking the list
+    ///
+    /// O(n) in the allocations; for cross-checking the counter. While a
+    /// sweep or another thread's allocation is in progress the two may
+    /// differ briefly.
+    pub fn allocation_count_slow(&self) -> usize {
+        self.splice_thread_lists();
+        let mut count = 0;
+        let mut current = self.head.load(Ordering::Acquire);
+
+        while !current.is_null() {
+            count += 1;
+            unsafe {
+                current = (*current).next.load(Ordering::Acquire);
+            }
+        }
+
+        count
+    }
+
+    /// Start the background collection thread
+    ///
+    /// Returns false if it is already running or disabled by the options.
+    /// In `single-threaded` builds this never starts a thread; collect
+    /// explicitly with `collect` or `force_collect` instead.
+    #[cfg(feature = "std")]
+    pub fn start_background_collection(self: &Arc<Self>) -> bool {
+        self.try_start_background_collection().unwrap_or(false)
+    }
+
+    /// Start the background collection thread, failing after `shutdown`
+    ///
+    /// Returns `Ok(false)` in the same cases `start_background_collection`
+    /// returns false.
+    #[cfg(feature = "std")]
+    pub fn try_start_background_collection(self: &Arc<Self>) -> Result<bool, Error> {
+        if self.is_shut_down() {
+            return Err(Error::HeapShuttingDown);
+        }
+        if !crate::sync::THREADS
+            || self.options.is_background_collection_off()
+            || self.bg_thread.is_started()
+        {
+            return Ok(false);
+        }
+
+        // A strong reference would keep the heap alive until the thread is
+        // stopped; the thread only holds one during a collection
+        let weak = Arc::downgrade(self);
+        let restart = self.options.restart_background_on_panic;
+        Ok(self.bg_thread.start(move |c| {
+            loop {
+                match std::panic::catch_unwind(AssertUnwindSafe(|| background_gc_thread(&weak, &c)))
+                {
+                    Ok(()) => break,
+                    Err(payload) => {
+                        // A panic while marking was handled by `AbortOnUnwind`
+                        if let Some(heap) = weak.upgrade().filter(|h| h.phase() != GcPhase::Idle) {
+                            heap.abort_collection();
+                        }
+                        if !restart {
+                            std::panic::resume_unwind(payload);
+                        }
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Check whether background collection is working
+    ///
+    /// Returns false if the background thread was started but terminated
+    /// unexpectedly (it panicked and `GcOptions::restart_background_on_panic`
+    /// is disabled). In that case, no automatic collection happens anymore;
+    /// call `stop_background_collection` and `start_background_collection` to
+    /// launch a new background thread.
+    #[cfg(feature = "std")]
+    pub fn background_healthy(&self) -> bool {
+        !self.bg_thread.is_started() || self.bg_thread.is_alive()
+    }
+
+    /// Check whether the background thread is running
+    ///
+    /// False before `start_background_collection`, after
+    /// `stop_background_collection` and once the thread terminated.
+    #[cfg(feature = "std")]
+    pub fn is_background_running(&self) -> bool {
+        self.bg_thread.is_alive()
+    }
+
+    /// Abandon a collection cycle that was interrupted by a panic
+    ///
+    /// Returns the heap to the idle state: pending gray work is dropped and all
+    /// objects are reset to white, so the next cycle starts from scratch.
+    fn abort_collection(&self) {
+        // Unswept garbage of an incremental sweep may point to freed objects,
+        // so it must not survive
+        self.do_sweep_work(usize::MAX);
+        self.incremental_cycle.store(false, Ordering::Release);
+        self.gray_queue.lock().0.clear();
+        self.dirty_objects.lock().0.clear();
+        self.clear_ephemerons();
+        self.mark_incomplete.store(false, Ordering::Relaxed);
+        {
+            let _sweep_guard = self.sweep_lock.write();
+            for header in self.iter_headers() {
+                header.state.reset_white();
+            }
+        }
+        self.finish_gc();
+    }
+
+    /// Abort the process with `message`
+    ///
+    /// The message goes to the collection hooks (the `tracing` integration
+    /// logs it), then becomes the message of a panic. Panicking again while
+    /// unwinding from it aborts, also without `std::process::abort`.
+    #[cold]
+    fn abort(&self, message: fmt::Arguments<'_>) -> ! {
+        struct PanicOnDrop;
+        impl Drop for PanicOnDrop {
+            fn drop(&mut self) {
+                panic!("abfall: aborting");
+            }
+        }
+        self.hooks.abort(message);
+        let _abort = PanicOnDrop;
+        panic!("{message}")
+    }
+
+    /// Stop the background collection thread and wait for it to exit
+    ///
+    /// Returns false if it was not running. A collection in progress on the
+    /// thread is abandoned at the next increment. `start_background_collection`
+    /// launches a new thread with the same options.
+    #[cfg(feature = "std")]
+    pub fn stop_background_collection(&self) -> bool {
+        self.bg_thread.stop()
+    }
+
+    /// Shut the heap down
+    ///
+    /// Stops the background thread and rejects new contexts
+    /// (`GcContext::try_with_heap`) and background threads with
+    /// [`Error::HeapShuttingDown`]. Existing contexts and roots stay usable;
+    /// the memory is released when the last reference to the heap is dropped.
+    ///
+    /// Returns [`Error::BackgroundThreadPanicked`] if the background thread
+    /// had terminated with a panic.
+    #[cfg(feature = "std")]
+    pub fn shutdown(&self) -> Result<(), Error> {
+        self.shut_down.store(true, Ordering::Release);
+        match self.bg_thread.stop_and_join() {
+            Some(Err(_)) => Err(Error::BackgroundThreadPanicked),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check whether `shutdown` was called
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::Acquire)
+    }
+
+    /// The last error a collection ran into, if any
+    ///
+    /// [`Error::TracePanicked`] once a `Trace` impl panicked while marking.
+    /// The thread that started the cycle (the caller of `force_collect`, or
+    /// the background thread) abandons it and unwinds: the heap returns to
+    /// [`GcPhase::Idle`] without freeing anything, and stays usable. Slices of
+    /// marking run by other threads (like `do_mark_work`) propagate the panic
+    /// as well; the cycle is then finished, but frees nothing.
+    pub fn last_error(&self) -> Option<Error> {
+        *self.last_error.lock()
+    }
+
+    /// See `GcOptions::collect_on_context_drop`
+    #[cfg(feature = "std")]
+    pub(crate) fn collect_after_context_drop(&self) {
+        if !self.kick_background() {
+            self.collect();
+        }
+    }
+
+    /// Wake up the background thread to run a collection cycle now
+    ///
+    /// The background thread immediately checks whether a collection is
+    /// needed (see `GcOptions::threshold_percent`) and, if so, runs it on the
+    /// background thread instead of the calling thread.
+    ///
+    /// Returns false if background collection is not running.
+    #[cfg(feature = "std")]
+    pub fn kick_background(&self) -> bool {
+        self.bg_thread.kick()
+    }
+}
+
+impl Drop for Heap {
+    fn drop(&mut self) {
+        // Stop the background thread before the objects go. It holds the heap
+        // during a collection, so none is in progress unless this runs on it
+        // after the collection finished; then it's detached instead.
+        #[cfg(feature = "std")]
+        self.bg_thread.stop();
+
+        // The handler may own roots, which must go before their objects
+        drop(self.oom_handler.lock().take());
+
+        // Drop all the data first, then free the objects: a `Drop` impl may
+        // still look at other objects. They are all dead before, so none can
+        // be upgraded from a `GcWeak`.
+        let lists = [
+            self.head.load(Ordering::Acquire),
+            // Garbage an unfinished incremental sweep unlinked for its finalizers
+            self.lazy_sweep
+                .lock()
+                .take()
+                .map_or(null_mut(), |cursor| cursor.unlinked),
+        ];
+        for mut current in lists {
+            while let Some(header) = unsafe { current.as_ref() } {
+                header.state.mark_dead();
+                current = header.next.load(Ordering::Acquire);
+            }
+        }
+        for mut current in lists {
+            while let Some(header) = unsafe { current.as_ref() } {
+                unsafe { (header.vtable.drop_data)(current) };
+                current = header.next.load(Ordering::Acquire);
+            }
+        }
+        let tombstones = core::mem::take(&mut self.tombstones.lock().0);
+        let mut arena_in_use = false;
+        let mut detach = |header: *mut GcHeader| unsafe {
+            arena_in_use |= (*header).from_arena && (*header).weak_count() > 0;
+            // Left to the `GcWeak`s, if there are any
+            GcHeader::detach(header);
+        };
+        for mut current in lists {
+            while !current.is_null() {
+                let next = unsafe { (*current).next.load(Ordering::Acquire) };
+                detach(current);
+                current = next;
+            }
+        }
+        tombstones.into_iter().for_each(detach);
+        if arena_in_use {
+            // Tombstones in the arena outlive the heap
+            core::mem::forget(self.arena.take());
+        }
+    }
+}
+
+/// Abandons the cycle of the thread that started it, if that unwinds
+///
+/// Forgotten once the part that can panic (`Trace` impls, while marking) is
+/// done.
+struct AbortOnUnwind<'h>(&'h Heap);
+
+impl Drop for AbortOnUnwind<'_> {
+    fn drop(&mut self) {
+        self.0.abort_collection();
+    }
+}
+
+/// Records a panic in a `Trace` impl, see `Heap::trace_gray`
+///
+/// Forgotten once the object was traced.
+struct TracePanicked<'h>(&'h Heap);
 
-        self.sweep_and_finish()
+impl Drop for TracePanicked<'_> {
+    #[cold]
+    fn drop(&mut self) {
+        self.0.mark_incomplete.store(true, Ordering::Relaxed);
+        *self.0.last_error.lock() = Some(Error::TracePanicked);
     }
+}
 
-    pub fn collect(&self) {
-        if self.should_collect() {
-            self.force_collect();
+/// Markers of `Heap::do_mark_parallel`
+///
+/// A marker without work waits on `wake` until another one shares some
+/// through the gray queue, or all are out of work.
+#[cfg(feature = "std")]
+struct MarkWorkers {
+    /// Markers that may still produce work: a marker only leaves this count
+    /// once its local queue is empty, so all work is in the shared queue
+    /// when it drops to zero
+    active: crate::sync::Mutex<usize>,
+    wake: crate::sync::Condvar,
+    /// Markers waiting on `wake`, so busy ones only notify if needed
+    idle: AtomicUsize,
+}
+
+#[cfg(feature = "std")]
+impl MarkWorkers {
+    fn new(markers: usize) -> Self {
+        Self {
+            active: crate::sync::Mutex::new(markers),
+            wake: crate::sync::Condvar::new(),
+            idle: AtomicUsize::new(0),
         }
     }
 
-    /// Check if GC is currently in marking phase
-    pub fn is_marking(&self) -> bool {
-        GcPhase::from(self.phase.load(Ordering::Acquire)) == GcPhase::Marking
+    /// Wake the idle markers after sharing work through the gray queue
+    ///
+    /// An idle marker counts itself before it checks the queue under its
+    /// lock, so either it sees the work, or the count is seen here.
+    fn wake_idle(&self) {
+        if self.idle.load(Ordering::SeqCst) > 0 {
+            drop(self.active.lock());
+            self.wake.notify_all();
+        }
     }
+}
 
-    pub fn check_is_marking_and_increment_busy(&self) -> bool {
-        self.n_busy_marking.fetch_add(1, Ordering::AcqRel);
-        if self.is_marking() {
-            true
-        } else {
-            self.n_busy_marking.fetch_sub(1, Ordering::AcqRel);
-            false
-        }
+/// Records the sweeping thread in `Heap::sweeping_thread`, also on unwinding
+struct SweepingThread<'h>(&'h Heap);
+
+impl<'h> SweepingThread<'h> {
+    fn enter(heap: &'h Heap) -> Self {
+        heap.sweeping_thread
+            .store(thread_token(), Ordering::Relaxed);
+        Self(heap)
     }
+}
 
-    pub fn decrement_busy_marking(&self) {
-        self.n_busy_marking.fetch_sub(1, Ordering::AcqRel);
+impl Drop for SweepingThread<'_> {
+    fn drop(&mut self) {
+        self.0.sweeping_thread.store(0, Ordering::Relaxed);
     }
+}
 
-    /// Try to transition to marking phase
-    fn try_start_marking(&self) -> bool {
-        self.phase
-            .compare_exchange(
-                GcPhase::Idle as u8,
-                GcPhase::Marking as u8,
-                Ordering::AcqRel,
-                Ordering::Acquire,
-            )
-            .is_ok()
+/// Identifies the calling thread, never 0
+#[cfg(all(feature = "std", not(loom)))]
+pub(crate) fn thread_token() -> usize {
+    std::thread_local!(static TOKEN: u8 = const { 0 });
+    TOKEN.with(|token| token as *const u8 as usize)
+}
+
+/// Loom runs its threads on one OS thread, with thread locals of its own
+#[cfg(all(feature = "std", loom))]
+pub(crate) fn thread_token() -> usize {
+    loom::thread_local!(static TOKEN: u8 = 0);
+    TOKEN.with(|token| token as *const u8 as usize)
+}
+
+/// Without `std` threads can't be told apart; locks don't block there either
+#[cfg(not(feature = "std"))]
+pub(crate) fn thread_token() -> usize {
+    1
+}
+
+/// `Instant::now`, or None where there is no clock (`wasm32-unknown-unknown`)
+#[cfg(feature = "std")]
+#[inline]
+fn clock_now() -> Option<Instant> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        None
+    } else {
+        Some(Instant::now())
     }
+}
 
-    /// Transition to sweeping phase
-    fn start_sweeping(&self) {
-        self.phase.store(GcPhase::Sweeping as u8, Ordering::Release);
+/// There is no clock without `std`
+#[cfg(not(feature = "std"))]
+#[inline]
+fn clock_now() -> Option<Instant> {
+    None
+}
+
+/// Stands in for `std::time::Instant` without `std`, never created
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy)]
+enum Instant {}
+
+#[cfg(not(feature = "std"))]
+impl Instant {
+    fn elapsed(&self) -> Duration {
+        match *self {}
     }
+}
 
-    /// Transition back to idle phase
-    fn finish_gc(&self) {
-        self.phase.store(GcPhase::Idle as u8, Ordering::Release);
+/// Xorshift generator for `GcOptions::interval_jitter`
+///
+/// Seeded from std's per-process random hash keys, the heap's address and
+/// the thread, so no two background threads follow the same sequence.
+#[cfg(feature = "std")]
+struct Jitter(u64);
+
+#[cfg(feature = "std")]
+impl Jitter {
+    fn new(heap: usize) -> Self {
+        use std::hash::BuildHasher;
+        let state = std::collections::hash_map::RandomState::new();
+        // Never 0, which xorshift would keep forever
+        Self(state.hash_one((heap, std::thread::current().id())) | 1)
     }
 
-    pub(crate) fn try_mark_full(&self) -> bool {
-        if !self.try_start_marking() {
-            return false;
+    /// `interval`, moved by a random amount of at most `jitter` either way
+    fn apply(&mut self, interval: Duration, jitter: Duration) -> Duration {
+        if jitter.is_zero() {
+            return interval;
         }
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        let range = jitter.as_nanos().min(u64::MAX as u128 / 2) as u64 * 2;
+        interval.saturating_sub(jitter) + Duration::from_nanos(x % (range + 1))
+    }
+}
 
-        {
-            let tracer = Tracer::new();
+/// Background GC thread that performs incremental marking and sweeping
+///
+/// Returns once stopped or when the heap was dropped.
+#[cfg(feature = "std")]
+fn background_gc_thread(weak: &alloc::sync::Weak<Heap>, c: &StopCondition) {
+    let mut tracer = Tracer::new();
+    let Some((interval, jitter)) = weak.upgrade().map(|heap| {
+        (
+            heap.options.collection_interval,
+            heap.options.interval_jitter,
+        )
+    }) else {
+        return;
+    };
+    let mut rng = Jitter::new(weak.as_ptr() as usize);
+    while !interval.is_zero() && !c.wait_stopped(rng.apply(interval, jitter)) {
+        let Some(heap) = weak.upgrade() else {
+            return;
+        };
+        // Check if we should start a collection
+        if heap.should_collect() && heap.try_start_marking() {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("gc_background").entered();
+            let abort = AbortOnUnwind(&heap);
 
             // STW pause: scan roots
-            self.do_mark_roots(&tracer);
+            heap.do_mark_roots(&mut tracer);
 
-            // Concurrent marking
-            self.do_mark_work_full(&tracer);
+            // Incremental marking phase
+            loop {
+                if c.is_stopped() {
+                    // Leaves no colors or gray work behind for the next cycle
+                    drop(abort);
+                    return;
+                }
+
+                let marking_complete = heap.do_mark_incremental(heap.incremental_work_budget());
+                if marking_complete {
+                    if !heap.yield_once_if_marking_busy() {
+                        break;
+                    }
+                } else {
+                    // Yield to allow mutators to make progress
+                    std::thread::yield_now();
+                }
+            }
+
+            core::mem::forget(abort);
+
+            // Sweeping phase and finish
+            if heap.options.incremental_sweep {
+                heap.begin_lazy_sweep();
+                // Completed even if stopped, allocations may not finish it
+                while !heap.do_sweep_work(heap.options.incremental_work_budget) {
+                    std::thread::yield_now();
+                }
+            } else {
+                heap.sweep_and_finish();
+            }
         }
-        true
     }
+}
 
-    pub(crate) fn sweep_and_finish(&self) -> usize {
-        let live_bytes = self.do_sweep();
-        self.update_threshold(live_bytes);
-        self.finish_gc();
-        live_bytes
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+
+    /// Two markers move work through the gray queue after every object,
+    /// like the parallel markers: each object is traced exactly once, and
+    /// none is left behind
+    #[test]
+    fn loom_markers_share_work_through_the_gray_queue() {
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+        model.check(|| {
+            let heap = Arc::new(Heap::with_options(GcOptions::manual()));
+            // Two roots pointing to the same unrooted object, which both
+            // markers may find (and has to be traced as well)
+            let shared = heap.allocate(None::<GcPtr<u32>>).as_ptr();
+            let roots = [(); 2].map(|_| heap.allocate(Some(shared)));
+            assert!(heap.begin_mark());
+
+            let mark = |heap: &Heap| {
+                let mut tracer = Tracer::new();
+                // One root each to begin with, a batch could take both
+                heap.steal_work(&mut tracer, 1);
+                while heap.do_mark_with_tracer(&mut tracer, 1) > 0 {}
+                assert!(!tracer.has_work());
+            };
+            let other = {
+                let heap = Arc::clone(&heap);
+                loom::thread::spawn(move || mark(&heap))
+            };
+            mark(&heap);
+            other.join().unwrap();
+
+            assert!(heap.gray_queue.lock().0.is_empty());
+            assert!(heap.iter_headers().all(|h| h.state.get() == Color::Black));
+            assert_eq!(heap.hooks.marked(), 3);
+            assert!(heap.sweep().is_some());
+            assert_eq!(heap.allocation_count(), 3);
+            drop(roots);
+        });
     }
+}
 
-    /// Steal work from the shared gray queue into a tracer
-    ///
-    /// Returns true if work was stolen, false if queue is empty
-    fn steal_work(&self, tracer: &Tracer, max_items: usize) -> bool {
-        let mut gray_queue = self.gray_queue.lock();
-        tracer.steal_from(max_items, &mut gray_queue.0)
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::GcContext;
+
+    #[test]
+    fn options_builder_validates() {
+        let options = GcOptions::builder()
+            .threshold_shrink_percent(100)
+            .assist_work_budget(0)
+            .limit_bytes(1024 * 1024)
+            .build()
+            .unwrap();
+        assert_eq!(options.threshold_shrink_percent, 100);
+        assert_eq!(options.assist_work_budget, 0);
+        assert_eq!(
+            options.collection_interval,
+            GcOptions::DEFAULT.collection_interval
+        );
+
+        let invalid =
+            |builder: GcOptionsBuilder| matches!(builder.build(), Err(Error::InvalidOptions(_)));
+        assert!(invalid(GcOptions::builder().threshold_shrink_percent(101)));
+        assert!(invalid(GcOptions::builder().incremental_work_budget(0)));
+        assert!(invalid(
+            GcOptions::builder()
+                .auto_tune(true)
+                .auto_tune_step(Duration::ZERO)
+        ));
+        assert!(!invalid(
+            GcOptions::builder().auto_tune_step(Duration::ZERO)
+        ));
+        assert!(invalid(GcOptions::builder().limit_bytes(1024)));
+        assert!(!invalid(
+            GcOptions::builder()
+                .limit_bytes(1024)
+                .min_threshold_bytes(1024)
+        ));
+
+        assert!(invalid(
+            GcOptions::builder()
+                .limit_bytes(2 * 1024 * 1024)
+                .soft_limit_bytes(4 * 1024 * 1024)
+        ));
+        assert!(invalid(
+            GcOptions::builder().interval_jitter(Duration::from_millis(100))
+        ));
+        assert!(!invalid(
+            GcOptions::builder().interval_jitter(Duration::from_millis(99))
+        ));
+
+        // The presets are valid
+        assert!(!invalid(GcOptions::OFF.into()));
+        assert!(!invalid(GcOptions::manual().into()));
     }
 
-    /// Merge tracer's local work back to the shared gray queue
-    pub(crate) fn merge_work(&self, tracer: &Tracer) {
-        let mut gray_queue = self.gray_queue.lock();
-        tracer.append_to(&mut gray_queue.0);
+    #[test]
+    fn no_trace_allocations_are_black_and_skip_the_assist() {
+        let ctx = GcContext::with_options(GcOptions {
+            assist_work_budget: 5,
+            ..GcOptions::OFF
+        });
+        let root = ctx.allocate(Some(ctx.allocate(0).as_ptr()));
+        let color_of = |address: usize| {
+            let mut color = None;
+            ctx.for_each_object(|info| {
+                if info.address == address {
+                    color = Some(info.color);
+                }
+            });
+            color.unwrap()
+        };
+        let root_address = &*root as *const _ as usize;
+
+        assert!(ctx.begin_mark());
+        assert_eq!(color_of(root_address), Color::Gray);
+        let int = ctx.allocate(1);
+        assert_eq!(color_of(&*int as *const i32 as usize), Color::Black);
+        // No assist: the root is still waiting in the gray queue
+        assert_eq!(color_of(root_address), Color::Gray);
+
+        // Traced types still assist
+        let traced = ctx.allocate(None::<GcPtr<i32>>);
+        assert_eq!(color_of(root_address), Color::Black);
+        assert_ne!(color_of(&*traced as *const _ as usize), Color::White);
+        assert!(ctx.sweep().is_some());
     }
 
-    /// Process marking work using a tracer
-    ///
-    /// Steals work, processes it locally, then merges new work back
-    fn do_mark_with_tracer(&self, tracer: &Tracer, work_budget: usize) -> usize {
-        let mut work_done = 0;
+    #[test]
+    fn paced_assist_keeps_up_with_allocation() {
+        struct Link(Option<GcPtr<Link>>);
 
-        while work_done < work_budget {
-            // Try to get work from tracer's local queue first
-            let ptr = if let Some(p) = tracer.pop_work() {
-                p
-            } else {
-                // Local queue empty, try to steal from shared queue
-                const BATCH_SIZE: usize = 8;
-                if !self.steal_work(tracer, BATCH_SIZE) {
-                    // No work available anywhere
-                    break;
-                }
-                continue;
-            };
+        unsafe impl Trace for Link {
+            fn trace(&self, tracer: &Tracer) {
+                self.0.trace(tracer);
+            }
+        }
 
-            // Process one object
-            unsafe {
-                let header = &*ptr;
-                (header.vtable.trace)(ptr, tracer);
-                header.color.mark_black();
+        // Allocated while marking: traced, so it assists, and much larger
+        struct Blob(#[allow(dead_code)] [u8; 1024], Option<GcPtr<Link>>);
+
+        unsafe impl Trace for Blob {
+            fn trace(&self, tracer: &Tracer) {
+                self.1.trace(tracer);
             }
+        }
 
-            work_done += 1;
+        const LINKS: usize = 1000;
+        const MAX_ALLOCATIONS: usize = 2 * LINKS;
+
+        // Number of allocations until the end of the chain is black
+        fn allocations_to_mark(assist_ratio: usize) -> Option<usize> {
+            let ctx = GcContext::with_options(GcOptions {
+                assist_work_budget: 1,
+                assist_ratio,
+                ..GcOptions::OFF
+            });
+            let heap = ctx.heap();
+            let mut chain = ctx.allocate(Link(None));
+            let tail = chain.as_ptr().header_ptr();
+            for _ in 1..LINKS {
+                chain = ctx.allocate(Link(Some(chain.as_ptr())));
+            }
+            assert!(heap.begin_mark());
+            let done = (0..MAX_ALLOCATIONS).position(|_| {
+                drop(ctx.allocate(Blob([0; 1024], None)));
+                unsafe { (*tail).state.get() == Color::Black }
+            });
+            while !heap.do_mark_work(usize::MAX) {}
+            heap.sweep();
+            done.map(|i| i + 1)
         }
 
-        // Merge any newly discovered work back to shared queue
-        if tracer.has_work() {
-            self.merge_work(tracer);
+        // Fixed budget: the new objects are marked first, the chain starves
+        assert_eq!(allocations_to_mark(0), None);
+        // Paced: done by the first assist after the mutator allocated the
+        // bytes live at the start
+        let live_bytes = LINKS * Layout::new::<GcBox<Link>>().size();
+        let blob = Layout::new::<GcBox<Blob>>().size();
+        let allocations = allocations_to_mark(100).unwrap();
+        assert!(
+            allocations <= live_bytes.div_ceil(blob) + 1,
+            "{allocations}"
+        );
+    }
+
+    #[test]
+    fn arena_and_overflow_objects_are_swept() {
+        struct Counted(usize, Arc<AtomicUsize>);
+
+        unsafe impl Trace for Counted {
+            const NO_TRACE: bool = true;
+            fn trace(&self, _tracer: &Tracer) {}
         }
 
-        work_done
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let object = Layout::new::<GcBox<Counted>>().size();
+        let heap = Heap::with_arena(GcOptions::manual(), object * 4);
+        let drops = Arc::new(AtomicUsize::new(0));
+        let from_arena =
+            |root: &GcRoot<Counted>| unsafe { (*root.as_ptr().header_ptr()).from_arena };
+
+        let roots: Vec<_> = (0..8)
+            .map(|i| heap.allocate(Counted(i, Arc::clone(&drops))))
+            .collect();
+        // The first four fit into the arena, the rest overflow
+        let sources: Vec<_> = roots.iter().map(from_arena).collect();
+        assert_eq!(
+            sources,
+            [true, true, true, true, false, false, false, false]
+        );
+        assert_eq!(heap.arena_bytes_used(), object * 4);
+
+        // Two arena and two overflow objects become garbage; a weak
+        // reference keeps one arena object as a tombstone
+        let weak = roots[1].downgrade();
+        let kept: Vec<_> = roots.into_iter().step_by(2).collect();
+        heap.force_collect();
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+        assert_eq!(heap.allocation_count(), 4);
+        assert!(kept.iter().enumerate().all(|(i, root)| root.0 == i * 2));
+        assert!(weak.upgrade().is_none());
+
+        // The swept arena object is reused, the tombstone only once released
+        // and reaped by a sweep
+        let reused = heap.allocate(Counted(8, Arc::clone(&drops)));
+        assert!(from_arena(&reused));
+        assert!(!from_arena(&heap.allocate(Counted(9, Arc::clone(&drops)))));
+        drop(weak);
+        heap.force_collect();
+        assert!(from_arena(&heap.allocate(Counted(10, Arc::clone(&drops)))));
+        assert_eq!(heap.arena_bytes_used(), object * 4);
+
+        drop(kept);
+        drop(reused);
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 0);
+        assert_eq!(drops.load(Ordering::Relaxed), 11);
     }
 
-    /// Perform a bounded amount of incremental marking work
-    ///
-    /// Returns true if marking is complete, false if more work remains
-    fn do_mark_incremental(&self, work_budget: usize) -> bool {
-        let tracer = Tracer::new();
-        let work_done = self.do_mark_with_tracer(&tracer, work_budget);
+    #[test]
+    fn shrink_to_fit_releases_queue_and_free_list() {
+        let heap = Heap::with_options(GcOptions {
+            free_list_cap_bytes: 1024 * 1024,
+            ..GcOptions::manual()
+        });
+        let burst: Vec<_> = (0..10_000).map(|i| heap.allocate(i)).collect();
+        assert!(heap.begin_mark());
+        assert!(heap.gray_queue.lock().0.capacity() >= 10_000);
+        // Busy collector: nothing is released
+        heap.shrink_to_fit();
+        assert!(heap.gray_queue.lock().0.capacity() >= 10_000);
+        heap.sweep();
 
-        // If we did no work, marking is complete
-        work_done == 0
+        drop(burst);
+        heap.force_collect();
+        assert!(heap.free_list.lock().retained_bytes > 0);
+        heap.shrink_to_fit();
+        assert_eq!(heap.gray_queue.lock().0.capacity(), 0);
+        assert_eq!(heap.free_list.lock().retained_bytes, 0);
+        assert_eq!(heap.allocation_count(), 0);
     }
 
-    fn yield_once_if_marking_busy(&self) -> bool {
-        if self.n_busy_marking.load(Ordering::Acquire) > 0 {
-            std::thread::yield_now();
-            true
-        } else {
-            false
+    #[test]
+    fn collect_incremental_in_small_slices() {
+        use crate::GcPtr;
+
+        struct Link(Option<GcPtr<Link>>);
+
+        unsafe impl Trace for Link {
+            fn trace(&self, tracer: &Tracer) {
+                self.0.trace(tracer);
+            }
         }
-    }
 
-    fn do_mark_work_full(&self, tracer: &Tracer) {
-        // Process until all work is complete
-        while self.do_mark_with_tracer(tracer, self.options.incremental_work_budget) > 0
-            || self.yield_once_if_marking_busy()
-        {
-            // Keep going until no more work
+        let ctx = GcContext::off();
+        let heap = ctx.heap();
+        // A chain of 10 objects, plus garbage
+        let mut chain = ctx.allocate(Link(None));
+        for _ in 1..10 {
+            chain = ctx.allocate(Link(Some(chain.as_ptr())));
+        }
+        for i in 0..5 {
+            drop(ctx.allocate(i));
         }
+
+        let mut slices = 0;
+        while !heap.collect_incremental(2) {
+            slices += 1;
+            assert!(heap.is_marking());
+        }
+        assert!(slices > 1, "marking took several slices");
+        assert_eq!(
+            heap.sweep().map(|result| result.bytes_after),
+            Some(heap.bytes_allocated())
+        );
+        assert!(!heap.is_marking());
+        assert_eq!(heap.allocation_count(), 10);
+        assert_eq!(heap.sweep(), None);
+        drop(chain);
     }
 
-    fn do_mark_roots(&self, tracer: &Tracer) {
-        // Walk the linked list to find roots
-        let mut current = self.head.load(Ordering::Acquire);
-        while !current.is_null() {
-            unsafe {
-                let header = &*current;
-                if header.is_root() {
-                    tracer.mark_header(header);
-                }
-                current = header.next.load(Ordering::Acquire);
+    #[test]
+    fn reorder_lists_sorts_by_address() {
+        let ctx = GcContext::off();
+        let heap = ctx.heap();
+        let mut keep = Vec::new();
+        for i in 0..1000 {
+            let root = ctx.allocate(i);
+            if i % 3 == 0 {
+                keep.push(root);
             }
         }
+        heap.force_collect();
+        // Fill some of the holes, scattering the list order
+        keep.extend((0..300).map(|i| ctx.allocate(i)));
 
-        // Merge roots into shared gray queue
-        self.merge_work(tracer);
+        assert!(heap.reorder_lists());
+        let addresses: Vec<_> = heap.iter_headers().map(|h| h as *const GcHeader).collect();
+        assert_eq!(addresses.len(), keep.len());
+        assert!(addresses.is_sorted());
+
+        // Still a valid heap afterwards
+        keep.truncate(10);
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 10);
     }
 
-    fn do_sweep(&self) -> usize {
-        self.start_sweeping();
+    #[test]
+    fn weak_upgrade_during_marking_keeps_children() {
+        struct Node(Option<crate::GcPtr<Node>>);
+        unsafe impl Trace for Node {
+            fn trace(&self, tracer: &Tracer) {
+                self.0.trace(tracer);
+            }
+        }
 
-        let mut freed = 0;
+        let ctx = GcContext::off();
+        let heap = ctx.heap();
+        let child = ctx.allocate(Node(None));
+        let parent = ctx.allocate(Node(Some(child.as_ptr())));
+        let weak = parent.downgrade();
+        drop(child);
+        drop(parent);
 
-        unsafe {
-            let mut current = self.head.load(Ordering::Acquire);
-            let mut prev_next: *const AtomicPtr<GcHeader> = &self.head;
+        // Upgrade after marking, before sweeping: `parent` is still white
+        assert!(heap.try_mark_full());
+        let parent = weak.upgrade().expect("not swept yet");
+        heap.sweep_and_finish();
 
-            while !current.is_null() {
-                let header = &*current;
-                let next = header.next.load(Ordering::Acquire);
+        // `child` was marked through the upgraded `parent`
+        assert_eq!(heap.allocation_count(), 2);
+        assert!(parent.0.is_some());
 
-                // Check if object should be collected
-                if header.is_white() {
-                    // Remove from list by updating previous node's next pointer
-                    (*prev_next).store(next, Ordering::Release);
+        drop(parent);
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 0);
+        assert!(weak.upgrade().is_none());
+    }
 
-                    // Get size from vtable and call drop function
-                    let size = header.vtable.layout.size();
-                    (header.vtable.drop)(current); // Proper Drop via Box::from_raw!
-                    freed += size;
+    #[test]
+    fn root_set_tracks_root_transitions() {
+        let ctx = GcContext::off();
+        let heap = ctx.heap();
+        let check_consistent = |expected: usize| {
+            let roots = heap.roots.lock();
+            assert_eq!(roots.0.len(), expected);
+            for (i, &header) in roots.0.iter().enumerate() {
+                let header = unsafe { &*header };
+                assert!(header.is_root());
+                assert_eq!(header.root_index.load(Ordering::Relaxed), i);
+            }
+        };
 
-                    // Move to next, keeping same prev
-                    current = next;
-                } else {
-                    // Reset color for next cycle
-                    header.color.reset_white();
+        let mut roots: Vec<_> = (0..20_000).map(|i| ctx.allocate(i)).collect();
+        check_consistent(20_000);
+        // Drop every other root; the rest stays reachable through `ptrs` only
+        let ptrs: Vec<_> = roots
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .map(|r| r.as_ptr())
+            .collect();
+        let mut i = 0;
+        roots.retain(|_| {
+            i += 1;
+            i % 2 == 1
+        });
+        check_consistent(10_000);
 
-                    // Move both forward
-                    prev_next = &header.next;
-                    current = next;
-                }
-            }
-        }
+        // Re-rooting and cloning only count transitions
+        let extra: Vec<_> = ptrs[..100].iter().map(|p| unsafe { p.root() }).collect();
+        let clones = roots[..100].to_vec();
+        check_consistent(10_100);
+        drop(extra);
+        drop(clones);
+        check_consistent(10_000);
 
-        let allocated = self.bytes_allocated.fetch_sub(freed, Ordering::Relaxed) - freed;
-        self.finish_gc();
-        allocated
-    }
+        // The root scan visits exactly the rooted headers
+        assert!(heap.try_start_marking());
+        assert_eq!(heap.do_mark_roots(&mut Tracer::new()), 10_000);
+        heap.sweep_and_finish();
+        assert_eq!(heap.allocation_count(), 10_000);
 
-    pub fn bytes_allocated(&self) -> usize {
-        self.bytes_allocated.load(Ordering::Relaxed)
+        drop(roots);
+        check_consistent(0);
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 0);
     }
 
-    pub fn allocation_count(&self) -> usize {
-        let mut count = 0;
-        let mut current = self.head.load(Ordering::Acquire);
+    #[test]
+    fn allocate_without_context_during_marking() {
+        use crate::{GcCell, GcPtr};
 
-        while !current.is_null() {
-            count += 1;
-            unsafe {
-                current = (*current).next.load(Ordering::Acquire);
+        struct Holder(GcCell<Option<GcPtr<u64>>>);
+        unsafe impl Trace for Holder {
+            fn trace(&self, tracer: &Tracer) {
+                self.0.trace(tracer);
             }
         }
 
-        count
+        // No `GcContext` on this thread
+        let heap = Heap::off();
+        let scanned = heap.allocate_rooted(Holder(GcCell::new(None)));
+        let unscanned = heap.allocate_rooted(Holder(GcCell::new(None)));
+        let moved = heap.allocate_rooted(7u64);
+        unscanned.0.set(Some(moved.as_ptr()));
+        drop(moved);
+
+        // Marking started, `scanned` was already scanned
+        assert!(heap.try_start_marking());
+        unsafe { &*scanned.as_ptr().header_ptr() }
+            .state
+            .mark_black();
+
+        // Allocated during marking: shaded right away
+        let fresh = heap.allocate_rooted(Holder(GcCell::new(None)));
+        assert!(!unsafe { &*fresh.as_ptr().header_ptr() }.state.is_white());
+        let fresh_int = heap.allocate_rooted(1u64);
+        assert_eq!(
+            unsafe { &*fresh_int.as_ptr().header_ptr() }.state.get(),
+            Color::Black
+        );
+
+        // Move the only reference into the scanned object; the barrier must
+        // shade it without a context
+        let ptr = unscanned.0.get();
+        unscanned.0.set(None);
+        scanned.0.set(ptr);
+
+        heap.do_mark_roots(&mut Tracer::new());
+        heap.do_mark_work_full(&mut Tracer::new());
+        heap.sweep_and_finish();
+        assert_eq!(heap.allocation_count(), 5);
+        let moved = unsafe { scanned.0.get().unwrap().root() };
+        assert_eq!(*moved, 7);
     }
 
-    pub fn start_background_collection(self: &Arc<Self>) -> bool {
-        if self.options.is_background_collection_off() || self.bg_thread.is_started() {
-            return false;
+    #[test]
+    fn auto_tune_adapts_step_budget_to_trace_cost() {
+        struct Costly(Duration);
+        unsafe impl Trace for Costly {
+            fn trace(&self, _tracer: &Tracer) {
+                let started = Instant::now();
+                while started.elapsed() < self.0 {
+                    std::hint::spin_loop();
+                }
+            }
         }
 
-        let heap_clone = Arc::clone(self);
-        self.bg_thread.start(move |c| {
-            background_gc_thread(heap_clone, c);
-        })
-    }
+        fn tuned_budget(trace_cost: Duration) -> usize {
+            let heap = Heap::with_options(GcOptions {
+                auto_tune: true,
+                auto_tune_step: Duration::from_micros(500),
+                ..GcOptions::OFF
+            });
+            let roots: Vec<_> = (0..200)
+                .map(|_| heap.allocate(Costly(trace_cost)))
+                .collect();
+            assert_eq!(
+                heap.incremental_work_budget(),
+                usize::MAX,
+                "not measured yet"
+            );
+            heap.force_collect();
+            assert_eq!(heap.allocation_count(), roots.len(), "marking completes");
+            heap.incremental_work_budget()
+        }
 
-    pub fn stop_background_collection(&self) -> bool {
-        self.bg_thread.stop()
+        let cheap = tuned_budget(Duration::ZERO);
+        let expensive = tuned_budget(Duration::from_micros(50));
+        assert!(expensive <= 10, "expensive budget {expensive}");
+        assert!(
+            cheap > expensive * 10,
+            "cheap {cheap} vs expensive {expensive}"
+        );
     }
-}
-
-impl Drop for Heap {
-    fn drop(&mut self) {
-        let mut current = self.head.load(Ordering::Acquire);
 
-        while !current.is_null() {
-            unsafe {
-                let header = &*current;
-                let next = header.next.load(Ordering::Acquire);
+    #[test]
+    fn thread_lists_are_marked_and_swept() {
+        let heap = Heap::with_options(GcOptions {
+            thread_alloc_batch: 100,
+            ..GcOptions::manual()
+        });
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let worker = {
+            let heap = Arc::clone(&heap);
+            std::thread::spawn(move || {
+                let ctx = GcContext::with_heap(heap);
+                let kept: Vec<_> = (0..10u32).map(|i| ctx.allocate(i)).collect();
+                for i in 0..10u32 {
+                    drop(ctx.allocate(i));
+                }
+                ready_tx.send(()).unwrap();
+                // Keep the context (and its list) alive while the main thread collects
+                done_rx.recv().unwrap();
+                assert!(kept.iter().map(|root| **root).eq(0..10));
+            })
+        };
+        ready_rx.recv().unwrap();
+        // Nothing was linked into the heap's list yet
+        assert!(heap.head.load(Ordering::Acquire).is_null());
 
-                // Use vtable drop for proper Drop semantics
-                (header.vtable.drop)(current);
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 10);
+        assert_eq!(heap.live_object_count(), 10);
+        done_tx.send(()).unwrap();
+        worker.join().unwrap();
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 0);
 
-                current = next;
-            }
-        }
+        // A full batch is linked by the allocating thread
+        let ctx = GcContext::with_heap(Arc::clone(&heap));
+        let batch: Vec<_> = (0..100u32).map(|i| ctx.allocate(i)).collect();
+        assert!(!heap.head.load(Ordering::Acquire).is_null());
+        drop(batch);
     }
 }
 
-/// Background GC thread that performs incremental marking and sweeping
-fn background_gc_thread(heap: Arc<Heap>, c: StopCondition) {
-    let tracer = Tracer::new();
-    while !heap.options.collection_interval.is_zero()
-        && !heap
-            .bg_thread
-            .wait_stopped(c, heap.options.collection_interval)
-    {
-        // Check if we should start a collection
-        if heap.should_collect() && heap.try_start_marking() {
-            // STW pause: scan roots
-            heap.do_mark_roots(&tracer);
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+    use super::*;
+    use crate::cell::GcCell;
 
-            // Incremental marking phase
-            loop {
-                if heap.bg_thread.is_stopped(c) {
-                    heap.finish_gc();
-                    return;
-                }
+    struct Node {
+        child: GcCell<Option<GcPtr<u32>>>,
+    }
 
-                let marking_complete =
-                    heap.do_mark_incremental(heap.options.incremental_work_budget);
-                if marking_complete {
-                    if !heap.yield_once_if_marking_busy() {
-                        break;
-                    }
-                } else {
-                    // Yield to allow mutators to make progress
-                    std::thread::yield_now();
-                }
-            }
+    unsafe impl Trace for Node {
+        fn trace(&self, tracer: &Tracer) {
+            self.child.trace(tracer);
+        }
+    }
 
-            // Sweeping phase and finish
-            heap.sweep_and_finish();
+    #[test]
+    fn manual_collection_from_explicit_roots() {
+        let heap = Heap::with_options(GcOptions::manual());
+        let root = heap.allocate(Node {
+            child: GcCell::new(None),
+        });
+        for i in 0..10 {
+            drop(heap.allocate(i));
         }
+        assert_eq!(heap.allocation_count(), 11);
+        assert_eq!(heap.force_collect().bytes_after, heap.bytes_allocated());
+        assert_eq!(heap.allocation_count(), 1);
+
+        // Stored while marking: the barrier shades the child without a context
+        assert!(heap.begin_mark());
+        while !heap.do_mark_work(10) {}
+        let child = heap.allocate(7u32);
+        root.child.set(Some(child.as_ptr()));
+        drop(child);
+        assert!(heap.sweep().is_some());
+        assert_eq!(heap.allocation_count(), 2);
+        let child = unsafe { root.child.get().unwrap().root() };
+        assert_eq!(*child, 7);
+        drop(child);
+
+        heap.collect();
+        root.child.set(None);
+        heap.force_collect();
+        assert_eq!(heap.allocation_count(), 1);
     }
 }
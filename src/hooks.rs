@@ -0,0 +1,199 @@
+//! Collection lifecycle hooks
+//!
+//! The heap reports the start and end of each collection phase to
+//! `CollectionHooks`. Integrations (the `metrics` and `tracing` features)
+//! and the user callback (`Heap::set_gc_callback`) are driven from here.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "metrics", feature = "tracing"))]
+use std::time::Instant;
+
+/// A collection lifecycle event, see [`Heap::set_gc_callback`](crate::Heap::set_gc_callback)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GcEvent {
+    /// A cycle started marking
+    MarkStart,
+    /// Marking is complete
+    MarkEnd {
+        /// Number of objects scanned during the mark phase
+        marked: usize,
+    },
+    /// The sweeper starts freeing unreachable objects
+    SweepStart,
+    /// The sweep is complete
+    SweepEnd {
+        freed_bytes: usize,
+        freed_objects: usize,
+    },
+}
+
+pub(crate) type GcCallback = Arc<dyn Fn(GcEvent) + Send + Sync>;
+
+/// Gauge: total bytes currently allocated on the heap
+#[cfg(feature = "metrics")]
+pub const BYTES_ALLOCATED: &str = "abfall_bytes_allocated";
+/// Counter: number of completed collection cycles
+#[cfg(feature = "metrics")]
+pub const COLLECTIONS_TOTAL: &str = "abfall_collections_total";
+/// Counter: number of objects freed by the sweeper
+#[cfg(feature = "metrics")]
+pub const OBJECTS_FREED_TOTAL: &str = "abfall_objects_freed_total";
+/// Histogram: duration of the mark phase in seconds
+#[cfg(feature = "metrics")]
+pub const MARK_DURATION_SECONDS: &str = "abfall_mark_duration_seconds";
+
+/// Per-heap state of the lifecycle hooks
+pub(crate) struct CollectionHooks {
+    /// Start of the current mark phase
+    #[cfg(feature = "metrics")]
+    mark_started: crate::sync::Mutex<Option<Instant>>,
+    /// Span of the current cycle, and its start
+    #[cfg(feature = "tracing")]
+    cycle: crate::sync::Mutex<Option<(tracing::Span, Instant)>>,
+    /// Objects scanned in the current mark phase
+    marked: AtomicUsize,
+    callback: crate::sync::Mutex<Option<GcCallback>>,
+}
+
+impl CollectionHooks {
+    pub fn new() -> Self {
+        #[cfg(feature = "metrics")]
+        {
+            metrics::describe_gauge!(
+                BYTES_ALLOCATED,
+                metrics::Unit::Bytes,
+                "Total bytes currently allocated on the GC heap"
+            );
+            metrics::describe_counter!(COLLECTIONS_TOTAL, "Number of completed GC cycles");
+            metrics::describe_counter!(
+                OBJECTS_FREED_TOTAL,
+                metrics::Unit::Count,
+                "Number of objects freed by the sweeper"
+            );
+            metrics::describe_histogram!(
+                MARK_DURATION_SECONDS,
+                metrics::Unit::Seconds,
+                "Duration of the mark phase"
+            );
+        }
+
+        Self {
+            #[cfg(feature = "metrics")]
+            mark_started: crate::sync::Mutex::new(None),
+            #[cfg(feature = "tracing")]
+            cycle: crate::sync::Mutex::new(None),
+            marked: AtomicUsize::new(0),
+            callback: crate::sync::Mutex::new(None),
+        }
+    }
+
+    pub fn set_callback(&self, callback: Option<GcCallback>) {
+        *self.callback.lock() = callback;
+    }
+
+    /// Invoke the user callback, without holding any lock
+    fn emit(&self, event: GcEvent) {
+        let callback = self.callback.lock().clone();
+        if let Some(callback) = callback {
+            callback(event);
+        }
+    }
+
+    /// Called after the heap transitioned from idle to marking
+    #[inline]
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn mark_start(&self, bytes_before: usize) {
+        #[cfg(feature = "metrics")]
+        {
+            *self.mark_started.lock() = Some(Instant::now());
+        }
+        #[cfg(feature = "tracing")]
+        {
+            // Not entered: the cycle may be finished on another thread
+            let span = tracing::info_span!(
+                "gc_cycle",
+                bytes_before,
+                freed_bytes = tracing::field::Empty,
+                live_bytes = tracing::field::Empty,
+                duration = tracing::field::Empty,
+            );
+            *self.cycle.lock() = Some((span, Instant::now()));
+        }
+        self.marked.store(0, Ordering::Relaxed);
+        self.emit(GcEvent::MarkStart);
+    }
+
+    /// Called after a marking step scanned `count` objects
+    #[inline]
+    pub fn count_marked(&self, count: usize) {
+        self.marked.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Objects scanned in the current mark phase so far
+    #[inline]
+    pub fn marked(&self) -> usize {
+        self.marked.load(Ordering::Relaxed)
+    }
+
+    /// Called when marking is complete, right before sweeping starts
+    #[inline]
+    pub fn mark_end(&self) {
+        #[cfg(feature = "metrics")]
+        if let Some(started) = self.mark_started.lock().take() {
+            metrics::histogram!(MARK_DURATION_SECONDS).record(started.elapsed());
+        }
+        self.emit(GcEvent::MarkEnd {
+            marked: self.marked.load(Ordering::Relaxed),
+        });
+    }
+
+    /// Called right before the heap aborts the process
+    #[cold]
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn abort(&self, message: core::fmt::Arguments<'_>) {
+        #[cfg(feature = "tracing")]
+        tracing::error!("{message}");
+    }
+
+    /// Called when the sweeper starts
+    #[inline]
+    pub fn sweep_start(&self) {
+        self.emit(GcEvent::SweepStart);
+    }
+
+    /// Called after the sweeper has freed all unreachable objects
+    #[inline]
+    #[cfg_attr(
+        not(any(feature = "metrics", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    pub fn sweep_end(&self, freed_bytes: usize, freed_objects: usize, bytes_allocated: usize) {
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!(COLLECTIONS_TOTAL).increment(1);
+            metrics::counter!(OBJECTS_FREED_TOTAL).increment(freed_objects as u64);
+            metrics::gauge!(BYTES_ALLOCATED).set(bytes_allocated as f64);
+        }
+        #[cfg(feature = "tracing")]
+        if let Some((span, started)) = self.cycle.lock().take() {
+            let duration = started.elapsed();
+            span.record("freed_bytes", freed_bytes);
+            span.record("live_bytes", bytes_allocated);
+            span.record("duration", tracing::field::debug(duration));
+            tracing::info!(
+                parent: &span,
+                freed_bytes,
+                freed_objects,
+                live_bytes = bytes_allocated,
+                ?duration,
+                "collection finished"
+            );
+        }
+        self.emit(GcEvent::SweepEnd {
+            freed_bytes,
+            freed_objects,
+        });
+    }
+}
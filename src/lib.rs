@@ -10,10 +10,12 @@
 //! - **Concurrent Collection**: Background thread performs collection without stopping application
 //! - **Thread-Safe**: Safe to use across multiple threads
 //! - **Manual Control**: Option to disable automatic collection and trigger manually
+//!   (`GcOptions::manual`)
 //!
 //! # Example
 //!
 //! ```
+//! # #[cfg(feature = "std")] {
 //! use abfall::GcContext;
 //! use std::sync::Arc;
 //!
@@ -27,28 +29,70 @@
 //! // Access through Deref
 //! assert_eq!(*value, 42);
 //! assert_eq!(*text, "Hello, GC!");
+//! # }
 //! ```
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature the crate only needs `alloc`. There is
+//! no background thread, no thread-local `GcContext` and no global heap:
+//! objects are allocated with [`Heap::allocate`] and stay alive while a
+//! [`GcRoot`] to them exists, and collection only happens through
+//! [`Heap::collect`] and [`Heap::force_collect`]. A heap may still be shared
+//! between threads: its locks spin while another thread holds them.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+mod arena;
 mod cell;
+pub mod collections;
 mod color;
+mod error;
+mod ffi;
+mod finalize;
+#[cfg(feature = "std")]
 mod gc;
 mod gc_box;
+#[cfg(feature = "std")]
+pub mod global;
+mod guard;
 mod heap;
+mod hooks;
+mod phase;
 mod ptr;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod sync;
 mod trace;
 
-pub use cell::GcCell;
-pub use gc::GcContext;
-pub use heap::{GcOptions, Heap};
-pub use ptr::{GcPtr, GcRoot};
-pub use trace::{Trace, Tracer};
-
-#[cfg(test)]
+pub use cell::{AtomicGcCell, GcCell, GcRef, GcRefCell, GcRefMut};
+pub use color::Color;
+pub use error::Error;
+pub use ffi::{GcHandle, abfall_deref, abfall_root, abfall_unroot};
+pub use finalize::Finalize;
+#[cfg(feature = "std")]
+pub use gc::{GcContext, GcContextHandle};
+pub use gc_box::{GcBox, GcHeader};
+pub use guard::ReadGuard;
+pub use heap::{
+    AllocError, BarrierKind, CollectionResult, GcOptions, GcOptionsBuilder, GcPhase, GcStats, Heap,
+    LimitAction, ObjectInfo, OomAction, PhaseTimings, WeakStats,
+};
+pub use hooks::GcEvent;
+pub use ptr::{Erased, GcAny, GcErased, GcPtr, GcRoot, GcWeak, PinnedGc};
+pub use trace::{ErasedRoot, Trace, TraceAny, Tracer};
+
+/// Derive macro for [`Trace`](trait@Trace), see [`abfall_derive`]
+#[cfg(feature = "derive")]
+pub use abfall_derive::Trace;
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use crate::heap::GcOptions;
 
     use super::*;
-    use std::time::Duration;
 
     #[test]
     fn basic_allocation() {
@@ -67,6 +111,8 @@ mod tests {
         assert_eq!(*ptr1, 100);
     }
 
+    // Locks panic on contention in single-threaded builds
+    #[cfg(not(feature = "single-threaded"))]
     #[test]
     fn concurrent_collection() {
         use std::sync::Arc;
@@ -106,11 +152,8 @@ mod tests {
 
         let peak_bytes = heap.bytes_allocated();
 
-        // Give background GC time to collect
-        thread::sleep(Duration::from_millis(300));
-
-        // Force a final collection
-        heap.force_collect();
+        // A final collection, after the background one that may be running
+        heap.collect_and_wait();
 
         // Verify roots are still alive
         for (i, root) in roots.iter().enumerate() {
@@ -145,4 +188,50 @@ mod tests {
         // Root should still be alive
         assert_eq!(root.len(), 3);
     }
+
+    #[test]
+    fn redundant_marks_are_counted() {
+        struct Pair(GcPtr<u32>, GcPtr<u32>);
+        unsafe impl Trace for Pair {
+            fn trace(&self, tracer: &Tracer) {
+                self.0.trace(tracer);
+                self.1.trace(tracer);
+            }
+        }
+
+        let ctx = GcContext::off();
+        let child = ctx.allocate(7u32).as_ptr();
+        let _pair = ctx.allocate(Pair(child, child));
+        assert_eq!(ctx.heap().redundant_marks(), 0);
+
+        // The second edge to `child` finds it already black
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().redundant_marks(), 1);
+        assert_eq!(ctx.heap().allocation_count(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "single-threaded")]
+    fn single_threaded_collects_synchronously() {
+        use std::time::Duration;
+        let ctx = GcContext::with_options(GcOptions {
+            collection_interval: Duration::from_millis(1),
+            min_threshold_bytes: 0,
+            ..GcOptions::DEFAULT
+        });
+        assert!(!ctx.heap().start_background_collection());
+        assert!(!ctx.heap().kick_background());
+
+        let keep = ctx.allocate(1);
+        for i in 0..100 {
+            let _garbage = ctx.allocate(i);
+        }
+        // Nothing collects in the background
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(ctx.heap().allocation_count(), 101);
+
+        ctx.heap().collect();
+        assert_eq!(ctx.heap().allocation_count(), 1);
+        assert_eq!(*keep, 1);
+    }
 }
@@ -0,0 +1,137 @@
+//! Collection phase state machine
+//!
+//! The phase moves `Idle → Marking → Sweeping → Idle`. Write barriers and
+//! allocations that shade objects while marking register as busy first, and
+//! the sweeper waits for them after leaving the marking phase.
+//!
+//! Both sides of this handshake use read-modify-write operations (which
+//! always read the latest value): a barrier increments the busy count and then
+//! reads the phase, the sweeper swaps the phase and then reads the busy count.
+//! Whichever of the two RMWs on the busy count comes second synchronizes with
+//! the first, so either the sweeper sees the barrier, or the barrier sees the
+//! phase change.
+
+use crate::heap::GcPhase;
+use crate::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+pub(crate) struct PhaseState {
+    phase: AtomicU8,
+    /// Barriers and allocations currently shading objects
+    busy: AtomicUsize,
+}
+
+impl PhaseState {
+    pub fn new() -> Self {
+        Self {
+            phase: AtomicU8::new(GcPhase::Idle as u8),
+            busy: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn get(&self) -> GcPhase {
+        GcPhase::from(self.phase.load(Ordering::Acquire))
+    }
+
+    /// Leave the idle phase for `phase`, returns false if not idle
+    pub fn try_start(&self, phase: GcPhase) -> bool {
+        self.phase
+            .compare_exchange(
+                GcPhase::Idle as u8,
+                phase as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    /// Register as busy if marking, see `Heap::check_is_marking_and_increment_busy`
+    pub fn enter_busy(&self) -> bool {
+        self.busy.fetch_add(1, Ordering::SeqCst);
+        if GcPhase::from(self.phase.load(Ordering::SeqCst)) == GcPhase::Marking {
+            true
+        } else {
+            self.busy.fetch_sub(1, Ordering::AcqRel);
+            false
+        }
+    }
+
+    /// Leave after `enter_busy` returned true
+    #[inline]
+    pub fn leave_busy(&self) {
+        self.busy.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Some barrier is still shading
+    ///
+    /// An RMW rather than a load, for the handshake with `enter_busy`.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.busy.fetch_add(0, Ordering::SeqCst) > 0
+    }
+
+    /// Leave the marking phase; barriers entering from now on don't shade
+    pub fn start_sweeping(&self) {
+        self.phase.swap(GcPhase::Sweeping as u8, Ordering::SeqCst);
+    }
+
+    /// Return to the idle phase
+    #[inline]
+    pub fn finish(&self) {
+        self.phase.store(GcPhase::Idle as u8, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use crate::{AtomicGcCell, GcOptions, GcPhase, Heap};
+    use loom::sync::Arc;
+
+    /// The roots `A` and `R` are being collected while the mutator moves `B`
+    /// from `R` to `A`, and allocates `D` into `R`
+    #[test]
+    fn loom_barriers_and_allocation_racing_the_sweep() {
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+        model.max_branches = 100_000;
+        model.check(|| {
+            let heap = Arc::new(Heap::with_options(GcOptions::manual()));
+            let b = heap.allocate(7);
+            let r = heap.allocate(AtomicGcCell::new(Some(b.as_ptr())));
+            let a = heap.allocate(AtomicGcCell::new(None));
+            let b = {
+                let weak = b.downgrade();
+                drop(b);
+                weak
+            };
+            assert!(heap.begin_mark());
+
+            let mutator = {
+                let heap = Arc::clone(&heap);
+                let (a, r) = (a.clone(), r.clone());
+                loom::thread::spawn(move || {
+                    // Only reachable from this thread in between: the guard
+                    // keeps the sweeper out
+                    let guard = heap.read_guard();
+                    let b = r.swap(None);
+                    a.store(b);
+                    drop(guard);
+                    let d = heap.allocate(8);
+                    r.store(Some(d.as_ptr()));
+                    let weak = d.downgrade();
+                    drop(d);
+                    weak
+                })
+            };
+            // Traces A, the sweep finishes marking
+            heap.do_mark_work(1);
+            assert!(heap.sweep().is_some());
+            let d = mutator.join().unwrap();
+
+            assert_eq!(heap.phase(), GcPhase::Idle);
+            assert_eq!(*b.upgrade().expect("B was swept"), 7);
+            assert_eq!(*d.upgrade().expect("D was swept"), 8);
+            assert_eq!(heap.allocation_count(), 4);
+        });
+    }
+}
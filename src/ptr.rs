@@ -6,11 +6,18 @@
 //! `GcRoot<T>` is a rooted pointer that manages root counts and implements Deref
 //! for access to the underlying value. Objects remain alive as long as at least
 //! one `GcRoot` exists pointing to them.
+//!
+//! `GcWeak<T>` is a weak reference that does not keep the object alive, but can
+//! be upgraded to a `GcRoot` while the object was not collected.
 
 use crate::gc_box::{GcBox, GcHeader};
-use crate::{Trace, Tracer};
-use std::ops::Deref;
-use std::ptr::NonNull;
+use crate::{Trace, TraceAny, Tracer};
+use alloc::sync::Arc;
+use core::any::TypeId;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::ptr::NonNull;
 
 /// Lightweight pointer to a GC-managed object
 ///
@@ -28,7 +35,6 @@ pub struct GcPtr<T: ?Sized>(NonNull<GcBox<T>>);
 impl<T: ?Sized> GcPtr<T> {
     /// Create a GcPtr from a raw pointer (for internal use or future API)
     #[inline]
-    #[allow(dead_code)]
     pub(crate) fn new(ptr: NonNull<GcBox<T>>) -> Self {
         Self(ptr)
     }
@@ -51,6 +57,36 @@ impl<T: ?Sized> GcPtr<T> {
         }
     }
 
+    /// Create a weak reference to the object, without rooting it
+    ///
+    /// The counterpart of [`GcWeak::upgrade`] for unrooted pointers, e.g.
+    /// read from a field.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be valid and point to a live GC object.
+    #[inline]
+    pub unsafe fn downgrade(self) -> GcWeak<T> {
+        GcWeak::new(self.0)
+    }
+
+    /// Convert this pointer to a rooted pointer, unless the object was swept
+    ///
+    /// Unlike [`root`](Self::root), this detects a collected object and
+    /// returns `None`, and it is safe to call concurrently with a sweep.
+    ///
+    /// # Safety
+    ///
+    /// The header must still be allocated: the object is live, or it was
+    /// swept while a [`GcWeak`] to it existed (which keeps a tombstone
+    /// header). Without weak references the memory of a swept object is
+    /// released, and no check can be done on it; use `GcWeak::upgrade` for
+    /// references that may outlive their object.
+    pub unsafe fn try_root(self) -> Option<GcRoot<T>> {
+        let header = unsafe { &self.0.as_ref().header };
+        header.heap().try_root(header).then(|| GcRoot(self))
+    }
+
     /// Get a raw pointer to the managed object
     ///
     /// # Safety
@@ -62,6 +98,48 @@ impl<T: ?Sized> GcPtr<T> {
         unsafe { &self.0.as_ref().data as *const T }
     }
 
+    /// Number of `GcWeak`s pointing to this object
+    ///
+    /// # Safety
+    ///
+    /// The object must be live, e.g. reachable from a root. Use
+    /// [`GcRoot::weak_count`] otherwise.
+    #[inline]
+    pub unsafe fn weak_count(&self) -> usize {
+        unsafe { self.0.as_ref().header.weak_count() }
+    }
+
+    /// Check whether both pointers point to the same object, like `Rc::ptr_eq`
+    #[inline]
+    pub fn ptr_eq(&self, other: &GcPtr<T>) -> bool {
+        self.0.cast::<u8>() == other.0.cast::<u8>()
+    }
+
+    /// Convert to a pointer to an unsized type, like `dyn Trait`
+    ///
+    /// `CoerceUnsized` is unstable, so the coercion is done by `coerce` on
+    /// the pointer to the box. The [`unsize!`](crate::unsize) macro passes a
+    /// closure that can do nothing else. The header stays at offset 0 and the
+    /// object is still traced and dropped through its allocated type.
+    ///
+    /// # Safety
+    ///
+    /// `coerce` must return its argument, only with added metadata.
+    #[inline]
+    pub unsafe fn unsize<U: ?Sized>(
+        self,
+        coerce: impl FnOnce(NonNull<GcBox<T>>) -> NonNull<GcBox<U>>,
+    ) -> GcPtr<U> {
+        let ptr = coerce(self.0);
+        debug_assert_eq!(ptr.cast::<u8>(), self.0.cast::<u8>());
+        GcPtr(ptr)
+    }
+
+    #[inline]
+    pub(crate) fn as_non_null(self) -> NonNull<GcBox<T>> {
+        self.0
+    }
+
     /// Get the header pointer for this object (internal use)
     #[inline]
     pub(crate) fn header_ptr(&self) -> *const GcHeader {
@@ -76,6 +154,133 @@ impl<T: ?Sized> Clone for GcPtr<T> {
     }
 }
 
+// Equality and hashing are by identity (the object address), not by value.
+// Metadata of unsized pointers is ignored.
+impl<T: ?Sized> PartialEq for GcPtr<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl<T: ?Sized> Eq for GcPtr<T> {}
+
+// The pointee can't be read without rooting, so only show the address
+impl<T: ?Sized> fmt::Debug for GcPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GcPtr({:p})", self.0.cast::<u8>())
+    }
+}
+
+impl<T: ?Sized> Hash for GcPtr<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.cast::<u8>().hash(state);
+    }
+}
+
+impl<T: Trace + 'static> GcPtr<T> {
+    /// Erase the type of the object, see [`GcAny`]
+    ///
+    /// Records the type in the object's header, for [`is`](Self::is).
+    ///
+    /// # Safety
+    ///
+    /// The object must be live, e.g. reachable from a root. Use
+    /// [`GcRoot::into_any`] otherwise.
+    #[inline]
+    pub unsafe fn into_any(self) -> GcAny {
+        unsafe { self.0.as_ref() }.header.record_type::<T>();
+        let ptr: NonNull<GcBox<dyn TraceAny>> = self.0;
+        GcPtr(ptr)
+    }
+}
+
+impl<T: ?Sized + 'static> GcPtr<T> {
+    /// Check whether the object is a `U`
+    ///
+    /// Uses the type the object was allocated with, so this also works for
+    /// type-erased pointers like [`GcAny`]. The type is only known for objects
+    /// that were passed through [`into_any`](Self::into_any) (or
+    /// [`GcRoot::into_any`]), where it is known to outlive `'static`: for any
+    /// other object, e.g. one found by [`Heap::roots`](crate::Heap::roots),
+    /// this is false.
+    ///
+    /// # Safety
+    ///
+    /// The object must be live, e.g. reachable from a root: the type is read
+    /// from its header. Use [`GcRoot::is`] otherwise.
+    #[inline]
+    pub unsafe fn is<U: ?Sized + 'static>(&self) -> bool {
+        unsafe { self.0.as_ref() }.header.type_id() == Some(TypeId::of::<U>())
+    }
+
+    /// Cast to a pointer to the concrete type `U`, if the object is a `U`
+    ///
+    /// Like any `GcPtr`, the result has to be rooted to access the object.
+    ///
+    /// # Safety
+    ///
+    /// The object must be live, see [`is`](Self::is). Use
+    /// [`GcRoot::downcast`] otherwise.
+    #[inline]
+    pub unsafe fn downcast<U: 'static>(self) -> Option<GcPtr<U>> {
+        unsafe { self.is::<U>() }.then(|| GcPtr(self.0.cast()))
+    }
+}
+
+/// Type-erased pointer to an object of any `'static` [`Trace`] type
+///
+/// Created with [`GcPtr::into_any`] (or [`GcRoot::into_any`]), for
+/// interpreters and containers of heterogeneous objects. The object is traced
+/// like through a pointer of its concrete type, which is recovered with
+/// [`GcPtr::downcast`] or [`GcRoot::downcast_ref`].
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcAny, GcContext};
+///
+/// let ctx = GcContext::off();
+/// let number = ctx.allocate(42u32);
+/// let text = ctx.allocate(String::from("text"));
+/// // Live while `number` and `text` are rooted
+/// let values: Vec<GcAny> = unsafe { vec![number.as_ptr().into_any(), text.as_ptr().into_any()] };
+/// assert!(unsafe { values[0].is::<u32>() });
+/// assert!(unsafe { values[1].downcast::<u32>() }.is_none());
+/// let text = unsafe { values[1].root() };
+/// assert_eq!(text.downcast_ref::<String>().unwrap(), "text");
+/// # }
+/// ```
+pub type GcAny = GcPtr<dyn TraceAny>;
+
+/// Pointer to an object of unknown type, see [`Heap::roots`](crate::Heap::roots)
+///
+/// Identifies the object, and recovers its type with [`GcPtr::is`] and
+/// [`GcPtr::downcast`] if it was erased to a [`GcAny`] before: otherwise the
+/// type may borrow, and is unknown. Like any `GcPtr` it keeps the object
+/// alive when traced, through the object's own type. The data is only
+/// accessible after a downcast.
+pub type GcErased = GcPtr<Erased>;
+
+/// Stands in for the unknown type of the object behind a [`GcErased`]
+pub struct Erased {
+    _private: (),
+}
+
+// Never called: the object behind a `GcErased` is traced by its own vtable
+unsafe impl Trace for Erased {
+    fn trace(&self, _tracer: &Tracer) {}
+}
+
+impl GcPtr<Erased> {
+    #[inline]
+    pub(crate) fn from_header(header: &GcHeader) -> Self {
+        GcPtr(NonNull::from(header).cast())
+    }
+}
+
 unsafe impl<T: Send> Send for GcPtr<T> {}
 unsafe impl<T: Sync> Sync for GcPtr<T> {}
 
@@ -111,6 +316,94 @@ impl<T: ?Sized> GcRoot<T> {
     pub fn as_ptr(&self) -> GcPtr<T> {
         self.0
     }
+
+    /// Number of `GcWeak`s pointing to this object
+    #[inline]
+    pub fn weak_count(&self) -> usize {
+        // SAFETY: rooted
+        unsafe { self.0.weak_count() }
+    }
+
+    /// Create a weak reference to the object
+    ///
+    /// Doesn't change the root count: the root keeps the object alive until
+    /// it is dropped, and [`GcWeak::upgrade`] creates a new one.
+    #[doc(alias = "as_weak")]
+    #[inline]
+    pub fn downgrade(&self) -> GcWeak<T> {
+        GcWeak::new(self.0.0)
+    }
+
+    /// Consume the root, transferring its root count to the returned pointer
+    ///
+    /// The object stays alive until the pointer is turned back into a
+    /// `GcRoot` with [`GcRoot::from_raw`] and dropped. Like `Rc::into_raw`,
+    /// forgetting to do so leaks the object.
+    #[inline]
+    pub fn into_raw(self) -> NonNull<GcBox<T>> {
+        let ptr = self.0.0;
+        core::mem::forget(self);
+        ptr
+    }
+
+    /// Reclaim a root from a pointer returned by [`GcRoot::into_raw`]
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `into_raw`, and each pointer returned by
+    /// `into_raw` must be passed to `from_raw` at most once; otherwise the
+    /// root count is released twice.
+    #[inline]
+    pub unsafe fn from_raw(ptr: NonNull<GcBox<T>>) -> Self {
+        Self(GcPtr(ptr))
+    }
+
+    /// Convert to a root of an unsized type, keeping the root, see
+    /// [`GcPtr::unsize`]
+    ///
+    /// # Safety
+    ///
+    /// `coerce` must return its argument, only with added metadata.
+    #[inline]
+    pub unsafe fn unsize<U: ?Sized>(
+        self,
+        coerce: impl FnOnce(NonNull<GcBox<T>>) -> NonNull<GcBox<U>>,
+    ) -> GcRoot<U> {
+        let ptr = unsafe { self.0.unsize(coerce) };
+        core::mem::forget(self);
+        GcRoot(ptr)
+    }
+}
+
+/// Coerce a [`GcPtr`] or [`GcRoot`] to an unsized type, like `dyn Trait`
+///
+/// The stable counterpart of an implicit unsizing coercion: it only compiles
+/// where `Box<T>` would coerce to `Box<U>`.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::{GcContext, GcRoot, unsize};
+/// use std::fmt::Display;
+///
+/// let ctx = GcContext::off();
+/// let values: Vec<GcRoot<dyn Display>> = vec![
+///     unsize!(ctx.allocate(42u32) => dyn Display),
+///     unsize!(ctx.allocate(String::from("text")) => dyn Display),
+/// ];
+/// assert_eq!(values[0].to_string(), "42");
+/// assert_eq!(values[1].to_string(), "text");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! unsize {
+    ($ptr:expr => $ty:ty) => {
+        match $ptr {
+            // SAFETY: the closure only compiles as an identity or unsizing coercion
+            ptr => unsafe { ptr.unsize(|ptr| -> ::core::ptr::NonNull<$crate::GcBox<$ty>> { ptr }) },
+        }
+    };
 }
 
 impl<T: ?Sized> Deref for GcRoot<T> {
@@ -140,12 +433,247 @@ impl<T: ?Sized> Drop for GcRoot<T> {
     }
 }
 
+// Identity, like `GcPtr`; compare `*a == *b` for value equality
+impl<T: ?Sized> PartialEq for GcRoot<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: ?Sized> Eq for GcRoot<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for GcRoot<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for GcRoot<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Hash for GcRoot<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<T: Trace + 'static> GcRoot<T> {
+    /// Erase the type of the object, keeping the root, see [`GcAny`]
+    #[inline]
+    pub fn into_any(self) -> GcRoot<dyn TraceAny> {
+        // SAFETY: rooted
+        GcRoot(unsafe { GcPtr(self.into_raw()).into_any() })
+    }
+}
+
+impl<T: ?Sized + 'static> GcRoot<T> {
+    /// Check whether the object is a `U`, see [`GcPtr::is`]
+    #[inline]
+    pub fn is<U: ?Sized + 'static>(&self) -> bool {
+        // SAFETY: rooted
+        unsafe { self.0.is::<U>() }
+    }
+
+    /// Get a reference to the object as a `U`, if it is one
+    #[inline]
+    pub fn downcast_ref<U: 'static>(&self) -> Option<&U> {
+        // SAFETY: rooted for the lifetime of `self`
+        let ptr = unsafe { self.0.downcast::<U>() }?;
+        Some(unsafe { &ptr.0.as_ref().data })
+    }
+
+    /// Cast to a root of the concrete type `U`, or give the root back
+    #[inline]
+    pub fn downcast<U: 'static>(self) -> Result<GcRoot<U>, Self> {
+        // SAFETY: rooted
+        match unsafe { self.0.downcast::<U>() } {
+            Some(ptr) => {
+                core::mem::forget(self);
+                Ok(GcRoot(ptr))
+            }
+            None => Err(self),
+        }
+    }
+}
+
 unsafe impl<T: Send> Send for GcRoot<T> {}
 unsafe impl<T: Sync> Sync for GcRoot<T> {}
 
+/// Handle to an object that is never collected while it is pinned
+///
+/// Created with [`Heap::pin`](crate::Heap::pin). Unlike a [`GcRoot`], the
+/// object is not rooted by a count in its header but by the heap's pin set:
+/// clones share one entry, so cloning and dropping a clone don't touch the
+/// object or the heap. The object is unpinned when the last clone is
+/// dropped, and collected once it is unreachable afterwards.
+pub struct PinnedGc<T: ?Sized>(Arc<Pinned<T>>);
+
+/// The pin set entry shared by the clones of a `PinnedGc`
+struct Pinned<T: ?Sized>(GcPtr<T>);
+
+impl<T: ?Sized> Drop for Pinned<T> {
+    fn drop(&mut self) {
+        let header = self.0.header_ptr();
+        unsafe { &*header }.heap().unpin(header);
+    }
+}
+
+impl<T: ?Sized> PinnedGc<T> {
+    /// Create the handle of an object in the pin set
+    #[inline]
+    pub(crate) fn new(ptr: GcPtr<T>) -> Self {
+        Self(Arc::new(Pinned(ptr)))
+    }
+
+    /// Get the underlying GcPtr
+    ///
+    /// Use this to store non-rooting references in data structures.
+    #[inline]
+    pub fn as_ptr(&self) -> GcPtr<T> {
+        self.0.0
+    }
+}
+
+impl<T: ?Sized> Deref for PinnedGc<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: pinned for the lifetime of `self`
+        unsafe { &self.0.0.0.as_ref().data }
+    }
+}
+
+impl<T: ?Sized> Clone for PinnedGc<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PinnedGc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Weak reference to a GC-managed object
+///
+/// A `GcWeak` does not keep its object alive. Use [`GcWeak::upgrade`] to get
+/// a `GcRoot` while the object was not collected yet. After the object was
+/// swept, its data is dropped, but a small tombstone (the header) stays
+/// allocated until the last `GcWeak` is dropped, so `upgrade` can detect it.
+///
+/// Unlike a `GcRoot`, a `GcWeak` may outlive the heap of its object; it then
+/// keeps such a tombstone, and `upgrade` fails. It must not be used while
+/// another thread drops the heap, though.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use abfall::GcContext;
+///
+/// let ctx = GcContext::off();
+/// let root = ctx.allocate(42);
+/// let weak = root.downgrade();
+/// assert_eq!(weak.upgrade().map(|r| *r), Some(42));
+///
+/// drop(root);
+/// ctx.heap().force_collect();
+/// assert!(weak.upgrade().is_none());
+/// # }
+/// ```
+pub struct GcWeak<T: ?Sized>(NonNull<GcBox<T>>);
+
+impl<T: ?Sized> GcWeak<T> {
+    fn new(ptr: NonNull<GcBox<T>>) -> Self {
+        let header = unsafe { &ptr.as_ref().header };
+        header.inc_weak();
+        if let Some(heap) = header.attached_heap() {
+            heap.count_weak_created();
+        }
+        Self(ptr)
+    }
+
+    /// Create a weak reference from a pointer to a live object
+    #[cfg(feature = "std")]
+    pub(crate) fn from_ptr(ptr: GcPtr<T>) -> Self {
+        Self::new(ptr.0)
+    }
+
+    #[inline]
+    pub(crate) fn header(&self) -> &GcHeader {
+        // SAFETY: the weak count keeps the header allocated, even after the
+        // object was swept. Only the header is accessed here.
+        unsafe { &*(self.0.as_ptr() as *const GcHeader) }
+    }
+
+    /// Number of `GcRoot`s of the object, 0 once it was collected
+    ///
+    /// For diagnostics only: roots may be created or dropped concurrently,
+    /// and an object without roots may still be reachable.
+    #[inline]
+    pub fn strong_count(&self) -> usize {
+        self.header().root_count()
+    }
+
+    /// Get a rooted pointer to the object, unless it was collected
+    ///
+    /// Counted in [`Heap::weak_stats`](crate::Heap::weak_stats).
+    pub fn upgrade(&self) -> Option<GcRoot<T>> {
+        let header = self.header();
+        let heap = header.attached_heap()?;
+        if heap.upgrade_weak(header) {
+            Some(GcRoot(GcPtr(self.0)))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for GcWeak<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new(self.0)
+    }
+}
+
+impl<T: ?Sized> Drop for GcWeak<T> {
+    fn drop(&mut self) {
+        if let Some(heap) = self.header().attached_heap() {
+            heap.count_weak_dropped();
+        }
+        unsafe { GcHeader::release_weak(self.0.as_ptr() as *mut GcHeader) };
+    }
+}
+
+impl<T: ?Sized> From<&GcRoot<T>> for GcWeak<T> {
+    #[inline]
+    fn from(root: &GcRoot<T>) -> Self {
+        root.downgrade()
+    }
+}
+
+unsafe impl<T: Send> Send for GcWeak<T> {}
+unsafe impl<T: Sync> Sync for GcWeak<T> {}
+
 // GcPtr implements Trace - it marks itself as reachable
-unsafe impl<T: Trace> Trace for GcPtr<T> {
+unsafe impl<T: Trace + ?Sized> Trace for GcPtr<T> {
     fn trace(&self, tracer: &Tracer) {
         tracer.mark(self);
     }
 }
+
+// Weak edges don't keep their target alive, so there is nothing to mark
+unsafe impl<T: ?Sized> Trace for GcWeak<T> {
+    const NO_TRACE: bool = true;
+    fn trace(&self, _tracer: &Tracer) {}
+}
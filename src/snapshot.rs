@@ -0,0 +1,330 @@
+//! Serde support for object graphs (`serde` feature)
+//!
+//! A [`GcRoot<T>`] serializes the whole graph reachable from it as a flat
+//! table. Every object gets an id in the order the graph is traced, starting
+//! with the root at 0, and a [`GcPtr`] serializes as the id of its target:
+//!
+//! ```text
+//! { "root": 0, "nodes": [{ "value": ..., "edges": [1, ...] }, ...] }
+//! ```
+//!
+//! `edges` lists the ids reported by the object's `Trace` impl. Shared
+//! objects and cycles are written once. All objects of a graph must have the
+//! type of the root, and every pointer reported by `Trace` must also be
+//! serialized (and the other way around).
+//!
+//! Deserializing a `GcRoot<T>` allocates the graph on the heap of the
+//! thread's `GcContext`. Objects are reserved when first referenced (like
+//! with `allocate_with`), so pointers can refer to objects that come later
+//! in the table.
+
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use serde::de::Error as _;
+use serde::ser::{Error as _, SerializeSeq, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::cell::{GcCell, GcRefCell};
+use crate::gc::with_current_context;
+use crate::gc_box::{GcBox, GcHeader};
+use crate::heap::Heap;
+use crate::ptr::{GcPtr, GcRoot};
+use crate::trace::{Trace, Tracer};
+
+thread_local! {
+    static SERIALIZING: RefCell<Option<SerializeSession>> = const { RefCell::new(None) };
+    static DESERIALIZING: RefCell<Option<DeserializeSession>> = const { RefCell::new(None) };
+}
+
+const NESTED: &str = "a `GcRoot` can't be part of a serialized graph";
+const OTHER_TYPE: &str = "all objects of a serialized graph must have the type of the root";
+
+/// The graph being serialized on this thread
+struct SerializeSession {
+    type_id: TypeId,
+    ids: HashMap<*const GcHeader, u64>,
+    /// Objects reached through a serialized `GcPtr` of the right type
+    verified: Vec<bool>,
+}
+
+/// The graph being deserialized on this thread
+struct DeserializeSession {
+    type_id: TypeId,
+    heap: Arc<Heap>,
+    /// Reserved objects by id
+    slots: HashMap<u64, NonNull<u8>>,
+    release: unsafe fn(NonNull<u8>),
+}
+
+/// Ends the session of this thread, releasing uncommitted reservations
+struct DeserializeGuard;
+
+impl Drop for DeserializeGuard {
+    fn drop(&mut self) {
+        if let Some(session) = DESERIALIZING.with(|s| s.borrow_mut().take()) {
+            for slot in session.slots.into_values() {
+                unsafe { (session.release)(slot) };
+            }
+        }
+    }
+}
+
+struct SerializeGuard;
+
+impl Drop for SerializeGuard {
+    fn drop(&mut self) {
+        SERIALIZING.with(|s| s.borrow_mut().take());
+    }
+}
+
+/// Assign ids to the objects reachable from `root`, and collect their edges
+fn walk(
+    root: *const GcHeader,
+) -> (
+    Vec<*const GcHeader>,
+    HashMap<*const GcHeader, u64>,
+    Vec<Vec<u64>>,
+) {
+    let mut tracer = Tracer::new_edges();
+    let mut order = vec![root];
+    let mut ids = HashMap::from([(root, 0)]);
+    let mut edges = Vec::new();
+    let mut targets = Vec::new();
+    let mut next = 0;
+    while let Some(&header) = order.get(next) {
+        unsafe { ((*header).vtable.trace)(header, &tracer) };
+        tracer.append_to(&mut targets);
+        let node_edges = targets
+            .drain(..)
+            .map(|target| {
+                *ids.entry(target).or_insert_with(|| {
+                    order.push(target);
+                    order.len() as u64 - 1
+                })
+            })
+            .collect();
+        edges.push(node_edges);
+        next += 1;
+    }
+    (order, ids, edges)
+}
+
+#[derive(Serialize)]
+struct NodeRef<'a, T> {
+    value: &'a T,
+    edges: &'a [u64],
+}
+
+/// The table of objects, written in id order
+struct Nodes<'a, T> {
+    order: &'a [*const GcHeader],
+    edges: &'a [Vec<u64>],
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T: Trace + Serialize> Serialize for Nodes<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.order.len()))?;
+        for (id, (&header, edges)) in self.order.iter().zip(self.edges).enumerate() {
+            // Known to be a `T` once a `GcPtr<T>` to it was serialized
+            let verified =
+                SERIALIZING.with(|s| s.borrow().as_ref().is_some_and(|s| s.verified[id]));
+            if !verified {
+                return Err(S::Error::custom(format_args!(
+                    "object {id} is reported by `Trace` but not serialized"
+                )));
+            }
+            let value = unsafe { &(*(header as *const GcBox<T>)).data };
+            seq.serialize_element(&NodeRef { value, edges })?;
+        }
+        seq.end()
+    }
+}
+
+impl<T: Trace + Serialize + 'static> Serialize for GcRoot<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if SERIALIZING.with(|s| s.borrow().is_some()) {
+            return Err(S::Error::custom(NESTED));
+        }
+        let root = self.as_ptr().header_ptr();
+        // Objects only reachable through the graph are not rooted
+        let _read_guard = unsafe { (*root).heap() }.read_guard();
+        let (order, ids, edges) = walk(root);
+        let mut verified = vec![false; order.len()];
+        verified[0] = true;
+        SERIALIZING.with(|s| {
+            *s.borrow_mut() = Some(SerializeSession {
+                type_id: TypeId::of::<T>(),
+                ids,
+                verified,
+            })
+        });
+        let _session = SerializeGuard;
+
+        let mut graph = serializer.serialize_struct("GcGraph", 2)?;
+        graph.serialize_field("root", &0u64)?;
+        graph.serialize_field(
+            "nodes",
+            &Nodes::<T> {
+                order: &order,
+                edges: &edges,
+                _marker: std::marker::PhantomData,
+            },
+        )?;
+        graph.end()
+    }
+}
+
+impl<T: Trace + 'static> Serialize for GcPtr<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let id = SERIALIZING
+            .with(|s| {
+                let mut session = s.borrow_mut();
+                let session = session
+                    .as_mut()
+                    .ok_or("a `GcPtr` can only be serialized as part of a `GcRoot` graph")?;
+                if session.type_id != TypeId::of::<T>() {
+                    return Err(OTHER_TYPE);
+                }
+                let id = *session
+                    .ids
+                    .get(&self.header_ptr())
+                    .ok_or("a serialized `GcPtr` is not reported by `Trace`")?;
+                session.verified[id as usize] = true;
+                Ok(id)
+            })
+            .map_err(S::Error::custom)?;
+        serializer.serialize_u64(id)
+    }
+}
+
+impl<'de, T: Trace + 'static> Deserialize<'de> for GcPtr<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u64::deserialize(deserializer)?;
+        DESERIALIZING.with(|s| {
+            let mut session = s.borrow_mut();
+            let session = session.as_mut().ok_or_else(|| {
+                D::Error::custom("a `GcPtr` can only be deserialized as part of a `GcRoot` graph")
+            })?;
+            if session.type_id != TypeId::of::<T>() {
+                return Err(D::Error::custom(OTHER_TYPE));
+            }
+            let slot = match session.slots.entry(id) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let ptr = session.heap.reserve::<T>().map_err(D::Error::custom)?;
+                    *entry.insert(ptr.cast())
+                }
+            };
+            Ok(GcPtr::new(slot.cast()))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct NodeRecord<T> {
+    value: T,
+    #[serde(rename = "edges")]
+    _edges: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+struct GraphRecord<T> {
+    root: u64,
+    nodes: Vec<NodeRecord<T>>,
+}
+
+impl<'de, T: Trace + Deserialize<'de> + 'static> Deserialize<'de> for GcRoot<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut heap = None;
+        with_current_context(|ctx| heap = Some(Arc::clone(&ctx.heap)));
+        let heap = heap.ok_or_else(|| {
+            D::Error::custom("deserializing a `GcRoot` needs a `GcContext` on this thread")
+        })?;
+        if DESERIALIZING.with(|s| s.borrow().is_some()) {
+            return Err(D::Error::custom(NESTED));
+        }
+        unsafe fn release<T: Trace>(slot: NonNull<u8>) {
+            unsafe { Heap::release(slot.cast::<GcBox<T>>()) };
+        }
+        DESERIALIZING.with(|s| {
+            *s.borrow_mut() = Some(DeserializeSession {
+                type_id: TypeId::of::<T>(),
+                heap: Arc::clone(&heap),
+                slots: HashMap::new(),
+                release: release::<T>,
+            })
+        });
+        let _session = DeserializeGuard;
+
+        let graph = GraphRecord::<T>::deserialize(deserializer)?;
+        let len = graph.nodes.len() as u64;
+        if graph.root >= len {
+            return Err(D::Error::custom("the root id is not in the table"));
+        }
+
+        // Take the reservations; the guard releases them if anything fails
+        let mut slots = DESERIALIZING.with(|s| {
+            let mut session = s.borrow_mut();
+            std::mem::take(&mut session.as_mut().unwrap().slots)
+        });
+        let commit = (|| {
+            if let Some(id) = slots.keys().find(|&&id| id >= len) {
+                return Err(D::Error::custom(format_args!(
+                    "object {id} is not in the table"
+                )));
+            }
+            for id in 0..len {
+                if let Entry::Vacant(entry) = slots.entry(id) {
+                    entry.insert(heap.reserve::<T>().map_err(D::Error::custom)?.cast());
+                }
+            }
+            Ok(())
+        })();
+        if let Err(err) = commit {
+            DESERIALIZING.with(|s| s.borrow_mut().as_mut().unwrap().slots = slots);
+            return Err(err);
+        }
+
+        let mut roots: Vec<GcRoot<T>> = graph
+            .nodes
+            .into_iter()
+            .enumerate()
+            .map(|(id, node)| {
+                let slot = slots.remove(&(id as u64)).unwrap();
+                unsafe { heap.commit(slot.cast(), node.value) }
+            })
+            .collect();
+        Ok(roots.swap_remove(graph.root as usize))
+    }
+}
+
+impl<T: Trace + Copy + Serialize> Serialize for GcCell<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+impl<'de, T: Trace + Deserialize<'de>> Deserialize<'de> for GcCell<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(GcCell::new)
+    }
+}
+
+/// Panics if the cell is mutably borrowed, like `borrow`
+impl<T: Trace + Serialize> Serialize for GcRefCell<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.borrow().serialize(serializer)
+    }
+}
+
+impl<'de, T: Trace + Deserialize<'de>> Deserialize<'de> for GcRefCell<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(GcRefCell::new)
+    }
+}
@@ -0,0 +1,369 @@
+//! Locking primitives used by the heap
+//!
+//! By default these are the `parking_lot` types. With the `single-threaded`
+//! feature (for targets without threads, like `wasm32-unknown-unknown`) they
+//! are replaced by non-blocking equivalents: acquiring a lock that is already
+//! held panics instead of waiting, since no other thread could release it,
+//! and waiting on a `Condvar` returns immediately. Builds without `std` use
+//! them as well, but spin while a lock is held, since the heap may still be
+//! shared with other threads there. With `--cfg loom` they are `loom`'s, so
+//! the heap itself can be model checked.
+
+#[cfg(all(feature = "std", not(feature = "single-threaded"), not(loom)))]
+pub(crate) use parking_lot::{Condvar, Mutex, RwLock, RwLockReadGuard};
+
+#[cfg(all(feature = "std", not(feature = "single-threaded"), loom))]
+pub(crate) use model::{Condvar, Mutex, RwLock, RwLockReadGuard};
+
+#[cfg(any(feature = "single-threaded", not(feature = "std")))]
+pub(crate) use single::{Condvar, Mutex, RwLock, RwLockReadGuard};
+
+/// Other threads can run (and release locks) while one waits
+pub(crate) const THREADS: bool = cfg!(all(feature = "std", not(feature = "single-threaded")));
+
+/// Atomics of the phase state machine, object states and links
+///
+/// With `--cfg loom` these are `loom`'s, so the heap can be model checked
+/// (see the loom tests in `phase`).
+pub(crate) mod atomic {
+    pub(crate) use core::sync::atomic::Ordering;
+    #[cfg(not(loom))]
+    pub(crate) use core::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize};
+    #[cfg(loom)]
+    pub(crate) use loom::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize};
+}
+
+/// Let other threads run while waiting (a scheduling point under loom)
+#[inline]
+pub(crate) fn yield_now() {
+    #[cfg(loom)]
+    loom::thread::yield_now();
+    #[cfg(all(not(loom), feature = "std"))]
+    std::thread::yield_now();
+    #[cfg(all(not(loom), not(feature = "std")))]
+    core::hint::spin_loop();
+}
+
+#[cfg(any(feature = "single-threaded", not(feature = "std")))]
+mod single {
+    use core::cell::UnsafeCell;
+    use core::ops::{Deref, DerefMut};
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use core::time::Duration;
+
+    const WRITER: usize = usize::MAX;
+
+    /// Another thread might hold the lock (builds without `std`)
+    const SPIN: bool = cfg!(not(feature = "single-threaded"));
+
+    #[cold]
+    #[track_caller]
+    fn already_locked() -> ! {
+        panic!("lock is already held (deadlock in a single-threaded build)")
+    }
+
+    /// Wait for the holder of a lock to release it, or panic if there is
+    /// no other thread
+    #[inline]
+    #[track_caller]
+    fn contended() {
+        if SPIN {
+            core::hint::spin_loop();
+        } else {
+            already_locked();
+        }
+    }
+
+    pub(crate) struct Mutex<T> {
+        locked: AtomicBool,
+        value: UnsafeCell<T>,
+    }
+
+    unsafe impl<T: Send> Send for Mutex<T> {}
+    unsafe impl<T: Send> Sync for Mutex<T> {}
+
+    impl<T> Mutex<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        #[track_caller]
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            while self.locked.swap(true, Ordering::Acquire) {
+                contended();
+            }
+            MutexGuard { mutex: self }
+        }
+    }
+
+    pub(crate) struct MutexGuard<'a, T> {
+        mutex: &'a Mutex<T>,
+    }
+
+    impl<T> Deref for MutexGuard<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            unsafe { &*self.mutex.value.get() }
+        }
+    }
+
+    impl<T> DerefMut for MutexGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            unsafe { &mut *self.mutex.value.get() }
+        }
+    }
+
+    impl<T> Drop for MutexGuard<'_, T> {
+        fn drop(&mut self) {
+            self.mutex.locked.store(false, Ordering::Release);
+        }
+    }
+
+    /// Reader-writer lock; the state counts readers or is `WRITER`
+    pub(crate) struct RwLock<T> {
+        state: AtomicUsize,
+        value: UnsafeCell<T>,
+    }
+
+    unsafe impl<T: Send> Send for RwLock<T> {}
+    unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+    impl<T> RwLock<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self {
+                state: AtomicUsize::new(0),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        #[track_caller]
+        pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+            let mut readers = self.state.load(Ordering::Relaxed);
+            loop {
+                if readers >= WRITER - 1 {
+                    contended();
+                    readers = self.state.load(Ordering::Relaxed);
+                    continue;
+                }
+                match self.state.compare_exchange_weak(
+                    readers,
+                    readers + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return RwLockReadGuard { lock: self },
+                    Err(actual) => readers = actual,
+                }
+            }
+        }
+
+        /// Readers never wait for writers here, so this is just `read`
+        #[track_caller]
+        pub(crate) fn read_recursive(&self) -> RwLockReadGuard<'_, T> {
+            self.read()
+        }
+
+        #[track_caller]
+        pub(crate) fn write(&self) -> RwLockWriteGuard<'_, T> {
+            while self
+                .state
+                .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                contended();
+            }
+            RwLockWriteGuard { lock: self }
+        }
+
+        pub(crate) fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+            self.state
+                .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                .ok()
+                .map(|_| RwLockWriteGuard { lock: self })
+        }
+    }
+
+    pub(crate) struct RwLockReadGuard<'a, T> {
+        lock: &'a RwLock<T>,
+    }
+
+    impl<T> Deref for RwLockReadGuard<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            unsafe { &*self.lock.value.get() }
+        }
+    }
+
+    impl<T> Drop for RwLockReadGuard<'_, T> {
+        fn drop(&mut self) {
+            self.lock.state.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    pub(crate) struct RwLockWriteGuard<'a, T> {
+        lock: &'a RwLock<T>,
+    }
+
+    impl<T> Deref for RwLockWriteGuard<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            unsafe { &*self.lock.value.get() }
+        }
+    }
+
+    impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            unsafe { &mut *self.lock.value.get() }
+        }
+    }
+
+    impl<T> Drop for RwLockWriteGuard<'_, T> {
+        fn drop(&mut self) {
+            self.lock.state.store(0, Ordering::Release);
+        }
+    }
+
+    /// Condition variable that never blocks
+    pub(crate) struct Condvar;
+
+    impl Condvar {
+        pub(crate) const fn new() -> Self {
+            Self
+        }
+
+        pub(crate) fn notify_all(&self) {}
+
+        /// Returns immediately: nothing could notify a single thread while it waits
+        pub(crate) fn wait_for<T>(&self, _guard: &mut MutexGuard<'_, T>, _timeout: Duration) {}
+
+        /// Returns immediately, like `wait_for`
+        #[cfg(feature = "std")]
+        pub(crate) fn wait<T>(&self, _guard: &mut MutexGuard<'_, T>) {}
+    }
+}
+
+/// The `parking_lot` API on top of `loom`'s locks
+#[cfg(all(feature = "std", not(feature = "single-threaded"), loom))]
+mod model {
+    use core::ops::{Deref, DerefMut};
+    use core::time::Duration;
+
+    pub(crate) struct Mutex<T>(loom::sync::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Self(loom::sync::Mutex::new(value))
+        }
+
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            MutexGuard {
+                mutex: &self.0,
+                guard: Some(self.0.lock().unwrap()),
+            }
+        }
+    }
+
+    pub(crate) struct MutexGuard<'a, T> {
+        mutex: &'a loom::sync::Mutex<T>,
+        /// Taken out while waiting on a `Condvar`
+        guard: Option<loom::sync::MutexGuard<'a, T>>,
+    }
+
+    impl<T> Deref for MutexGuard<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            self.guard.as_ref().unwrap()
+        }
+    }
+
+    impl<T> DerefMut for MutexGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            self.guard.as_mut().unwrap()
+        }
+    }
+
+    pub(crate) struct RwLock<T>(loom::sync::RwLock<T>);
+
+    pub(crate) type RwLockReadGuard<'a, T> = loom::sync::RwLockReadGuard<'a, T>;
+
+    impl<T> RwLock<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Self(loom::sync::RwLock::new(value))
+        }
+
+        pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+            self.0.read().unwrap()
+        }
+
+        pub(crate) fn read_recursive(&self) -> RwLockReadGuard<'_, T> {
+            self.read()
+        }
+
+        pub(crate) fn write(&self) -> loom::sync::RwLockWriteGuard<'_, T> {
+            self.0.write().unwrap()
+        }
+
+        pub(crate) fn try_write(&self) -> Option<loom::sync::RwLockWriteGuard<'_, T>> {
+            self.0.try_write().ok()
+        }
+    }
+
+    pub(crate) struct Condvar(loom::sync::Condvar);
+
+    impl Condvar {
+        pub(crate) fn new() -> Self {
+            Self(loom::sync::Condvar::new())
+        }
+
+        pub(crate) fn notify_all(&self) {
+            self.0.notify_all();
+        }
+
+        pub(crate) fn wait<T>(&self, guard: &mut MutexGuard<'_, T>) {
+            guard.guard = Some(self.0.wait(guard.guard.take().unwrap()).unwrap());
+        }
+
+        /// Unlocks and lets other threads run: loom can't time out, and a
+        /// timed wait may return at any point anyway
+        pub(crate) fn wait_for<T>(&self, guard: &mut MutexGuard<'_, T>, _timeout: Duration) {
+            drop(guard.guard.take());
+            loom::thread::yield_now();
+            guard.guard = Some(guard.mutex.lock().unwrap());
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    extern crate std;
+
+    use super::{Mutex, RwLock};
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn locks_wait_for_other_threads() {
+        let counter = Arc::new(Mutex::new(0));
+        let total = Arc::new(RwLock::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let (counter, total) = (Arc::clone(&counter), Arc::clone(&total));
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        *counter.lock() += 1;
+                        *total.write() += 1;
+                        assert!(*total.read() > 0);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*counter.lock(), 4000);
+        assert_eq!(*total.read(), 4000);
+    }
+}
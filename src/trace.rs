@@ -5,38 +5,326 @@
 //! mark reachable objects.
 
 use crate::gc_box::GcHeader;
-use std::{
-    cell::UnsafeCell,
-    collections::{BTreeSet, HashSet, VecDeque},
+use alloc::borrow::{Cow, ToOwned};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, BinaryHeap, LinkedList, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{
+    cell::{Cell, UnsafeCell},
     convert::Infallible,
 };
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+/// Objects visited by a shadow mark
+#[cfg(feature = "std")]
+type VisitedSet = HashSet<*const GcHeader>;
+#[cfg(not(feature = "std"))]
+type VisitedSet = BTreeSet<*const GcHeader>;
+
+/// Nesting depth of owned values traced recursively, see `Tracer::mark_deferred`
+const MAX_TRACE_DEPTH: usize = 128;
+
+/// A value whose tracing was queued by `Tracer::trace_nested`
+struct DeferredTrace {
+    value: *const (),
+    trace: unsafe fn(*const (), &Tracer),
+}
+
+/// Drops the values queued in a `Tracer::drain_after` scope, even on unwind
+struct DeferredScope<'a> {
+    tracer: &'a Tracer,
+    base: usize,
+    depth: usize,
+}
+
+impl Drop for DeferredScope<'_> {
+    fn drop(&mut self) {
+        unsafe { &mut *self.tracer.deferred.get() }.truncate(self.base);
+        self.tracer.depth.set(self.depth);
+    }
+}
 
 /// A tracer for marking reachable objects
 ///
 /// Used during the mark phase to traverse the object graph.
 /// Each thread can have its own tracer that accumulates gray objects,
 /// which are then merged back to the shared gray queue.
-pub struct Tracer(UnsafeCell<Vec<*const GcHeader>>);
+///
+/// [`Trace::trace`] takes `&Tracer`: the local queue uses interior
+/// mutability, and `mark` only borrows it for the push itself, never while
+/// calling back into a `trace` implementation. A `Tracer` is neither `Send`
+/// nor `Sync`, so these borrows can't overlap:
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<abfall::Tracer>();
+/// ```
+///
+/// Only `mark` and the other methods for `Trace` impls take `&self`. The
+/// collector moves work in and out of the local queue with methods taking
+/// `&mut self` (`steal_from`, `pop_work`, `append_to`, `move_to`), so it
+/// owns the tracer while doing so. Every marking thread (the parallel
+/// markers of `GcOptions::mark_threads` included) owns a tracer of its own;
+/// threads only share work through the heap's gray queue, behind a lock.
+pub struct Tracer {
+    queue: UnsafeCell<Vec<*const GcHeader>>,
+    /// Objects visited by a shadow mark, which doesn't touch the real colors
+    shadow: Option<UnsafeCell<VisitedSet>>,
+    /// Marks of objects that were already shaded (wasted work)
+    redundant_marks: Cell<usize>,
+    /// Set when an object could not be traced, see `mark_incomplete`
+    incomplete: Cell<bool>,
+    /// Objects with ephemeron tables waiting for their keys, see
+    /// `defer_ephemeron`
+    ephemerons: UnsafeCell<Vec<*const GcHeader>>,
+    /// Number of objects this tracer shaded
+    shaded: Cell<usize>,
+    /// Nesting depth of `trace_nested`
+    depth: Cell<usize>,
+    /// Values queued once `depth` reached `MAX_TRACE_DEPTH`
+    deferred: UnsafeCell<Vec<DeferredTrace>>,
+    /// Don't mark old objects (minor collections)
+    skip_old: bool,
+    /// Only record every marked object in the queue, see `new_edges`
+    edges_only: bool,
+    /// Set if `shadow`, `skip_old` or `edges_only` is: keeps their checks
+    /// off the hot path of `mark`
+    special: bool,
+    /// Decide ephemerons by the colors of their keys, see `new_check`
+    #[cfg(feature = "std")]
+    check: bool,
+    /// The object being traced and the mark cycle, see `owner`
+    owner: Cell<(*const GcHeader, usize)>,
+    /// The owner is old after this cycle, see `owner_is_old`
+    owner_old: Cell<bool>,
+}
 
 impl Tracer {
     /// Create a new tracer without heap reference (for internal GC use)
     pub(crate) fn new() -> Self {
-        Self(UnsafeCell::new(Vec::new()))
+        Self {
+            queue: UnsafeCell::new(Vec::new()),
+            shadow: None,
+            redundant_marks: Cell::new(0),
+            incomplete: Cell::new(false),
+            ephemerons: UnsafeCell::new(Vec::new()),
+            shaded: Cell::new(0),
+            depth: Cell::new(0),
+            deferred: UnsafeCell::new(Vec::new()),
+            skip_old: false,
+            edges_only: false,
+            special: false,
+            #[cfg(feature = "std")]
+            check: false,
+            owner: Cell::new((core::ptr::null(), 0)),
+            owner_old: Cell::new(false),
+        }
+    }
+
+    /// Create a tracer that records visited objects in a side table
+    /// instead of coloring them
+    pub(crate) fn new_shadow() -> Self {
+        Self {
+            queue: UnsafeCell::new(Vec::new()),
+            shadow: Some(UnsafeCell::new(VisitedSet::new())),
+            redundant_marks: Cell::new(0),
+            incomplete: Cell::new(false),
+            ephemerons: UnsafeCell::new(Vec::new()),
+            shaded: Cell::new(0),
+            depth: Cell::new(0),
+            deferred: UnsafeCell::new(Vec::new()),
+            skip_old: false,
+            edges_only: false,
+            special: true,
+            #[cfg(feature = "std")]
+            check: false,
+            owner: Cell::new((core::ptr::null(), 0)),
+            owner_old: Cell::new(false),
+        }
+    }
+
+    /// Create a tracer for minor collections, which stops at old objects
+    pub(crate) fn new_minor() -> Self {
+        Self {
+            skip_old: true,
+            special: true,
+            ..Self::new()
+        }
+    }
+
+    /// Create a tracer that collects the outgoing pointers of the traced
+    /// objects in order (duplicates included), without coloring anything
+    #[cfg(feature = "serde")]
+    pub(crate) fn new_edges() -> Self {
+        Self {
+            edges_only: true,
+            special: true,
+            ..Self::new()
+        }
+    }
+
+    /// Create a tracer that collects the outgoing pointers of the traced
+    /// objects, for checking them against the colors after marking
+    ///
+    /// Unlike `new_edges`, ephemeron values are only followed for keys
+    /// that marking reached, like marking itself does.
+    #[cfg(all(debug_assertions, feature = "std"))]
+    pub(crate) fn new_check() -> Self {
+        Self {
+            edges_only: true,
+            check: true,
+            special: true,
+            ..Self::new()
+        }
+    }
+
+    /// Check whether a shadow mark visited the object
+    pub(crate) fn shadow_visited(&self, header: *const GcHeader) -> bool {
+        self.shadow
+            .as_ref()
+            .is_some_and(|visited| unsafe { &*visited.get() }.contains(&header))
+    }
+
+    /// Take the number of redundant marks counted since the last call
+    pub(crate) fn take_redundant_marks(&self) -> usize {
+        self.redundant_marks.take()
+    }
+
+    /// Record that an object's children could not be traced
+    ///
+    /// The current cycle then keeps all objects alive instead of sweeping.
+    pub(crate) fn mark_incomplete(&self) {
+        self.incomplete.set(true);
+    }
+
+    /// Take the incomplete flag set since the last call
+    pub(crate) fn take_incomplete(&self) -> bool {
+        self.incomplete.take()
+    }
+
+    /// Set the object whose fields are traced next, in mark cycle `cycle`
+    ///
+    /// Cells remember it for the incremental-update barrier. A null object
+    /// (or cycle 0) means the traced values are not part of an object. `old`
+    /// tells the cells that the object is in the old generation once this
+    /// cycle is swept, see `owner_is_old`.
+    pub(crate) fn set_owner(&self, header: *const GcHeader, cycle: usize, old: bool) {
+        self.owner.set((header, cycle));
+        self.owner_old.set(old);
+    }
+
+    /// The traced values belong to an old object (generational mode)
+    ///
+    /// Cells then add the young objects stored into them to the remembered
+    /// set, see `Heap::remember`.
+    #[inline]
+    pub(crate) fn owner_is_old(&self) -> bool {
+        self.owner_old.get()
+    }
+
+    /// Whether this tracer belongs to a minor collection
+    #[inline]
+    pub(crate) fn is_minor(&self) -> bool {
+        self.skip_old
     }
+
+    /// The object being traced and its mark cycle, see `set_owner`
+    #[inline]
+    pub(crate) fn owner(&self) -> (*const GcHeader, usize) {
+        self.owner.get()
+    }
+
+    #[inline]
+    fn count_redundant_mark(&self) {
+        self.redundant_marks.set(self.redundant_marks.get() + 1);
+    }
+
+    #[inline]
+    fn count_shaded(&self) {
+        self.shaded.set(self.shaded.get() + 1);
+    }
+
+    /// Number of objects shaded by this tracer so far
+    ///
+    /// Marking made progress if this changed.
+    #[inline]
+    pub(crate) fn shaded(&self) -> usize {
+        self.shaded.get()
+    }
+
+    /// Check whether an object is known to be reachable to this tracer
+    ///
+    /// Decides whether the value of an ephemeron entry with this key is
+    /// traced. Swept objects never are.
+    #[cfg(feature = "std")]
+    pub(crate) fn is_marked(&self, header: &GcHeader) -> bool {
+        if header.is_dead() {
+            false
+        } else if self.edges_only && !self.check {
+            true
+        } else if self.shadow.is_some() {
+            self.shadow_visited(header)
+        } else {
+            !header.is_white() || (self.skip_old && header.is_old())
+        }
+    }
+
+    /// Retrace the object being traced once marking ran out of other work,
+    /// for an ephemeron table with keys that aren't marked yet
+    ///
+    /// Returns false if the table isn't traced as part of an object (see
+    /// `owner`), then its values have to be traced right away.
+    #[cfg(feature = "std")]
+    pub(crate) fn defer_ephemeron(&self) -> bool {
+        let (owner, _) = self.owner.get();
+        if owner.is_null() {
+            return false;
+        }
+        unsafe { &mut *self.ephemerons.get() }.push(owner);
+        true
+    }
+
+    /// Take the objects deferred by `defer_ephemeron` since the last call
+    pub(crate) fn take_ephemerons(&mut self) -> Vec<*const GcHeader> {
+        core::mem::take(self.ephemerons.get_mut())
+    }
+
+    /// Take the objects visited by a shadow mark
+    pub(crate) fn take_shadow_visited(&mut self) -> Vec<*const GcHeader> {
+        match &mut self.shadow {
+            Some(visited) => core::mem::take(visited.get_mut()).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
     /// Append this tracer's accumulated work to a destination
-    pub(crate) fn append_to(&self, dest: &mut Vec<*const GcHeader>) {
-        dest.append(unsafe { &mut *self.0.get() });
+    pub(crate) fn append_to(&mut self, dest: &mut Vec<*const GcHeader>) {
+        dest.append(self.queue.get_mut());
+    }
+
+    /// Move at most `max_items` of the accumulated work to a destination
+    pub(crate) fn move_to(&mut self, max_items: usize, dest: &mut Vec<*const GcHeader>) {
+        let queue = self.queue.get_mut();
+        let start = queue.len().saturating_sub(max_items);
+        dest.extend(queue.drain(start..));
     }
 
     /// Steal work from a list of gray objects
-    pub(crate) fn steal_from(&self, mut num_items: usize, src: &mut Vec<*const GcHeader>) -> bool {
+    pub(crate) fn steal_from(
+        &mut self,
+        mut num_items: usize,
+        src: &mut Vec<*const GcHeader>,
+    ) -> bool {
         if src.is_empty() || num_items == 0 {
             return false;
         }
         // move num_items from src to self
+        let queue = self.queue.get_mut();
         while num_items > 0 {
             if let Some(item) = src.pop() {
-                unsafe { &mut *self.0.get() }.push(item);
+                queue.push(item);
                 num_items -= 1;
             } else {
                 break;
@@ -46,34 +334,146 @@ impl Tracer {
     }
 
     /// Pop a gray object from local work queue
-    pub(crate) fn pop_work(&self) -> Option<*const GcHeader> {
-        unsafe { &mut *self.0.get() }.pop()
+    pub(crate) fn pop_work(&mut self) -> Option<*const GcHeader> {
+        self.queue.get_mut().pop()
     }
 
-    pub(crate) fn has_work(&self) -> bool {
-        !unsafe { &*self.0.get() }.is_empty()
+    pub(crate) fn has_work(&mut self) -> bool {
+        !self.queue.get_mut().is_empty()
     }
 
     /// Mark an object as reachable
     ///
     /// Adds the object to the gray queue for processing if it's currently white
-    pub fn mark<T: Trace>(&self, ptr: &crate::GcPtr<T>) {
-        let header_ptr = ptr.header_ptr();
-        unsafe {
-            let header = &*header_ptr;
-            if T::NO_TRACE {
-                // Immediately mark black if no tracing is needed
-                header.color.mark_black();
+    pub fn mark<T: Trace + ?Sized>(&self, ptr: &crate::GcPtr<T>) {
+        let header = unsafe { &*ptr.header_ptr() };
+        if self.special {
+            self.mark_special(header, T::NO_TRACE);
+        } else {
+            self.mark_color(header, T::NO_TRACE);
+        }
+    }
+
+    /// Shade a white object: gray, or black right away if `no_trace`
+    #[inline]
+    fn mark_color(&self, header: &GcHeader, no_trace: bool) {
+        if no_trace {
+            if header.state.is_white() {
+                self.count_shaded();
             } else {
-                self.mark_header(header);
+                self.count_redundant_mark();
+            }
+            // Immediately mark black if no tracing is needed
+            header.state.mark_black();
+        } else if header.state.mark_white_to_gray() {
+            // Enqueue for scanning
+            self.count_shaded();
+            unsafe { &mut *self.queue.get() }.push(header);
+        } else {
+            self.count_redundant_mark();
+        }
+    }
+
+    /// `mark` for the tracers that don't just color objects, see `special`
+    #[cold]
+    #[inline(never)]
+    fn mark_special(&self, header: &GcHeader, no_trace: bool) {
+        if self.edges_only {
+            unsafe { &mut *self.queue.get() }.push(header);
+        } else if let Some(visited) = &self.shadow {
+            // Shadow marks never need to scan NO_TRACE objects
+            if unsafe { &mut *visited.get() }.insert(header) {
+                self.count_shaded();
+                if !no_trace {
+                    unsafe { &mut *self.queue.get() }.push(header);
+                }
             }
+        } else if self.skip_old && header.is_old() {
+            // Old objects are alive until the next full collection
+        } else {
+            self.mark_color(header, no_trace);
+        }
+    }
+
+    /// Trace a value owned by the object being traced, without deep recursion
+    ///
+    /// Calling `trace` on a field recurses as deep as the owned data is
+    /// nested, and a long chain of boxes overflows the stack. Through this
+    /// method, values nested more than a fixed depth are queued instead, and
+    /// traced in a loop by the outermost call before it returns. Boxes and
+    /// the standard collections already go through it; use it for the
+    /// recursive fields of your own types.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use abfall::{GcPtr, Trace, Tracer};
+    ///
+    /// struct Link {
+    ///     target: GcPtr<u32>,
+    ///     next: Option<Box<Link>>,
+    /// }
+    ///
+    /// unsafe impl Trace for Link {
+    ///     fn trace(&self, tracer: &Tracer) {
+    ///         tracer.mark(&self.target);
+    ///         tracer.mark_deferred(&self.next);
+    ///     }
+    /// }
+    /// ```
+    pub fn mark_deferred<T: Trace>(&self, value: &T) {
+        unsafe fn trace_value<T: Trace>(value: *const (), tracer: &Tracer) {
+            unsafe { &*(value as *const T) }.trace(tracer);
+        }
+        if !T::NO_TRACE {
+            self.trace_nested(value as *const T as *const (), trace_value::<T>);
+        }
+    }
+
+    /// Call `trace` on `value` one level deeper, or queue it when nested
+    /// `MAX_TRACE_DEPTH` levels deep
+    ///
+    /// # Safety
+    ///
+    /// `trace` must accept `value`, which must stay valid until the
+    /// outermost `trace_nested` or `drain_after` returns.
+    pub(crate) fn trace_nested(&self, value: *const (), trace: unsafe fn(*const (), &Tracer)) {
+        let depth = self.depth.get();
+        if depth == 0 {
+            self.drain_after(|| unsafe { trace(value, self) });
+        } else if depth < MAX_TRACE_DEPTH {
+            self.depth.set(depth + 1);
+            unsafe { trace(value, self) };
+            self.depth.set(depth);
+        } else {
+            unsafe { &mut *self.deferred.get() }.push(DeferredTrace { value, trace });
         }
     }
 
+    /// Run `f` one level deeper, then trace the values it queued
+    ///
+    /// For impls that only borrow their contents while `f` runs, e.g. under
+    /// a lock: the queued values must not outlive the borrow.
+    pub(crate) fn drain_after<R>(&self, f: impl FnOnce() -> R) -> R {
+        let scope = DeferredScope {
+            tracer: self,
+            base: unsafe { &*self.deferred.get() }.len(),
+            depth: self.depth.get(),
+        };
+        self.depth.set(scope.depth + 1);
+        let result = f();
+        while unsafe { &*self.deferred.get() }.len() > scope.base {
+            let next = unsafe { &mut *self.deferred.get() }.pop().unwrap();
+            unsafe { (next.trace)(next.value, self) };
+        }
+        result
+    }
+
     pub(crate) fn mark_header(&self, header: &GcHeader) {
-        if header.color.mark_white_to_gray() {
-            // Enqueue for scanning
-            unsafe { &mut *self.0.get() }.push(header);
+        if self.special {
+            self.mark_special(header, false);
+        } else {
+            self.mark_color(header, false);
         }
     }
 }
@@ -86,6 +486,12 @@ impl Tracer {
 /// Failing to trace all GC pointers will result in premature collection
 /// and use-after-free bugs.
 ///
+/// Objects are freed by the sweeper using their regular `Drop`. Long chains of
+/// owned boxes inside a single object drop recursively and can overflow the
+/// stack; use [`DropList`](crate::collections::DropList) for those. Tracing
+/// through boxes and collections is bounded in depth, see
+/// [`Tracer::mark_deferred`].
+///
 /// # Example
 ///
 /// ```
@@ -105,12 +511,53 @@ impl Tracer {
 /// }
 /// ```
 pub unsafe trait Trace {
+    /// The type contains no GC pointers, so `trace` does nothing
+    ///
+    /// Objects of such types are marked black without being scanned, and
+    /// containers of them skip iterating their elements.
     const NO_TRACE: bool = false;
 
     /// Trace all GC pointers in this object
     fn trace(&self, tracer: &Tracer);
 }
 
+/// Roots of [`Heap::collect_with_roots`](crate::Heap::collect_with_roots)
+///
+/// An object-safe form of [`Trace`], implemented for every `Trace` type, so
+/// roots of different types can be passed together.
+pub trait ErasedRoot {
+    /// Trace the GC pointers of this root
+    fn trace_root(&self, tracer: &Tracer);
+}
+
+impl<T: Trace + ?Sized> ErasedRoot for T {
+    #[inline]
+    fn trace_root(&self, tracer: &Tracer) {
+        self.trace(tracer);
+    }
+}
+
+/// Object-safe form of [`Trace`] for type-erased objects, see [`GcAny`](crate::GcAny)
+///
+/// Implemented for every `'static` `Trace` type.
+pub trait TraceAny: core::any::Any {
+    /// Trace the GC pointers of this object
+    fn trace_any(&self, tracer: &Tracer);
+}
+
+impl<T: Trace + core::any::Any> TraceAny for T {
+    #[inline]
+    fn trace_any(&self, tracer: &Tracer) {
+        self.trace(tracer);
+    }
+}
+
+unsafe impl Trace for dyn TraceAny {
+    fn trace(&self, tracer: &Tracer) {
+        self.trace_any(tracer);
+    }
+}
+
 macro_rules! impl_no_trace {
     ($(impl$([$($tt:tt)*])? for $ty:ty);* $(;)?) => {
         $(
@@ -143,18 +590,60 @@ impl_no_trace! {
     impl for bool;
     impl for char;
     impl for String;
+    impl for str;
     impl for &str;
     impl for Infallible;
-    impl[T] for std::marker::PhantomData<T>;
+    impl[T] for core::marker::PhantomData<T>;
+    impl for core::time::Duration;
+    impl for core::num::NonZeroI8;
+    impl for core::num::NonZeroI16;
+    impl for core::num::NonZeroI32;
+    impl for core::num::NonZeroI64;
+    impl for core::num::NonZeroI128;
+    impl for core::num::NonZeroIsize;
+    impl for core::num::NonZeroU8;
+    impl for core::num::NonZeroU16;
+    impl for core::num::NonZeroU32;
+    impl for core::num::NonZeroU64;
+    impl for core::num::NonZeroU128;
+    impl for core::num::NonZeroUsize;
+    impl for core::net::IpAddr;
+    impl for core::net::Ipv4Addr;
+    impl for core::net::Ipv6Addr;
+    impl for core::net::SocketAddr;
+    impl for core::net::SocketAddrV4;
+    impl for core::net::SocketAddrV6;
+}
+
+#[cfg(feature = "std")]
+impl_no_trace! {
+    impl for std::time::Instant;
+    impl for std::time::SystemTime;
+    impl for std::path::PathBuf;
+    impl for std::path::Path;
+    impl for std::ffi::OsString;
+    impl for std::ffi::OsStr;
+}
+
+unsafe impl<T: Trace> Trace for core::num::Wrapping<T> {
+    const NO_TRACE: bool = T::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        self.0.trace(tracer);
+    }
 }
 
 macro_rules! impl_trace_deref {
     ($(impl<$i:ident> for $ty:ty);* $(;)?) => {
         $(
-            unsafe impl<$i: Trace> Trace for $ty {
+            unsafe impl<$i: Trace + ?Sized> Trace for $ty {
                 const NO_TRACE: bool = $i::NO_TRACE;
                 fn trace(&self, tracer: &Tracer) {
-                    $i::trace(self, tracer);
+                    unsafe fn trace_target<$i: Trace + ?Sized>(this: *const (), tracer: &Tracer) {
+                        $i::trace(unsafe { &*(this as *const $ty) }, tracer);
+                    }
+                    if !Self::NO_TRACE {
+                        tracer.trace_nested(self as *const Self as *const (), trace_target::<$i>);
+                    }
                 }
             }
         )*
@@ -163,8 +652,16 @@ macro_rules! impl_trace_deref {
 
 impl_trace_deref! {
     impl<T> for Box<T>;
-    impl<T> for std::rc::Rc<T>;
-    impl<T> for std::sync::Arc<T>;
+    impl<T> for alloc::rc::Rc<T>;
+    impl<T> for alloc::sync::Arc<T>;
+}
+
+/// Traces the borrowed or the owned value, through `Deref`
+unsafe impl<B: Trace + ToOwned + ?Sized> Trace for Cow<'_, B> {
+    const NO_TRACE: bool = B::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        B::trace(self, tracer);
+    }
 }
 
 macro_rules! impl_trace_iterable {
@@ -173,8 +670,13 @@ macro_rules! impl_trace_iterable {
             unsafe impl<$i: Trace> Trace for $ty {
                 const NO_TRACE: bool = $i::NO_TRACE;
                 fn trace(&self, tracer: &Tracer) {
-                    for item in self {
-                        item.trace(tracer);
+                    unsafe fn trace_items<$i: Trace>(this: *const (), tracer: &Tracer) {
+                        for item in unsafe { &*(this as *const $ty) } {
+                            item.trace(tracer);
+                        }
+                    }
+                    if !Self::NO_TRACE {
+                        tracer.trace_nested(self as *const Self as *const (), trace_items::<$i>);
                     }
                 }
             }
@@ -185,8 +687,14 @@ macro_rules! impl_trace_iterable {
 impl_trace_iterable! {
     impl<T> for Vec<T>;
     impl<T> for VecDeque<T>;
-    impl<T> for HashSet<T>;
     impl<T> for BTreeSet<T>;
+    impl<T> for LinkedList<T>;
+    impl<T> for BinaryHeap<T>;
+}
+
+#[cfg(feature = "std")]
+impl_trace_iterable! {
+    impl<T> for HashSet<T>;
 }
 
 macro_rules! impl_trace_map {
@@ -195,9 +703,14 @@ macro_rules! impl_trace_map {
             unsafe impl<$i: Trace,$j: Trace> Trace for $ty {
                 const NO_TRACE: bool = $i::NO_TRACE && $j::NO_TRACE;
                 fn trace(&self, tracer: &Tracer) {
-                    for (k,v) in self.iter() {
-                        k.trace(tracer);
-                        v.trace(tracer);
+                    unsafe fn trace_entries<$i: Trace, $j: Trace>(this: *const (), tracer: &Tracer) {
+                        for (k, v) in unsafe { &*(this as *const $ty) }.iter() {
+                            k.trace(tracer);
+                            v.trace(tracer);
+                        }
+                    }
+                    if !Self::NO_TRACE {
+                        tracer.trace_nested(self as *const Self as *const (), trace_entries::<$i, $j>);
                     }
                 }
             }
@@ -206,8 +719,40 @@ macro_rules! impl_trace_map {
 }
 
 impl_trace_map! {
-    impl<K,V> for std::collections::HashMap<K,V>;
-    impl<K,V> for std::collections::BTreeMap<K,V>;
+    impl<K,V> for BTreeMap<K,V>;
+}
+
+#[cfg(feature = "std")]
+impl_trace_map! {
+    impl<K,V> for HashMap<K,V>;
+}
+
+macro_rules! impl_trace_tuple {
+    ($(($($i:ident $n:tt),+));* $(;)?) => {
+        $(
+            unsafe impl<$($i: Trace),+> Trace for ($($i,)+) {
+                const NO_TRACE: bool = $($i::NO_TRACE)&&+;
+                fn trace(&self, tracer: &Tracer) {
+                    $(self.$n.trace(tracer);)+
+                }
+            }
+        )*
+    };
+}
+
+impl_trace_tuple! {
+    (T0 0);
+    (T0 0, T1 1);
+    (T0 0, T1 1, T2 2);
+    (T0 0, T1 1, T2 2, T3 3);
+    (T0 0, T1 1, T2 2, T3 3, T4 4);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10);
+    (T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10, T11 11);
 }
 
 unsafe impl<T: Trace, E: Trace> Trace for Result<T, E> {
@@ -223,14 +768,68 @@ unsafe impl<T: Trace, E: Trace> Trace for Result<T, E> {
 unsafe impl<T: Trace> Trace for Option<T> {
     const NO_TRACE: bool = T::NO_TRACE;
     fn trace(&self, tracer: &Tracer) {
+        if T::NO_TRACE {
+            return;
+        }
         if let Some(value) = self {
             value.trace(tracer);
         }
     }
 }
+/// Traces a copy of the value
+///
+/// Only with the `single-threaded` feature: a `Cell` is not `Sync`, but the
+/// collector may trace it from another thread. Writes through `Cell::set`
+/// bypass the write barrier; use [`GcCell`](crate::GcCell) for pointers that
+/// change while marking.
+#[cfg(feature = "single-threaded")]
+unsafe impl<T: Trace + Copy> Trace for Cell<T> {
+    const NO_TRACE: bool = T::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        self.get().trace(tracer);
+    }
+}
+
+/// Traces through a shared borrow
+///
+/// Only with the `single-threaded` feature: a `RefCell` is not `Sync`, but
+/// the collector may trace it from another thread. Writes through
+/// `borrow_mut` bypass the write barrier; use
+/// [`GcRefCell`](crate::GcRefCell) instead.
+///
+/// If the cell is mutably borrowed while it is traced, its contents can't
+/// be inspected. Skipping them could free reachable objects, so instead the
+/// current cycle is marked incomplete and sweeps nothing.
+#[cfg(feature = "single-threaded")]
+unsafe impl<T: Trace> Trace for core::cell::RefCell<T> {
+    const NO_TRACE: bool = T::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        match self.try_borrow() {
+            Ok(value) => tracer.drain_after(|| value.trace(tracer)),
+            Err(_) => tracer.mark_incomplete(),
+        }
+    }
+}
+
+unsafe impl<T: Trace> Trace for [T] {
+    const NO_TRACE: bool = T::NO_TRACE;
+    fn trace(&self, tracer: &Tracer) {
+        // Skips the loop over `NO_TRACE` elements, e.g. large byte arrays
+        if T::NO_TRACE {
+            return;
+        }
+        for item in self {
+            item.trace(tracer);
+        }
+    }
+}
+
 unsafe impl<T: Trace, const N: usize> Trace for [T; N] {
     const NO_TRACE: bool = T::NO_TRACE;
     fn trace(&self, tracer: &Tracer) {
+        if T::NO_TRACE {
+            return;
+        }
         for item in self {
             item.trace(tracer);
         }
@@ -0,0 +1,110 @@
+#![cfg(all(feature = "derive", feature = "std"))]
+
+use abfall::{GcCell, GcContext, GcPtr, Trace};
+
+// The `Node` type from the examples
+#[derive(Trace)]
+struct Node {
+    value: i32,
+    next: Option<GcPtr<Node>>,
+}
+
+#[derive(Trace)]
+struct Pair(GcPtr<Node>, GcCell<Option<GcPtr<Node>>>);
+
+#[derive(Trace)]
+enum Tree<T> {
+    Leaf(T),
+    Branch {
+        left: GcPtr<Tree<T>>,
+        right: GcPtr<Tree<T>>,
+    },
+    Empty,
+}
+
+#[derive(Trace)]
+struct Plain {
+    id: u64,
+    #[trace(skip)]
+    name: String,
+}
+
+#[test]
+fn derived_struct_keeps_list_alive() {
+    let ctx = GcContext::off();
+    let head = {
+        let tail = ctx.allocate(Node {
+            value: 2,
+            next: None,
+        });
+        ctx.allocate(Node {
+            value: 1,
+            next: Some(tail.as_ptr()),
+        })
+    };
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 2);
+    let tail = unsafe { head.next.unwrap().root() };
+    assert_eq!(head.value + tail.value, 3);
+    drop(tail);
+
+    drop(head);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+}
+
+#[test]
+fn derived_tuple_struct_traces_all_fields() {
+    let ctx = GcContext::off();
+    let a = ctx.allocate(Node {
+        value: 1,
+        next: None,
+    });
+    let b = ctx.allocate(Node {
+        value: 2,
+        next: None,
+    });
+    let pair = ctx.allocate(Pair(a.as_ptr(), GcCell::new(Some(b.as_ptr()))));
+    drop(a);
+    drop(b);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 3);
+    drop(pair);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+}
+
+#[test]
+fn derived_enum_traces_variant_fields() {
+    let ctx = GcContext::off();
+    let tree = {
+        let left = ctx.allocate(Tree::Leaf(1u32));
+        let right = ctx.allocate(Tree::Empty);
+        ctx.allocate(Tree::Branch {
+            left: left.as_ptr(),
+            right: right.as_ptr(),
+        })
+    };
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 3);
+    drop(tree);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+}
+
+#[test]
+fn derived_no_trace() {
+    const {
+        assert!(!<Node as Trace>::NO_TRACE);
+        assert!(!<Tree<u32> as Trace>::NO_TRACE);
+        assert!(<Plain as Trace>::NO_TRACE);
+    }
+
+    let ctx = GcContext::off();
+    let plain = ctx.allocate(Plain {
+        id: 7,
+        name: String::from("plain"),
+    });
+    ctx.force_collect();
+    assert_eq!((plain.id, plain.name.as_str()), (7, "plain"));
+}
@@ -1,8 +1,10 @@
+#![cfg(feature = "std")]
+
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use abfall::{GcCell, GcContext, GcPtr, GcRoot, Trace, Tracer};
+use abfall::{GcContext, GcPtr, GcRoot, Trace, Tracer};
 
 // Simple acyclic node type for graph tracing tests
 struct Node {
@@ -72,12 +74,11 @@ fn threshold_triggers_collection() {
 #[test]
 fn tracing_chain_keeps_all_nodes() {
     let ctx = GcContext::new();
-    let mut prev: Option<GcRoot<Node>> = None;
     let head = ctx.allocate(Node {
         value: 0,
         next: None,
     });
-    prev = Some(head.clone());
+    let mut prev: Option<GcRoot<Node>> = Some(head.clone());
     for i in 1..100 {
         let n = ctx.allocate(Node {
             value: i,
@@ -102,6 +103,28 @@ fn tracing_chain_keeps_all_nodes() {
 }
 
 #[test]
+fn traced_chain_keeps_node_values() {
+    let ctx = GcContext::new();
+    let mut prev: Option<GcRoot<Node>> = None;
+    for i in 0..100 {
+        prev = Some(ctx.allocate(Node {
+            value: i,
+            next: prev.map(|p| p.as_ptr()),
+        }));
+    }
+    ctx.heap().force_collect();
+    let mut cur = prev;
+    let mut expected = 100;
+    while let Some(node) = cur {
+        expected -= 1;
+        assert_eq!(node.value, expected);
+        cur = node.next.map(|next| unsafe { next.root() });
+    }
+    assert_eq!(expected, 0);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
 fn concurrent_alloc_and_collect_no_race() {
     let ctx = GcContext::new();
     let heap = Arc::clone(ctx.heap());
@@ -132,11 +155,12 @@ fn concurrent_alloc_and_collect_no_race() {
 }
 
 #[test]
+#[cfg(not(feature = "single-threaded"))]
 fn write_barrier_concurrent_mutation() {
     let ctx = GcContext::new();
     // Create many unrooted values
     let values: Vec<_> = (0..1000).map(|i| ctx.allocate(i).as_ptr()).collect();
-    let cell_root = ctx.allocate(GcCell::new(values[0]));
+    let cell_root = ctx.allocate(abfall::GcCell::new(values[0]));
     // Attempt to start a collection quickly by exceeding threshold
     for _ in 0..2000 {
         let _ = ctx.allocate([0u8; 16]);
@@ -149,7 +173,7 @@ fn write_barrier_concurrent_mutation() {
         let cell_ptr = cell_root.clone();
         let values_cl = values.clone();
         handles.push(thread::spawn(move || {
-            let ctx = GcContext::with_heap(heap_cl);
+            let _ctx = GcContext::with_heap(heap_cl);
             for v in values_cl {
                 cell_ptr.set(v);
             }
@@ -180,3 +204,2924 @@ fn large_object_graph_survives_multiple_cycles() {
         println!("cycle {} ok", cycle);
     }
 }
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn kick_background_collects_on_background_thread() {
+    use abfall::{GcEvent, GcOptions};
+    use std::sync::Mutex;
+    let ctx = GcContext::with_options(GcOptions {
+        // never wakes up on its own during this test
+        collection_interval: Duration::from_secs(3600),
+        min_threshold_bytes: 4 * 1024,
+        ..GcOptions::DEFAULT
+    });
+    // Threads that finished a sweep
+    let sweepers = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&sweepers);
+    ctx.heap().set_gc_callback(Box::new(move |event| {
+        if let GcEvent::SweepEnd { .. } = event {
+            recorded.lock().unwrap().push(thread::current().id());
+        }
+    }));
+    for _ in 0..500 {
+        let _t = ctx.allocate([0u8; 64]);
+    }
+    assert!(ctx.heap().bytes_allocated() > 4 * 1024);
+
+    sweepers.lock().unwrap().clear();
+    assert!(ctx.heap().kick_background());
+    let this_thread = thread::current().id();
+    assert!(
+        wait_until(|| sweepers.lock().unwrap().iter().any(|&id| id != this_thread)),
+        "background thread should have collected after kick"
+    );
+}
+
+#[test]
+fn manual_heap_waits_for_force_collect() {
+    use abfall::GcOptions;
+    let ctx = GcContext::with_options(GcOptions {
+        min_threshold_bytes: 1024,
+        ..GcOptions::manual()
+    });
+    assert!(!ctx.heap().start_background_collection());
+    for _ in 0..100 {
+        let _t = ctx.allocate([0u8; 64]);
+    }
+    assert_eq!(ctx.allocation_count(), 100);
+
+    // Several default collection intervals pass without a collection
+    thread::sleep(GcOptions::DEFAULT.collection_interval * 3);
+    assert_eq!(ctx.allocation_count(), 100);
+
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+}
+
+// Object tracing its elements through the `[T]` impl
+struct Slots(Box<[GcPtr<usize>]>);
+
+unsafe impl Trace for Slots {
+    fn trace(&self, tracer: &Tracer) {
+        (*self.0).trace(tracer);
+    }
+}
+
+#[test]
+fn slice_trace_keeps_all_elements_alive() {
+    let ctx = GcContext::off();
+    let elements: Box<[_]> = (0..10).map(|i| ctx.allocate(i).as_ptr()).collect();
+    let slots = ctx.allocate(Slots(elements));
+    for _ in 0..10 {
+        let _garbage = ctx.allocate(0usize);
+    }
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 11);
+    for (i, p) in slots.0.iter().enumerate() {
+        assert_eq!(unsafe { *p.as_ptr() }, i);
+    }
+}
+
+#[test]
+fn boxed_slice_root_keeps_all_targets_alive() {
+    let ctx = GcContext::off();
+    let elements: Box<[_]> = (0..10).map(|i| ctx.allocate(i).as_ptr()).collect();
+    let slots = ctx.allocate(elements);
+    drop(ctx.allocate(0usize));
+    for _ in 0..2 {
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 11);
+    }
+    for (i, p) in slots.iter().enumerate() {
+        assert_eq!(unsafe { *p.as_ptr() }, i);
+    }
+}
+
+#[test]
+fn container_impls_propagate_no_trace() {
+    use std::borrow::Cow;
+    use std::collections::{BinaryHeap, LinkedList};
+    use std::rc::Rc;
+
+    const fn no_trace<T: Trace + ?Sized>() -> bool {
+        T::NO_TRACE
+    }
+    assert!(no_trace::<Cow<'static, str>>());
+    assert!(no_trace::<Box<[u8]>>());
+    assert!(no_trace::<Rc<[u32]>>());
+    assert!(no_trace::<Arc<[u64]>>());
+    assert!(no_trace::<LinkedList<String>>());
+    assert!(no_trace::<BinaryHeap<i32>>());
+
+    assert!(!no_trace::<Cow<'static, [GcPtr<u8>]>>());
+    assert!(!no_trace::<Box<[GcPtr<u8>]>>());
+    assert!(!no_trace::<Rc<[GcPtr<u8>]>>());
+    assert!(!no_trace::<Arc<[GcPtr<u8>]>>());
+    assert!(!no_trace::<LinkedList<GcPtr<u8>>>());
+    assert!(!no_trace::<BinaryHeap<GcPtr<u8>>>());
+}
+
+#[test]
+fn std_value_types_are_no_trace() {
+    use std::ffi::OsString;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::num::{NonZeroI64, NonZeroU32, NonZeroUsize, Wrapping};
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant, SystemTime};
+
+    fn allocate<T: Trace>(ctx: &GcContext, value: T) -> GcRoot<T> {
+        assert!(T::NO_TRACE, "{}", std::any::type_name::<T>());
+        ctx.allocate(value)
+    }
+
+    let ctx = GcContext::off();
+    let roots = (
+        allocate(&ctx, Duration::from_secs(1)),
+        allocate(&ctx, Instant::now()),
+        allocate(&ctx, SystemTime::now()),
+        allocate(&ctx, NonZeroU32::MIN),
+        allocate(&ctx, NonZeroI64::MAX),
+        allocate(&ctx, NonZeroUsize::new(7).unwrap()),
+        allocate(&ctx, IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        allocate(&ctx, Ipv6Addr::UNSPECIFIED),
+        allocate(&ctx, SocketAddr::from(([127, 0, 0, 1], 8080))),
+        allocate(&ctx, PathBuf::from("/tmp")),
+        allocate(&ctx, OsString::from("abfall")),
+        allocate(&ctx, Wrapping(3u8)),
+    );
+    const { assert!(!<Wrapping<GcPtr<u8>> as Trace>::NO_TRACE) };
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 12);
+    assert_eq!(*roots.9, PathBuf::from("/tmp"));
+}
+
+#[test]
+fn linked_list_and_cow_keep_targets_alive() {
+    use std::borrow::Cow;
+    use std::collections::LinkedList;
+
+    let ctx = GcContext::off();
+    let list: LinkedList<_> = (0..3).map(|i| ctx.allocate(i).as_ptr()).collect();
+    let list = ctx.allocate(list);
+    let owned: Cow<'static, [GcPtr<usize>]> = Cow::Owned(vec![ctx.allocate(3).as_ptr()]);
+    let owned = ctx.allocate(owned);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 6);
+    let values: Vec<_> = list
+        .iter()
+        .chain(owned.iter())
+        .map(|p| unsafe { *p.as_ptr() })
+        .collect();
+    assert_eq!(values, [0, 1, 2, 3]);
+}
+
+#[test]
+fn preview_garbage_reports_unreachable_objects() {
+    let ctx = GcContext::off();
+    let reachable = ctx.allocate(Node {
+        value: 1,
+        next: None,
+    });
+    let root = ctx.allocate(Node {
+        value: 2,
+        next: Some(reachable.as_ptr()),
+    });
+    drop(reachable);
+    let garbage: Vec<_> = (0..3)
+        .map(|i| {
+            ctx.allocate(Node {
+                value: 10 + i,
+                next: None,
+            })
+            .as_ptr()
+        })
+        .collect();
+
+    let mut preview: Vec<_> = ctx.heap().preview_garbage();
+    preview.sort_by_key(|info| info.address);
+    let mut expected: Vec<_> = garbage.iter().map(|p| p.as_ptr() as usize).collect();
+    expected.sort();
+    assert_eq!(
+        preview.iter().map(|info| info.address).collect::<Vec<_>>(),
+        expected
+    );
+    assert!(preview.iter().all(|info| !info.is_root));
+
+    // The preview didn't free anything
+    assert_eq!(ctx.heap().allocation_count(), 5);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 2);
+    assert_eq!(root.value, 2);
+}
+
+// Object whose `trace` panics once after `armed` was set
+struct Bomb {
+    armed: Arc<std::sync::atomic::AtomicBool>,
+}
+
+unsafe impl Trace for Bomb {
+    fn trace(&self, _tracer: &Tracer) {
+        use std::sync::atomic::Ordering;
+        if self
+            .armed
+            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            panic!("trace exploded");
+        }
+    }
+}
+
+#[cfg(not(feature = "single-threaded"))]
+fn trigger_background_panic(restart: bool) -> (GcContext, Arc<std::sync::atomic::AtomicBool>) {
+    use abfall::GcOptions;
+    use std::sync::atomic::AtomicBool;
+    let ctx = GcContext::with_options(GcOptions {
+        collection_interval: Duration::from_secs(3600),
+        min_threshold_bytes: 1024,
+        restart_background_on_panic: restart,
+        ..GcOptions::DEFAULT
+    });
+    let armed = Arc::new(AtomicBool::new(true));
+    let bomb = ctx.allocate(Bomb {
+        armed: Arc::clone(&armed),
+    });
+    std::mem::forget(bomb); // keep rooted
+    for _ in 0..100 {
+        let _t = ctx.allocate([0u8; 64]);
+    }
+    ctx.heap().kick_background();
+    (ctx, armed)
+}
+
+#[cfg(not(feature = "single-threaded"))]
+fn wait_until(mut f: impl FnMut() -> bool) -> bool {
+    for _ in 0..200 {
+        if f() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn background_thread_panic_is_detected() {
+    let (ctx, _armed) = trigger_background_panic(false);
+    assert!(wait_until(|| !ctx.heap().background_healthy()));
+
+    // A new background thread can be launched
+    ctx.heap().stop_background_collection();
+    assert!(ctx.heap().start_background_collection());
+    assert!(ctx.heap().background_healthy());
+    ctx.heap().kick_background();
+    assert!(wait_until(|| ctx.heap().allocation_count() == 1));
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn background_thread_restarts_after_panic() {
+    use std::sync::atomic::Ordering;
+    let (ctx, armed) = trigger_background_panic(true);
+    assert!(
+        wait_until(|| !armed.load(Ordering::SeqCst)),
+        "bomb went off"
+    );
+    assert!(ctx.heap().background_healthy());
+
+    // The restarted loop still collects
+    assert!(wait_until(|| {
+        ctx.heap().kick_background();
+        ctx.heap().allocation_count() == 1
+    }));
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn shutdown_reports_background_panic() {
+    let (ctx, _armed) = trigger_background_panic(false);
+    assert!(wait_until(|| !ctx.heap().background_healthy()));
+    assert_eq!(
+        ctx.heap().shutdown(),
+        Err(abfall::Error::BackgroundThreadPanicked)
+    );
+}
+
+#[test]
+fn second_context_is_a_typed_error() {
+    let ctx = GcContext::off();
+    let heap = Arc::clone(ctx.heap());
+    assert!(matches!(
+        GcContext::try_with_heap(abfall::Heap::off()),
+        Err(abfall::Error::ContextAlreadySet)
+    ));
+    // The failed attempt leaves the existing context in place
+    let value = ctx.allocate(7);
+    assert_eq!(*value, 7);
+
+    drop(value);
+    drop(ctx);
+    assert!(GcContext::try_with_heap(Arc::clone(&heap)).is_ok());
+}
+
+#[test]
+fn shut_down_heap_rejects_new_contexts() {
+    let heap = abfall::Heap::new();
+    assert_eq!(heap.shutdown(), Ok(()));
+    assert!(matches!(
+        GcContext::try_with_heap(Arc::clone(&heap)),
+        Err(abfall::Error::HeapShuttingDown)
+    ));
+    assert_eq!(
+        heap.try_start_background_collection(),
+        Err(abfall::Error::HeapShuttingDown)
+    );
+}
+
+#[test]
+fn raw_root_round_trip_preserves_root_count() {
+    let ctx = GcContext::off();
+    let root = ctx.allocate(String::from("raw"));
+    let raw = root.clone().into_raw();
+    drop(root);
+
+    // Only the raw pointer holds a root now
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 1);
+
+    let root = unsafe { GcRoot::from_raw(raw) };
+    assert_eq!(*root, "raw");
+    let raw = root.into_raw();
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 1);
+
+    // Reclaiming releases the last root
+    drop(unsafe { GcRoot::from_raw(raw) });
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 0);
+}
+
+#[test]
+fn weak_count_and_upgrade_stats() {
+    let ctx = GcContext::off();
+    let root = ctx.allocate(String::from("cached"));
+    assert_eq!(root.weak_count(), 0);
+
+    let weak = root.downgrade();
+    let weak2 = weak.clone();
+    assert_eq!(root.weak_count(), 2);
+    assert_eq!(ctx.heap().weak_stats().weak_refs, 2);
+    drop(weak2);
+    assert_eq!(root.weak_count(), 1);
+
+    assert_eq!(
+        weak.upgrade().as_deref().map(String::as_str),
+        Some("cached")
+    );
+    // The upgraded root was dropped again, and `root` is gone now
+    drop(root);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 0);
+    assert!(weak.upgrade().is_none());
+
+    let stats = ctx.heap().weak_stats();
+    assert_eq!(stats.weak_refs, 1);
+    assert_eq!(stats.upgrades, 1);
+    assert_eq!(stats.failed_upgrades, 1);
+    drop(weak);
+    assert_eq!(ctx.heap().weak_stats().weak_refs, 0);
+}
+
+#[test]
+fn weak_strong_count_follows_the_roots() {
+    let ctx = GcContext::off();
+    let root = ctx.allocate(7u32);
+    let weak = abfall::GcWeak::from(&root);
+    assert_eq!(weak.strong_count(), 1);
+
+    let second = weak.upgrade().unwrap();
+    assert_eq!(weak.strong_count(), 2);
+    // The other direction: a weak reference from an unrooted pointer
+    let from_ptr = unsafe { second.as_ptr().downgrade() };
+    assert_eq!(root.weak_count(), 2);
+    drop(second);
+    assert_eq!(from_ptr.strong_count(), 1);
+
+    drop(root);
+    assert_eq!(weak.strong_count(), 0);
+    ctx.force_collect();
+    assert_eq!(weak.strong_count(), 0);
+    assert!(weak.upgrade().is_none());
+    assert!(from_ptr.upgrade().is_none());
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn context_drop_collects_worker_garbage() {
+    use abfall::GcOptions;
+    let heap = abfall::Heap::with_options(GcOptions {
+        collection_interval: Duration::from_secs(3600),
+        min_threshold_bytes: 1024,
+        collect_on_context_drop: true,
+        ..GcOptions::DEFAULT
+    });
+    let ctx = GcContext::with_heap(Arc::clone(&heap));
+    let keep = ctx.allocate(0usize);
+
+    let worker_heap = Arc::clone(&heap);
+    thread::spawn(move || {
+        let ctx = GcContext::with_heap(worker_heap);
+        let burst: Vec<_> = (0..1000).map(|i| ctx.allocate([i as u8; 64])).collect();
+        assert_eq!(burst.len(), 1000);
+        // `burst` is dropped before `ctx`
+    })
+    .join()
+    .unwrap();
+
+    assert!(
+        wait_until(|| heap.allocation_count() == 1),
+        "worker garbage was not collected"
+    );
+    assert_eq!(*keep, 0);
+}
+
+#[test]
+fn tuple_keeps_elements_alive() {
+    let ctx = GcContext::off();
+    let gc_a = ctx.allocate(String::from("a"));
+    let gc_b = ctx.allocate(7u64);
+    let pair = ctx.allocate((gc_a.as_ptr(), gc_b.as_ptr()));
+    drop(gc_a);
+    drop(gc_b);
+
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 3);
+    let (a, b) = unsafe { (pair.0.root(), pair.1.root()) };
+    assert_eq!((a.as_str(), *b), ("a", 7));
+    drop((a, b));
+
+    drop(pair);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 0);
+    const { assert!(<(u8, char, bool) as abfall::Trace>::NO_TRACE) };
+}
+
+#[test]
+#[cfg(feature = "single-threaded")]
+fn refcell_keeps_elements_alive() {
+    use std::cell::RefCell;
+    let ctx = GcContext::off();
+    let list = ctx.allocate(RefCell::new(Vec::new()));
+    for i in 0..3 {
+        let item = ctx.allocate(i);
+        list.borrow_mut().push(item.as_ptr());
+    }
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 4);
+
+    list.borrow_mut().truncate(1);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 2);
+}
+
+#[test]
+#[cfg(feature = "single-threaded")]
+fn mutably_borrowed_refcell_sweeps_nothing() {
+    use std::cell::RefCell;
+    let ctx = GcContext::off();
+    let item = ctx.allocate(1);
+    let list = ctx.allocate(RefCell::new(vec![item.as_ptr()]));
+    drop(item);
+    drop(ctx.allocate(2)); // garbage
+
+    let borrowed = list.borrow_mut();
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 3);
+    drop(borrowed);
+
+    // The next cycle can trace the cell again
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 2);
+}
+
+#[test]
+#[cfg(feature = "single-threaded")]
+fn cell_traces_copy() {
+    use std::cell::Cell;
+    let ctx = GcContext::off();
+    let item = ctx.allocate(1);
+    let cell = ctx.allocate(Cell::new(Some(item.as_ptr())));
+    drop(item);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 2);
+    cell.set(None);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 1);
+}
+
+#[test]
+fn weak_edge_does_not_keep_target_alive() {
+    use abfall::GcWeak;
+    let ctx = GcContext::off();
+    let target = ctx.allocate(String::from("target"));
+    // An object holding only a weak edge to `target`
+    let holder = ctx.allocate(Some(target.downgrade()));
+    let weak: GcWeak<String> = holder.as_ref().unwrap().clone();
+
+    ctx.heap().force_collect();
+    assert_eq!(
+        weak.upgrade().as_deref().map(String::as_str),
+        Some("target")
+    );
+
+    drop(target);
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 1);
+    assert!(weak.upgrade().is_none());
+    assert!(holder.as_ref().unwrap().upgrade().is_none());
+}
+
+#[test]
+fn weak_outlives_its_heap() {
+    use abfall::{GcOptions, Heap};
+    for arena in [false, true] {
+        let heap = if arena {
+            Heap::with_arena(GcOptions::manual(), 4096)
+        } else {
+            Heap::with_options(GcOptions::manual())
+        };
+        let ctx = GcContext::with_heap(heap);
+        let swept = ctx.allocate(String::from("swept")).downgrade();
+        ctx.force_collect();
+        // Live when the heap is dropped, with weak edges between the objects
+        let live = ctx.allocate(String::from("live"));
+        let holder = ctx.allocate(Some(live.downgrade()));
+        let weak = live.downgrade();
+        let other = weak.clone();
+        drop((live, holder));
+        drop(ctx);
+
+        assert!(swept.upgrade().is_none());
+        assert!(weak.upgrade().is_none());
+        assert_eq!(weak.strong_count(), 0);
+        let clone = weak.clone();
+        drop((weak, swept, other));
+        assert!(clone.upgrade().is_none());
+    }
+}
+
+#[test]
+fn finalizer_runs_once_per_collected_object() {
+    use abfall::Finalize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Resource {
+        finalized: Arc<AtomicUsize>,
+        next: Option<GcPtr<Resource>>,
+    }
+
+    unsafe impl Trace for Resource {
+        fn trace(&self, tracer: &Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+
+    impl Finalize for Resource {
+        fn finalize(&self) {
+            self.finalized.fetch_add(1, Ordering::SeqCst);
+            // Other garbage is still readable here
+            if let Some(next) = self.next {
+                let next = unsafe { next.root() };
+                assert!(next.next.is_none());
+            }
+        }
+    }
+
+    let ctx = GcContext::off();
+    let finalized = Arc::new(AtomicUsize::new(0));
+    let kept = ctx.allocate_finalized(Resource {
+        finalized: Arc::clone(&finalized),
+        next: None,
+    });
+    for _ in 0..10 {
+        let leaf = ctx.allocate_finalized(Resource {
+            finalized: Arc::clone(&finalized),
+            next: None,
+        });
+        ctx.allocate_finalized(Resource {
+            finalized: Arc::clone(&finalized),
+            next: Some(leaf.as_ptr()),
+        });
+    }
+    // Not finalized
+    drop(ctx.allocate(1));
+
+    ctx.force_collect();
+    assert_eq!(finalized.load(Ordering::SeqCst), 20);
+    assert_eq!(ctx.allocation_count(), 1);
+    ctx.force_collect();
+    assert_eq!(finalized.load(Ordering::SeqCst), 20);
+
+    drop(kept);
+    ctx.force_collect();
+    assert_eq!(finalized.load(Ordering::SeqCst), 21);
+}
+
+#[test]
+fn pointers_compare_by_identity() {
+    use std::collections::HashSet;
+    let ctx = GcContext::off();
+    let a = ctx.allocate(1);
+    let b = ctx.allocate(1); // equal value, different object
+    assert!(a.as_ptr().ptr_eq(&a.clone().as_ptr()));
+    assert!(!a.as_ptr().ptr_eq(&b.as_ptr()));
+    assert_eq!(a, a.clone());
+    assert_ne!(a, b);
+
+    let set: HashSet<GcPtr<i32>> = [a.as_ptr(), b.as_ptr(), a.as_ptr(), b.as_ptr()]
+        .into_iter()
+        .collect();
+    assert_eq!(set.len(), 2);
+    assert!(set.contains(&a.as_ptr()));
+
+    let roots: HashSet<GcRoot<i32>> = [a.clone(), a.clone(), b.clone()].into_iter().collect();
+    assert_eq!(roots.len(), 2);
+}
+
+#[test]
+fn roots_format_their_value() {
+    let ctx = GcContext::off();
+    assert_eq!(format!("{}", ctx.allocate(42)), "42");
+    assert_eq!(format!("{:?}", ctx.allocate("text")), "\"text\"");
+    assert_eq!(format!("{:?}", ctx.allocate(Some(1.5))), "Some(1.5)");
+
+    let root = ctx.allocate(7);
+    let debug = format!("{:?}", root.as_ptr());
+    assert!(debug.starts_with("GcPtr(0x"), "{debug}");
+}
+
+#[test]
+fn try_root_detects_swept_object() {
+    let ctx = GcContext::off();
+    let root = ctx.allocate(String::from("live"));
+    let ptr = root.as_ptr();
+    // Keeps the tombstone of the object allocated
+    let weak = root.downgrade();
+
+    let again = unsafe { ptr.try_root() }.expect("object is live");
+    assert_eq!(*again, "live");
+    drop((root, again));
+
+    ctx.heap().force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 0);
+    assert!(unsafe { ptr.try_root() }.is_none());
+    drop(weak);
+}
+
+#[test]
+fn stats_count_collections_and_freed_bytes() {
+    let ctx = GcContext::off();
+    let keep = ctx.allocate(0u64);
+    let before = ctx.heap().stats();
+    assert_eq!(before.live_objects, 1);
+    assert_eq!(before.phase, abfall::GcPhase::Idle);
+
+    for round in 1..=3 {
+        for i in 0..10u64 {
+            drop(ctx.allocate(i));
+        }
+        assert_eq!(ctx.heap().stats().live_objects, 11);
+        ctx.heap().force_collect();
+        let stats = ctx.heap().stats();
+        assert_eq!(stats.total_collections, before.total_collections + round);
+        assert_eq!(stats.live_objects, 1);
+        assert_eq!(stats.bytes_allocated, ctx.heap().bytes_allocated());
+    }
+    let stats = ctx.heap().stats();
+    assert!(stats.total_bytes_freed > 0);
+    assert_eq!(*keep, 0);
+}
+
+#[test]
+fn live_counts_reflect_the_last_sweep() {
+    let ctx = GcContext::off();
+    let heap = ctx.heap();
+    assert_eq!(heap.live_object_count(), 0);
+
+    let mut roots: Vec<_> = (0..100u64).map(|i| ctx.allocate(i)).collect();
+    roots.truncate(50);
+    heap.force_collect();
+    assert_eq!(heap.live_object_count(), 50);
+    assert_eq!(heap.allocation_count(), 50);
+    assert_eq!(heap.live_bytes(), heap.bytes_allocated());
+
+    // Allocations after the sweep only count towards the total heap
+    let extra: Vec<_> = (0..10u64).map(|i| ctx.allocate(i)).collect();
+    assert_eq!(heap.live_object_count(), 50);
+    assert_eq!(heap.allocation_count(), 60);
+    assert!(heap.live_bytes() < heap.bytes_allocated());
+    drop((roots, extra));
+}
+
+#[test]
+fn phase_follows_a_manual_collection() {
+    use abfall::{GcEvent, GcOptions, GcPhase};
+    use std::sync::Mutex;
+
+    let ctx = GcContext::with_options(GcOptions::manual());
+    let phases = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&phases);
+    let heap = Arc::downgrade(ctx.heap());
+    ctx.heap().set_gc_callback(Box::new(move |event| {
+        let heap = heap.upgrade().unwrap();
+        sink.lock().unwrap().push((event, heap.phase()));
+    }));
+
+    drop(ctx.allocate(1u64));
+    let size = ctx.heap().bytes_allocated();
+    assert_eq!(ctx.heap().phase(), GcPhase::Idle);
+    assert!(ctx.begin_mark());
+    assert_eq!(ctx.heap().phase(), GcPhase::Marking);
+    while !ctx.collect_incremental(1) {
+        assert_eq!(ctx.heap().phase(), GcPhase::Marking);
+    }
+    assert!(ctx.sweep().is_some());
+    assert_eq!(ctx.heap().phase(), GcPhase::Idle);
+
+    // Marking ends with the switch to the sweeping phase
+    assert_eq!(
+        *phases.lock().unwrap(),
+        [
+            (GcEvent::MarkStart, GcPhase::Marking),
+            (GcEvent::MarkEnd { marked: 0 }, GcPhase::Sweeping),
+            (GcEvent::SweepStart, GcPhase::Sweeping),
+            (
+                GcEvent::SweepEnd {
+                    freed_bytes: size,
+                    freed_objects: 1
+                },
+                GcPhase::Sweeping
+            ),
+        ]
+    );
+    assert_eq!(GcPhase::from(GcPhase::Sweeping as u8), GcPhase::Sweeping);
+}
+
+#[test]
+fn gc_callback_receives_lifecycle_events() {
+    use abfall::GcEvent;
+    use std::sync::Mutex;
+
+    let ctx = GcContext::off();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    ctx.heap()
+        .set_gc_callback(Box::new(move |event| sink.lock().unwrap().push(event)));
+
+    let keep = ctx.allocate(1u64);
+    drop(ctx.allocate(2u64));
+    let size = ctx.heap().bytes_allocated() / 2;
+    ctx.heap().force_collect();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            GcEvent::MarkStart,
+            GcEvent::MarkEnd { marked: 1 },
+            GcEvent::SweepStart,
+            GcEvent::SweepEnd {
+                freed_bytes: size,
+                freed_objects: 1
+            },
+        ]
+    );
+
+    ctx.heap().clear_gc_callback();
+    ctx.heap().force_collect();
+    assert_eq!(events.lock().unwrap().len(), 4);
+    drop(keep);
+}
+
+#[test]
+fn manual_cycle_step_by_step() {
+    let ctx = GcContext::off();
+    let mut head = ctx.allocate(Node {
+        value: 0,
+        next: None,
+    });
+    for value in 1..20 {
+        head = ctx.allocate(Node {
+            value,
+            next: Some(head.as_ptr()),
+        });
+    }
+    for _ in 0..5 {
+        drop(ctx.allocate(0u64));
+    }
+
+    // Out of order calls are no-ops
+    assert_eq!(ctx.sweep(), None);
+    assert!(ctx.do_mark_work(10));
+    assert_eq!(ctx.allocation_count(), 25);
+
+    assert!(ctx.begin_mark());
+    assert!(ctx.is_marking());
+    assert!(!ctx.begin_mark(), "a cycle is already in progress");
+    let mut steps = 1;
+    while !ctx.do_mark_work(3) {
+        steps += 1;
+    }
+    assert!(steps > 1);
+    assert!(ctx.is_marking(), "still marking until the sweep");
+    assert_eq!(
+        ctx.sweep().map(|result| result.bytes_after),
+        Some(ctx.bytes_allocated())
+    );
+    assert!(!ctx.is_marking());
+    assert_eq!(ctx.allocation_count(), 20);
+    assert_eq!(ctx.sweep(), None);
+    assert_eq!(head.value, 19);
+}
+
+#[test]
+fn for_each_object_reports_sizes_and_roots() {
+    use std::collections::HashMap;
+    let ctx = GcContext::off();
+    let small = ctx.allocate(1u8);
+    let large = ctx.allocate([0u64; 32]);
+    let unrooted = ctx.allocate(String::from("unrooted")).as_ptr();
+
+    let mut seen = HashMap::new();
+    ctx.heap().for_each_object(|info| {
+        assert_eq!(info.color, abfall::Color::White);
+        seen.insert(info.address, info);
+    });
+    assert_eq!(seen.len(), 3);
+
+    let small_info = seen[&(small.as_ptr().as_ptr() as usize)];
+    let large_info = seen[&(large.as_ptr().as_ptr() as usize)];
+    let unrooted_info = seen[&(unrooted.as_ptr() as usize)];
+    assert!(small_info.is_root && large_info.is_root);
+    assert!(!unrooted_info.is_root);
+    assert!(large_info.size >= small_info.size + 31 * 8);
+    assert_eq!(
+        seen.values().map(|info| info.size).sum::<usize>(),
+        ctx.heap().bytes_allocated()
+    );
+}
+
+#[test]
+fn minor_collection_frees_young_garbage_only() {
+    use abfall::{GcCell, GcEvent, GcOptions};
+    use std::sync::Mutex;
+
+    let ctx = GcContext::with_options(GcOptions {
+        generational: true,
+        promote_after: 2,
+        ..GcOptions::OFF
+    });
+    let mut head = ctx.allocate(Node {
+        value: 0,
+        next: None,
+    });
+    for value in 1..50 {
+        head = ctx.allocate(Node {
+            value,
+            next: Some(head.as_ptr()),
+        });
+    }
+    let holder = ctx.allocate(GcCell::new(None::<GcPtr<u64>>));
+    // Promotes all survivors
+    ctx.force_collect();
+
+    let marked = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&marked);
+    ctx.heap().set_gc_callback(Box::new(move |event| {
+        if let GcEvent::MarkEnd { marked } = event {
+            sink.lock().unwrap().push(marked);
+        }
+    }));
+
+    for i in 0..30 {
+        drop(ctx.allocate(i as u64));
+    }
+    // Only reachable from an old object, through the remembered set
+    holder.set(Some(ctx.allocate(7u64).as_ptr()));
+    ctx.collect_minor();
+    assert_eq!(ctx.allocation_count(), 52);
+    assert_eq!(*unsafe { holder.get().unwrap().root() }, 7);
+
+    // The second survival promotes it
+    ctx.collect_minor();
+    holder.set(None);
+    ctx.collect_minor();
+    assert_eq!(ctx.allocation_count(), 52);
+    // The old roots were never traced
+    assert_eq!(*marked.lock().unwrap(), [1, 1, 0]);
+
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 51);
+    assert_eq!(head.value, 49);
+}
+
+#[test]
+fn minor_collection_remembers_stores_into_old_objects_only() {
+    use abfall::{GcCell, GcOptions};
+
+    let ctx = GcContext::with_options(GcOptions {
+        generational: true,
+        promote_after: 2,
+        ..GcOptions::OFF
+    });
+    // A young holder doesn't keep its young value in the remembered set
+    let holder = ctx.allocate(GcCell::new(None::<GcPtr<u64>>));
+    holder.set(Some(ctx.allocate(1u64).as_ptr()));
+    drop(holder);
+    ctx.collect_minor();
+    assert_eq!(ctx.allocation_count(), 0);
+
+    let holder = ctx.allocate(GcCell::new(None::<GcPtr<u64>>));
+    ctx.collect_minor();
+    holder.set(Some(ctx.allocate(2u64).as_ptr()));
+    // Promotes the holder, but not its value
+    ctx.collect_minor();
+    // Only reachable from the old holder, which minor collections don't trace
+    ctx.collect_minor();
+    assert_eq!(ctx.allocation_count(), 2);
+    assert_eq!(*unsafe { holder.get().unwrap().root() }, 2);
+}
+
+#[test]
+fn swept_allocations_are_reused() {
+    use abfall::GcOptions;
+    use std::collections::HashSet;
+
+    let ctx = GcContext::with_options(GcOptions {
+        free_list_cap_bytes: 1024 * 1024,
+        ..GcOptions::OFF
+    });
+    let first: Vec<_> = (0..16u64).map(|i| ctx.allocate(i)).collect();
+    let addresses: HashSet<_> = first.iter().map(|r| &**r as *const u64 as usize).collect();
+    let bytes = ctx.heap().bytes_allocated();
+    drop(first);
+    ctx.force_collect();
+    assert_eq!(ctx.heap().bytes_allocated(), 0);
+
+    let second: Vec<_> = (100..116u64).map(|i| ctx.allocate(i)).collect();
+    assert!(
+        second
+            .iter()
+            .all(|r| addresses.contains(&(&**r as *const u64 as usize)))
+    );
+    assert_eq!(ctx.heap().bytes_allocated(), bytes);
+    assert_eq!(*second[3], 103);
+
+    // Other layouts don't take the slots
+    let other = ctx.allocate([7u64; 4]);
+    assert!(!addresses.contains(&(&*other as *const [u64; 4] as usize)));
+    let other_bytes = ctx.heap().bytes_allocated() - bytes;
+    drop(second);
+    ctx.force_collect();
+    assert_eq!(ctx.heap().bytes_allocated(), other_bytes);
+    assert_eq!(*other, [7; 4]);
+}
+
+#[test]
+fn scoped_context_nests_and_restores_the_outer_one() {
+    use abfall::{Error, Heap};
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    let ctx = GcContext::off();
+    let outer = ctx.allocate(1);
+    let scoped_heap = Heap::off();
+    let scoped_count = GcContext::scope(Arc::clone(&scoped_heap), |scoped| {
+        assert!(Arc::ptr_eq(scoped.heap(), &scoped_heap));
+        let _value = scoped.allocate(2);
+        let innermost_count = GcContext::scope(Heap::off(), |innermost| {
+            let _value = innermost.allocate(3);
+            innermost.allocation_count()
+        });
+        assert_eq!(innermost_count, 1);
+        // The scoped context is current again
+        assert!(matches!(
+            GcContext::try_with_heap(Heap::off()),
+            Err(Error::ContextAlreadySet)
+        ));
+        scoped.allocation_count()
+    });
+    assert_eq!(scoped_count, 1);
+    assert_eq!(ctx.allocation_count(), 1);
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        GcContext::scope(Heap::off(), |_| panic!("scoped panic"))
+    }));
+    assert!(result.is_err());
+    assert!(matches!(
+        GcContext::try_with_heap(Heap::off()),
+        Err(Error::ContextAlreadySet)
+    ));
+
+    // Dropping a shadowed context inside the scope doesn't resurrect it
+    drop(outer);
+    GcContext::scope(Heap::off(), move |_| drop(ctx));
+    assert!(GcContext::try_with_heap(Heap::off()).is_ok());
+}
+
+#[test]
+fn allocate_slice_survives_collection() {
+    let ctx = GcContext::off();
+    let data: Vec<u32> = (0..1000).collect();
+    let slice = ctx.allocate_slice(&data);
+    assert_eq!(slice.len(), 1000);
+
+    // Slices of pointers keep their elements alive
+    let targets: Vec<_> = (0..10u64).map(|i| ctx.allocate(i)).collect();
+    let ptrs: Vec<_> = targets.iter().map(GcRoot::as_ptr).collect();
+    let slice_of_ptrs = ctx.allocate_slice(&ptrs);
+    drop(targets);
+
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 12);
+    assert_eq!(*slice, *data);
+    assert_eq!(*unsafe { slice_of_ptrs[9].root() }, 9);
+
+    let size = ctx.bytes_allocated();
+    drop(slice_of_ptrs);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 1);
+    assert!(ctx.bytes_allocated() < size);
+    assert!(ctx.bytes_allocated() >= 4000);
+
+    let empty: GcRoot<[u8]> = ctx.allocate_slice(&[]);
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn allocate_with_builds_a_cycle() {
+    struct Twin {
+        value: u32,
+        other: GcPtr<Twin>,
+    }
+
+    unsafe impl Trace for Twin {
+        fn trace(&self, tracer: &Tracer) {
+            tracer.mark(&self.other);
+        }
+    }
+
+    let ctx = GcContext::off();
+    let mut b_root = None;
+    let a = ctx.allocate_with(|a_ptr| {
+        let b = ctx.allocate(Twin {
+            value: 2,
+            other: a_ptr,
+        });
+        let b_ptr = b.as_ptr();
+        // Only reachable through `a` once it exists
+        b_root = Some(b);
+        Twin {
+            value: 1,
+            other: b_ptr,
+        }
+    });
+    drop(b_root);
+
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 2);
+    let b = unsafe { a.other.root() };
+    assert_eq!(b.value, 2);
+    assert!(b.other.ptr_eq(&a.as_ptr()));
+    drop(b);
+
+    drop(a);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+}
+
+#[test]
+fn allocate_with_panic_releases_the_reservation() {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    let ctx = GcContext::off();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        ctx.allocate_with::<u64>(|_| panic!("no value"));
+    }));
+    assert!(result.is_err());
+    assert_eq!(ctx.allocation_count(), 0);
+    assert_eq!(ctx.bytes_allocated(), 0);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn barriers_and_allocations_racing_the_sweep_keep_reachable_objects() {
+    use abfall::{GcCell, GcOptions, Heap};
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    struct Tracked {
+        id: usize,
+        child: Option<GcPtr<Tracked>>,
+        freed: Arc<Mutex<HashSet<usize>>>,
+    }
+
+    unsafe impl Trace for Tracked {
+        fn trace(&self, tracer: &Tracer) {
+            self.child.trace(tracer);
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.freed.lock().unwrap().insert(self.id);
+        }
+    }
+
+    let heap = Heap::with_options(GcOptions::OFF);
+    let ctx = GcContext::with_heap(Arc::clone(&heap));
+    let freed = Arc::new(Mutex::new(HashSet::new()));
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+    let mutator = {
+        let heap = Arc::clone(&heap);
+        let freed = Arc::clone(&freed);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            let ctx = GcContext::with_heap(heap);
+            let holder = ctx.allocate(GcCell::new(None::<GcPtr<Tracked>>));
+            let tracked = |id, child| Tracked {
+                id,
+                child,
+                freed: Arc::clone(&freed),
+            };
+            for round in 0..1_000 {
+                let leaf = ctx.allocate(tracked(2 * round, None));
+                let parent = ctx.allocate(tracked(2 * round + 1, Some(leaf.as_ptr())));
+                drop(leaf);
+                holder.set(Some(parent.as_ptr()));
+                drop(parent);
+                thread::yield_now();
+                // Both are still reachable through the holder
+                let freed = freed.lock().unwrap();
+                assert!(!freed.contains(&(2 * round)), "leaf {round} was freed");
+                assert!(
+                    !freed.contains(&(2 * round + 1)),
+                    "parent {round} was freed"
+                );
+            }
+            done.store(true, std::sync::atomic::Ordering::Release);
+        })
+    };
+
+    while !done.load(std::sync::atomic::Ordering::Acquire) && !mutator.is_finished() {
+        ctx.force_collect();
+    }
+    mutator.join().unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn request_collection_waits_for_the_running_cycle() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+
+    let ctx = GcContext::off();
+    let heap = Arc::clone(ctx.heap());
+    let swept = Arc::new(AtomicBool::new(false));
+    let (started_tx, started_rx) = mpsc::channel();
+
+    let marker = {
+        let heap = Arc::clone(&heap);
+        let swept = Arc::clone(&swept);
+        thread::spawn(move || {
+            let _ctx = GcContext::with_heap(Arc::clone(&heap));
+            assert!(heap.begin_mark());
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(100));
+            swept.store(true, Ordering::SeqCst);
+            assert!(heap.sweep().is_some());
+        })
+    };
+    started_rx.recv().unwrap();
+
+    // Allocated black during marking: the running cycle keeps it
+    drop(ctx.allocate(1));
+    assert!(ctx.request_collection(false).is_none());
+
+    assert!(ctx.request_collection(true).is_some());
+    assert!(swept.load(Ordering::SeqCst));
+    assert_eq!(ctx.allocation_count(), 0);
+    marker.join().unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn allocations_racing_the_head_sweep_stay_linked() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const THREADS: usize = 4;
+    const ROUNDS: usize = if cfg!(miri) { 200 } else { 20_000 };
+
+    let ctx = GcContext::off();
+    let heap = Arc::clone(ctx.heap());
+    let done = Arc::new(AtomicBool::new(false));
+
+    let mutators: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let heap = Arc::clone(&heap);
+            thread::spawn(move || {
+                let ctx = GcContext::with_heap(heap);
+                let mut kept = Vec::new();
+                for i in 0..ROUNDS {
+                    // The newest objects are garbage: the sweep unlinks at the head
+                    let value = ctx.allocate(t * 1_000_000 + i);
+                    if i % 100 == 0 {
+                        kept.push(value);
+                    }
+                }
+                for (n, value) in kept.iter().enumerate() {
+                    assert_eq!(**value, t * 1_000_000 + n * 100);
+                }
+                kept
+            })
+        })
+        .collect();
+
+    let sweeper = {
+        let heap = Arc::clone(&heap);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            while !done.load(Ordering::Acquire) {
+                heap.force_collect();
+            }
+        })
+    };
+
+    let kept: Vec<_> = mutators.into_iter().map(|m| m.join().unwrap()).collect();
+    done.store(true, Ordering::Release);
+    sweeper.join().unwrap();
+
+    ctx.force_collect();
+    // Objects spliced out of the list would be missing here
+    assert_eq!(ctx.allocation_count(), THREADS * ROUNDS / 100);
+    assert_eq!(ctx.stats().live_objects, THREADS * ROUNDS / 100);
+    drop(kept);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn allocation_during_the_sweep_of_the_head_stays_linked() {
+    use abfall::GcEvent;
+
+    let ctx = GcContext::off();
+    // Garbage at the head of the list: the sweep unlinks it first
+    drop(ctx.allocate(0usize));
+
+    let weak_heap = Arc::downgrade(ctx.heap());
+    ctx.heap().set_gc_callback(Box::new(move |event| {
+        if event == GcEvent::SweepStart {
+            let heap = weak_heap.upgrade().unwrap();
+            // Pushed in front of the garbage before it is unlinked
+            thread::spawn(move || {
+                let ctx = GcContext::with_heap(heap);
+                std::mem::forget(ctx.allocate(1usize));
+            })
+            .join()
+            .unwrap();
+        }
+    }));
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 1);
+    assert_eq!(ctx.stats().live_objects, 1);
+}
+
+#[test]
+fn gray_queue_stays_near_its_soft_cap() {
+    use abfall::GcOptions;
+
+    struct Fan(Box<[GcPtr<Node>]>);
+
+    unsafe impl Trace for Fan {
+        fn trace(&self, tracer: &Tracer) {
+            (*self.0).trace(tracer);
+        }
+    }
+
+    fn peak_for(cap: usize) -> usize {
+        let ctx = GcContext::with_options(GcOptions {
+            // Steps short enough to merge most of the fan-out
+            incremental_work_budget: 100,
+            gray_queue_soft_cap: cap,
+            ..GcOptions::OFF
+        });
+        // A wide fan-out: tracing the root grays all children at once
+        let children: Box<[_]> = (0..10_000)
+            .map(|value| {
+                let next = Some(ctx.allocate(Node { value, next: None }).as_ptr());
+                ctx.allocate(Node { value, next }).as_ptr()
+            })
+            .collect();
+        let _root = ctx.allocate(Fan(children));
+        ctx.heap().force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 20_001);
+        ctx.heap().peak_gray_queue_len()
+    }
+
+    assert!(peak_for(usize::MAX) > 9_000);
+    assert!(peak_for(64) <= 64);
+}
+
+#[test]
+fn explicit_roots_keep_unrooted_objects_alive() {
+    use abfall::{GcOptions, Heap};
+
+    let heap = Heap::with_options(GcOptions::manual());
+    // A VM stack of unrooted pointers: one chain, and a plain value
+    let tail = heap.allocate(Node {
+        value: 1,
+        next: None,
+    });
+    let head = heap.allocate(Node {
+        value: 2,
+        next: Some(tail.as_ptr()),
+    });
+    let stack = vec![head.as_ptr()];
+    let value = heap.allocate(3usize).as_ptr();
+    drop((head, tail));
+    let rooted = heap.allocate(4usize);
+    drop(heap.allocate(5usize));
+    assert_eq!(heap.allocation_count(), 5);
+
+    // The union of the explicit roots and the root set survives
+    heap.collect_with_roots(&[&stack, &value]);
+    assert_eq!(heap.allocation_count(), 4);
+    let head = unsafe { stack[0].root() };
+    assert_eq!(head.value, 2);
+    assert_eq!(unsafe { head.next.unwrap().root() }.value, 1);
+    assert_eq!(unsafe { *value.root() }, 3);
+    assert_eq!(*rooted, 4);
+    drop(head);
+
+    // Without them, only the rooted object is left
+    heap.collect_with_roots(&[]);
+    assert_eq!(heap.allocation_count(), 1);
+}
+
+// Allocates and collects from its `Drop`, which runs during the sweep
+struct Reentrant {
+    heap: Arc<abfall::Heap>,
+    allocated: Arc<std::sync::Mutex<Vec<GcRoot<[u8; 512]>>>>,
+}
+
+unsafe impl Trace for Reentrant {
+    const NO_TRACE: bool = true;
+    fn trace(&self, _tracer: &Tracer) {}
+}
+
+impl Drop for Reentrant {
+    fn drop(&mut self) {
+        assert_eq!(self.heap.phase(), abfall::GcPhase::Sweeping);
+        let root = self.heap.allocate([1u8; 512]);
+        drop(self.heap.allocate(2usize));
+        self.allocated.lock().unwrap().push(root);
+        // A cycle is in progress: these return without collecting
+        self.heap.force_collect();
+        self.heap.collect();
+        assert!(self.heap.sweep().is_none());
+        assert_eq!(self.heap.request_collection(false), None);
+    }
+}
+
+#[test]
+fn drop_during_the_sweep_can_allocate() {
+    use abfall::{GcOptions, Heap, LimitAction};
+
+    let heap = Arc::new(Heap::with_options(GcOptions {
+        limit_bytes: 4096,
+        on_limit_exceeded: LimitAction::Block,
+        ..GcOptions::OFF
+    }));
+    let allocated = Arc::new(std::sync::Mutex::new(Vec::new()));
+    for _ in 0..16 {
+        drop(heap.allocate(Reentrant {
+            heap: Arc::clone(&heap),
+            allocated: Arc::clone(&allocated),
+        }));
+    }
+    heap.force_collect();
+    // Both allocations of each `Drop` survived the cycle they were made in
+    assert_eq!(allocated.lock().unwrap().len(), 16);
+    assert_eq!(heap.allocation_count(), 32);
+    // Not blocked by the limit until the sweep is over
+    assert!(heap.bytes_allocated() > 4096);
+
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 16);
+    assert!(allocated.lock().unwrap().iter().all(|root| root[0] == 1));
+}
+
+#[test]
+#[should_panic(expected = "can't be created from a `Drop` or finalizer run by the sweep")]
+fn read_guard_during_the_sweep_panics() {
+    struct Inspect(Arc<abfall::Heap>);
+
+    unsafe impl Trace for Inspect {
+        const NO_TRACE: bool = true;
+        fn trace(&self, _tracer: &Tracer) {}
+    }
+
+    impl Drop for Inspect {
+        fn drop(&mut self) {
+            let _guard = self.0.read_guard();
+        }
+    }
+
+    let heap = Arc::new(abfall::Heap::with_options(abfall::GcOptions::OFF));
+    drop(heap.allocate(Inspect(Arc::clone(&heap))));
+    heap.force_collect();
+}
+
+#[test]
+fn black_allocations_during_marking_are_not_swept() {
+    use abfall::{Color, GcCell, GcOptions};
+
+    let ctx = GcContext::with_options(GcOptions {
+        alloc_black_during_mark: true,
+        ..GcOptions::OFF
+    });
+    let first = ctx.allocate(Node {
+        value: 0,
+        next: None,
+    });
+    let holder = ctx.allocate(GcCell::new(Some(first.as_ptr())));
+    drop(first);
+
+    assert!(ctx.begin_mark());
+    // Taken out of the holder before the collector traced it: only the
+    // new node points to it
+    let taken = holder.get();
+    holder.set(None);
+    let mut head = ctx.allocate(Node {
+        value: 1,
+        next: taken,
+    });
+    let mut colors = Vec::new();
+    ctx.for_each_object(|info| colors.push(info.color));
+    assert!(colors.contains(&Color::Black));
+    for value in 2..1000 {
+        head = ctx.allocate(Node {
+            value,
+            next: Some(head.as_ptr()),
+        });
+        if value % 100 == 0 {
+            ctx.do_mark_work(5);
+        }
+    }
+    while !ctx.do_mark_work(100) {}
+    assert!(ctx.sweep().is_some());
+    assert_eq!(ctx.allocation_count(), 1001);
+
+    let mut expected = 999;
+    let mut current = Some(head.as_ptr());
+    while let Some(node) = current {
+        let node = unsafe { &*node.as_ptr() };
+        assert_eq!(node.value, expected);
+        expected = expected.wrapping_sub(1);
+        current = node.next;
+    }
+    assert_eq!(expected, usize::MAX);
+}
+
+#[test]
+fn type_erased_pointers_downcast_to_their_type() {
+    use abfall::GcAny;
+
+    struct Env(Vec<GcAny>);
+
+    unsafe impl Trace for Env {
+        fn trace(&self, tracer: &Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    let ctx = GcContext::off();
+    let node = ctx.allocate(Node {
+        value: 3,
+        next: Some(
+            ctx.allocate(Node {
+                value: 4,
+                next: None,
+            })
+            .as_ptr(),
+        ),
+    });
+    let env = ctx.allocate(Env(vec![
+        ctx.allocate(1u8).into_any().as_ptr(),
+        ctx.allocate(String::from("two")).into_any().as_ptr(),
+        node.clone().into_any().as_ptr(),
+    ]));
+    drop(node);
+
+    // Only `env` is rooted, the erased pointers trace their objects
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 5);
+
+    let [byte, text, node] = &env.0[..] else {
+        panic!()
+    };
+    assert!(unsafe { byte.is::<u8>() && !byte.is::<u32>() });
+    assert!(unsafe { text.downcast::<u8>() }.is_none());
+    let byte = unsafe { byte.downcast::<u8>().unwrap().root() };
+    assert_eq!(*byte, 1);
+    let text = unsafe { text.root() };
+    assert_eq!(text.downcast_ref::<String>().unwrap(), "two");
+    assert!(text.downcast_ref::<&str>().is_none());
+    let node = unsafe { node.root() }.downcast::<Node>().ok().unwrap();
+    let next = unsafe { node.next.unwrap().root() };
+    assert_eq!((node.value, next.value), (3, 4));
+
+    // A failed downcast gives the root back
+    let erased = ctx.allocate(5u64).into_any();
+    let erased = erased.downcast::<u32>().unwrap_err();
+    assert_eq!(*erased.downcast::<u64>().ok().unwrap(), 5);
+}
+
+#[test]
+fn incremental_update_barrier_rescans_the_owning_object() {
+    use abfall::{BarrierKind, GcCell, GcOptions};
+
+    let ctx = GcContext::with_options(GcOptions {
+        barrier: BarrierKind::IncrementalUpdate,
+        ..GcOptions::manual()
+    });
+    // Unreachable once marking starts, until it is stored into `holder`
+    let value = ctx.allocate(7).as_ptr();
+    let holder = ctx.allocate(GcCell::new(Some(GcCell::new(None))));
+    assert!(ctx.begin_mark());
+    while !ctx.do_mark_work(10) {}
+    // The inner cell is stored while moved out of the (black) holder
+    let inner = holder.take().unwrap();
+    inner.set(Some(value));
+    holder.set(Some(inner));
+    assert!(ctx.sweep().is_some());
+    assert_eq!(ctx.allocation_count(), 2);
+
+    // A cell dropped after the barrier recorded its object
+    assert!(ctx.begin_mark());
+    while !ctx.do_mark_work(10) {}
+    holder.take().unwrap().set(None);
+    assert!(ctx.sweep().is_some());
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 1);
+}
+
+#[test]
+fn incremental_update_barrier_of_moved_atomic_cells() {
+    use abfall::{AtomicGcCell, BarrierKind, GcCell, GcOptions};
+
+    let ctx = GcContext::with_options(GcOptions {
+        barrier: BarrierKind::IncrementalUpdate,
+        ..GcOptions::manual()
+    });
+    let value = ctx.allocate(7).as_ptr();
+    let holder = ctx.allocate(GcCell::new(vec![AtomicGcCell::new(None)]));
+    assert!(ctx.begin_mark());
+    while !ctx.do_mark_work(10) {}
+    // Moving the cells reallocates them while they are dirty
+    let mut cells = holder.take();
+    cells[0].store(Some(value));
+    cells.push(AtomicGcCell::new(None));
+    cells.remove(1);
+    holder.set(cells);
+    assert!(ctx.sweep().is_some());
+    assert_eq!(ctx.allocation_count(), 2);
+    let cells = holder.take();
+    assert_eq!(*unsafe { cells[0].load().unwrap().root() }, 7);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn cell_replace_races_tracing() {
+    use abfall::{GcCell, GcOptions};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const ROUNDS: usize = if cfg!(miri) { 50 } else { 20_000 };
+
+    let ctx = GcContext::with_options(GcOptions::manual());
+    let items: Vec<_> = (0..8).map(|i| ctx.allocate(i)).collect();
+    let cell = ctx.allocate(GcCell::new(
+        items.iter().map(GcRoot::as_ptr).collect::<Vec<_>>(),
+    ));
+    let done = Arc::new(AtomicBool::new(false));
+    let collector = {
+        let heap = Arc::clone(ctx.heap());
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                heap.force_collect();
+            }
+        })
+    };
+    // The old vectors are freed while the collector may trace the cell
+    for round in 0..ROUNDS {
+        let next = items[..round % 8 + 1].iter().map(GcRoot::as_ptr).collect();
+        assert_eq!(cell.replace(next).len(), (round + 7) % 8 + 1);
+    }
+    done.store(true, Ordering::Relaxed);
+    collector.join().unwrap();
+    drop(items);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 1 + cell.replace(Vec::new()).len());
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn atomic_cell_compare_exchange_races_marking() {
+    use abfall::{AtomicGcCell, BarrierKind, GcOptions, Heap};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const THREADS: usize = 4;
+    const ROUNDS: usize = if cfg!(miri) { 20 } else { 1_000 };
+
+    struct Link {
+        id: usize,
+        next: AtomicGcCell<Link>,
+        freed: Arc<Vec<AtomicBool>>,
+    }
+
+    unsafe impl Trace for Link {
+        fn trace(&self, tracer: &Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+
+    impl Drop for Link {
+        fn drop(&mut self) {
+            self.freed[self.id].store(true, Ordering::Relaxed);
+        }
+    }
+
+    for barrier in [BarrierKind::Dijkstra, BarrierKind::IncrementalUpdate] {
+        let heap = Heap::with_options(GcOptions {
+            barrier,
+            ..GcOptions::OFF
+        });
+        let ctx = GcContext::with_heap(Arc::clone(&heap));
+        let freed: Arc<Vec<_>> = Arc::new(
+            (0..THREADS * ROUNDS)
+                .map(|_| AtomicBool::new(false))
+                .collect(),
+        );
+        let stack = ctx.allocate(AtomicGcCell::<Link>::new(None));
+
+        // Lock-free stack: links are only reachable through the cells
+        let pushers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let heap = Arc::clone(&heap);
+                let stack = stack.clone();
+                let freed = Arc::clone(&freed);
+                thread::spawn(move || {
+                    let ctx = GcContext::with_heap(heap);
+                    for i in 0..ROUNDS {
+                        let link = ctx.allocate(Link {
+                            id: t * ROUNDS + i,
+                            next: AtomicGcCell::new(None),
+                            freed: Arc::clone(&freed),
+                        });
+                        let mut current = stack.load();
+                        loop {
+                            link.next.store(current);
+                            match stack.compare_exchange(current, Some(link.as_ptr())) {
+                                Ok(_) => break,
+                                Err(actual) => current = actual,
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        while pushers.iter().any(|pusher| !pusher.is_finished()) {
+            ctx.force_collect();
+        }
+        for pusher in pushers {
+            pusher.join().unwrap();
+        }
+        ctx.force_collect();
+
+        // Every push is linked exactly once, and nothing linked was swept
+        let mut seen = vec![false; THREADS * ROUNDS];
+        let mut current = stack.load();
+        while let Some(link) = current {
+            let link = unsafe { &*link.as_ptr() };
+            assert!(
+                !freed[link.id].load(Ordering::Relaxed),
+                "link {} was freed",
+                link.id
+            );
+            assert!(!std::mem::replace(&mut seen[link.id], true));
+            current = link.next.load();
+        }
+        assert!(seen.iter().all(|&seen| seen), "{barrier:?}");
+        assert_eq!(ctx.allocation_count(), THREADS * ROUNDS + 1);
+    }
+}
+
+#[test]
+fn pause_histogram_counts_recent_pauses() {
+    let ctx = GcContext::with_options(abfall::GcOptions {
+        collect_metrics: true,
+        ..abfall::GcOptions::manual()
+    });
+    let _keep = ctx.allocate(1usize);
+    for round in 0..5 {
+        for i in 0..100 {
+            let _tmp = ctx.allocate(round * 100 + i);
+        }
+        ctx.force_collect();
+    }
+
+    let histogram = ctx.pause_histogram();
+    assert!(histogram.iter().sum::<u64>() >= 5, "{histogram:?}");
+    let timings = ctx.phase_timings();
+    assert!(timings.mark > Duration::ZERO, "{timings:?}");
+    assert!(timings.sweep > Duration::ZERO, "{timings:?}");
+
+    drop(_keep);
+    drop(ctx);
+
+    // Without the option nothing is measured
+    let ctx = GcContext::with_options(abfall::GcOptions::manual());
+    ctx.force_collect();
+    assert_eq!(ctx.pause_histogram(), [0; abfall::Heap::PAUSE_BUCKETS]);
+    assert_eq!(ctx.last_pause_micros(), 0);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn background_collection_restarts_after_stop() {
+    use abfall::GcOptions;
+    let ctx = GcContext::with_options(GcOptions {
+        collection_interval: Duration::from_millis(5),
+        min_threshold_bytes: 1024,
+        ..GcOptions::DEFAULT
+    });
+    let heap = ctx.heap();
+    assert!(heap.is_background_running());
+    assert!(heap.stop_background_collection());
+    assert!(!heap.is_background_running());
+    assert!(!heap.stop_background_collection());
+
+    // Garbage above the threshold stays around while stopped
+    let collections = heap.stats().total_collections;
+    for _ in 0..100 {
+        let _t = ctx.allocate([0u8; 64]);
+    }
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(heap.stats().total_collections, collections);
+    assert_eq!(ctx.allocation_count(), 100);
+
+    for _ in 0..3 {
+        assert!(heap.start_background_collection());
+        assert!(!heap.start_background_collection());
+        assert!(heap.is_background_running());
+        assert!(wait_until(|| ctx.allocation_count() == 0));
+        assert!(heap.stats().total_collections > collections);
+        assert!(heap.stop_background_collection());
+        for _ in 0..100 {
+            let _t = ctx.allocate([0u8; 64]);
+        }
+    }
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn stopping_background_collection_while_marking_abandons_the_cycle() {
+    use abfall::{AtomicGcCell, GcOptions};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Blocks in its first trace, once it is black, until released
+    struct Gate {
+        other: GcPtr<u32>,
+        child: AtomicGcCell<u32>,
+        entered: Arc<AtomicBool>,
+        released: Arc<AtomicBool>,
+    }
+
+    unsafe impl Trace for Gate {
+        fn trace(&self, tracer: &Tracer) {
+            tracer.mark(&self.other);
+            self.child.trace(tracer);
+            if !self.entered.swap(true, Ordering::AcqRel) {
+                while !self.released.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+
+    let ctx = GcContext::with_options(GcOptions {
+        collection_interval: Duration::from_millis(5),
+        min_threshold_bytes: 1024,
+        incremental_work_budget: 1,
+        auto_tune: false,
+        ..GcOptions::DEFAULT
+    });
+    let heap = Arc::clone(ctx.heap());
+    assert!(heap.stop_background_collection());
+    let entered = Arc::new(AtomicBool::new(false));
+    let released = Arc::new(AtomicBool::new(false));
+    let a = ctx.allocate(Gate {
+        // Marking work left after `a`, so the thread checks for a stop
+        other: ctx.allocate(0u32).as_ptr(),
+        child: AtomicGcCell::new(None),
+        entered: Arc::clone(&entered),
+        released: Arc::clone(&released),
+    });
+    for _ in 0..100 {
+        let _t = ctx.allocate([0u8; 64]);
+    }
+
+    assert!(heap.start_background_collection());
+    assert!(wait_until(|| entered.load(Ordering::Acquire)));
+    let stopper = thread::spawn({
+        let heap = Arc::clone(&heap);
+        move || heap.stop_background_collection()
+    });
+    assert!(wait_until(|| !heap.is_background_running()));
+    released.store(true, Ordering::Release);
+    assert!(stopper.join().unwrap());
+
+    // `a` is white again: the next cycle traces its new child
+    let c = ctx.allocate(3u32);
+    a.child.store(Some(c.as_ptr()));
+    drop(c);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 3);
+    assert_eq!(*unsafe { a.child.load().unwrap().root() }, 3);
+}
+
+#[test]
+fn no_trace_containers_skip_their_elements() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static TRACED: AtomicUsize = AtomicUsize::new(0);
+
+    // Declares `NO_TRACE` but counts the calls it would get anyway
+    struct Opaque(#[allow(dead_code)] u8);
+    unsafe impl Trace for Opaque {
+        const NO_TRACE: bool = true;
+        fn trace(&self, _tracer: &Tracer) {
+            TRACED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    struct Holder {
+        arrays: [[Opaque; 16]; 4],
+        maybe: Option<Opaque>,
+        list: Vec<Opaque>,
+        next: Option<GcPtr<Holder>>,
+    }
+    unsafe impl Trace for Holder {
+        fn trace(&self, tracer: &Tracer) {
+            self.arrays.trace(tracer);
+            self.maybe.trace(tracer);
+            self.list.trace(tracer);
+            self.next.trace(tracer);
+        }
+    }
+    let holder = || Holder {
+        arrays: std::array::from_fn(|_| std::array::from_fn(|i| Opaque(i as u8))),
+        maybe: Some(Opaque(0)),
+        list: (0..16).map(Opaque).collect(),
+        next: None,
+    };
+
+    let ctx = GcContext::off();
+    let tail = ctx.allocate(holder());
+    let head = ctx.allocate(Holder {
+        next: Some(tail.as_ptr()),
+        ..holder()
+    });
+    drop(tail);
+    let bytes = ctx.allocate([0u8; 4096]);
+    ctx.force_collect();
+
+    assert_eq!(TRACED.load(Ordering::Relaxed), 0);
+    assert_eq!(ctx.allocation_count(), 3);
+    assert!(head.next.is_some());
+    assert_eq!(bytes[4095], 0);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn heap_dropped_during_background_collection() {
+    use abfall::{GcOptions, GcPhase, Heap};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counted(Option<GcPtr<Counted>>);
+    unsafe impl Trace for Counted {
+        fn trace(&self, tracer: &Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    const CHAIN: usize = if cfg!(miri) { 50 } else { 20_000 };
+    let heap = Heap::with_options(GcOptions {
+        collection_interval: Duration::from_secs(3600),
+        min_threshold_bytes: 1024,
+        incremental_work_budget: 10,
+        ..GcOptions::DEFAULT
+    });
+    let weak = Arc::downgrade(&heap);
+    {
+        let ctx = GcContext::with_heap(Arc::clone(&heap));
+        let mut chain: Option<GcRoot<Counted>> = None;
+        for _ in 0..CHAIN {
+            chain = Some(ctx.allocate(Counted(chain.map(|n| n.as_ptr()))));
+        }
+        for _ in 0..CHAIN {
+            drop(ctx.allocate(Counted(None)));
+        }
+        assert!(heap.kick_background());
+        assert!(wait_until(|| heap.phase() != GcPhase::Idle));
+    }
+    // The background thread finishes the collection and drops the heap
+    drop(heap);
+    assert!(wait_until(|| weak.strong_count() == 0));
+    assert!(wait_until(|| DROPPED.load(Ordering::Relaxed) == 2 * CHAIN));
+}
+
+#[test]
+fn incremental_sweep_interleaves_with_allocation() {
+    use abfall::{GcOptions, GcPhase};
+
+    struct Payload {
+        value: usize,
+        bytes: Vec<u8>,
+        next: Option<GcPtr<Payload>>,
+    }
+    unsafe impl Trace for Payload {
+        fn trace(&self, tracer: &Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+    let node = |value, next| Payload {
+        value,
+        bytes: vec![0; 16],
+        next,
+    };
+
+    const GARBAGE: usize = 50_000;
+    const BUDGET: usize = 64;
+    let ctx = GcContext::with_options(GcOptions {
+        incremental_sweep: true,
+        incremental_work_budget: BUDGET,
+        free_list_cap_bytes: 1024 * 1024,
+        ..GcOptions::manual()
+    });
+    let mut chain: Option<GcRoot<Payload>> = None;
+    for i in 0..100 {
+        chain = Some(ctx.allocate(node(i, chain.map(|n| n.as_ptr()))));
+    }
+    let garbage = ctx.allocate(node(0, None)).downgrade();
+    for i in 0..GARBAGE {
+        drop(ctx.allocate(node(i, None)));
+    }
+    assert!(ctx.begin_mark());
+    while !ctx.do_mark_work(1000) {}
+
+    assert!(ctx.begin_sweep());
+    assert_eq!(ctx.phase(), GcPhase::Sweeping);
+    // Not swept yet, but gone already
+    assert!(garbage.upgrade().is_none());
+    // Allocated during the sweep and only reachable through `fresh`
+    let child = ctx.allocate(node(7, None));
+    let weak_child = child.downgrade();
+    let fresh = ctx.allocate(node(8, Some(child.as_ptr())));
+    drop(child);
+
+    // Every allocation sweeps one slice of at most `BUDGET` objects first
+    let mut allocations = 0;
+    let mut count = ctx.allocation_count();
+    while ctx.phase() == GcPhase::Sweeping {
+        drop(ctx.allocate(node(allocations, None)));
+        assert_eq!(weak_child.upgrade().map(|child| child.value), Some(7));
+        allocations += 1;
+        let swept = count + 1 - ctx.allocation_count();
+        assert!(swept <= BUDGET, "{swept} objects swept in one slice");
+        count = ctx.allocation_count();
+    }
+    assert!(allocations >= GARBAGE / BUDGET, "{allocations} slices");
+    assert!(ctx.do_sweep_work(1));
+
+    let mut expected = 100;
+    let mut current = chain.as_ref().map(|n| n.as_ptr());
+    while let Some(n) = current {
+        let n = unsafe { &*n.as_ptr() };
+        expected -= 1;
+        assert_eq!(n.value, expected);
+        assert_eq!(n.bytes.len(), 16);
+        current = n.next;
+    }
+    assert_eq!(expected, 0);
+    assert!(fresh.next.is_some());
+    // Allocations during the sweep weren't in its snapshot
+    assert_eq!(ctx.allocation_count(), 100 + 2 + allocations);
+}
+
+#[test]
+fn collect_and_wait_frees_garbage_despite_background_cycles() {
+    use abfall::GcOptions;
+    let ctx = GcContext::with_options(GcOptions {
+        collection_interval: Duration::from_millis(1),
+        min_threshold_bytes: 1024,
+        incremental_work_budget: 8,
+        ..GcOptions::DEFAULT
+    });
+    let keep: Vec<_> = (0..10).map(|i| ctx.allocate(i)).collect();
+    for round in 0..20 {
+        for i in 0..500 {
+            drop(ctx.allocate([round, i]));
+        }
+        ctx.collect_and_wait();
+        assert_eq!(ctx.allocation_count(), keep.len(), "round {round}");
+    }
+}
+
+#[test]
+fn roots_reports_exactly_the_rooted_objects() {
+    let ctx = GcContext::off();
+    assert!(ctx.is_empty());
+    assert_eq!(ctx.roots().count(), 0);
+
+    let number = ctx.allocate(1u32);
+    let text = ctx.allocate(String::from("root"));
+    let list = ctx.allocate_slice(&[1u8, 2, 3]);
+    let tail = ctx.allocate(Node {
+        value: 0,
+        next: None,
+    });
+    let head = ctx.allocate(Node {
+        value: 1,
+        next: Some(tail.as_ptr()),
+    });
+    let unrooted = ctx.allocate(2u32).as_ptr();
+    drop(tail);
+    assert!(!ctx.is_empty());
+
+    let roots: Vec<_> = ctx.roots().collect();
+    assert_eq!(roots.len(), 4);
+    // The objects are live while rooted. Their types are unknown until
+    // erased to a `GcAny`, which records them as `'static`
+    assert!(!roots.iter().any(|r| unsafe { r.is::<u32>() }));
+    let _ = number.clone().into_any();
+    let _ = text.clone().into_any();
+    let _ = head.clone().into_any();
+    assert!(
+        roots
+            .iter()
+            .any(|r| unsafe { r.downcast::<u32>() } == Some(number.as_ptr()))
+    );
+    assert!(
+        roots
+            .iter()
+            .any(|r| unsafe { r.downcast::<String>() } == Some(text.as_ptr()))
+    );
+    assert!(
+        roots
+            .iter()
+            .any(|r| unsafe { r.downcast::<Node>() } == Some(head.as_ptr()))
+    );
+    // Slices can't be erased, so theirs stays unknown
+    assert!(!roots.iter().any(|r| unsafe { r.is::<[u8]>() }));
+    assert!(
+        !roots
+            .iter()
+            .any(|r| unsafe { r.downcast::<u32>() } == Some(unrooted))
+    );
+    assert_eq!(
+        roots.iter().filter(|r| unsafe { r.is::<Node>() }).count(),
+        1
+    );
+
+    // Erased pointers keep their objects alive, tracing the real type
+    let erased_head = ctx.allocate(
+        roots
+            .iter()
+            .copied()
+            .find(|r| unsafe { r.is::<Node>() })
+            .unwrap(),
+    );
+    drop(head);
+    ctx.force_collect();
+    {
+        let head = unsafe { erased_head.root() };
+        let head = head.downcast_ref::<Node>().unwrap();
+        assert_eq!(head.value, 1);
+        assert_eq!(unsafe { &*head.next.unwrap().as_ptr() }.value, 0);
+    }
+
+    drop((number, text, list, erased_head));
+    ctx.force_collect();
+    assert_eq!(ctx.roots().count(), 0);
+    assert!(ctx.is_empty());
+}
+
+#[test]
+fn roots_do_not_downcast_borrowing_objects_to_static_types() {
+    let ctx = GcContext::off();
+    let text = String::from("borrowed");
+    let borrowed = ctx.allocate(text.as_str());
+
+    let roots: Vec<_> = ctx.roots().collect();
+    assert_eq!(roots.len(), 1);
+    // Would outlive `text` as a `&'static str`
+    assert!(unsafe { roots[0].downcast::<&'static str>() }.is_none());
+    assert!(!unsafe { roots[0].is::<&str>() });
+
+    drop(borrowed);
+    ctx.force_collect();
+}
+
+#[test]
+fn deeply_nested_boxes_trace_without_overflow() {
+    struct Deep {
+        leaf: Option<GcPtr<u32>>,
+        next: Option<Box<Deep>>,
+    }
+
+    unsafe impl Trace for Deep {
+        fn trace(&self, tracer: &Tracer) {
+            self.leaf.trace(tracer);
+            self.next.trace(tracer);
+        }
+    }
+
+    impl Drop for Deep {
+        fn drop(&mut self) {
+            // Unlink iteratively, dropping the chain recursively would overflow too
+            let mut next = self.next.take();
+            while let Some(mut deep) = next {
+                next = deep.next.take();
+            }
+        }
+    }
+
+    let ctx = GcContext::off();
+    let leaf = ctx.allocate(7u32);
+    let weak = leaf.downgrade();
+    let mut chain = Deep {
+        leaf: Some(leaf.as_ptr()),
+        next: None,
+    };
+    for _ in 0..100_000 {
+        chain = Deep {
+            leaf: None,
+            next: Some(Box::new(chain)),
+        };
+    }
+    let root = ctx.allocate(vec![chain]);
+    drop(leaf);
+
+    ctx.force_collect();
+    assert_eq!(weak.upgrade().map(|leaf| *leaf), Some(7));
+    drop(root);
+    ctx.force_collect();
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn latency_preset_runs_more_and_smaller_collections() {
+    use abfall::GcOptions;
+
+    // The same workload on both presets; the collections are triggered by
+    // the thresholds only, without the timing of the background thread
+    fn run(options: GcOptions) -> (usize, u64) {
+        let ctx = GcContext::with_options(GcOptions {
+            collection_interval: Duration::ZERO,
+            ..options
+        });
+        let live: Vec<_> = (0..100).map(|i| ctx.allocate([i; 16])).collect();
+        for i in 0..20_000 {
+            drop(ctx.allocate([i; 128]));
+            ctx.collect();
+        }
+        let stats = ctx.stats();
+        assert!(live.iter().enumerate().all(|(i, value)| value[0] == i));
+        (stats.total_collections, stats.total_bytes_freed)
+    }
+
+    let (latency_cycles, latency_freed) = run(GcOptions::LOW_LATENCY);
+    let (throughput_cycles, throughput_freed) = run(GcOptions::HIGH_THROUGHPUT);
+    assert!(
+        latency_cycles > throughput_cycles,
+        "{latency_cycles} vs {throughput_cycles} collections"
+    );
+    assert!(throughput_cycles > 0);
+    assert!(
+        latency_freed / (latency_cycles as u64) < throughput_freed / (throughput_cycles as u64),
+        "{latency_freed} freed in {latency_cycles} vs {throughput_freed} in {throughput_cycles}"
+    );
+}
+
+#[test]
+fn force_collect_reports_the_freed_objects() {
+    let ctx = GcContext::off();
+    let keep: Vec<_> = (0..5).map(|i| ctx.allocate(i)).collect();
+    let dropped: Vec<_> = (0..37).map(|i| ctx.allocate([i; 4])).collect();
+    assert_eq!(ctx.force_collect().freed_objects, 0);
+    let count = dropped.len();
+    drop(dropped);
+
+    let result = ctx.force_collect();
+    assert_eq!(result.freed_objects, count);
+    assert!(result.freed_bytes >= count * std::mem::size_of::<[usize; 4]>());
+    assert_eq!(result.live_objects, keep.len());
+    assert_eq!(result.bytes_after, ctx.bytes_allocated());
+
+    // Without a threshold, `collect` doesn't run
+    assert_eq!(ctx.collect(), None);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn barrier_shaded_objects_survive_collections_on_other_threads() {
+    use abfall::{GcCell, GcOptions};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const ROUNDS: usize = if cfg!(miri) { 50 } else { 2_000 };
+
+    struct Flagged {
+        id: usize,
+        freed: Arc<Vec<AtomicBool>>,
+    }
+
+    unsafe impl Trace for Flagged {
+        fn trace(&self, _tracer: &Tracer) {}
+    }
+
+    impl Drop for Flagged {
+        fn drop(&mut self) {
+            self.freed[self.id].store(true, Ordering::Relaxed);
+        }
+    }
+
+    let ctx = GcContext::with_options(GcOptions::OFF);
+    let heap = Arc::clone(ctx.heap());
+    let done = Arc::new(AtomicBool::new(false));
+    let freed: Arc<Vec<_>> = Arc::new((0..ROUNDS).map(|_| AtomicBool::new(false)).collect());
+
+    let collector = {
+        let heap = Arc::clone(&heap);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            while !done.load(Ordering::Acquire) {
+                heap.force_collect();
+            }
+        })
+    };
+
+    // The stored objects are only reachable through the cell: the barrier
+    // of this thread shades them when a cycle of the collector is marking
+    let holder = ctx.allocate(GcCell::new(None::<GcPtr<Flagged>>));
+    for id in 0..ROUNDS {
+        let fresh = ctx.allocate(Flagged {
+            id,
+            freed: Arc::clone(&freed),
+        });
+        holder.set(Some(fresh.as_ptr()));
+        drop(fresh);
+        thread::yield_now();
+        assert!(!freed[id].load(Ordering::Relaxed), "object {id} was swept");
+    }
+    done.store(true, Ordering::Release);
+    collector.join().unwrap();
+
+    ctx.force_collect();
+    assert!(!freed[ROUNDS - 1].load(Ordering::Relaxed));
+    assert!(
+        freed[..ROUNDS - 1]
+            .iter()
+            .all(|f| f.load(Ordering::Relaxed))
+    );
+}
+
+#[test]
+fn unsized_roots_keep_mixed_objects_alive() {
+    use abfall::unsize;
+    use std::fmt::{self, Display};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Named(GcPtr<String>);
+
+    unsafe impl Trace for Named {
+        fn trace(&self, tracer: &Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    impl Display for Named {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "<{}>", unsafe { &*self.0.as_ptr() })
+        }
+    }
+
+    impl Drop for Named {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let ctx = GcContext::off();
+    {
+        let name = ctx.allocate(String::from("name"));
+        let values: Vec<GcRoot<dyn Display>> = vec![
+            unsize!(ctx.allocate(42u32) => dyn Display),
+            unsize!(ctx.allocate(String::from("text")) => dyn Display),
+            unsize!(ctx.allocate(Named(name.as_ptr())) => dyn Display),
+            unsize!(ctx.allocate(-1.5f64) => dyn Display),
+        ];
+        drop(name);
+
+        ctx.force_collect();
+        assert_eq!(ctx.heap().allocation_count(), 5);
+        let shown: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(shown, ["42", "text", "<name>", "-1.5"]);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+    }
+
+    ctx.force_collect();
+    assert_eq!(ctx.heap().allocation_count(), 0);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn conservative_scan_keeps_unrooted_objects_alive() {
+    use abfall::{GcOptions, Heap};
+
+    let heap = Heap::with_options(GcOptions::manual());
+    let kept = heap.allocate(1u64).as_ptr();
+    let inner = heap.allocate(String::from("inner")).as_ptr();
+    drop(heap.allocate(3u64));
+
+    // A `GcPtr` as foreign code would hold it, and a pointer into an object
+    let stack: [usize; 3] = [
+        0,
+        unsafe { core::mem::transmute::<GcPtr<u64>, usize>(kept) },
+        inner.as_ptr() as usize + 1,
+    ];
+    assert!(heap.roots().next().is_none());
+
+    let range = stack.as_ptr_range();
+    unsafe { heap.collect_conservative(range.start.cast(), range.end.cast()) };
+    assert_eq!(heap.allocation_count(), 2);
+    assert_eq!(unsafe { *kept.root() }, 1);
+    assert_eq!(*unsafe { inner.root() }, "inner");
+
+    // Without the range, nothing keeps them alive
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 0);
+}
+
+#[test]
+fn lowering_the_min_threshold_collects_sooner() {
+    use abfall::GcOptions;
+
+    let ctx = GcContext::with_options(GcOptions {
+        collection_interval: Duration::ZERO,
+        min_threshold_bytes: 1024 * 1024,
+        ..GcOptions::DEFAULT
+    });
+    let heap = ctx.heap();
+    assert_eq!(heap.current_threshold(), 1024 * 1024);
+
+    let run = || {
+        let before = heap.stats().total_collections;
+        for i in 0..1000 {
+            drop(ctx.allocate([i; 16]));
+            ctx.collect();
+        }
+        heap.stats().total_collections - before
+    };
+    assert_eq!(run(), 0);
+
+    heap.set_min_threshold(4 * 1024);
+    assert_eq!(heap.current_threshold(), 4 * 1024);
+    assert!(run() > 0);
+    assert!(heap.current_threshold() >= 4 * 1024);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn batched_barriers_keep_moved_objects_alive() {
+    use abfall::{AtomicGcCell, GcOptions};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const OBJECTS: usize = 256;
+    const THREADS: usize = 4;
+    const SETS: usize = if cfg!(miri) { 100 } else { 100_000 };
+
+    struct Node {
+        id: usize,
+        child: Option<GcPtr<Node>>,
+        freed: Arc<Vec<AtomicBool>>,
+    }
+
+    unsafe impl Trace for Node {
+        fn trace(&self, tracer: &Tracer) {
+            self.child.trace(tracer);
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.freed[self.id].store(true, Ordering::Relaxed);
+        }
+    }
+
+    let ctx = GcContext::with_options(GcOptions::OFF);
+    let freed: Arc<Vec<_>> = Arc::new((0..2 * OBJECTS).map(|_| AtomicBool::new(false)).collect());
+    // Each node (and with it its child) is only reachable through one cell.
+    // The cells are objects of their own, which marking scans one by one
+    let cells: Vec<_> = (0..OBJECTS)
+        .map(|id| {
+            let child = ctx.allocate(Node {
+                id: OBJECTS + id,
+                child: None,
+                freed: Arc::clone(&freed),
+            });
+            let node = ctx.allocate(Node {
+                id,
+                child: Some(child.as_ptr()),
+                freed: Arc::clone(&freed),
+            });
+            ctx.allocate(AtomicGcCell::new(Some(node.as_ptr())))
+        })
+        .collect();
+
+    let done = AtomicBool::new(false);
+    let cycles_before = ctx.stats().total_collections;
+    let flushes_before = ctx.barrier_flushes();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let heap = Arc::clone(ctx.heap());
+                let cells = &cells;
+                scope.spawn(move || {
+                    let ctx = GcContext::with_heap(heap);
+                    // Swap the contents of two cells of this thread: a node
+                    // moved into a cell the collector already scanned is
+                    // only found through the barrier. The guard keeps the
+                    // nodes from being swept until they are rooted
+                    let own = |n: usize| &cells[n % (OBJECTS / THREADS) * THREADS + t];
+                    for i in 0..SETS {
+                        let _guard = ctx.read_guard();
+                        let (a, b) = (own(i * 7), own(i * 13 + 1));
+                        let first = a.swap(None).map(|node| unsafe { node.root() });
+                        let second = b
+                            .swap(first.as_ref().map(GcRoot::as_ptr))
+                            .map(|node| unsafe { node.root() });
+                        a.store(second.as_ref().map(GcRoot::as_ptr));
+                    }
+                })
+            })
+            .collect();
+        let heap = ctx.heap();
+        let done = &done;
+        scope.spawn(move || {
+            while !done.load(Ordering::Acquire) {
+                assert!(heap.begin_mark());
+                while !heap.do_mark_work(1) {}
+                heap.sweep();
+            }
+        });
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        done.store(true, Ordering::Release);
+    });
+    let cycles = ctx.stats().total_collections - cycles_before;
+    let flushes = ctx.barrier_flushes() - flushes_before;
+    assert!(cycles > 0);
+    assert!(
+        flushes < THREADS * SETS / 100,
+        "{flushes} flushes for {} stores",
+        THREADS * SETS * 3
+    );
+
+    ctx.force_collect();
+    assert!(freed.iter().all(|f| !f.load(Ordering::Relaxed)));
+    assert_eq!(ctx.allocation_count(), 3 * OBJECTS);
+    let held = cells.iter().filter(|cell| cell.load().is_some()).count();
+    assert_eq!(held, OBJECTS);
+}
+
+#[test]
+fn pinned_objects_survive_until_unpinned() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Env {
+        value: GcPtr<String>,
+    }
+
+    unsafe impl Trace for Env {
+        fn trace(&self, tracer: &Tracer) {
+            self.value.trace(tracer);
+        }
+    }
+
+    impl Drop for Env {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let ctx = GcContext::off();
+    let value = ctx.allocate(String::from("global"));
+    let env = ctx.pin(Env {
+        value: value.as_ptr(),
+    });
+    drop(value);
+    let clone = env.clone();
+    assert_eq!(ctx.roots().count(), 0);
+
+    for _ in 0..20 {
+        ctx.force_collect();
+    }
+    assert_eq!(ctx.allocation_count(), 2);
+    assert_eq!(*unsafe { clone.value.root() }, "global");
+
+    // Dropping one clone keeps the object pinned
+    drop(env);
+    ctx.force_collect();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+
+    drop(clone);
+    ctx.force_collect();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    assert_eq!(ctx.allocation_count(), 0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "points to the unmarked object")]
+fn verify_marking_reports_stores_without_barrier() {
+    use abfall::GcOptions;
+    use std::cell::Cell;
+
+    // Stores through a `Cell` skip the write barrier of `GcCell`
+    struct Unbarriered(Cell<Option<GcPtr<u32>>>);
+
+    unsafe impl Trace for Unbarriered {
+        fn trace(&self, tracer: &Tracer) {
+            self.0.get().trace(tracer);
+        }
+    }
+
+    let ctx = GcContext::with_options(GcOptions {
+        verify_marking: true,
+        ..GcOptions::OFF
+    });
+    let holder = ctx.allocate(Unbarriered(Cell::new(None)));
+    let hidden = ctx.allocate(7u32).as_ptr();
+
+    assert!(ctx.begin_mark());
+    while !ctx.do_mark_work(100) {}
+    // The holder is black already, the hidden object stays white
+    holder.0.set(Some(hidden));
+    ctx.sweep();
+}
+
+#[test]
+fn merged_objects_are_collected_by_the_target_heap() {
+    use abfall::{GcOptions, Heap};
+
+    struct Node {
+        value: usize,
+        next: Option<GcPtr<Node>>,
+    }
+
+    unsafe impl Trace for Node {
+        fn trace(&self, tracer: &Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+
+    let heap = Heap::with_options(GcOptions::manual());
+    let kept = heap.allocate(Node {
+        value: 0,
+        next: None,
+    });
+    let other = Heap::with_options(GcOptions::manual());
+
+    // A rooted list of ten nodes and ten unreachable ones
+    let mut list = other.allocate(Node {
+        value: 1,
+        next: None,
+    });
+    for value in 2..=10 {
+        list = other.allocate(Node {
+            value,
+            next: Some(list.as_ptr()),
+        });
+        drop(other.allocate(value));
+    }
+    drop(other.allocate(11));
+    let pinned = other.pin(12u32);
+    assert_eq!(other.allocation_count(), 21);
+
+    heap.merge_from(other);
+    assert_eq!(heap.allocation_count(), 22);
+    assert_eq!(heap.roots().count(), 2);
+
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 12);
+    let mut values = Vec::new();
+    let mut node = Some(list.as_ptr());
+    while let Some(current) = node {
+        let current = unsafe { current.root() };
+        values.push(current.value);
+        node = current.next;
+    }
+    assert_eq!(values, (1..=10).rev().collect::<Vec<_>>());
+    assert_eq!(*pinned, 12);
+    assert_eq!(kept.value, 0);
+
+    // Roots and pins of moved objects are released on the target heap
+    drop(list);
+    drop(pinned);
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 1);
+}
+
+#[test]
+fn merged_weaks_to_swept_objects_use_the_target_heap() {
+    use abfall::{GcOptions, Heap};
+
+    let heap = Heap::with_options(GcOptions::manual());
+    let other = Heap::with_options(GcOptions::manual());
+    let weak = other.allocate(1u32).downgrade();
+    other.force_collect();
+    assert_eq!(other.allocation_count(), 0);
+
+    heap.merge_from(other);
+    assert!(weak.upgrade().is_none());
+    let stats = heap.weak_stats();
+    assert_eq!((stats.weak_refs, stats.failed_upgrades), (1, 1));
+
+    // The tombstone is freed by the target heap
+    drop(weak);
+    assert_eq!(heap.weak_stats().weak_refs, 0);
+    heap.force_collect();
+}
+
+#[test]
+fn merge_completes_the_cycle_in_progress_on_the_merged_heap() {
+    use abfall::{GcOptions, Heap};
+
+    let heap = Heap::with_options(GcOptions::manual());
+    let other = Heap::with_options(GcOptions::manual());
+    let kept = other.allocate(1u32);
+    let weak = other.allocate(2u32).downgrade();
+    assert!(other.begin_mark());
+    other.do_mark_work(1);
+
+    // The merge sweeps `other`, leaving a tombstone for `weak`
+    heap.merge_from(other);
+    assert_eq!(heap.allocation_count(), 1);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(*kept, 1);
+    drop(weak);
+    drop(kept);
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 0);
+}
+
+#[test]
+#[should_panic(expected = "still in use")]
+fn merging_a_shared_heap_panics() {
+    use abfall::{GcOptions, Heap};
+
+    let heap = Heap::with_options(GcOptions::manual());
+    let other = Heap::with_options(GcOptions::manual());
+    heap.merge_from(Arc::clone(&other));
+}
+
+#[test]
+fn panicking_trace_returns_the_heap_to_idle() {
+    use abfall::{Error, GcPhase};
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::sync::atomic::AtomicBool;
+
+    let ctx = GcContext::off();
+    let armed = Arc::new(AtomicBool::new(true));
+    let bomb = ctx.allocate(Bomb {
+        armed: Arc::clone(&armed),
+    });
+    let keep = ctx.allocate(1u32);
+    drop(ctx.allocate(2u32));
+    assert_eq!(ctx.last_error(), None);
+
+    let result = catch_unwind(AssertUnwindSafe(|| ctx.force_collect()));
+    assert!(result.is_err());
+    assert_eq!(ctx.phase(), GcPhase::Idle);
+    assert_eq!(ctx.last_error(), Some(Error::TracePanicked));
+    // The abandoned cycle freed nothing
+    assert_eq!(ctx.allocation_count(), 3);
+
+    // The next cycle runs normally
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 2);
+    assert_eq!(*keep, 1);
+
+    // A user-driven cycle continues after the panic, but frees nothing
+    armed.store(true, std::sync::atomic::Ordering::SeqCst);
+    drop(ctx.allocate(3u32));
+    assert!(ctx.begin_mark());
+    assert!(catch_unwind(AssertUnwindSafe(|| while !ctx.do_mark_work(10) {})).is_err());
+    assert_eq!(ctx.phase(), GcPhase::Marking);
+    assert!(ctx.sweep().is_some());
+    assert_eq!(ctx.allocation_count(), 3);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 2);
+    drop(bomb);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn interval_jitter_spreads_the_collections_of_heaps() {
+    use abfall::{GcEvent, GcOptions, Heap};
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    const HEAPS: usize = 16;
+    let options = GcOptions {
+        collection_interval: Duration::from_millis(100),
+        interval_jitter: Duration::from_millis(50),
+        min_threshold_bytes: 1,
+        ..GcOptions::DEFAULT
+    };
+    let created = Instant::now();
+    let first_marks: Vec<_> = (0..HEAPS)
+        .map(|_| {
+            let heap = Heap::with_options(options);
+            let first_mark = Arc::new(Mutex::new(None));
+            let recorded = Arc::clone(&first_mark);
+            heap.set_gc_callback(Box::new(move |event| {
+                if event == GcEvent::MarkStart {
+                    recorded.lock().unwrap().get_or_insert_with(Instant::now);
+                }
+            }));
+            drop(heap.allocate([0u8; 64]));
+            (heap, first_mark)
+        })
+        .collect();
+    assert!(created.elapsed() < Duration::from_millis(50));
+
+    thread::sleep(Duration::from_millis(400));
+    let delays: Vec<_> = first_marks
+        .iter()
+        .map(|(_, first_mark)| {
+            let marked = first_mark
+                .lock()
+                .unwrap()
+                .expect("the heap wasn't collected");
+            marked - created
+        })
+        .collect();
+    let earliest = *delays.iter().min().unwrap();
+    let latest = *delays.iter().max().unwrap();
+    // All within `interval ± jitter`, and not at the same moment
+    assert!(earliest >= Duration::from_millis(50), "{delays:?}");
+    assert!(latest - earliest >= Duration::from_millis(20), "{delays:?}");
+}
+
+#[test]
+fn allocation_count_matches_a_walk_of_the_list() {
+    let ctx = GcContext::off();
+    let heap = ctx.heap();
+    let mut kept = Vec::new();
+    for round in 0..20usize {
+        let mut next = None;
+        for i in 0..50 {
+            let node = ctx.allocate(Node {
+                value: round * 50 + i,
+                next: next.take(),
+            });
+            next = Some(node.as_ptr());
+            if i % 7 == 0 {
+                kept.push(node);
+            }
+        }
+        // Allocated during the cycle, with garbage still unswept
+        if round % 2 == 0 {
+            heap.begin_mark();
+            drop(ctx.allocate(Node { value: 0, next }));
+            assert_eq!(heap.allocation_count(), heap.allocation_count_slow());
+            assert!(heap.sweep().is_some());
+        } else {
+            heap.force_collect();
+        }
+        assert_eq!(heap.allocation_count(), heap.allocation_count_slow());
+        kept.retain(|node| node.value % 3 != round % 3);
+    }
+    drop(kept);
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 0);
+    assert_eq!(heap.allocation_count_slow(), 0);
+}
+
+#[test]
+fn zero_sized_objects_are_allocated_and_freed() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Marker;
+
+    unsafe impl Trace for Marker {
+        const NO_TRACE: bool = true;
+        fn trace(&self, _tracer: &Tracer) {}
+    }
+
+    impl Drop for Marker {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    const OBJECTS: usize = if cfg!(miri) { 20 } else { 1_000 };
+    let ctx = GcContext::off();
+    let baseline = ctx.bytes_allocated();
+    let units: Vec<_> = (0..OBJECTS).map(|_| ctx.allocate(())).collect();
+    let markers: Vec<_> = (0..OBJECTS).map(|_| ctx.allocate(Marker)).collect();
+    let slices = [ctx.allocate_slice(&[(); 8]), ctx.allocate_slice::<()>(&[])];
+    assert_eq!(slices[0].len(), 8);
+    assert!(slices[1].is_empty());
+    // Each object is a distinct allocation, holding at least its header
+    assert!(!units[0].as_ptr().ptr_eq(&units[1].as_ptr()));
+    assert!(ctx.bytes_allocated() >= baseline + 2 * OBJECTS * size_of::<usize>());
+
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 2 * OBJECTS + 2);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+
+    drop((units, markers, slices));
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+    assert_eq!(ctx.bytes_allocated(), baseline);
+    assert_eq!(DROPS.load(Ordering::Relaxed), OBJECTS);
+
+    // Again, possibly in the slots recycled by the sweep
+    let again: Vec<_> = (0..OBJECTS).map(|_| ctx.allocate(Marker)).collect();
+    drop(again);
+    ctx.force_collect();
+    assert_eq!(ctx.bytes_allocated(), baseline);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2 * OBJECTS);
+}
+
+#[test]
+fn with_heap_reenters_the_context_of_the_same_heap() {
+    fn nested(depth: usize) -> GcRoot<usize> {
+        let current = GcContext::current().expect("no context");
+        let ctx = GcContext::with_heap(Arc::clone(current.heap()));
+        if depth == 0 {
+            ctx.allocate(depth)
+        } else {
+            nested(depth - 1)
+        }
+    }
+
+    assert!(GcContext::current().is_none());
+    let ctx = GcContext::off();
+    let value = nested(3);
+    assert_eq!(*value, 0);
+    // The inner contexts left the outer one in place
+    let current = GcContext::current().unwrap();
+    assert!(Arc::ptr_eq(current.heap(), ctx.heap()));
+    assert!(matches!(
+        GcContext::try_with_heap(abfall::Heap::off()),
+        Err(abfall::Error::ContextAlreadySet)
+    ));
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 1);
+
+    // A reentered context outliving the outer one doesn't restore it
+    let inner = GcContext::with_heap(Arc::clone(ctx.heap()));
+    drop(ctx);
+    assert!(GcContext::current().is_none());
+    drop(value);
+    drop(inner);
+    assert!(GcContext::current().is_none());
+    drop(GcContext::off());
+}
@@ -0,0 +1,37 @@
+// The global heap is shared between threads
+#![cfg(all(feature = "std", not(feature = "single-threaded")))]
+
+use std::sync::Arc;
+use std::thread;
+
+use abfall::{GcContext, GcOptions, global};
+
+#[test]
+fn threads_share_global_heap() {
+    assert!(global::init_global(GcOptions::OFF));
+    assert!(
+        !global::init_global(GcOptions::DEFAULT),
+        "double init must be rejected"
+    );
+
+    let handles: Vec<_> = (0..2)
+        .map(|t| {
+            thread::spawn(move || {
+                let ctx = GcContext::global();
+                assert!(Arc::ptr_eq(ctx.heap(), global::global_heap()));
+                for i in 0..100 {
+                    let _garbage = ctx.allocate(i);
+                }
+                ctx.allocate(t)
+            })
+        })
+        .collect();
+    let roots: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    let heap = global::global_heap();
+    assert_eq!(heap.allocation_count(), 202);
+    heap.force_collect();
+    assert_eq!(heap.allocation_count(), 2);
+    assert_eq!(*roots[0], 0);
+    assert_eq!(*roots[1], 1);
+}
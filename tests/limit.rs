@@ -0,0 +1,189 @@
+#![cfg(feature = "std")]
+
+use std::process::Command;
+
+use abfall::{GcContext, GcOptions, GcRoot, LimitAction};
+
+const LIMIT: usize = 1024;
+
+type Payload = [u8; 64];
+
+fn context(action: LimitAction) -> GcContext {
+    GcContext::with_options(GcOptions {
+        limit_bytes: LIMIT,
+        on_limit_exceeded: action,
+        ..GcOptions::OFF
+    })
+}
+
+/// Allocate rooted objects until the next allocation would exceed the limit
+fn fill(ctx: &GcContext) -> Vec<GcRoot<Payload>> {
+    let mut live = Vec::new();
+    let before = ctx.heap().bytes_allocated();
+    live.push(ctx.allocate([0u8; 64]));
+    let per_object = ctx.heap().bytes_allocated() - before;
+    while ctx.heap().bytes_allocated() + per_object <= LIMIT {
+        live.push(ctx.allocate([0u8; 64]));
+    }
+    live
+}
+
+#[test]
+fn collect_then_continue_exceeds_limit() {
+    let ctx = context(LimitAction::CollectThenContinue);
+    let live = fill(&ctx);
+    let _garbage = ctx.allocate([1u8; 64]).as_ptr();
+    assert!(ctx.heap().bytes_allocated() > LIMIT);
+
+    // The next allocation collects the garbage first
+    let count = ctx.heap().allocation_count();
+    let _extra = ctx.allocate([2u8; 64]);
+    assert_eq!(ctx.heap().allocation_count(), count);
+    assert_eq!(live.len() + 1, count);
+}
+
+#[test]
+fn error_returns_err_for_pinned_live_objects() {
+    let ctx = context(LimitAction::Error);
+    let _live = fill(&ctx);
+    let allocated = ctx.heap().bytes_allocated();
+
+    let Err(err) = ctx.try_allocate([1u8; 64]) else {
+        panic!("allocation over the limit must fail");
+    };
+    assert_eq!(err.allocated_bytes, allocated);
+    assert_eq!(err.limit_bytes, LIMIT);
+    assert_eq!(ctx.heap().bytes_allocated(), allocated);
+}
+
+#[test]
+fn error_succeeds_after_collecting_garbage() {
+    let ctx = context(LimitAction::Error);
+    let mut live = fill(&ctx);
+    live.pop();
+    assert!(ctx.try_allocate([1u8; 64]).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "exceeds the heap limit")]
+fn error_panics_in_allocate() {
+    let ctx = context(LimitAction::Error);
+    let _live = fill(&ctx);
+    let _ = ctx.allocate([1u8; 64]);
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn block_waits_until_roots_are_released() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    let ctx = context(LimitAction::Block);
+    let live = fill(&ctx);
+
+    let released = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let released = Arc::clone(&released);
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            released.store(true, Ordering::SeqCst);
+            drop(live);
+        })
+    };
+
+    let _value = ctx.allocate([1u8; 64]);
+    assert!(released.load(Ordering::SeqCst), "allocation must block");
+    assert_eq!(ctx.heap().allocation_count(), 1);
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "single-threaded")]
+fn block_fails_without_other_threads() {
+    let ctx = context(LimitAction::Block);
+    let _live = fill(&ctx);
+    assert!(ctx.try_allocate([1u8; 64]).is_err());
+}
+
+#[test]
+fn abort_terminates_process() {
+    const CHILD_ENV: &str = "ABFALL_LIMIT_ABORT_CHILD";
+    if std::env::var_os(CHILD_ENV).is_some() {
+        let ctx = context(LimitAction::Abort);
+        let _live = fill(&ctx);
+        let _ = ctx.allocate([1u8; 64]);
+        unreachable!("allocation over the limit must abort");
+    }
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "abort_terminates_process", "--nocapture"])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(output.status.signal(), Some(6), "expected SIGABRT");
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("exceeds the heap limit"), "{stderr}");
+}
+
+#[test]
+fn try_allocate_eventually_fails_with_tiny_limit() {
+    let ctx = context(LimitAction::Error);
+    let mut live = Vec::new();
+    let err = loop {
+        match ctx.try_allocate([0u8; 64]) {
+            Ok(root) => live.push(root),
+            Err(err) => break err,
+        }
+        assert!(live.len() <= LIMIT / 64, "limit was never enforced");
+    };
+    assert!(!live.is_empty());
+    assert!(err.allocated_bytes + err.requested_bytes > err.limit_bytes);
+    assert!(ctx.heap().bytes_allocated() <= LIMIT);
+}
+
+#[test]
+fn oom_handler_releases_roots_and_retries() {
+    use abfall::OomAction;
+    use std::sync::{Arc, Mutex};
+
+    let ctx = context(LimitAction::Error);
+    let live = Arc::new(Mutex::new(fill(&ctx)));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    {
+        let live = Arc::clone(&live);
+        let calls = Arc::clone(&calls);
+        ctx.heap().set_oom_handler(Box::new(move |size| {
+            calls.lock().unwrap().push(size);
+            live.lock().unwrap().truncate(2);
+            OomAction::Retry
+        }));
+    }
+
+    assert!(ctx.try_allocate([1u8; 64]).is_ok());
+    assert_eq!(calls.lock().unwrap().len(), 1);
+    assert_eq!(ctx.heap().allocation_count(), 3);
+}
+
+#[test]
+fn soft_limit_collects_on_allocation() {
+    let ctx = GcContext::with_options(GcOptions {
+        soft_limit_bytes: LIMIT,
+        ..GcOptions::OFF
+    });
+    let live = fill(&ctx);
+    let count = live.len();
+    drop(live);
+
+    // Over the soft limit: the allocation collects the garbage first
+    let _value = ctx.allocate([1u8; 64]);
+    assert!(count > 1);
+    assert_eq!(ctx.heap().allocation_count(), 1);
+    assert_eq!(ctx.stats().total_collections, 1);
+}
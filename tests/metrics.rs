@@ -0,0 +1,43 @@
+#![cfg(feature = "metrics")]
+
+use abfall::GcContext;
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use std::collections::HashMap;
+
+/// Take a snapshot of all counters
+///
+/// Note that the debugging recorder resets its counters on each snapshot.
+fn counters(recorder: &DebuggingRecorder) -> HashMap<String, u64> {
+    recorder
+        .snapshotter()
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .filter_map(|(key, _, _, value)| match value {
+            DebugValue::Counter(v) => Some((key.key().name().to_string(), v)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn force_collect_increments_collections_total() {
+    let recorder = DebuggingRecorder::new();
+    metrics::with_local_recorder(&recorder, || {
+        let ctx = GcContext::off();
+        let _keep = ctx.allocate(1);
+        for _ in 0..10 {
+            let _tmp = ctx.allocate(0usize);
+        }
+
+        ctx.heap().force_collect();
+        let snapshot = counters(&recorder);
+        assert_eq!(snapshot["abfall_collections_total"], 1);
+        assert_eq!(snapshot["abfall_objects_freed_total"], 10);
+
+        ctx.heap().force_collect();
+        let snapshot = counters(&recorder);
+        assert_eq!(snapshot["abfall_collections_total"], 1);
+        assert_eq!(snapshot["abfall_objects_freed_total"], 0);
+    });
+}
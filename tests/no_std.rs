@@ -0,0 +1,31 @@
+// Builds the crate without `std` and runs its unit tests in that configuration
+#![cfg(not(miri))]
+
+use std::path::Path;
+use std::process::Command;
+
+fn cargo_without_std(args: &[&str]) {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let output = Command::new(cargo)
+        .args(args)
+        .args(["--lib", "--no-default-features"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        // A separate target directory, the outer one is locked by this run
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"),
+        )
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "cargo {args:?} --no-default-features failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn builds_and_collects_without_std() {
+    cargo_without_std(&["build"]);
+    cargo_without_std(&["test", "no_std_tests"]);
+}
@@ -0,0 +1,102 @@
+// Marker threads need real threads
+#![cfg(all(feature = "std", not(feature = "single-threaded")))]
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use abfall::{GcContext, GcOptions, GcPtr, GcRoot, Trace, Tracer};
+
+const NODES: usize = 20_000;
+
+struct Node {
+    id: usize,
+    children: Vec<GcPtr<Node>>,
+    freed: Arc<Mutex<BTreeSet<usize>>>,
+}
+
+unsafe impl Trace for Node {
+    fn trace(&self, tracer: &Tracer) {
+        self.children.trace(tracer);
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.freed.lock().unwrap().insert(self.id);
+    }
+}
+
+/// Children of every node, always with higher ids
+fn edges() -> Vec<Vec<usize>> {
+    let mut seed = 0x2545_f491_u64;
+    let mut next = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+    (0..NODES)
+        .map(|id| {
+            let mut children = Vec::new();
+            // A long chain makes the graph deep
+            if id + 1 < NODES && id % 97 != 96 {
+                children.push(id + 1);
+            }
+            for _ in 0..2 {
+                let remaining = NODES - id - 1;
+                if remaining > 0 && next() % 3 == 0 {
+                    children.push(id + 1 + next() % remaining);
+                }
+            }
+            children
+        })
+        .collect()
+}
+
+/// Build the graph rooted at node 0, collect, and return the freed ids
+fn collect_with(mark_threads: usize, edges: &[Vec<usize>]) -> BTreeSet<usize> {
+    let ctx = GcContext::with_options(GcOptions {
+        mark_threads,
+        ..GcOptions::OFF
+    });
+    let freed = Arc::new(Mutex::new(BTreeSet::new()));
+    let mut nodes: Vec<Option<GcRoot<Node>>> = (0..NODES).map(|_| None).collect();
+    for id in (0..NODES).rev() {
+        let children = edges[id]
+            .iter()
+            .map(|&child| nodes[child].as_ref().unwrap().as_ptr())
+            .collect();
+        nodes[id] = Some(ctx.allocate(Node {
+            id,
+            children,
+            freed: Arc::clone(&freed),
+        }));
+    }
+    let root = nodes[0].take().unwrap();
+    drop(nodes);
+
+    ctx.force_collect();
+    let freed_ids = freed.lock().unwrap().clone();
+    assert_eq!(ctx.allocation_count(), NODES - freed_ids.len());
+    drop(root);
+    freed_ids
+}
+
+#[test]
+fn parallel_marking_keeps_the_same_objects() {
+    let edges = edges();
+
+    // Reachable from node 0
+    let mut reachable = BTreeSet::new();
+    let mut stack = vec![0];
+    while let Some(id) = stack.pop() {
+        if reachable.insert(id) {
+            stack.extend(&edges[id]);
+        }
+    }
+    let garbage: BTreeSet<_> = (0..NODES).filter(|id| !reachable.contains(id)).collect();
+    assert!(!garbage.is_empty());
+
+    assert_eq!(collect_with(1, &edges), garbage);
+    assert_eq!(collect_with(4, &edges), garbage);
+}
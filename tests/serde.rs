@@ -0,0 +1,69 @@
+#![cfg(feature = "serde")]
+
+use abfall::{GcCell, GcContext, GcPtr, GcRoot, Trace, Tracer};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    name: String,
+    next: GcCell<Option<GcPtr<Node>>>,
+}
+
+unsafe impl Trace for Node {
+    fn trace(&self, tracer: &Tracer) {
+        self.next.trace(tracer);
+    }
+}
+
+#[test]
+fn cyclic_graph_round_trips() {
+    let ctx = GcContext::off();
+    let a = ctx.allocate(Node {
+        name: "a".into(),
+        next: GcCell::new(None),
+    });
+    let b = ctx.allocate(Node {
+        name: "b".into(),
+        next: GcCell::new(Some(a.as_ptr())),
+    });
+    a.next.set(Some(b.as_ptr()));
+    drop(b);
+
+    let json = serde_json::to_string(&a).unwrap();
+    assert_eq!(
+        json,
+        r#"{"root":0,"nodes":[{"value":{"name":"a","next":1},"edges":[1]},{"value":{"name":"b","next":0},"edges":[0]}]}"#
+    );
+    drop(a);
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 0);
+
+    let a: GcRoot<Node> = serde_json::from_str(&json).unwrap();
+    // The restored objects survive a collection through the root
+    ctx.force_collect();
+    assert_eq!(ctx.allocation_count(), 2);
+    let b = unsafe { a.next.get().unwrap().root() };
+    assert_eq!(a.name, "a");
+    assert_eq!(b.name, "b");
+    assert!(b.next.get().unwrap().ptr_eq(&a.as_ptr()));
+}
+
+#[test]
+fn invalid_tables_release_their_reservations() {
+    let ctx = GcContext::off();
+    // `next` refers to an object that is not in the table
+    let json = r#"{"root":0,"nodes":[{"value":{"name":"a","next":5},"edges":[5]}]}"#;
+    assert!(serde_json::from_str::<GcRoot<Node>>(json).is_err());
+    let json = r#"{"root":1,"nodes":[{"value":{"name":"a","next":null},"edges":[]}]}"#;
+    assert!(serde_json::from_str::<GcRoot<Node>>(json).is_err());
+    assert_eq!(ctx.allocation_count(), 0);
+    assert_eq!(ctx.bytes_allocated(), 0);
+}
+
+#[test]
+fn pointers_outside_a_graph_are_rejected() {
+    let ctx = GcContext::off();
+    let value = ctx.allocate(1);
+    assert!(serde_json::to_string(&value.as_ptr()).is_err());
+    assert!(serde_json::from_str::<GcPtr<i32>>("0").is_err());
+}
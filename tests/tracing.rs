@@ -0,0 +1,88 @@
+#![cfg(feature = "tracing")]
+
+use abfall::GcContext;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Integer fields of an event or span, by name
+#[derive(Debug, Default)]
+struct Fields(HashMap<String, u64>);
+
+impl Visit for Fields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value);
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// An event's fields, and the name of the span it was emitted in
+type CapturedEvent = (Option<String>, Fields);
+
+/// Records the fields of the events and spans
+#[derive(Clone, Default)]
+struct Capture {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+    spans: Arc<Mutex<Vec<(String, Fields)>>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let name = attrs.metadata().name().to_string();
+        self.spans.lock().unwrap().push((name, fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let name = ctx.span(id).unwrap().name();
+        let mut spans = self.spans.lock().unwrap();
+        if let Some((_, fields)) = spans.iter_mut().rev().find(|(n, _)| n == name) {
+            values.record(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let parent = ctx.event_span(event).map(|span| span.name().to_string());
+        self.events.lock().unwrap().push((parent, fields));
+    }
+}
+
+#[test]
+fn collections_emit_cycle_spans_with_freed_bytes() {
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        let ctx = GcContext::off();
+        let _keep = ctx.allocate(1u64);
+        for i in 0..10u64 {
+            drop(ctx.allocate(i));
+        }
+        let before = ctx.bytes_allocated() as u64;
+        let result = ctx.force_collect();
+
+        let events = capture.events.lock().unwrap();
+        let (parent, fields) = events.last().expect("no event emitted");
+        assert_eq!(parent.as_deref(), Some("gc_cycle"));
+        assert_eq!(fields.0["freed_bytes"], result.freed_bytes as u64);
+        assert!(fields.0["freed_bytes"] > 0);
+        assert_eq!(fields.0["freed_objects"], 10);
+        assert_eq!(fields.0["live_bytes"], ctx.bytes_allocated() as u64);
+
+        let spans = capture.spans.lock().unwrap();
+        let names: Vec<_> = spans.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"gc_mark"));
+        assert!(names.contains(&"gc_sweep"));
+        let (_, cycle) = spans.iter().find(|(name, _)| name == "gc_cycle").unwrap();
+        assert_eq!(cycle.0["bytes_before"], before);
+        assert_eq!(cycle.0["freed_bytes"], result.freed_bytes as u64);
+    });
+}